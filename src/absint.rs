@@ -0,0 +1,793 @@
+pub mod interval;
+
+use crate::{
+    ast::ast_node::*,
+    backend::{array_params, Kind, Scope},
+    interpreter::Direction,
+    tokenizer::span::Span,
+};
+use interval::{flip, negate, Interval, MAX};
+use std::collections::{BTreeMap, LinkedList};
+
+const DELAY: usize = 16;
+const NARROW: usize = 2;
+const MAX_CALLS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Check {
+    Fi,
+    From,
+    Reentry,
+    Delocal,
+    Pop,
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Holds,
+    Fails,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Site {
+    pub span: Span,
+    pub check: Check,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub subject: String,
+    pub outcome: Outcome,
+    pub index: Option<Interval>,
+    pub len: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Facts(pub BTreeMap<Site, Observation>);
+
+impl Facts {
+    pub fn holds(&self, span: Span, check: Check, direction: Direction) -> bool {
+        let site = Site {
+            span,
+            check,
+            direction,
+        };
+        self.0
+            .get(&site)
+            .is_some_and(|observation| observation.outcome == Outcome::Holds)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Abs {
+    Int(Interval),
+    Array { len: Option<usize>, elems: Interval },
+    Stack { len: Interval, elems: Interval },
+}
+
+impl Abs {
+    fn int(&self) -> Interval {
+        match self {
+            Abs::Int(n) => *n,
+            _ => Interval::TOP,
+        }
+    }
+
+    fn nil() -> Self {
+        Abs::Stack {
+            len: Interval::constant(0),
+            elems: Interval::TOP,
+        }
+    }
+
+    fn havoc(&self) -> Self {
+        match self {
+            Abs::Int(_) => Abs::Int(Interval::TOP),
+            Abs::Array { len, .. } => Abs::Array {
+                len: *len,
+                elems: Interval::TOP,
+            },
+            Abs::Stack { .. } => Abs::Stack {
+                len: Interval { lo: 0, hi: MAX },
+                elems: Interval::TOP,
+            },
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Abs::Int(a), Abs::Int(b)) => Abs::Int(a.join(*b)),
+            (
+                Abs::Array { len, elems },
+                Abs::Array {
+                    elems: elems_2,
+                    len: len_2,
+                },
+            ) => Abs::Array {
+                len: (len == len_2).then_some(*len).flatten(),
+                elems: elems.join(*elems_2),
+            },
+            (
+                Abs::Stack { len, elems },
+                Abs::Stack {
+                    len: len_2,
+                    elems: elems_2,
+                },
+            ) => Abs::Stack {
+                len: len.join(*len_2),
+                elems: elems.join(*elems_2),
+            },
+            _ => self.havoc(),
+        }
+    }
+
+    fn widen(&self, next: &Self, thresholds: &[i64]) -> Self {
+        match (self, next) {
+            (Abs::Int(a), Abs::Int(b)) => Abs::Int(a.widen(*b, thresholds)),
+            (Abs::Array { len, elems }, Abs::Array { elems: elems_2, .. }) => Abs::Array {
+                len: *len,
+                elems: elems.widen(*elems_2, thresholds),
+            },
+            (
+                Abs::Stack { len, elems },
+                Abs::Stack {
+                    len: len_2,
+                    elems: elems_2,
+                },
+            ) => Abs::Stack {
+                len: len.widen(*len_2, thresholds),
+                elems: elems.widen(*elems_2, thresholds),
+            },
+            _ => next.clone(),
+        }
+    }
+
+    fn equal(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (Abs::Int(a), Abs::Int(b)) => a.compare(Op::Equal, *b),
+            (Abs::Stack { len, .. }, Abs::Stack { len: len_2, .. }) => {
+                match (len.singleton(), len_2.singleton(), len.meet(*len_2)) {
+                    (Some(0), Some(0), _) => Some(true),
+                    (_, _, None) => Some(false),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+type State = BTreeMap<String, Abs>;
+
+pub fn analyze(prog: &Prog) -> Facts {
+    let mut analyzer = Analyzer {
+        prog,
+        thresholds: thresholds(prog),
+        facts: BTreeMap::new(),
+        recording: true,
+        calls: Vec::new(),
+    };
+
+    if let Proc::Main { main_stuff, s, .. } = &prog.p_main {
+        let state = main_stuff
+            .iter()
+            .map(|stuff| match stuff {
+                MainStuff::Int(Vdec::Scalar(x)) => (x.0.clone(), Abs::Int(Interval::constant(0))),
+                MainStuff::Int(Vdec::Array { x, c }) => (
+                    x.0.clone(),
+                    Abs::Array {
+                        len: usize::try_from(c.0).ok(),
+                        elems: Interval::constant(0),
+                    },
+                ),
+                MainStuff::Stack(x) => (x.0.clone(), Abs::nil()),
+            })
+            .collect();
+        analyzer.stm(s, Some(state), Direction::Forward);
+    }
+
+    let arrays = array_params(prog);
+    for p in &prog.ps {
+        let Proc::Other { q, args, s } = p else {
+            continue;
+        };
+        let scope = Scope::params(args, &arrays[q.0.as_str()]);
+        let state = args
+            .iter()
+            .filter_map(|arg| {
+                let value = match scope.get(&arg.x.0)?.kind {
+                    Kind::Scalar => Abs::Int(Interval::TOP),
+                    Kind::Array(len) => Abs::Array {
+                        len,
+                        elems: Interval::TOP,
+                    },
+                    Kind::Stack => Abs::nil().havoc(),
+                };
+                Some((arg.x.0.clone(), value))
+            })
+            .collect::<State>();
+        for direction in [Direction::Forward, Direction::Backward] {
+            analyzer.calls = vec![&q.0];
+            analyzer.stm(s, Some(state.clone()), direction);
+        }
+    }
+
+    Facts(analyzer.facts)
+}
+
+fn thresholds(prog: &Prog) -> Vec<i64> {
+    fn constants(e: &Exp, value: &mut Vec<i64>) {
+        match e {
+            Exp::Constant(c) => value.push(c.0.into()),
+            Exp::Indexed { e, .. } => constants(e, value),
+            Exp::BinOp(e_1, _, e_2) => {
+                constants(e_1, value);
+                constants(e_2, value);
+            }
+            _ => {}
+        }
+    }
+
+    let mut value = vec![0];
+    if let Proc::Main { main_stuff, .. } = &prog.p_main {
+        for stuff in main_stuff {
+            if let MainStuff::Int(Vdec::Array { c, .. }) = stuff {
+                value.push(c.0.into());
+            }
+        }
+    }
+    for p in prog.procs() {
+        p.body().walk(&mut |s| {
+            for e in s.exps() {
+                constants(e, &mut value);
+            }
+        });
+    }
+    let mut value = value
+        .into_iter()
+        .flat_map(|n| [n - 1, n, n + 1])
+        .collect::<Vec<_>>();
+    value.sort_unstable();
+    value.dedup();
+    value
+}
+
+fn join(a: Option<State>, b: Option<State>) -> Option<State> {
+    match (a, b) {
+        (Some(mut a), Some(b)) => {
+            for (x, value) in b {
+                let joined = match a.get(&x) {
+                    Some(old) => old.join(&value),
+                    None => value,
+                };
+                a.insert(x, joined);
+            }
+            Some(a)
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+struct Analyzer<'a> {
+    prog: &'a Prog,
+    thresholds: Vec<i64>,
+    facts: BTreeMap<Site, Observation>,
+    recording: bool,
+    calls: Vec<&'a str>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn record(
+        &mut self,
+        site: (Span, Check, Direction),
+        subject: &str,
+        holds: Option<bool>,
+        index: Option<(Interval, Option<usize>)>,
+    ) {
+        if !self.recording {
+            return;
+        }
+        let (span, check, direction) = site;
+        let outcome = match holds {
+            Some(true) => Outcome::Holds,
+            Some(false) => Outcome::Fails,
+            None => Outcome::Unknown,
+        };
+        let site = Site {
+            span,
+            check,
+            direction,
+        };
+        let observation = self.facts.entry(site).or_insert(Observation {
+            subject: subject.to_string(),
+            outcome,
+            index: index.map(|(index, _)| index),
+            len: index.and_then(|(_, len)| len),
+        });
+        if observation.outcome != outcome {
+            observation.outcome = Outcome::Unknown;
+        }
+        if let Some((index, len)) = index {
+            observation.index = observation.index.map(|old| old.join(index));
+            if observation.len != len {
+                observation.len = None;
+            }
+        }
+    }
+
+    fn index(
+        &mut self,
+        x: &Var,
+        index: Interval,
+        span: Span,
+        direction: Direction,
+        state: &State,
+    ) -> bool {
+        let Some(Abs::Array { len, .. }) = state.get(&x.0) else {
+            return true;
+        };
+        let holds = match len {
+            Some(len) => {
+                let len = *len as i64;
+                match (
+                    index.lo >= 0 && index.hi < len,
+                    index.hi < 0 || index.lo >= len,
+                ) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                }
+            }
+            None if index.hi < 0 => Some(false),
+            None => None,
+        };
+        self.record(
+            (span, Check::Index, direction),
+            &x.0,
+            holds,
+            Some((index, *len)),
+        );
+        holds != Some(false)
+    }
+
+    fn eval(&mut self, e: &Exp, state: &State, direction: Direction) -> Abs {
+        match e {
+            Exp::Constant(c) => Abs::Int(Interval::constant(c.0.into())),
+            Exp::Variable(x) => state.get(&x.0).cloned().unwrap_or(Abs::Int(Interval::TOP)),
+            Exp::Indexed { x, e } => {
+                let index = self.eval(e, state, direction).int();
+                self.index(x, index, x.1, direction, state);
+                match state.get(&x.0) {
+                    Some(Abs::Array { elems, .. }) => Abs::Int(*elems),
+                    _ => Abs::Int(Interval::TOP),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (v_1, v_2) = (
+                    self.eval(e_1, state, direction),
+                    self.eval(e_2, state, direction),
+                );
+                match (op, &v_1, &v_2) {
+                    (Op::Equal | Op::NotEqual, Abs::Stack { .. }, _)
+                    | (Op::Equal | Op::NotEqual, _, Abs::Stack { .. }) => {
+                        let equal = v_1.equal(&v_2).map(|equal| equal == (*op == Op::Equal));
+                        Abs::Int(match equal {
+                            Some(truth) => Interval::constant(truth.into()),
+                            None => Interval::BOOL,
+                        })
+                    }
+                    _ => Abs::Int(v_1.int().binop(*op, v_2.int())),
+                }
+            }
+            Exp::Empty(x) => Abs::Int(match state.get(&x.0) {
+                Some(Abs::Stack { len, .. }) => match (len.singleton(), len.lo >= 1) {
+                    (Some(0), _) => Interval::constant(1),
+                    (_, true) => Interval::constant(0),
+                    _ => Interval::BOOL,
+                },
+                _ => Interval::BOOL,
+            }),
+            Exp::Top(x) => match state.get(&x.0) {
+                Some(Abs::Stack { elems, .. }) => Abs::Int(*elems),
+                _ => Abs::Int(Interval::TOP),
+            },
+            Exp::Nil => Abs::nil(),
+        }
+    }
+
+    fn truth(&mut self, e: &Exp, state: &State, direction: Direction) -> Option<bool> {
+        self.eval(e, state, direction).int().truth()
+    }
+
+    fn assume(
+        &mut self,
+        e: &Exp,
+        truth: bool,
+        state: Option<State>,
+        direction: Direction,
+    ) -> Option<State> {
+        let state = state?;
+        let recording = std::mem::replace(&mut self.recording, false);
+        let value = self.refine(e, truth, state, direction);
+        self.recording = recording;
+        value
+    }
+
+    fn refine(
+        &mut self,
+        e: &Exp,
+        truth: bool,
+        mut state: State,
+        direction: Direction,
+    ) -> Option<State> {
+        if self
+            .truth(e, &state, direction)
+            .is_some_and(|value| value != truth)
+        {
+            return None;
+        }
+        match e {
+            Exp::BinOp(e_1, Op::And2, e_2) if truth => {
+                let state = self.refine(e_1, true, state, direction)?;
+                return self.refine(e_2, true, state, direction);
+            }
+            Exp::BinOp(e_1, Op::Or2, e_2) if !truth => {
+                let state = self.refine(e_1, false, state, direction)?;
+                return self.refine(e_2, false, state, direction);
+            }
+            Exp::BinOp(
+                e_1,
+                op @ (Op::Less
+                | Op::Greater
+                | Op::Equal
+                | Op::NotEqual
+                | Op::LessEqual
+                | Op::GreaterEqual),
+                e_2,
+            ) => {
+                let op = match truth {
+                    true => *op,
+                    false => negate(*op),
+                };
+                let (v_1, v_2) = (
+                    self.eval(e_1, &state, direction),
+                    self.eval(e_2, &state, direction),
+                );
+                if let (Exp::Variable(x), Abs::Int(n)) = (&**e_1, &v_1) {
+                    state.insert(x.0.clone(), Abs::Int(n.refine(op, v_2.int())?));
+                }
+                if let (Exp::Variable(y), Abs::Int(n)) = (&**e_2, &v_2) {
+                    state.insert(y.0.clone(), Abs::Int(n.refine(flip(op), v_1.int())?));
+                }
+            }
+            Exp::Variable(x) => {
+                if let Some(Abs::Int(n)) = state.get(&x.0) {
+                    let op = match truth {
+                        true => Op::NotEqual,
+                        false => Op::Equal,
+                    };
+                    let n = n.refine(op, Interval::constant(0))?;
+                    state.insert(x.0.clone(), Abs::Int(n));
+                }
+            }
+            Exp::Empty(x) => {
+                if let Some(Abs::Stack { len, elems }) = state.get(&x.0) {
+                    let len = match truth {
+                        true => len.meet(Interval::constant(0))?,
+                        false => len.meet(Interval { lo: 1, hi: MAX })?,
+                    };
+                    let elems = *elems;
+                    state.insert(x.0.clone(), Abs::Stack { len, elems });
+                }
+            }
+            _ => {}
+        }
+        Some(state)
+    }
+
+    fn widen(&self, head: &State, next: State) -> State {
+        next.into_iter()
+            .map(|(x, value)| {
+                let value = match head.get(&x) {
+                    Some(old) => old.widen(&old.join(&value), &self.thresholds),
+                    None => value,
+                };
+                (x, value)
+            })
+            .collect()
+    }
+
+    fn stm(&mut self, s: &'a Stm, state: Option<State>, direction: Direction) -> Option<State> {
+        let mut state = state?;
+        match s {
+            Stm::AssignScalar { x, mod_op, e, .. } => {
+                let n = self.eval(e, &state, direction).int();
+                let old = state.get(&x.0).map_or(Interval::TOP, Abs::int);
+                let op = match (mod_op, direction) {
+                    (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => Op::Add,
+                    (ModOp::Sub, Direction::Forward) | (ModOp::Add, Direction::Backward) => Op::Sub,
+                    (ModOp::Xor, _) => Op::Xor,
+                };
+                state.insert(x.0.clone(), Abs::Int(old.binop(op, n)));
+                Some(state)
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                let index = self.eval(e_index, &state, direction).int();
+                if !self.index(x, index, *span, direction, &state) {
+                    return None;
+                }
+                if let (Exp::Variable(y), Some(Abs::Array { len, .. })) = (e_index, state.get(&x.0))
+                {
+                    let hi = len.map_or(MAX, |len| len as i64) - 1;
+                    let bounds = Interval::new(0, hi).and_then(|bounds| index.meet(bounds))?;
+                    state.insert(y.0.clone(), Abs::Int(bounds));
+                }
+                let n = self.eval(e, &state, direction).int();
+                if let Some(Abs::Array { len, elems }) = state.get(&x.0) {
+                    let op =
+                        match (mod_op, direction) {
+                            (ModOp::Add, Direction::Forward)
+                            | (ModOp::Sub, Direction::Backward) => Op::Add,
+                            (ModOp::Sub, Direction::Forward)
+                            | (ModOp::Add, Direction::Backward) => Op::Sub,
+                            (ModOp::Xor, _) => Op::Xor,
+                        };
+                    let value = Abs::Array {
+                        len: *len,
+                        elems: elems.join(elems.binop(op, n)),
+                    };
+                    state.insert(x.0.clone(), value);
+                }
+                Some(state)
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                self.eval(e_entry, &state, direction);
+                let then = self.assume(e_entry, true, Some(state.clone()), direction);
+                let then = self.stm(s_then, then, direction);
+                let otherwise = self.assume(e_entry, false, Some(state), direction);
+                let otherwise = self.stm(s_else, otherwise, direction);
+                let mut outcomes = Vec::new();
+                if let Some(state) = &then {
+                    outcomes.push(self.truth(e_exit, state, direction));
+                }
+                if let Some(state) = &otherwise {
+                    let truth = self.truth(e_exit, state, direction);
+                    outcomes.push(truth.map(|truth| !truth));
+                }
+                if let Some(&first) = outcomes.first() {
+                    let holds = match outcomes.iter().all(|&outcome| outcome == first) {
+                        true => first,
+                        false => None,
+                    };
+                    self.record((*span, Check::Fi, direction), "", holds, None);
+                }
+                let then = self.assume(e_exit, true, then, direction);
+                let otherwise = self.assume(e_exit, false, otherwise, direction);
+                join(then, otherwise)
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                let holds = self.truth(e_entry, &state, direction);
+                self.record((*span, Check::From, direction), "", holds, None);
+                let start = self.assume(e_entry, true, Some(state), direction);
+
+                let recording = std::mem::replace(&mut self.recording, false);
+                let mut head = start.clone();
+                for n in 0.. {
+                    let (_, back) = self.iterate(head.clone(), s_do, s_loop, e_exit, direction);
+                    let back = self.assume(e_entry, false, back, direction);
+                    let next = match (n < DELAY, &head, join(start.clone(), back)) {
+                        (false, Some(old), Some(next)) => Some(self.widen(old, next)),
+                        (_, _, next) => next,
+                    };
+                    if next == head {
+                        break;
+                    }
+                    head = next;
+                }
+                for _ in 0..NARROW {
+                    let (_, back) = self.iterate(head.clone(), s_do, s_loop, e_exit, direction);
+                    let back = self.assume(e_entry, false, back, direction);
+                    head = join(start.clone(), back);
+                }
+                self.recording = recording;
+
+                let (exit, back) = self.iterate(head, s_do, s_loop, e_exit, direction);
+                if let Some(back) = &back {
+                    let holds = self.truth(e_entry, back, direction).map(|truth| !truth);
+                    self.record((*span, Check::Reentry, direction), "", holds, None);
+                }
+                exit
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                let n = state.get(&x.0).map_or(Interval::TOP, Abs::int);
+                let Some(Abs::Stack { len, elems }) = state.get(&xs.0).cloned() else {
+                    return Some(state);
+                };
+                let stack = match push {
+                    true => Abs::Stack {
+                        len: Interval {
+                            lo: len.lo + 1,
+                            hi: (len.hi + 1).min(MAX),
+                        },
+                        elems: match len.hi {
+                            0 => n,
+                            _ => elems.join(n),
+                        },
+                    },
+                    false => {
+                        let holds = match (n.singleton() == Some(0) && len.lo >= 1, n.contains(0)) {
+                            (true, _) => Some(true),
+                            (_, false) => Some(false),
+                            _ if len.hi == 0 => Some(false),
+                            _ => None,
+                        };
+                        self.record((*span, Check::Pop, direction), &xs.0, holds, None);
+                        if holds == Some(false) {
+                            return None;
+                        }
+                        Abs::Stack {
+                            len: Interval {
+                                lo: (len.lo - 1).max(0),
+                                hi: len.hi - 1,
+                            },
+                            elems,
+                        }
+                    }
+                };
+                let n = match push {
+                    true => Interval::constant(0),
+                    false => elems,
+                };
+                state.insert(x.0.clone(), Abs::Int(n));
+                state.insert(xs.0.clone(), stack);
+                Some(state)
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                e_delocal,
+                span,
+                ..
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_local, e_delocal),
+                    Direction::Backward => (e_delocal, e_local),
+                };
+                let initial = match t_local {
+                    Type::Int => Abs::Int(self.eval(e_entry, &state, direction).int()),
+                    Type::Stack => Abs::nil(),
+                };
+                let shadowed = state.insert(x_local.0.clone(), initial);
+                let mut state = self.stm(s, Some(state), direction)?;
+                let value = state.get(&x_local.0).cloned().unwrap_or(Abs::nil());
+                let holds = value.equal(&self.eval(e_exit, &state, direction));
+                self.record((*span, Check::Delocal, direction), &x_local.0, holds, None);
+                if holds == Some(false) {
+                    return None;
+                }
+                match shadowed {
+                    Some(shadowed) => state.insert(x_local.0.clone(), shadowed),
+                    None => state.remove(&x_local.0),
+                };
+                Some(state)
+            }
+            Stm::Call { q, xs, .. } | Stm::Uncall { q, xs, .. } => {
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                self.call(&q.0, xs, state, direction)
+            }
+            Stm::Skip(_) => Some(state),
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    let state = self.stm(s_1, Some(state), direction);
+                    self.stm(s_2, state, direction)
+                }
+                Direction::Backward => {
+                    let state = self.stm(s_2, Some(state), direction);
+                    self.stm(s_1, state, direction)
+                }
+            },
+        }
+    }
+
+    fn iterate(
+        &mut self,
+        head: Option<State>,
+        s_do: &'a Stm,
+        s_loop: &'a Stm,
+        e_exit: &Exp,
+        direction: Direction,
+    ) -> (Option<State>, Option<State>) {
+        let state = self.stm(s_do, head, direction);
+        if let Some(state) = &state {
+            self.eval(e_exit, state, direction);
+        }
+        let exit = self.assume(e_exit, true, state.clone(), direction);
+        let repeat = self.assume(e_exit, false, state, direction);
+        (exit, self.stm(s_loop, repeat, direction))
+    }
+
+    fn call(
+        &mut self,
+        q: &'a str,
+        xs: &LinkedList<Var>,
+        mut state: State,
+        direction: Direction,
+    ) -> Option<State> {
+        let callee = self.prog.ps.iter().find(|p| p.name() == q);
+        let (args, s) = match callee {
+            Some(Proc::Other { args, s, .. })
+                if args.len() == xs.len()
+                    && !self.calls.contains(&q)
+                    && self.calls.len() < MAX_CALLS =>
+            {
+                (args, s)
+            }
+            _ => {
+                for x in xs {
+                    if let Some(value) = state.get(&x.0) {
+                        let value = value.havoc();
+                        state.insert(x.0.clone(), value);
+                    }
+                }
+                return Some(state);
+            }
+        };
+        let env = args
+            .iter()
+            .zip(xs)
+            .map(|(arg, x)| {
+                let value = state.get(&x.0).cloned().unwrap_or(Abs::Int(Interval::TOP));
+                (arg.x.0.clone(), value)
+            })
+            .collect();
+        self.calls.push(q);
+        let env = self.stm(s, Some(env), direction);
+        self.calls.pop();
+        let env = env?;
+        for (arg, x) in args.iter().zip(xs) {
+            if let Some(value) = env.get(&arg.x.0) {
+                state.insert(x.0.clone(), value.clone());
+            }
+        }
+        Some(state)
+    }
+}
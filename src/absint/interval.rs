@@ -0,0 +1,214 @@
+use crate::{ast::ast_node::Op, interpreter::eval_op};
+use std::fmt;
+
+pub const MIN: i64 = i32::MIN as i64;
+pub const MAX: i64 = i32::MAX as i64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: i64,
+    pub hi: i64,
+}
+
+impl Interval {
+    pub const TOP: Self = Self { lo: MIN, hi: MAX };
+    pub const BOOL: Self = Self { lo: 0, hi: 1 };
+
+    pub fn constant(n: i64) -> Self {
+        Self { lo: n, hi: n }
+    }
+
+    pub fn new(lo: i64, hi: i64) -> Option<Self> {
+        (lo <= hi).then_some(Self { lo, hi })
+    }
+
+    fn int(lo: i64, hi: i64) -> Self {
+        match lo < MIN || hi > MAX {
+            true => Self::TOP,
+            false => Self { lo, hi },
+        }
+    }
+
+    fn truth_value(truth: Option<bool>) -> Self {
+        match truth {
+            Some(truth) => Self::constant(truth.into()),
+            None => Self::BOOL,
+        }
+    }
+
+    pub fn singleton(self) -> Option<i64> {
+        (self.lo == self.hi).then_some(self.lo)
+    }
+
+    pub fn contains(self, n: i64) -> bool {
+        self.lo <= n && n <= self.hi
+    }
+
+    pub fn truth(self) -> Option<bool> {
+        match (self.singleton(), self.contains(0)) {
+            (Some(0), _) => Some(false),
+            (_, false) => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn join(self, other: Self) -> Self {
+        Self {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    pub fn meet(self, other: Self) -> Option<Self> {
+        Self::new(self.lo.max(other.lo), self.hi.min(other.hi))
+    }
+
+    pub fn widen(self, next: Self, thresholds: &[i64]) -> Self {
+        let lo = match next.lo < self.lo {
+            true => thresholds
+                .iter()
+                .rev()
+                .copied()
+                .find(|&t| t <= next.lo)
+                .unwrap_or(MIN),
+            false => self.lo,
+        };
+        let hi = match next.hi > self.hi {
+            true => thresholds
+                .iter()
+                .copied()
+                .find(|&t| t >= next.hi)
+                .unwrap_or(MAX),
+            false => self.hi,
+        };
+        Self { lo, hi }
+    }
+
+    pub fn binop(self, op: Op, other: Self) -> Self {
+        if let (Some(n_1), Some(n_2)) = (self.singleton(), other.singleton()) {
+            if let Ok(n) = eval_op(&op, n_1 as i32, n_2 as i32) {
+                return Self::constant(n.into());
+            }
+        }
+        let (a, b) = (self, other);
+        match op {
+            Op::Add => Self::int(a.lo + b.lo, a.hi + b.hi),
+            Op::Sub => Self::int(a.lo - b.hi, a.hi - b.lo),
+            Op::Mul => {
+                let products = [a.lo * b.lo, a.lo * b.hi, a.hi * b.lo, a.hi * b.hi];
+                Self::int(
+                    *products.iter().min().expect("four products"),
+                    *products.iter().max().expect("four products"),
+                )
+            }
+            Op::Div if !b.contains(0) => {
+                let quotients = [a.lo / b.lo, a.lo / b.hi, a.hi / b.lo, a.hi / b.hi];
+                Self::int(
+                    *quotients.iter().min().expect("four quotients"),
+                    *quotients.iter().max().expect("four quotients"),
+                )
+            }
+            Op::Mod if !b.contains(0) => {
+                let m = b.lo.abs().max(b.hi.abs()) - 1;
+                match (a.lo >= 0, a.hi <= 0) {
+                    (true, _) => Self::int(0, a.hi.min(m)),
+                    (_, true) => Self::int(a.lo.max(-m), 0),
+                    _ => Self::int(-m, m),
+                }
+            }
+            Op::And => match (a.lo >= 0, b.lo >= 0) {
+                (true, true) => Self::int(0, a.hi.min(b.hi)),
+                (true, false) => Self::int(0, a.hi),
+                (false, true) => Self::int(0, b.hi),
+                (false, false) => Self::TOP,
+            },
+            Op::Or | Op::Xor if a.lo >= 0 && b.lo >= 0 => {
+                let hi = a.hi.max(b.hi) as u64;
+                Self::int(0, ((hi + 1).next_power_of_two() - 1) as i64)
+            }
+            Op::And2 => Self::truth_value(match (a.truth(), b.truth()) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            }),
+            Op::Or2 => Self::truth_value(match (a.truth(), b.truth()) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            }),
+            Op::Less
+            | Op::Greater
+            | Op::Equal
+            | Op::NotEqual
+            | Op::LessEqual
+            | Op::GreaterEqual => Self::truth_value(a.compare(op, b)),
+            _ => Self::TOP,
+        }
+    }
+
+    pub fn compare(self, op: Op, other: Self) -> Option<bool> {
+        let (a, b) = (self, other);
+        match op {
+            Op::Less if a.hi < b.lo => Some(true),
+            Op::Less if a.lo >= b.hi => Some(false),
+            Op::LessEqual if a.hi <= b.lo => Some(true),
+            Op::LessEqual if a.lo > b.hi => Some(false),
+            Op::Greater => b.compare(Op::Less, a),
+            Op::GreaterEqual => b.compare(Op::LessEqual, a),
+            Op::Equal => match (a.singleton(), b.singleton(), a.meet(b)) {
+                (Some(n_1), Some(n_2), _) if n_1 == n_2 => Some(true),
+                (_, _, None) => Some(false),
+                _ => None,
+            },
+            Op::NotEqual => a.compare(Op::Equal, b).map(|equal| !equal),
+            _ => None,
+        }
+    }
+
+    pub fn refine(self, op: Op, other: Self) -> Option<Self> {
+        match op {
+            Op::Less => self.meet(Self::new(MIN, other.hi - 1)?),
+            Op::LessEqual => self.meet(Self::new(MIN, other.hi)?),
+            Op::Greater => self.meet(Self::new(other.lo + 1, MAX)?),
+            Op::GreaterEqual => self.meet(Self::new(other.lo, MAX)?),
+            Op::Equal => self.meet(other),
+            Op::NotEqual => match other.singleton() {
+                Some(n) if n == self.lo => Self::new(self.lo + 1, self.hi),
+                Some(n) if n == self.hi => Self::new(self.lo, self.hi - 1),
+                _ => Some(self),
+            },
+            _ => Some(self),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.singleton() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "[{}, {}]", self.lo, self.hi),
+        }
+    }
+}
+
+pub fn negate(op: Op) -> Op {
+    match op {
+        Op::Less => Op::GreaterEqual,
+        Op::Greater => Op::LessEqual,
+        Op::Equal => Op::NotEqual,
+        Op::NotEqual => Op::Equal,
+        Op::LessEqual => Op::Greater,
+        Op::GreaterEqual => Op::Less,
+        op => op,
+    }
+}
+
+pub fn flip(op: Op) -> Op {
+    match op {
+        Op::Less => Op::Greater,
+        Op::Greater => Op::Less,
+        Op::LessEqual => Op::GreaterEqual,
+        Op::GreaterEqual => Op::LessEqual,
+        op => op,
+    }
+}
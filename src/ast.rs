@@ -1,14 +1,44 @@
 #[allow(unused)]
-mod ast_node;
-
-use crate::tokenizer::{token::Token, Tokenizer};
+pub mod ast_node;
+pub mod pretty;
+pub mod sexpr;
+
+use crate::{
+    tokenizer::{
+        span::{Pos, Span},
+        token::Token,
+        Tokenizer,
+    },
+    util::read_file,
+};
 use anyhow::{anyhow, bail, Result};
 use ast_node::*;
-use std::collections::LinkedList;
+use std::{collections::LinkedList, fs, path::Path};
+
+pub fn parse(source: LinkedList<char>) -> Result<Prog> {
+    let mut tokenizer = Tokenizer::new(source);
+    tokenizer.tokenize()?;
+    let mut ast = Ast::new(tokenizer);
+    ast.build()?;
+    ast.into_tree()
+        .ok_or_else(|| anyhow!("failed to build the tree"))
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Prog> {
+    let path = path.as_ref();
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+        Some("sexpr") => Ok(serde_json::from_value(sexpr::from_str(
+            &fs::read_to_string(path)?,
+        )?)?),
+        _ => parse(read_file(path)?),
+    }
+}
 
 #[derive(Debug)]
 pub struct Ast {
-    source: LinkedList<Token>,
+    source: LinkedList<(Token, Span)>,
+    last: Span,
     tree: Option<Prog>,
 }
 
@@ -16,38 +46,72 @@ impl Ast {
     pub fn new(tokenizer: Tokenizer) -> Self {
         Self {
             source: tokenizer.into(),
+            last: Span::default(),
             tree: None,
         }
     }
 
+    pub fn into_tree(self) -> Option<Prog> {
+        self.tree
+    }
+
+    pub fn position(&self) -> Span {
+        self.source
+            .front()
+            .map(|(_, span)| *span)
+            .unwrap_or(self.last)
+    }
+
     fn advance(&mut self, len: usize) {
         for _ in 0..len {
-            self.source.pop_front();
+            if let Some((_, span)) = self.source.pop_front() {
+                self.last = span;
+            }
         }
     }
 
-    fn next(&mut self) {
+    pub(crate) fn next(&mut self) {
         self.advance(1);
     }
 
-    fn step(&mut self, token: Token) -> Result<()> {
-        match self.source.pop_front() {
-            Some(x) if x == token => Ok(()),
+    pub(crate) fn step(&mut self, token: Token) -> Result<()> {
+        match self.source.front() {
+            Some((x, _)) if *x == token => {
+                self.next();
+                Ok(())
+            }
             x => bail!("expected {token:?} found {x:?}"),
         }
     }
 
-    fn step_identifier(&mut self) -> Result<String> {
-        match self.source.pop_front() {
-            Some(Token::Identifier(identifier)) => Ok(identifier),
+    fn step_identifier(&mut self) -> Result<(String, Span)> {
+        match self.source.front() {
+            Some((Token::Identifier(identifier), span)) => {
+                let value = (identifier.clone(), *span);
+                self.next();
+                Ok(value)
+            }
             x => bail!("expected an identifier found {x:?}"),
         }
     }
 
     fn front(&self) -> Result<&Token> {
+        self.peek().ok_or_else(|| anyhow!("source is empty"))
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.source.front().map(|(token, _)| token)
+    }
+
+    fn start(&self) -> Pos {
         self.source
             .front()
-            .ok_or_else(|| anyhow!("source is empty"))
+            .map(|(_, span)| span.start)
+            .unwrap_or(self.last.end)
+    }
+
+    fn span_from(&self, start: Pos) -> Span {
+        Span::new(start, self.last.end)
     }
 
     pub fn build(&mut self) -> Result<()> {
@@ -65,6 +129,7 @@ impl Ast {
     fn p_main(&mut self) -> Result<Proc> {
         self.step(Token::Procedure)?;
         self.step(Token::Identifier("main".to_string()))?;
+        let span = self.last;
         self.step(Token::LParen)?;
         self.step(Token::RParen)?;
 
@@ -84,6 +149,7 @@ impl Ast {
         }
 
         Ok(Proc::Main {
+            span,
             main_stuff,
             s: self.s()?,
         })
@@ -144,9 +210,9 @@ impl Ast {
         Ok(value)
     }
 
-    fn d(&mut self) -> Result<Vdec> {
+    pub(crate) fn d(&mut self) -> Result<Vdec> {
         let x = self.x()?;
-        if let Some(&Token::LSquareBracket) = self.source.front() {
+        if let Some(&Token::LSquareBracket) = self.peek() {
             self.next();
             let c = self.c()?;
             self.step(Token::RSquareBracket)?;
@@ -156,27 +222,30 @@ impl Ast {
         }
     }
 
-    fn x(&mut self) -> Result<Var> {
-        Ok(Var(self.step_identifier()?))
+    pub(crate) fn x(&mut self) -> Result<Var> {
+        let (identifier, span) = self.step_identifier()?;
+        Ok(Var(identifier, span))
     }
 
     fn q(&mut self) -> Result<PId> {
-        Ok(PId(self.step_identifier()?))
+        let (identifier, span) = self.step_identifier()?;
+        Ok(PId(identifier, span))
     }
 
-    fn s_non_recursive(&mut self) -> Result<Stm> {
+    pub(crate) fn s_non_recursive(&mut self) -> Result<Stm> {
         let call_or_uncall = {
             match self.front()? {
-                Token::Call => |q, xs| Stm::Call { q, xs },
-                Token::Uncall => |q, xs| Stm::Uncall { q, xs },
-                _ => |_q, _xs| unreachable!(),
+                Token::Call => |q, xs, span| Stm::Call { q, xs, span },
+                Token::Uncall => |q, xs, span| Stm::Uncall { q, xs, span },
+                _ => |_q, _xs, _span| unreachable!(),
             }
         };
 
+        let start = self.start();
         match self.front()? {
             Token::Identifier(_) => {
                 let x = self.x()?;
-                if let Some(Token::LSquareBracket) = self.source.front() {
+                if let Some(Token::LSquareBracket) = self.peek() {
                     self.next();
                     let e_index = self.e()?;
                     self.step(Token::RSquareBracket)?;
@@ -187,11 +256,17 @@ impl Ast {
                         e_index,
                         mod_op,
                         e,
+                        span: self.span_from(start),
                     })
                 } else {
                     let mod_op = self.mod_op()?;
                     let e = self.e()?;
-                    Ok(Stm::AssignScalar { x, mod_op, e })
+                    Ok(Stm::AssignScalar {
+                        x,
+                        mod_op,
+                        e,
+                        span: self.span_from(start),
+                    })
                 }
             }
             Token::If => {
@@ -208,6 +283,7 @@ impl Ast {
                     s_then: Box::new(s_then),
                     s_else: Box::new(s_else),
                     e_fi,
+                    span: self.span_from(start),
                 })
             }
             Token::From => {
@@ -224,6 +300,7 @@ impl Ast {
                     s_do: Box::new(s_do),
                     s_loop: Box::new(s_loop),
                     e_until,
+                    span: self.span_from(start),
                 })
             }
             Token::Push => {
@@ -233,7 +310,7 @@ impl Ast {
                 self.step(Token::Comma)?;
                 let right = self.x()?;
                 self.step(Token::RParen)?;
-                Ok(Stm::Push(left, right))
+                Ok(Stm::Push(left, right, self.span_from(start)))
             }
             Token::Pop => {
                 self.next();
@@ -242,7 +319,7 @@ impl Ast {
                 self.step(Token::Comma)?;
                 let right = self.x()?;
                 self.step(Token::RParen)?;
-                Ok(Stm::Pop(left, right))
+                Ok(Stm::Pop(left, right, self.span_from(start)))
             }
             Token::Local => {
                 self.next();
@@ -264,6 +341,7 @@ impl Ast {
                     t_delocal,
                     x_delocal,
                     e_delocal,
+                    span: self.span_from(start),
                 })
             }
             Token::Call | Token::Uncall => {
@@ -289,11 +367,11 @@ impl Ast {
                     }
                 }
 
-                Ok(call_or_uncall(q, xs))
+                Ok(call_or_uncall(q, xs, self.span_from(start)))
             }
             Token::Skip => {
                 self.next();
-                Ok(Stm::Skip)
+                Ok(Stm::Skip(self.span_from(start)))
             }
             x => bail!("expected non-recursive statement found {x:?}"),
         }
@@ -302,8 +380,7 @@ impl Ast {
     fn s(&mut self) -> Result<Stm> {
         let primary = self.s_non_recursive()?;
         let sequence = self
-            .source
-            .front()
+            .peek()
             .map(|token| {
                 matches!(
                     token,
@@ -311,6 +388,7 @@ impl Ast {
                         | Token::If
                         | Token::From
                         | Token::Push
+                        | Token::Pop
                         | Token::Local
                         | Token::Call
                         | Token::Uncall
@@ -341,7 +419,7 @@ impl Ast {
             Token::Constant(_) => Ok(Exp::Constant(self.c()?)),
             Token::Identifier(_) => {
                 let x = self.x()?;
-                if let Some(Token::LSquareBracket) = self.source.front() {
+                if let Some(Token::LSquareBracket) = self.peek() {
                     self.next();
                     let e = self.e()?;
                     self.step(Token::RSquareBracket)?;
@@ -368,15 +446,20 @@ impl Ast {
                 self.next();
                 Ok(Exp::Nil)
             }
+            Token::LParen => {
+                self.next();
+                let e = self.e()?;
+                self.step(Token::RParen)?;
+                Ok(e)
+            }
             x => bail!("expected non-recursive expression found {x:?}"),
         }
     }
 
-    fn e(&mut self) -> Result<Exp> {
+    pub(crate) fn e(&mut self) -> Result<Exp> {
         let primary = self.e_non_recursive()?;
         let bin_op = self
-            .source
-            .front()
+            .peek()
             .map(|token| {
                 matches!(
                     token,
@@ -1,38 +1,42 @@
+use crate::tokenizer::span::Span;
+use serde::{Deserialize, Serialize};
 use std::collections::LinkedList;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Prog {
     pub p_main: Proc,
     pub ps: LinkedList<Proc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Vdec {
     Scalar(Var),
     Array { x: Var, c: Con },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     Int,
     Stack,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Arg {
     pub t: Type,
     pub x: Var,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MainStuff {
     Int(Vdec),
     Stack(Var),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Proc {
     Main {
+        #[serde(default)]
+        span: Span,
         main_stuff: LinkedList<MainStuff>,
         s: Stm,
     },
@@ -43,33 +47,37 @@ pub enum Proc {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Stm {
     AssignScalar {
         x: Var,
         mod_op: ModOp,
         e: Exp,
+        span: Span,
     },
     AssignArray {
         x: Var,
         e_index: Exp,
         mod_op: ModOp,
         e: Exp,
+        span: Span,
     },
     Conditional {
         e_if: Exp,
         s_then: Box<Stm>,
         s_else: Box<Stm>,
         e_fi: Exp,
+        span: Span,
     },
     Loop {
         e_from: Exp,
         s_do: Box<Stm>,
         s_loop: Box<Stm>,
         e_until: Exp,
+        span: Span,
     },
-    Push(Var, Var),
-    Pop(Var, Var),
+    Push(Var, Var, Span),
+    Pop(Var, Var, Span),
     Local {
         t_local: Type,
         x_local: Var,
@@ -78,20 +86,23 @@ pub enum Stm {
         t_delocal: Type,
         x_delocal: Var,
         e_delocal: Exp,
+        span: Span,
     },
     Call {
         q: PId,
         xs: LinkedList<Var>,
+        span: Span,
     },
     Uncall {
         q: PId,
         xs: LinkedList<Var>,
+        span: Span,
     },
-    Skip,
+    Skip(Span),
     Sequence(Box<Stm>, Box<Stm>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Exp {
     Constant(Con),
     Variable(Var),
@@ -102,17 +113,17 @@ pub enum Exp {
     Nil,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Con(pub i32);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ModOp {
     Add,
     Sub,
     Xor,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Op {
     Add,
     Sub,
@@ -132,8 +143,130 @@ pub enum Op {
     GreaterEqual,
 }
 
-#[derive(Debug)]
-pub struct Var(pub String);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Var(pub String, pub Span);
 
-#[derive(Debug)]
-pub struct PId(pub String);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PId(pub String, pub Span);
+
+impl Stm {
+    pub fn span(&self) -> Span {
+        match self {
+            Stm::AssignScalar { span, .. }
+            | Stm::AssignArray { span, .. }
+            | Stm::Conditional { span, .. }
+            | Stm::Loop { span, .. }
+            | Stm::Push(_, _, span)
+            | Stm::Pop(_, _, span)
+            | Stm::Local { span, .. }
+            | Stm::Call { span, .. }
+            | Stm::Uncall { span, .. }
+            | Stm::Skip(span) => *span,
+            Stm::Sequence(s_1, s_2) => s_1.span().join(s_2.span()),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Stm::AssignScalar { .. } => "AssignScalar",
+            Stm::AssignArray { .. } => "AssignArray",
+            Stm::Conditional { .. } => "Conditional",
+            Stm::Loop { .. } => "Loop",
+            Stm::Push(..) => "Push",
+            Stm::Pop(..) => "Pop",
+            Stm::Local { .. } => "Local",
+            Stm::Call { .. } => "Call",
+            Stm::Uncall { .. } => "Uncall",
+            Stm::Skip(_) => "Skip",
+            Stm::Sequence(..) => "Sequence",
+        }
+    }
+
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Stm)) {
+        f(self);
+        match self {
+            Stm::Conditional { s_then, s_else, .. } => {
+                s_then.walk(f);
+                s_else.walk(f);
+            }
+            Stm::Loop { s_do, s_loop, .. } => {
+                s_do.walk(f);
+                s_loop.walk(f);
+            }
+            Stm::Local { s, .. } => s.walk(f),
+            Stm::Sequence(s_1, s_2) => {
+                s_1.walk(f);
+                s_2.walk(f);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn exps(&self) -> Vec<&Exp> {
+        match self {
+            Stm::AssignScalar { e, .. } => vec![e],
+            Stm::AssignArray { e_index, e, .. } => vec![e_index, e],
+            Stm::Conditional { e_if, e_fi, .. } => vec![e_if, e_fi],
+            Stm::Loop {
+                e_from, e_until, ..
+            } => vec![e_from, e_until],
+            Stm::Local {
+                e_local, e_delocal, ..
+            } => vec![e_local, e_delocal],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn indexes(&self, x: &Var) -> bool {
+        let mut value = false;
+        self.walk(&mut |s| {
+            value |= matches!(s, Stm::AssignArray { x: y, .. } if y.0 == x.0)
+                || s.exps().iter().any(|e| e.indexes(x));
+        });
+        value
+    }
+}
+
+impl Exp {
+    pub fn mentions(&self, x: &Var) -> bool {
+        match self {
+            Exp::Constant(_) | Exp::Nil => false,
+            Exp::Variable(y) | Exp::Empty(y) | Exp::Top(y) => y.0 == x.0,
+            Exp::Indexed { x: y, e } => y.0 == x.0 || e.mentions(x),
+            Exp::BinOp(e_1, _, e_2) => e_1.mentions(x) || e_2.mentions(x),
+        }
+    }
+
+    pub fn indexes(&self, x: &Var) -> bool {
+        match self {
+            Exp::Indexed { x: y, e } => y.0 == x.0 || e.indexes(x),
+            Exp::BinOp(e_1, _, e_2) => e_1.indexes(x) || e_2.indexes(x),
+            _ => false,
+        }
+    }
+}
+
+impl Proc {
+    pub fn name(&self) -> &str {
+        match self {
+            Proc::Main { .. } => "main",
+            Proc::Other { q, .. } => &q.0,
+        }
+    }
+
+    pub fn body(&self) -> &Stm {
+        match self {
+            Proc::Main { s, .. } | Proc::Other { s, .. } => s,
+        }
+    }
+}
+
+impl Prog {
+    pub fn procs(&self) -> impl Iterator<Item = &Proc> {
+        std::iter::once(&self.p_main).chain(self.ps.iter())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Proc> {
+        self.procs().find(|p| p.name() == name)
+    }
+}
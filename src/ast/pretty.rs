@@ -0,0 +1,181 @@
+use super::ast_node::*;
+use crate::backend::Writer;
+use std::fmt;
+
+impl Op {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Xor => "^",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Mod => "%",
+            Op::And => "&",
+            Op::Or => "|",
+            Op::And2 => "&&",
+            Op::Or2 => "||",
+            Op::Less => "<",
+            Op::Greater => ">",
+            Op::Equal => "=",
+            Op::NotEqual => "!=",
+            Op::LessEqual => "<=",
+            Op::GreaterEqual => ">=",
+        }
+    }
+}
+
+impl ModOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ModOp::Add => "+=",
+            ModOp::Sub => "-=",
+            ModOp::Xor => "^=",
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Stack => write!(f, "stack"),
+        }
+    }
+}
+
+impl fmt::Display for Exp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exp::Constant(c) => write!(f, "{}", c.0),
+            Exp::Variable(x) => write!(f, "{}", x.0),
+            Exp::Indexed { x, e } => write!(f, "{}[{e}]", x.0),
+            Exp::BinOp(e_1, op, e_2) if matches!(**e_1, Exp::BinOp(..)) => {
+                write!(f, "({e_1}) {} {e_2}", op.symbol())
+            }
+            Exp::BinOp(e_1, op, e_2) => write!(f, "{e_1} {} {e_2}", op.symbol()),
+            Exp::Empty(x) => write!(f, "empty({})", x.0),
+            Exp::Top(x) => write!(f, "top({})", x.0),
+            Exp::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+fn vars(xs: &std::collections::LinkedList<Var>) -> String {
+    xs.iter()
+        .map(|x| x.0.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn stm(out: &mut Writer, s: &Stm) {
+    match s {
+        Stm::AssignScalar { x, mod_op, e, .. } => {
+            out.line(format!("{} {} {e}", x.0, mod_op.symbol()))
+        }
+        Stm::AssignArray {
+            x,
+            e_index,
+            mod_op,
+            e,
+            ..
+        } => out.line(format!("{}[{e_index}] {} {e}", x.0, mod_op.symbol())),
+        Stm::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+            ..
+        } => {
+            out.line(format!("if {e_if} then"));
+            block(out, s_then);
+            out.line("else");
+            block(out, s_else);
+            out.line(format!("fi {e_fi}"));
+        }
+        Stm::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+            ..
+        } => {
+            out.line(format!("from {e_from} do"));
+            block(out, s_do);
+            out.line("loop");
+            block(out, s_loop);
+            out.line(format!("until {e_until}"));
+        }
+        Stm::Push(x, xs, _) => out.line(format!("push({}, {})", x.0, xs.0)),
+        Stm::Pop(x, xs, _) => out.line(format!("pop({}, {})", x.0, xs.0)),
+        Stm::Local {
+            t_local,
+            x_local,
+            e_local,
+            s,
+            t_delocal,
+            x_delocal,
+            e_delocal,
+            ..
+        } => {
+            out.line(format!("local {t_local} {} = {e_local}", x_local.0));
+            block(out, s);
+            out.line(format!("delocal {t_delocal} {} = {e_delocal}", x_delocal.0));
+        }
+        Stm::Call { q, xs, .. } => out.line(format!("call {}({})", q.0, vars(xs))),
+        Stm::Uncall { q, xs, .. } => out.line(format!("uncall {}({})", q.0, vars(xs))),
+        Stm::Skip(_) => out.line("skip"),
+        Stm::Sequence(s_1, s_2) => {
+            stm(out, s_1);
+            stm(out, s_2);
+        }
+    }
+}
+
+fn block(out: &mut Writer, s: &Stm) {
+    out.indent();
+    stm(out, s);
+    out.dedent();
+}
+
+fn proc(out: &mut Writer, p: &Proc) {
+    match p {
+        Proc::Main { main_stuff, s, .. } => {
+            out.line("procedure main()");
+            out.indent();
+            for stuff in main_stuff {
+                match stuff {
+                    MainStuff::Int(Vdec::Scalar(x)) => out.line(format!("int {}", x.0)),
+                    MainStuff::Int(Vdec::Array { x, c }) => {
+                        out.line(format!("int {}[{}]", x.0, c.0))
+                    }
+                    MainStuff::Stack(x) => out.line(format!("stack {}", x.0)),
+                }
+            }
+            out.dedent();
+            block(out, s);
+        }
+        Proc::Other { q, args, s } => {
+            let args = args
+                .iter()
+                .map(|arg| format!("{} {}", arg.t, arg.x.0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.line(format!("procedure {}({args})", q.0));
+            block(out, s);
+        }
+    }
+}
+
+impl fmt::Display for Prog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = Writer::default();
+        for (i, p) in self.procs().enumerate() {
+            if i > 0 {
+                out.line("");
+            }
+            proc(&mut out, p);
+        }
+        write!(f, "{}", out.finish())
+    }
+}
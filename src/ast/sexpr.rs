@@ -0,0 +1,167 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value as Json};
+use std::{iter::Peekable, str::Chars};
+
+const WIDTH: usize = 80;
+
+pub fn to_string(value: &Json) -> String {
+    let mut out = String::new();
+    write(&mut out, value, 0);
+    out.push('\n');
+    out
+}
+
+fn inline(value: &Json) -> String {
+    match value {
+        Json::Null => "nil".to_string(),
+        Json::Bool(b) => b.to_string(),
+        Json::Number(n) => n.to_string(),
+        Json::String(x) => Json::String(x.clone()).to_string(),
+        Json::Array(values) => {
+            let values = values.iter().map(inline).collect::<Vec<_>>();
+            format!("({})", values.join(" "))
+        }
+        Json::Object(map) if map.is_empty() => "(:)".to_string(),
+        Json::Object(map) => {
+            let values = map
+                .iter()
+                .map(|(key, value)| format!(":{key} {}", inline(value)))
+                .collect::<Vec<_>>();
+            format!("({})", values.join(" "))
+        }
+    }
+}
+
+fn write(out: &mut String, value: &Json, depth: usize) {
+    let text = inline(value);
+    if depth * 2 + text.len() <= WIDTH || !(value.is_array() || value.is_object()) {
+        out.push_str(&text);
+        return;
+    }
+    let pad = "  ".repeat(depth + 1);
+    out.push('(');
+    match value {
+        Json::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    out.push_str(&pad);
+                }
+                write(out, value, depth + 1);
+            }
+        }
+        Json::Object(map) => {
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    out.push_str(&pad);
+                }
+                out.push_str(&format!(":{key} "));
+                write(out, value, depth + 1);
+            }
+        }
+        _ => unreachable!("scalars are always written inline"),
+    }
+    out.push(')');
+}
+
+pub fn from_str(text: &str) -> Result<Json> {
+    let mut chars = text.chars().peekable();
+    let value = read(&mut chars)?;
+    skip_space(&mut chars);
+    match chars.next() {
+        Some(c) => bail!("unexpected {c:?} after the expression"),
+        None => Ok(value),
+    }
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => while chars.next_if(|&c| c != '\n').is_some() {},
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => break,
+        }
+    }
+}
+
+fn atom(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '(' && c != ')') {
+        value.push(c);
+    }
+    value
+}
+
+fn read(chars: &mut Peekable<Chars>) -> Result<Json> {
+    skip_space(chars);
+    match chars.peek() {
+        None => bail!("unexpected end of input"),
+        Some('(') => {
+            chars.next();
+            let mut values = Vec::new();
+            let mut map = Map::new();
+            let mut keyed = false;
+            loop {
+                skip_space(chars);
+                match chars.peek() {
+                    None => bail!("unclosed parenthesis"),
+                    Some(')') => {
+                        chars.next();
+                        break;
+                    }
+                    Some(':') => {
+                        chars.next();
+                        if !values.is_empty() {
+                            bail!("a list cannot mix keys and values");
+                        }
+                        keyed = true;
+                        let key = atom(chars);
+                        if key.is_empty() && map.is_empty() && chars.peek() == Some(&')') {
+                            continue;
+                        }
+                        map.insert(key, read(chars)?);
+                    }
+                    Some(_) => {
+                        if keyed {
+                            bail!("a list cannot mix keys and values");
+                        }
+                        values.push(read(chars)?);
+                    }
+                }
+            }
+            match keyed {
+                false => Ok(Json::Array(values)),
+                true => Ok(Json::Object(map)),
+            }
+        }
+        Some(')') => bail!("unexpected )"),
+        Some('"') => {
+            let mut text = String::from('"');
+            chars.next();
+            loop {
+                let c = chars.next().ok_or_else(|| anyhow!("unclosed string"))?;
+                text.push(c);
+                match c {
+                    '\\' => text.push(chars.next().ok_or_else(|| anyhow!("unclosed string"))?),
+                    '"' => break,
+                    _ => {}
+                }
+            }
+            Ok(serde_json::from_str(&text)?)
+        }
+        Some(_) => {
+            let atom = atom(chars);
+            match atom.as_str() {
+                "nil" => Ok(Json::Null),
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                _ => serde_json::from_str::<serde_json::Number>(&atom)
+                    .map(Json::Number)
+                    .map_err(|_| anyhow!("unexpected atom {atom}")),
+            }
+        }
+    }
+}
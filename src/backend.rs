@@ -0,0 +1,208 @@
+pub mod c;
+pub mod rust;
+pub mod wat;
+
+use crate::{
+    ast::ast_node::{Arg, Proc, Prog, Stm, Type},
+    tokenizer::span::Span,
+};
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Scalar,
+    Array(Option<usize>),
+    Stack,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Binding {
+    pub kind: Kind,
+    pub by_ref: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Scope(Vec<(String, Binding)>);
+
+impl Scope {
+    pub fn params(args: &LinkedList<Arg>, arrays: &[bool]) -> Self {
+        Self(
+            args.iter()
+                .zip(arrays)
+                .map(|(arg, &array)| {
+                    let kind = match arg.t {
+                        Type::Int if array => Kind::Array(None),
+                        Type::Int => Kind::Scalar,
+                        Type::Stack => Kind::Stack,
+                    };
+                    (arg.x.0.clone(), Binding { kind, by_ref: true })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn push(&mut self, x: &str, kind: Kind, by_ref: bool) {
+        self.0.push((x.to_string(), Binding { kind, by_ref }));
+    }
+
+    pub fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    pub fn get(&self, x: &str) -> Option<Binding> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(y, _)| y == x)
+            .map(|&(_, binding)| binding)
+    }
+}
+
+pub fn check_names(prog: &Prog) -> Result<()> {
+    let mut names = BTreeSet::new();
+    for p in &prog.ps {
+        if !names.insert(p.name()) {
+            bail!("procedure {} is defined twice", p.name());
+        }
+    }
+    Ok(())
+}
+
+pub fn array_params(prog: &Prog) -> BTreeMap<&str, Vec<bool>> {
+    let mut value = prog
+        .ps
+        .iter()
+        .filter_map(|p| match p {
+            Proc::Other { q, args, s } => Some((
+                q.0.as_str(),
+                args.iter().map(|arg| s.indexes(&arg.x)).collect::<Vec<_>>(),
+            )),
+            Proc::Main { .. } => None,
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for p in &prog.ps {
+            let Proc::Other { q, args, s } = p else {
+                continue;
+            };
+            let mut passed = Vec::new();
+            s.walk(&mut |s| match s {
+                Stm::Call { q, xs, .. } | Stm::Uncall { q, xs, .. } => {
+                    if let Some(arrays) = value.get(q.0.as_str()) {
+                        passed.extend(xs.iter().zip(arrays).filter(|(_, &a)| a).map(|(x, _)| &x.0));
+                    }
+                }
+                _ => {}
+            });
+            let arrays = value.get_mut(q.0.as_str()).expect("procedure is collected");
+            for (arg, array) in args.iter().zip(arrays.iter_mut()) {
+                if !*array && passed.contains(&&arg.x.0) {
+                    *array = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+    value
+}
+
+#[derive(Debug, Default)]
+pub struct Writer {
+    out: String,
+    indent: usize,
+}
+
+impl Writer {
+    pub fn line(&mut self, line: impl AsRef<str>) {
+        let line = line.as_ref();
+        let (leading, depth) = nesting(line);
+        if !line.is_empty() {
+            let indent = self.indent.saturating_sub(leading);
+            self.out.push_str(&"    ".repeat(indent));
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+        self.indent = self.indent.saturating_add_signed(depth);
+    }
+
+    pub fn block(&mut self, text: &str) {
+        for line in text.lines() {
+            if !line.is_empty() {
+                self.out.push_str(&"    ".repeat(self.indent));
+            }
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+    }
+
+    pub fn indent(&mut self) {
+        self.indent += 1;
+    }
+
+    pub fn dedent(&mut self) {
+        self.indent = self.indent.saturating_sub(1);
+    }
+
+    pub fn raw(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn nesting(line: &str) -> (usize, isize) {
+    let leading = line
+        .chars()
+        .take_while(|c| matches!(c, '}' | ')' | ']'))
+        .count();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        match (quoted, escaped, c) {
+            (true, true, _) => escaped = false,
+            (true, false, '\\') => escaped = true,
+            (_, _, '"') => quoted = !quoted,
+            (true, ..) => {}
+            (false, _, '{' | '(' | '[') => depth += 1,
+            (false, _, '}' | ')' | ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    (leading, depth)
+}
+
+pub fn fresh(used: &mut BTreeSet<String>, x: &str) -> String {
+    let mut name = x.to_string();
+    let mut i = 1;
+    while used.contains(&name) {
+        name = format!("{x}_{i}");
+        i += 1;
+    }
+    used.insert(name.clone());
+    name
+}
+
+pub fn sequence(stms: Vec<Stm>) -> Stm {
+    stms.into_iter()
+        .rev()
+        .reduce(|s_2, s_1| Stm::Sequence(Box::new(s_1), Box::new(s_2)))
+        .unwrap_or(Stm::Skip(Span::default()))
+}
+
+pub fn quote(x: &str) -> String {
+    x.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn escape(x: &str, reserved: &[&str]) -> String {
+    match reserved.contains(&x) || x.starts_with("janus_") {
+        true => format!("janus_var_{x}"),
+        false => x.to_string(),
+    }
+}
@@ -0,0 +1,576 @@
+use super::{array_params, check_names, escape, Binding, Kind, Scope, Writer};
+use crate::{
+    absint::{Check, Facts},
+    ast::ast_node::*,
+    interpreter::Direction,
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+const RESERVED: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "double", "else",
+    "enum", "exit", "extern", "float", "for", "free", "goto", "inline", "long", "main", "printf",
+    "register", "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+    "typedef", "union", "unsigned", "void", "volatile", "while",
+];
+
+const PRELUDE: &str = r#"#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#ifndef JANUS_CHECKS
+#define JANUS_CHECKS 1
+#endif
+
+typedef struct {
+    int32_t *data;
+    size_t len;
+    size_t cap;
+} janus_stack;
+
+#define JANUS_NIL (&(const janus_stack){0})
+
+static inline void janus_fail(const char *message) {
+    fprintf(stderr, "%s\n", message);
+    exit(1);
+}
+
+#if JANUS_CHECKS
+#define JANUS_ASSERT(c, message) do { if (!(c)) janus_fail(message); } while (0)
+#else
+#define JANUS_ASSERT(c, message) ((void)(message))
+#endif
+
+static inline size_t janus_index(int32_t i, size_t len, const char *message) {
+    JANUS_ASSERT(i >= 0 && (size_t)i < len, message);
+    return (size_t)i;
+}
+
+static inline int32_t janus_add(int32_t a, int32_t b) { return (int32_t)((uint32_t)a + (uint32_t)b); }
+static inline int32_t janus_sub(int32_t a, int32_t b) { return (int32_t)((uint32_t)a - (uint32_t)b); }
+static inline int32_t janus_mul(int32_t a, int32_t b) { return (int32_t)((uint32_t)a * (uint32_t)b); }
+
+static inline int32_t janus_div(int32_t a, int32_t b) {
+    if (b == 0) janus_fail("division by zero");
+    if (a == INT32_MIN && b == -1) return INT32_MIN;
+    return a / b;
+}
+
+static inline int32_t janus_mod(int32_t a, int32_t b) {
+    if (b == 0) janus_fail("division by zero");
+    if (a == INT32_MIN && b == -1) return 0;
+    return a % b;
+}
+
+static inline void janus_push(int32_t *x, janus_stack *s) {
+    if (s->len == s->cap) {
+        s->cap = s->cap ? s->cap * 2 : 8;
+        s->data = realloc(s->data, s->cap * sizeof(int32_t));
+        if (!s->data) janus_fail("out of memory");
+    }
+    s->data[s->len++] = *x;
+    *x = 0;
+}
+
+static inline void janus_pop(int32_t *x, janus_stack *s, const char *message) {
+    JANUS_ASSERT(*x == 0, message);
+    if (s->len == 0) janus_fail("pop from an empty stack");
+    *x = s->data[--s->len];
+}
+
+static inline int32_t janus_top(const janus_stack *s) {
+    if (s->len == 0) janus_fail("top of an empty stack");
+    return s->data[s->len - 1];
+}
+
+static inline int janus_stack_eq(const janus_stack *a, const janus_stack *b) {
+    return a->len == b->len && (a->len == 0 || memcmp(a->data, b->data, a->len * sizeof(int32_t)) == 0);
+}
+
+static inline void janus_print_array(const char *x, const int32_t *ns, size_t len) {
+    printf("%s = [", x);
+    for (size_t i = 0; i < len; i++) printf(i ? ", %d" : "%d", ns[i]);
+    printf("]\n");
+}
+
+static inline void janus_print_stack(const char *x, const janus_stack *s) {
+    if (s->len == 0) {
+        printf("%s = nil\n", x);
+        return;
+    }
+    printf("%s = <", x);
+    for (size_t i = 0; i < s->len; i++) printf(i ? ", %d" : "%d", s->data[i]);
+    printf(">\n");
+}
+"#;
+
+pub fn emit(prog: &Prog, checks: bool, facts: &Facts) -> Result<String> {
+    check_names(prog)?;
+    let mut w = Writer::default();
+    if !checks {
+        w.line("#define JANUS_CHECKS 0");
+    }
+    w.raw(PRELUDE);
+
+    let arrays = array_params(prog);
+    let mut signatures = Vec::new();
+    for p in &prog.ps {
+        if let Proc::Other { q, args, .. } = p {
+            let scope = Scope::params(args, &arrays[q.0.as_str()]);
+            let params = args
+                .iter()
+                .map(|arg| {
+                    let binding = scope.get(&arg.x.0).expect("parameter is in scope");
+                    let name = escape(&arg.x.0, RESERVED);
+                    match binding.kind {
+                        Kind::Stack => format!("janus_stack *{name}"),
+                        Kind::Array(_) => format!("int32_t *{name}, size_t janus_len_{name}"),
+                        Kind::Scalar => format!("int32_t *{name}"),
+                    }
+                })
+                .collect::<Vec<_>>();
+            let params = match params.is_empty() {
+                true => "void".to_string(),
+                false => params.join(", "),
+            };
+            for direction in [Direction::Forward, Direction::Backward] {
+                signatures.push((p, function_name(&q.0, direction), params.clone(), direction));
+            }
+        }
+    }
+
+    w.line("");
+    for (_, name, params, _) in &signatures {
+        w.line(format!("void {name}({params});"));
+    }
+    for (p, name, params, direction) in &signatures {
+        let Proc::Other { q, args, s } = p else {
+            continue;
+        };
+        let mut emitter = Emitter {
+            prog,
+            proc: p.name(),
+            scope: Scope::params(args, &arrays[q.0.as_str()]),
+            arrays: &arrays,
+            facts,
+            direction: *direction,
+            locals: Vec::new(),
+        };
+        w.line("");
+        w.line(format!("void {name}({params}) {{"));
+        emitter.stm(&mut w, s, *direction)?;
+        w.line("}");
+    }
+
+    let Proc::Main { main_stuff, s, .. } = &prog.p_main else {
+        bail!("main must be the first procedure");
+    };
+    let mut emitter = Emitter {
+        prog,
+        proc: "main",
+        scope: Scope::default(),
+        arrays: &arrays,
+        facts,
+        direction: Direction::Forward,
+        locals: Vec::new(),
+    };
+    w.line("");
+    w.line("int main(void) {");
+    for stuff in main_stuff {
+        match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => {
+                emitter.scope.push(&x.0, Kind::Scalar, false);
+                w.line(format!("int32_t {} = 0;", escape(&x.0, RESERVED)));
+            }
+            MainStuff::Int(Vdec::Array { x, c }) => {
+                let len = usize::try_from(c.0)
+                    .ok()
+                    .filter(|&len| len > 0)
+                    .ok_or_else(|| anyhow!("{}: invalid array size", x.1))?;
+                emitter.scope.push(&x.0, Kind::Array(Some(len)), false);
+                w.line(format!(
+                    "int32_t {}[{len}] = {{0}};",
+                    escape(&x.0, RESERVED)
+                ));
+            }
+            MainStuff::Stack(x) => {
+                emitter.scope.push(&x.0, Kind::Stack, false);
+                w.line(format!("janus_stack {} = {{0}};", escape(&x.0, RESERVED)));
+            }
+        }
+    }
+    emitter.stm(&mut w, s, Direction::Forward)?;
+    for stuff in main_stuff {
+        match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => w.line(format!(
+                "printf(\"{} = %d\\n\", {});",
+                x.0,
+                escape(&x.0, RESERVED)
+            )),
+            MainStuff::Int(Vdec::Array { x, c }) => w.line(format!(
+                "janus_print_array(\"{}\", {}, {});",
+                x.0,
+                escape(&x.0, RESERVED),
+                c.0
+            )),
+            MainStuff::Stack(x) => w.line(format!(
+                "janus_print_stack(\"{}\", &{});",
+                x.0,
+                escape(&x.0, RESERVED)
+            )),
+        }
+    }
+    w.line("return 0;");
+    w.line("}");
+
+    Ok(w.finish())
+}
+
+fn function_name(q: &str, direction: Direction) -> String {
+    match direction {
+        Direction::Forward => format!("janus_fwd_{q}"),
+        Direction::Backward => format!("janus_bwd_{q}"),
+    }
+}
+
+struct Emitter<'a> {
+    prog: &'a Prog,
+    proc: &'a str,
+    scope: Scope,
+    arrays: &'a BTreeMap<&'a str, Vec<bool>>,
+    facts: &'a Facts,
+    direction: Direction,
+    locals: Vec<(String, String)>,
+}
+
+impl Emitter<'_> {
+    fn binding(&self, x: &Var) -> Result<Binding> {
+        self.scope
+            .get(&x.0)
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn name(&self, x: &Var) -> String {
+        match self.locals.iter().rev().find(|(y, _)| *y == x.0) {
+            Some((_, name)) => name.clone(),
+            None => escape(&x.0, RESERVED),
+        }
+    }
+
+    fn lvalue(&self, x: &Var) -> Result<String> {
+        let binding = self.binding(x)?;
+        let name = self.name(x);
+        match (binding.kind, binding.by_ref) {
+            (Kind::Scalar, true) => Ok(format!("(*{name})")),
+            (Kind::Scalar, false) => Ok(name),
+            _ => bail!("{}: {} is not a scalar", x.1, x.0),
+        }
+    }
+
+    fn pointer(&self, x: &Var) -> Result<String> {
+        let binding = self.binding(x)?;
+        let name = self.name(x);
+        match (binding.kind, binding.by_ref) {
+            (Kind::Array(_), _) | (_, true) => Ok(name),
+            (_, false) => Ok(format!("&{name}")),
+        }
+    }
+
+    fn len(&self, x: &Var) -> Result<String> {
+        match self.binding(x)?.kind {
+            Kind::Array(Some(len)) => Ok(len.to_string()),
+            Kind::Array(None) => Ok(format!("janus_len_{}", self.name(x))),
+            _ => bail!("{}: {} is not an array", x.1, x.0),
+        }
+    }
+
+    fn message(&self, keyword: &str, span: Span) -> String {
+        format!("\"{}: assertion {keyword} failed at {span}\"", self.proc)
+    }
+
+    fn stm(&mut self, w: &mut Writer, s: &Stm, direction: Direction) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let x = self.lvalue(x)?;
+                let e = self.int(e)?;
+                w.line(update(&x, *mod_op, &e, direction));
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let index = self.index(x, e_index, *span)?;
+                let e = self.int(e)?;
+                w.line("{");
+                w.line(format!("int32_t *janus_p = &{index};"));
+                w.line(update("*janus_p", *mod_op, &e, direction));
+                w.line("}");
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_if, e_fi, "fi"),
+                    Direction::Backward => (e_fi, e_if, "if"),
+                };
+                w.line("{");
+                w.line(format!("int janus_c = ({}) != 0;", self.int(e_entry)?));
+                w.line("if (janus_c) {");
+                self.stm(w, s_then, direction)?;
+                w.line("} else {");
+                self.stm(w, s_else, direction)?;
+                w.line("}");
+                if !self.facts.holds(*span, Check::Fi, direction) {
+                    w.line(format!(
+                        "JANUS_ASSERT((({}) != 0) == janus_c, {});",
+                        self.int(e_exit)?,
+                        self.message(keyword, *span)
+                    ));
+                }
+                w.line("}");
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_from, e_until, "from"),
+                    Direction::Backward => (e_until, e_from, "until"),
+                };
+                let entry = self.int(e_entry)?;
+                let message = self.message(keyword, *span);
+                if !self.facts.holds(*span, Check::From, direction) {
+                    w.line(format!("JANUS_ASSERT(({entry}) != 0, {message});"));
+                }
+                w.line("for (;;) {");
+                self.stm(w, s_do, direction)?;
+                w.line(format!("if (({}) != 0) break;", self.int(e_exit)?));
+                self.stm(w, s_loop, direction)?;
+                if !self.facts.holds(*span, Check::Reentry, direction) {
+                    w.line(format!("JANUS_ASSERT(({entry}) == 0, {message});"));
+                }
+                w.line("}");
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                let (px, pxs) = (self.pointer(x)?, self.pointer(xs)?);
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                match push {
+                    true => w.line(format!("janus_push({px}, {pxs});")),
+                    false => w.line(format!(
+                        "janus_pop({px}, {pxs}, {});",
+                        self.message("pop", *span)
+                    )),
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_local, e_delocal, "delocal"),
+                    Direction::Backward => (e_delocal, e_local, "local"),
+                };
+                let name = format!("janus_local{}_{}", self.locals.len(), x_local.0);
+                let message = self.message(keyword, *span);
+                let checked = !self.facts.holds(*span, Check::Delocal, direction);
+                w.line("{");
+                match t_local {
+                    Type::Int => {
+                        w.line(format!("int32_t {name} = {};", self.int(e_entry)?));
+                        self.scope.push(&x_local.0, Kind::Scalar, false);
+                        self.locals.push((x_local.0.clone(), name.clone()));
+                        self.stm(w, s, direction)?;
+                        let exit = self.int(e_exit)?;
+                        if checked {
+                            w.line(format!("JANUS_ASSERT({name} == ({exit}), {message});"));
+                        }
+                    }
+                    Type::Stack => {
+                        if !matches!(e_entry, Exp::Nil) {
+                            bail!("{span}: stack locals must be initialised with nil");
+                        }
+                        w.line(format!("janus_stack {name} = {{0}};"));
+                        self.scope.push(&x_local.0, Kind::Stack, false);
+                        self.locals.push((x_local.0.clone(), name.clone()));
+                        self.stm(w, s, direction)?;
+                        if checked {
+                            let exit = self.stack(e_exit)?;
+                            w.line(format!(
+                                "JANUS_ASSERT(janus_stack_eq(&{name}, {exit}), {message});"
+                            ));
+                        }
+                        w.line(format!("free({name}.data);"));
+                    }
+                }
+                self.scope.pop();
+                self.locals.pop();
+                w.line("}");
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, .. }) = self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if args.len() != xs.len() {
+                    bail!("{span}: {} expects {} arguments", q.0, args.len());
+                }
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                let mut pointers = Vec::new();
+                for (x, &array) in xs.iter().zip(&self.arrays[q.0.as_str()]) {
+                    pointers.push(self.pointer(x)?);
+                    if array {
+                        pointers.push(self.len(x)?);
+                    }
+                }
+                w.line(format!(
+                    "{}({});",
+                    function_name(&q.0, direction),
+                    pointers.join(", ")
+                ));
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(w, s_1, direction)?;
+                    self.stm(w, s_2, direction)?;
+                }
+                Direction::Backward => {
+                    self.stm(w, s_2, direction)?;
+                    self.stm(w, s_1, direction)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn index(&self, x: &Var, e: &Exp, span: Span) -> Result<String> {
+        let binding = self.binding(x)?;
+        let Kind::Array(_) = binding.kind else {
+            bail!("{span}: {} is not an array", x.0);
+        };
+        let name = self.name(x);
+        let index = self.int(e)?;
+        if self.facts.holds(span, Check::Index, self.direction) {
+            return Ok(format!("{name}[{index}]"));
+        }
+        Ok(format!(
+            "{name}[janus_index({index}, {}, \"{}: index out of bounds at {span}\")]",
+            self.len(x)?,
+            self.proc
+        ))
+    }
+
+    fn int(&self, e: &Exp) -> Result<String> {
+        let value = match e {
+            Exp::Constant(c) => c.0.to_string(),
+            Exp::Variable(x) => self.lvalue(x)?,
+            Exp::Indexed { x, e } => self.index(x, e, x.1)?,
+            Exp::BinOp(e_1, op @ (Op::Equal | Op::NotEqual), e_2) if self.is_stack(e_1) => {
+                let eq = format!("janus_stack_eq({}, {})", self.stack(e_1)?, self.stack(e_2)?);
+                match op {
+                    Op::Equal => eq,
+                    _ => format!("!{eq}"),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (a, b) = (self.int(e_1)?, self.int(e_2)?);
+                match op {
+                    Op::Add => format!("janus_add({a}, {b})"),
+                    Op::Sub => format!("janus_sub({a}, {b})"),
+                    Op::Mul => format!("janus_mul({a}, {b})"),
+                    Op::Div => format!("janus_div({a}, {b})"),
+                    Op::Mod => format!("janus_mod({a}, {b})"),
+                    Op::Xor => format!("({a} ^ {b})"),
+                    Op::And => format!("({a} & {b})"),
+                    Op::Or => format!("({a} | {b})"),
+                    Op::And2 => format!("((({a}) != 0) & (({b}) != 0))"),
+                    Op::Or2 => format!("((({a}) != 0) | (({b}) != 0))"),
+                    Op::Less => format!("({a} < {b})"),
+                    Op::Greater => format!("({a} > {b})"),
+                    Op::Equal => format!("({a} == {b})"),
+                    Op::NotEqual => format!("({a} != {b})"),
+                    Op::LessEqual => format!("({a} <= {b})"),
+                    Op::GreaterEqual => format!("({a} >= {b})"),
+                }
+            }
+            Exp::Empty(x) => format!("(({})->len == 0)", self.stack_var(x)?),
+            Exp::Top(x) => format!("janus_top({})", self.stack_var(x)?),
+            Exp::Nil => bail!("nil is not an integer"),
+        };
+        Ok(value)
+    }
+
+    fn is_stack(&self, e: &Exp) -> bool {
+        match e {
+            Exp::Nil => true,
+            Exp::Variable(x) => self
+                .scope
+                .get(&x.0)
+                .is_some_and(|binding| binding.kind == Kind::Stack),
+            _ => false,
+        }
+    }
+
+    fn stack(&self, e: &Exp) -> Result<String> {
+        match e {
+            Exp::Nil => Ok("JANUS_NIL".to_string()),
+            Exp::Variable(x) => self.stack_var(x),
+            _ => bail!("expected a stack expression"),
+        }
+    }
+
+    fn stack_var(&self, x: &Var) -> Result<String> {
+        match self.binding(x)?.kind {
+            Kind::Stack => self.pointer(x),
+            _ => bail!("{}: {} is not a stack", x.1, x.0),
+        }
+    }
+}
+
+fn update(x: &str, mod_op: ModOp, e: &str, direction: Direction) -> String {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => {
+            format!("{x} = janus_add({x}, {e});")
+        }
+        (ModOp::Sub, Direction::Forward) | (ModOp::Add, Direction::Backward) => {
+            format!("{x} = janus_sub({x}, {e});")
+        }
+        (ModOp::Xor, _) => format!("{x} ^= {e};"),
+    }
+}
@@ -0,0 +1,431 @@
+use super::{array_params, check_names, escape, Binding, Kind, Scope, Writer};
+use crate::{
+    ast::{ast_node::*, load},
+    interpreter::Direction,
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::{fs, path::Path};
+
+const RESERVED: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "try",
+    "type", "unsafe", "use", "where", "while", "yield",
+];
+
+const ALLOW: &str =
+    "#[allow(unused_mut, unused_parens, unused_variables, non_snake_case, clippy::all)]";
+
+pub fn generate(src: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<()> {
+    let prog = load(src)?;
+    fs::write(out, emit(&prog)?)?;
+    Ok(())
+}
+
+pub fn emit(prog: &Prog) -> Result<String> {
+    check_names(prog)?;
+    let mut w = Writer::default();
+    let arrays = array_params(prog);
+
+    for p in &prog.ps {
+        let Proc::Other { q, args, s } = p else {
+            continue;
+        };
+        let scope = Scope::params(args, &arrays[q.0.as_str()]);
+        let params = args
+            .iter()
+            .map(|arg| {
+                let binding = scope.get(&arg.x.0).expect("parameter is in scope");
+                let t = match binding.kind {
+                    Kind::Scalar => "&mut i32",
+                    Kind::Array(_) => "&mut [i32]",
+                    Kind::Stack => "&mut Vec<i32>",
+                };
+                format!("{}: {t}", escape(&arg.x.0, RESERVED))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for direction in [Direction::Forward, Direction::Backward] {
+            let mut emitter = Emitter {
+                prog,
+                proc: &q.0,
+                scope: Scope::params(args, &arrays[q.0.as_str()]),
+            };
+            w.line(ALLOW);
+            w.line(format!(
+                "pub fn {}({params}) {{",
+                function_name(&q.0, direction)
+            ));
+            emitter.stm(&mut w, s, direction)?;
+            w.line("}");
+            w.line("");
+        }
+    }
+
+    let Proc::Main { main_stuff, s, .. } = &prog.p_main else {
+        bail!("main must be the first procedure");
+    };
+    w.line("#[derive(Debug, Clone, Default, PartialEq, Eq)]");
+    w.line("pub struct Main {");
+    for stuff in main_stuff {
+        let (x, t) = match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => (x, "i32".to_string()),
+            MainStuff::Int(Vdec::Array { x, c }) => {
+                let len =
+                    usize::try_from(c.0).map_err(|_| anyhow!("{}: invalid array size", x.1))?;
+                (x, format!("[i32; {len}]"))
+            }
+            MainStuff::Stack(x) => (x, "Vec<i32>".to_string()),
+        };
+        w.line(format!("pub {}: {t},", escape(&x.0, RESERVED)));
+    }
+    w.line("}");
+    w.line("");
+    w.line("impl Main {");
+    for direction in [Direction::Forward, Direction::Backward] {
+        w.line(ALLOW);
+        w.line(format!(
+            "pub fn {}(&mut self) {{",
+            direction_name(direction)
+        ));
+        for stuff in main_stuff {
+            let x = match stuff {
+                MainStuff::Int(Vdec::Scalar(x) | Vdec::Array { x, .. }) | MainStuff::Stack(x) => {
+                    escape(&x.0, RESERVED)
+                }
+            };
+            w.line(format!("let {x} = &mut self.{x};"));
+        }
+        let mut emitter = Emitter {
+            prog,
+            proc: "main",
+            scope: Scope::default(),
+        };
+        for stuff in main_stuff {
+            let (x, kind) = match stuff {
+                MainStuff::Int(Vdec::Scalar(x)) => (x, Kind::Scalar),
+                MainStuff::Int(Vdec::Array { x, .. }) => (x, Kind::Array(None)),
+                MainStuff::Stack(x) => (x, Kind::Stack),
+            };
+            emitter.scope.push(&x.0, kind, true);
+        }
+        emitter.stm(&mut w, s, direction)?;
+        w.line("}");
+    }
+    w.line("}");
+
+    Ok(w.finish())
+}
+
+fn function_name(q: &str, direction: Direction) -> String {
+    format!("{q}_{}", direction_name(direction))
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Forward => "fwd",
+        Direction::Backward => "bwd",
+    }
+}
+
+struct Emitter<'a> {
+    prog: &'a Prog,
+    proc: &'a str,
+    scope: Scope,
+}
+
+impl Emitter<'_> {
+    fn binding(&self, x: &Var) -> Result<Binding> {
+        self.scope
+            .get(&x.0)
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn place(&self, x: &Var) -> Result<String> {
+        let binding = self.binding(x)?;
+        let name = escape(&x.0, RESERVED);
+        match binding.by_ref {
+            true => Ok(format!("(*{name})")),
+            false => Ok(name),
+        }
+    }
+
+    fn reborrow(&self, x: &Var) -> Result<String> {
+        let binding = self.binding(x)?;
+        let name = escape(&x.0, RESERVED);
+        match binding.by_ref {
+            true => Ok(format!("&mut *{name}")),
+            false => Ok(format!("&mut {name}")),
+        }
+    }
+
+    fn message(&self, keyword: &str, span: Span) -> String {
+        format!("\"{}: assertion {keyword} failed at {span}\"", self.proc)
+    }
+
+    fn stm(&mut self, w: &mut Writer, s: &Stm, direction: Direction) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                if self.binding(x)?.kind != Kind::Scalar {
+                    bail!("{span}: {} is not a scalar", x.0);
+                }
+                let x = self.place(x)?;
+                w.line(update(&x, *mod_op, &self.int(e)?, direction));
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let index = self.index(x, e_index, *span)?;
+                w.line(update(&index, *mod_op, &self.int(e)?, direction));
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_if, e_fi, "fi"),
+                    Direction::Backward => (e_fi, e_if, "if"),
+                };
+                w.line(format!("let janus_c = {} != 0;", self.int(e_entry)?));
+                w.line("if janus_c {");
+                self.stm(w, s_then, direction)?;
+                w.line("} else {");
+                self.stm(w, s_else, direction)?;
+                w.line("}");
+                w.line(format!(
+                    "assert!(({} != 0) == janus_c, {});",
+                    self.int(e_exit)?,
+                    self.message(keyword, *span)
+                ));
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_from, e_until, "from"),
+                    Direction::Backward => (e_until, e_from, "until"),
+                };
+                let entry = self.int(e_entry)?;
+                let message = self.message(keyword, *span);
+                w.line(format!("assert!({entry} != 0, {message});"));
+                w.line("loop {");
+                self.stm(w, s_do, direction)?;
+                w.line(format!("if {} != 0 {{", self.int(e_exit)?));
+                w.line("break;");
+                w.line("}");
+                self.stm(w, s_loop, direction)?;
+                w.line(format!("assert!({entry} == 0, {message});"));
+                w.line("}");
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                let (px, pxs) = (self.place(x)?, self.stack_var(xs)?);
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                match push {
+                    true => w.line(format!("{pxs}.push(std::mem::take(&mut {px}));")),
+                    false => {
+                        let message = self.message("pop", *span);
+                        w.line(format!("assert!({px} == 0, {message});"));
+                        w.line(format!(
+                            "{px} = {pxs}.pop().expect(\"{}: pop from an empty stack\");",
+                            self.proc
+                        ));
+                    }
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_local, e_delocal, "delocal"),
+                    Direction::Backward => (e_delocal, e_local, "local"),
+                };
+                let name = escape(&x_local.0, RESERVED);
+                let message = self.message(keyword, *span);
+                w.line("{");
+                match t_local {
+                    Type::Int => {
+                        w.line(format!("let mut {name}: i32 = {};", self.int(e_entry)?));
+                        self.scope.push(&x_local.0, Kind::Scalar, false);
+                        self.stm(w, s, direction)?;
+                        let exit = self.int(e_exit)?;
+                        w.line(format!("assert!({name} == {exit}, {message});"));
+                    }
+                    Type::Stack => {
+                        if !matches!(e_entry, Exp::Nil) {
+                            bail!("{span}: stack locals must be initialised with nil");
+                        }
+                        w.line(format!("let mut {name}: Vec<i32> = Vec::new();"));
+                        self.scope.push(&x_local.0, Kind::Stack, false);
+                        self.stm(w, s, direction)?;
+                        let exit = self.stack(e_exit)?;
+                        w.line(format!("assert!({name} == {exit}, {message});"));
+                    }
+                }
+                self.scope.pop();
+                w.line("}");
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, .. }) = self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if args.len() != xs.len() {
+                    bail!("{span}: {} expects {} arguments", q.0, args.len());
+                }
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                let xs = xs
+                    .iter()
+                    .map(|x| self.reborrow(x))
+                    .collect::<Result<Vec<_>>>()?;
+                w.line(format!(
+                    "{}({});",
+                    function_name(&q.0, direction),
+                    xs.join(", ")
+                ));
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(w, s_1, direction)?;
+                    self.stm(w, s_2, direction)?;
+                }
+                Direction::Backward => {
+                    self.stm(w, s_2, direction)?;
+                    self.stm(w, s_1, direction)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn index(&self, x: &Var, e: &Exp, span: Span) -> Result<String> {
+        let Kind::Array(_) = self.binding(x)?.kind else {
+            bail!("{span}: {} is not an array", x.0);
+        };
+        Ok(format!("{}[{} as usize]", self.place(x)?, self.int(e)?))
+    }
+
+    fn int(&self, e: &Exp) -> Result<String> {
+        let value = match e {
+            Exp::Constant(c) if c.0 < 0 => format!("({}i32)", c.0),
+            Exp::Constant(c) => format!("{}i32", c.0),
+            Exp::Variable(x) => match self.binding(x)?.kind {
+                Kind::Scalar => self.place(x)?,
+                _ => bail!("{}: {} is not a scalar", x.1, x.0),
+            },
+            Exp::Indexed { x, e } => self.index(x, e, x.1)?,
+            Exp::BinOp(e_1, op @ (Op::Equal | Op::NotEqual), e_2) if self.is_stack(e_1) => {
+                let (a, b) = (self.stack(e_1)?, self.stack(e_2)?);
+                match op {
+                    Op::Equal => format!("({a} == {b}) as i32"),
+                    _ => format!("({a} != {b}) as i32"),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (a, b) = (self.int(e_1)?, self.int(e_2)?);
+                match op {
+                    Op::Add => format!("{a}.wrapping_add({b})"),
+                    Op::Sub => format!("{a}.wrapping_sub({b})"),
+                    Op::Mul => format!("{a}.wrapping_mul({b})"),
+                    Op::Div => format!("{a}.wrapping_div({b})"),
+                    Op::Mod => format!("{a}.wrapping_rem({b})"),
+                    Op::Xor => format!("({a} ^ {b})"),
+                    Op::And => format!("({a} & {b})"),
+                    Op::Or => format!("({a} | {b})"),
+                    Op::And2 => format!("(({a} != 0) & ({b} != 0)) as i32"),
+                    Op::Or2 => format!("(({a} != 0) | ({b} != 0)) as i32"),
+                    Op::Less => format!("({a} < {b}) as i32"),
+                    Op::Greater => format!("({a} > {b}) as i32"),
+                    Op::Equal => format!("({a} == {b}) as i32"),
+                    Op::NotEqual => format!("({a} != {b}) as i32"),
+                    Op::LessEqual => format!("({a} <= {b}) as i32"),
+                    Op::GreaterEqual => format!("({a} >= {b}) as i32"),
+                }
+            }
+            Exp::Empty(x) => format!("{}.is_empty() as i32", self.stack_var(x)?),
+            Exp::Top(x) => format!(
+                "*{}.last().expect(\"{}: top of an empty stack\")",
+                self.stack_var(x)?,
+                self.proc
+            ),
+            Exp::Nil => bail!("nil is not an integer"),
+        };
+        Ok(value)
+    }
+
+    fn is_stack(&self, e: &Exp) -> bool {
+        match e {
+            Exp::Nil => true,
+            Exp::Variable(x) => self
+                .scope
+                .get(&x.0)
+                .is_some_and(|binding| binding.kind == Kind::Stack),
+            _ => false,
+        }
+    }
+
+    fn stack(&self, e: &Exp) -> Result<String> {
+        match e {
+            Exp::Nil => Ok("Vec::<i32>::new()".to_string()),
+            Exp::Variable(x) => self.stack_var(x),
+            _ => bail!("expected a stack expression"),
+        }
+    }
+
+    fn stack_var(&self, x: &Var) -> Result<String> {
+        match self.binding(x)?.kind {
+            Kind::Stack => self.place(x),
+            _ => bail!("{}: {} is not a stack", x.1, x.0),
+        }
+    }
+}
+
+fn update(x: &str, mod_op: ModOp, e: &str, direction: Direction) -> String {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => {
+            format!("{x} = {x}.wrapping_add({e});")
+        }
+        (ModOp::Sub, Direction::Forward) | (ModOp::Add, Direction::Backward) => {
+            format!("{x} = {x}.wrapping_sub({e});")
+        }
+        (ModOp::Xor, _) => format!("{x} ^= {e};"),
+    }
+}
@@ -0,0 +1,654 @@
+use super::{array_params, check_names, escape, Kind, Scope, Writer};
+use crate::{ast::ast_node::*, interpreter::Direction, tokenizer::span::Span};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+const STACK_CAPACITY: u32 = 1024;
+const LOCAL_PAGES: u32 = 16;
+const PAGE: u32 = 65536;
+
+const PRELUDE: &str = r#"(func $janus_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.const -1
+    i32.eq
+    if (result i32)
+        i32.const 0
+        local.get $a
+        i32.sub
+    else
+        local.get $a
+        local.get $b
+        i32.div_s
+    end)
+
+(func $janus_cell (param $s i32) (param $i i32) (result i32)
+    local.get $s
+    i32.load offset=4
+    local.get $i
+    i32.const 4
+    i32.mul
+    i32.add)
+
+(func $janus_push (param $x i32) (param $s i32)
+    (local $len i32)
+    local.get $s
+    i32.load
+    local.tee $len
+    global.get $janus_stack_capacity
+    i32.ge_u
+    if unreachable end
+    local.get $s
+    local.get $len
+    call $janus_cell
+    local.get $x
+    i32.load
+    i32.store
+    local.get $s
+    local.get $len
+    i32.const 1
+    i32.add
+    i32.store
+    local.get $x
+    i32.const 0
+    i32.store)
+
+(func $janus_pop (param $x i32) (param $s i32)
+    (local $len i32)
+    local.get $x
+    i32.load
+    if unreachable end
+    local.get $s
+    i32.load
+    local.tee $len
+    i32.eqz
+    if unreachable end
+    local.get $s
+    local.get $len
+    i32.const 1
+    i32.sub
+    local.tee $len
+    i32.store
+    local.get $x
+    local.get $s
+    local.get $len
+    call $janus_cell
+    i32.load
+    i32.store)
+
+(func $janus_top (param $s i32) (result i32)
+    (local $len i32)
+    local.get $s
+    i32.load
+    local.tee $len
+    i32.eqz
+    if unreachable end
+    local.get $s
+    local.get $len
+    i32.const 1
+    i32.sub
+    call $janus_cell
+    i32.load)
+
+(func $janus_stack_eq (param $a i32) (param $b i32) (result i32)
+    (local $i i32)
+    local.get $a
+    i32.load
+    local.get $b
+    i32.load
+    i32.ne
+    if
+        i32.const 0
+        return
+    end
+    block
+        loop
+            local.get $i
+            local.get $a
+            i32.load
+            i32.ge_u
+            br_if 1
+            local.get $a
+            local.get $i
+            call $janus_cell
+            i32.load
+            local.get $b
+            local.get $i
+            call $janus_cell
+            i32.load
+            i32.ne
+            if
+                i32.const 0
+                return
+            end
+            local.get $i
+            i32.const 1
+            i32.add
+            local.set $i
+            br 0
+        end
+    end
+    i32.const 1)
+"#;
+
+pub fn emit(prog: &Prog) -> Result<String> {
+    check_names(prog)?;
+    let Proc::Main { main_stuff, s, .. } = &prog.p_main else {
+        bail!("main must be the first procedure");
+    };
+
+    let mut globals = Vec::new();
+    let mut stacks = Vec::new();
+    let mut top = 8;
+    for stuff in main_stuff {
+        let (x, kind, size) = match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => (x, Kind::Scalar, 4),
+            MainStuff::Int(Vdec::Array { x, c }) => {
+                let len = u32::try_from(c.0)
+                    .ok()
+                    .filter(|&len| len > 0)
+                    .ok_or_else(|| anyhow!("{}: invalid array size", x.1))?;
+                (x, Kind::Array(Some(len as usize)), len * 4)
+            }
+            MainStuff::Stack(x) => {
+                stacks.push(top);
+                (x, Kind::Stack, 8 + STACK_CAPACITY * 4)
+            }
+        };
+        if prog.ps.iter().any(|p| {
+            [Direction::Forward, Direction::Backward]
+                .iter()
+                .any(|&direction| function_name(p.name(), direction) == x.0)
+        }) {
+            bail!("{}: {} clashes with an exported procedure", x.1, x.0);
+        }
+        globals.push((x.0.clone(), kind, format!("i32.const {top}")));
+        top += size;
+    }
+
+    let mut w = Writer::default();
+    w.line("(module");
+    let pages = top.div_ceil(PAGE) + LOCAL_PAGES;
+    w.line(format!("(memory (export \"memory\") {pages})"));
+    w.line(format!(
+        "(global $janus_stack_capacity i32 (i32.const {STACK_CAPACITY}))"
+    ));
+    w.line(format!("(global $janus_sp (mut i32) (i32.const {top}))"));
+    for (x, _, address) in &globals {
+        w.line(format!("(global (export \"{x}\") i32 ({address}))"));
+    }
+    for address in stacks {
+        w.line(format!(
+            "(data (i32.const {}) \"{}\")",
+            address + 4,
+            le_bytes(address + 8)
+        ));
+    }
+    w.line("");
+    w.block(PRELUDE);
+
+    let arrays = array_params(prog);
+    for p in &prog.ps {
+        let Proc::Other { q, args, s } = p else {
+            continue;
+        };
+        let params = args
+            .iter()
+            .zip(&arrays[q.0.as_str()])
+            .map(|(arg, &array)| match array {
+                true => format!(
+                    " (param ${x} i32) (param ${x}.len i32)",
+                    x = escape(&arg.x.0, &[])
+                ),
+                false => format!(" (param ${} i32)", escape(&arg.x.0, &[])),
+            })
+            .collect::<String>();
+        for direction in [Direction::Forward, Direction::Backward] {
+            let mut emitter = Emitter {
+                prog,
+                scope: Scope::params(args, &arrays[q.0.as_str()]),
+                addresses: args
+                    .iter()
+                    .map(|arg| {
+                        let address = format!("local.get ${}", escape(&arg.x.0, &[]));
+                        (arg.x.0.clone(), address)
+                    })
+                    .collect(),
+                locals: Vec::new(),
+                arrays: &arrays,
+            };
+            function(&mut w, &mut emitter, &q.0, &params, s, direction)?;
+        }
+    }
+
+    for direction in [Direction::Forward, Direction::Backward] {
+        let mut emitter = Emitter {
+            prog,
+            scope: Scope::default(),
+            addresses: Vec::new(),
+            locals: Vec::new(),
+            arrays: &arrays,
+        };
+        for (x, kind, address) in &globals {
+            emitter.scope.push(x, *kind, false);
+            emitter.addresses.push((x.clone(), address.clone()));
+        }
+        function(&mut w, &mut emitter, "main", "", s, direction)?;
+    }
+
+    w.line(")");
+    Ok(w.finish())
+}
+
+fn function(
+    w: &mut Writer,
+    emitter: &mut Emitter,
+    q: &str,
+    params: &str,
+    s: &Stm,
+    direction: Direction,
+) -> Result<()> {
+    let mut body = Writer::default();
+    emitter.stm(&mut body, s, direction)?;
+
+    let name = function_name(q, direction);
+    w.line("");
+    w.line(format!("(func ${name} (export \"{name}\"){params}"));
+    w.line("(local $janus_p i32)");
+    w.line("(local $janus_i i32)");
+    for local in &emitter.locals {
+        w.line(format!("(local {local} i32)"));
+    }
+    w.block(&body.finish());
+    w.line(")");
+    Ok(())
+}
+
+fn function_name(q: &str, direction: Direction) -> String {
+    match direction {
+        Direction::Forward => format!("{q}_fwd"),
+        Direction::Backward => format!("{q}_bwd"),
+    }
+}
+
+fn le_bytes(n: u32) -> String {
+    n.to_le_bytes()
+        .iter()
+        .map(|b| format!("\\{b:02x}"))
+        .collect()
+}
+
+struct Emitter<'a> {
+    prog: &'a Prog,
+    scope: Scope,
+    addresses: Vec<(String, String)>,
+    locals: Vec<String>,
+    arrays: &'a BTreeMap<&'a str, Vec<bool>>,
+}
+
+impl Emitter<'_> {
+    fn kind(&self, x: &Var) -> Result<Kind> {
+        self.scope
+            .get(&x.0)
+            .map(|binding| binding.kind)
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn address(&self, x: &Var) -> Result<&str> {
+        self.addresses
+            .iter()
+            .rev()
+            .find(|(y, _)| y == &x.0)
+            .map(|(_, address)| address.as_str())
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn len(&self, x: &Var) -> Result<String> {
+        match self.kind(x)? {
+            Kind::Array(Some(len)) => Ok(format!("i32.const {len}")),
+            Kind::Array(None) => Ok(format!("local.get ${}.len", escape(&x.0, &[]))),
+            _ => bail!("{}: {} is not an array", x.1, x.0),
+        }
+    }
+
+    fn scalar(&self, x: &Var) -> Result<&str> {
+        match self.kind(x)? {
+            Kind::Scalar => self.address(x),
+            _ => bail!("{}: {} is not a scalar", x.1, x.0),
+        }
+    }
+
+    fn stack_var(&self, x: &Var) -> Result<&str> {
+        match self.kind(x)? {
+            Kind::Stack => self.address(x),
+            _ => bail!("{}: {} is not a stack", x.1, x.0),
+        }
+    }
+
+    fn assert(&self, w: &mut Writer, holds: bool) {
+        if holds {
+            w.line("i32.eqz");
+        }
+        w.line("if unreachable end");
+    }
+
+    fn open(&self, w: &mut Writer, line: &str) {
+        w.line(line);
+        w.indent();
+    }
+
+    fn close(&self, w: &mut Writer, line: &str) {
+        w.dedent();
+        w.line(line);
+    }
+
+    fn stm(&mut self, w: &mut Writer, s: &Stm, direction: Direction) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let address = self.scalar(x)?.to_string();
+                w.line(&address);
+                w.line(&address);
+                w.line("i32.load");
+                self.exp(w, e)?;
+                w.line(update(*mod_op, direction));
+                w.line("i32.store");
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                self.index(w, x, e_index, *span)?;
+                w.line("local.tee $janus_p");
+                w.line("local.get $janus_p");
+                w.line("i32.load");
+                self.exp(w, e)?;
+                w.line(update(*mod_op, direction));
+                w.line("i32.store");
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                ..
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                self.exp(w, e_entry)?;
+                self.open(w, "if");
+                self.stm(w, s_then, direction)?;
+                self.exp(w, e_exit)?;
+                self.assert(w, true);
+                self.close(w, "else");
+                w.indent();
+                self.stm(w, s_else, direction)?;
+                self.exp(w, e_exit)?;
+                self.assert(w, false);
+                self.close(w, "end");
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                ..
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                self.exp(w, e_entry)?;
+                self.assert(w, true);
+                self.open(w, "block");
+                self.open(w, "loop");
+                self.stm(w, s_do, direction)?;
+                self.exp(w, e_exit)?;
+                w.line("br_if 1");
+                self.stm(w, s_loop, direction)?;
+                self.exp(w, e_entry)?;
+                self.assert(w, false);
+                w.line("br 0");
+                self.close(w, "end");
+                self.close(w, "end");
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                w.line(self.scalar(x)?);
+                w.line(self.stack_var(xs)?);
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                match push {
+                    true => w.line("call $janus_push"),
+                    false => w.line("call $janus_pop"),
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_local, e_delocal),
+                    Direction::Backward => (e_delocal, e_local),
+                };
+                let local = format!("${}.{}", x_local.0, self.locals.len());
+                self.locals.push(local.clone());
+                w.line("global.get $janus_sp");
+                w.line(format!("local.tee {local}"));
+                let kind = match t_local {
+                    Type::Int => {
+                        self.exp(w, e_entry)?;
+                        w.line("i32.store");
+                        w.line("global.get $janus_sp");
+                        w.line("i32.const 4");
+                        Kind::Scalar
+                    }
+                    Type::Stack => {
+                        if !matches!(e_entry, Exp::Nil) {
+                            bail!("{span}: stack locals must be initialised with nil");
+                        }
+                        w.line("i32.const 0");
+                        w.line("i32.store");
+                        w.line(format!("local.get {local}"));
+                        w.line(format!("local.get {local}"));
+                        w.line("i32.const 8");
+                        w.line("i32.add");
+                        w.line("i32.store offset=4");
+                        w.line("global.get $janus_sp");
+                        w.line(format!("i32.const {}", 8 + STACK_CAPACITY * 4));
+                        Kind::Stack
+                    }
+                };
+                w.line("i32.add");
+                w.line("global.set $janus_sp");
+
+                self.scope.push(&x_local.0, kind, false);
+                self.addresses
+                    .push((x_local.0.clone(), format!("local.get {local}")));
+                self.stm(w, s, direction)?;
+                w.line(format!("local.get {local}"));
+                match t_local {
+                    Type::Int => {
+                        w.line("i32.load");
+                        self.exp(w, e_exit)?;
+                        w.line("i32.eq");
+                    }
+                    Type::Stack => {
+                        self.stack(w, e_exit)?;
+                        w.line("call $janus_stack_eq");
+                    }
+                }
+                self.assert(w, true);
+                self.scope.pop();
+                self.addresses.pop();
+                w.line(format!("local.get {local}"));
+                w.line("global.set $janus_sp");
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, .. }) = self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if args.len() != xs.len() {
+                    bail!("{span}: {} expects {} arguments", q.0, args.len());
+                }
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                for (x, &array) in xs.iter().zip(&self.arrays[q.0.as_str()]) {
+                    w.line(self.address(x)?);
+                    if array {
+                        w.line(self.len(x)?);
+                    }
+                }
+                w.line(format!("call ${}", function_name(&q.0, direction)));
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(w, s_1, direction)?;
+                    self.stm(w, s_2, direction)?;
+                }
+                Direction::Backward => {
+                    self.stm(w, s_2, direction)?;
+                    self.stm(w, s_1, direction)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn index(&self, w: &mut Writer, x: &Var, e: &Exp, span: Span) -> Result<()> {
+        let Kind::Array(_) = self.kind(x)? else {
+            bail!("{span}: {} is not an array", x.0);
+        };
+        w.line(self.address(x)?);
+        self.exp(w, e)?;
+        w.line("local.tee $janus_i");
+        w.line(self.len(x)?);
+        w.line("i32.ge_u");
+        w.line("if unreachable end");
+        w.line("local.get $janus_i");
+        w.line("i32.const 4");
+        w.line("i32.mul");
+        w.line("i32.add");
+        Ok(())
+    }
+
+    fn exp(&self, w: &mut Writer, e: &Exp) -> Result<()> {
+        match e {
+            Exp::Constant(c) => w.line(format!("i32.const {}", c.0)),
+            Exp::Variable(x) => {
+                w.line(self.scalar(x)?);
+                w.line("i32.load");
+            }
+            Exp::Indexed { x, e } => {
+                self.index(w, x, e, x.1)?;
+                w.line("i32.load");
+            }
+            Exp::BinOp(e_1, op @ (Op::Equal | Op::NotEqual), e_2) if self.is_stack(e_1) => {
+                self.stack(w, e_1)?;
+                self.stack(w, e_2)?;
+                w.line("call $janus_stack_eq");
+                if *op == Op::NotEqual {
+                    w.line("i32.eqz");
+                }
+            }
+            Exp::BinOp(e_1, op @ (Op::And2 | Op::Or2), e_2) => {
+                self.exp(w, e_1)?;
+                w.line("i32.const 0");
+                w.line("i32.ne");
+                self.exp(w, e_2)?;
+                w.line("i32.const 0");
+                w.line("i32.ne");
+                match op {
+                    Op::And2 => w.line("i32.and"),
+                    _ => w.line("i32.or"),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                self.exp(w, e_1)?;
+                self.exp(w, e_2)?;
+                w.line(match op {
+                    Op::Add => "i32.add",
+                    Op::Sub => "i32.sub",
+                    Op::Mul => "i32.mul",
+                    Op::Div => "call $janus_div",
+                    Op::Mod => "i32.rem_s",
+                    Op::Xor => "i32.xor",
+                    Op::And => "i32.and",
+                    Op::Or => "i32.or",
+                    Op::Less => "i32.lt_s",
+                    Op::Greater => "i32.gt_s",
+                    Op::Equal => "i32.eq",
+                    Op::NotEqual => "i32.ne",
+                    Op::LessEqual => "i32.le_s",
+                    Op::GreaterEqual => "i32.ge_s",
+                    Op::And2 | Op::Or2 => unreachable!(),
+                });
+            }
+            Exp::Empty(x) => {
+                w.line(self.stack_var(x)?);
+                w.line("i32.load");
+                w.line("i32.eqz");
+            }
+            Exp::Top(x) => {
+                w.line(self.stack_var(x)?);
+                w.line("call $janus_top");
+            }
+            Exp::Nil => bail!("nil is not an integer"),
+        }
+        Ok(())
+    }
+
+    fn is_stack(&self, e: &Exp) -> bool {
+        match e {
+            Exp::Nil => true,
+            Exp::Variable(x) => self
+                .scope
+                .get(&x.0)
+                .is_some_and(|binding| binding.kind == Kind::Stack),
+            _ => false,
+        }
+    }
+
+    fn stack(&self, w: &mut Writer, e: &Exp) -> Result<()> {
+        match e {
+            Exp::Nil => w.line("i32.const 0"),
+            Exp::Variable(x) => w.line(self.stack_var(x)?),
+            _ => bail!("expected a stack expression"),
+        }
+        Ok(())
+    }
+}
+
+fn update(mod_op: ModOp, direction: Direction) -> &'static str {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => "i32.add",
+        (ModOp::Sub, Direction::Forward) | (ModOp::Add, Direction::Backward) => "i32.sub",
+        (ModOp::Xor, _) => "i32.xor",
+    }
+}
@@ -0,0 +1,316 @@
+pub mod syntax;
+
+use crate::{
+    ast::ast_node::*,
+    backend::{escape, fresh, sequence},
+    tokenizer::span::Span,
+};
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
+use syntax::{Program, Stmt};
+
+const RESERVED: &[&str] = &[
+    "int",
+    "stack",
+    "procedure",
+    "if",
+    "then",
+    "else",
+    "fi",
+    "from",
+    "do",
+    "loop",
+    "until",
+    "push",
+    "pop",
+    "local",
+    "delocal",
+    "call",
+    "uncall",
+    "skip",
+    "empty",
+    "top",
+    "nil",
+];
+
+pub fn translate(program: &Program) -> Result<Prog> {
+    let mut order = Vec::new();
+    for (x, _) in &program.inputs {
+        if order.contains(&x.0) {
+            bail!("{}: input {} is declared twice", x.1, x.0);
+        }
+        order.push(x.0.clone());
+    }
+    collect(&program.body, &mut order);
+    for x in &program.outputs {
+        if !order.contains(&x.0) {
+            bail!("{}: output {} is never assigned", x.1, x.0);
+        }
+    }
+
+    let mut translator = Translator {
+        used: BTreeSet::new(),
+        names: BTreeMap::new(),
+        history: String::new(),
+    };
+    for x in &order {
+        let name = fresh(&mut translator.used, &escape(x, RESERVED));
+        translator.names.insert(x.clone(), name);
+    }
+    translator.history = fresh(&mut translator.used, "history");
+
+    let inputs = program
+        .inputs
+        .iter()
+        .map(|(x, _)| translator.names[&x.0].clone())
+        .collect::<Vec<_>>();
+    let outputs = match program.outputs.is_empty() {
+        true => order
+            .iter()
+            .filter(|x| !program.inputs.iter().any(|(y, _)| &y.0 == *x))
+            .cloned()
+            .collect::<Vec<_>>(),
+        false => program.outputs.iter().map(|x| x.0.clone()).collect(),
+    };
+    let results = outputs
+        .iter()
+        .map(|x| {
+            let x = &translator.names[x];
+            (x.clone(), fresh(&mut translator.used, &format!("{x}_out")))
+        })
+        .collect::<Vec<_>>();
+    let vars = order
+        .iter()
+        .map(|x| translator.names[x].clone())
+        .collect::<Vec<_>>();
+
+    let compute = Proc::Other {
+        q: pid("compute"),
+        args: vars
+            .iter()
+            .map(|x| arg(Type::Int, x))
+            .chain([arg(Type::Stack, &translator.history)])
+            .collect(),
+        s: translator.block(&program.body),
+    };
+
+    let mut xs = vars.clone();
+    xs.push(translator.history.clone());
+    let mut body = vec![call("compute", &xs)];
+    body.extend(
+        results
+            .iter()
+            .map(|(x, result)| assign(result, ModOp::Add, Exp::Variable(var(x)))),
+    );
+    body.push(Stm::Uncall {
+        q: pid("compute"),
+        xs: xs.iter().map(|x| var(x)).collect(),
+        span: Span::default(),
+    });
+    let mut s = sequence(body);
+    for x in vars.iter().rev().filter(|x| !inputs.contains(x)) {
+        s = local(
+            Type::Int,
+            x,
+            Exp::Constant(Con(0)),
+            s,
+            Exp::Constant(Con(0)),
+        );
+    }
+    s = local(Type::Stack, &translator.history, Exp::Nil, s, Exp::Nil);
+    let run = Proc::Other {
+        q: pid("run"),
+        args: inputs
+            .iter()
+            .chain(results.iter().map(|(_, result)| result))
+            .map(|x| arg(Type::Int, x))
+            .collect(),
+        s,
+    };
+
+    let mut body = program
+        .inputs
+        .iter()
+        .filter(|(_, n)| *n != 0)
+        .map(|(x, n)| assign(&translator.names[&x.0], ModOp::Add, Exp::Constant(Con(*n))))
+        .collect::<Vec<_>>();
+    let args = inputs
+        .iter()
+        .chain(results.iter().map(|(_, result)| result))
+        .cloned()
+        .collect::<Vec<_>>();
+    body.push(call("run", &args));
+    let main = Proc::Main {
+        span: Span::default(),
+        main_stuff: args
+            .iter()
+            .map(|x| MainStuff::Int(Vdec::Scalar(var(x))))
+            .collect(),
+        s: sequence(body),
+    };
+
+    Ok(Prog {
+        p_main: main,
+        ps: LinkedList::from([run, compute]),
+    })
+}
+
+fn collect(block: &[Stmt], order: &mut Vec<String>) {
+    let add = |x: &Var, order: &mut Vec<String>| {
+        if !order.contains(&x.0) {
+            order.push(x.0.clone());
+        }
+    };
+    fn exp(e: &Exp, add: &mut impl FnMut(&Var)) {
+        match e {
+            Exp::Variable(x) => add(x),
+            Exp::BinOp(e_1, _, e_2) => {
+                exp(e_1, add);
+                exp(e_2, add);
+            }
+            _ => {}
+        }
+    }
+    for s in block {
+        match s {
+            Stmt::Assign(x, e) => {
+                add(x, order);
+                exp(e, &mut |x| add(x, order));
+            }
+            Stmt::If(e, s_then, s_else) => {
+                exp(e, &mut |x| add(x, order));
+                collect(s_then, order);
+                collect(s_else, order);
+            }
+            Stmt::While(e, s) => {
+                exp(e, &mut |x| add(x, order));
+                collect(s, order);
+            }
+            Stmt::Skip => {}
+        }
+    }
+}
+
+struct Translator {
+    used: BTreeSet<String>,
+    names: BTreeMap<String, String>,
+    history: String,
+}
+
+impl Translator {
+    fn exp(&self, e: &Exp) -> Exp {
+        match e {
+            Exp::Constant(c) => Exp::Constant(Con(c.0)),
+            Exp::Variable(x) => Exp::Variable(var(&self.names[&x.0])),
+            Exp::BinOp(e_1, op, e_2) => {
+                Exp::BinOp(Box::new(self.exp(e_1)), *op, Box::new(self.exp(e_2)))
+            }
+            _ => unreachable!("the while language has no arrays or stacks"),
+        }
+    }
+
+    fn block(&mut self, block: &[Stmt]) -> Stm {
+        let stms = block.iter().map(|s| self.stmt(s)).collect();
+        sequence(stms)
+    }
+
+    fn stmt(&mut self, s: &Stmt) -> Stm {
+        let h = var(&self.history);
+        match s {
+            Stmt::Assign(x, e) => {
+                let x = self.names[&x.0].clone();
+                let t = fresh(&mut self.used, "t");
+                let s = sequence(vec![
+                    Stm::Push(var(&x), h, Span::default()),
+                    assign(&x, ModOp::Add, Exp::Variable(var(&t))),
+                ]);
+                local(Type::Int, &t, self.exp(e), s, Exp::Variable(var(&x)))
+            }
+            Stmt::If(e, s_then, s_else) => {
+                let b = fresh(&mut self.used, "b");
+                let conditional = Stm::Conditional {
+                    e_if: Exp::Variable(var(&b)),
+                    s_then: Box::new(self.block(s_then)),
+                    s_else: Box::new(self.block(s_else)),
+                    e_fi: Exp::Variable(var(&b)),
+                    span: Span::default(),
+                };
+                let s = sequence(vec![conditional, Stm::Push(var(&b), h, Span::default())]);
+                local(Type::Int, &b, self.exp(e), s, Exp::Constant(Con(0)))
+            }
+            Stmt::While(e, s) => {
+                let c = fresh(&mut self.used, "c");
+                let s_loop = sequence(vec![
+                    self.block(s),
+                    assign(&c, ModOp::Add, Exp::Constant(Con(1))),
+                ]);
+                let repetition = Stm::Loop {
+                    e_from: Exp::BinOp(
+                        Box::new(Exp::Variable(var(&c))),
+                        Op::Equal,
+                        Box::new(Exp::Constant(Con(0))),
+                    ),
+                    s_do: Box::new(Stm::Skip(Span::default())),
+                    s_loop: Box::new(s_loop),
+                    e_until: Exp::BinOp(
+                        Box::new(Exp::Constant(Con(0))),
+                        Op::Equal,
+                        Box::new(self.exp(e)),
+                    ),
+                    span: Span::default(),
+                };
+                let s = sequence(vec![repetition, Stm::Push(var(&c), h, Span::default())]);
+                local(
+                    Type::Int,
+                    &c,
+                    Exp::Constant(Con(0)),
+                    s,
+                    Exp::Constant(Con(0)),
+                )
+            }
+            Stmt::Skip => Stm::Skip(Span::default()),
+        }
+    }
+}
+
+fn var(x: &str) -> Var {
+    Var(x.to_string(), Span::default())
+}
+
+fn pid(q: &str) -> PId {
+    PId(q.to_string(), Span::default())
+}
+
+fn arg(t: Type, x: &str) -> Arg {
+    Arg { t, x: var(x) }
+}
+
+fn assign(x: &str, mod_op: ModOp, e: Exp) -> Stm {
+    Stm::AssignScalar {
+        x: var(x),
+        mod_op,
+        e,
+        span: Span::default(),
+    }
+}
+
+fn call(q: &str, xs: &[String]) -> Stm {
+    Stm::Call {
+        q: pid(q),
+        xs: xs.iter().map(|x| var(x)).collect(),
+        span: Span::default(),
+    }
+}
+
+fn local(t: Type, x: &str, e_local: Exp, s: Stm, e_delocal: Exp) -> Stm {
+    Stm::Local {
+        t_local: t,
+        x_local: var(x),
+        e_local,
+        s: Box::new(s),
+        t_delocal: t,
+        x_delocal: var(x),
+        e_delocal,
+        span: Span::default(),
+    }
+}
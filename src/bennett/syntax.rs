@@ -0,0 +1,305 @@
+use crate::{
+    ast::ast_node::{Con, Exp, Op, Var},
+    tokenizer::span::{Pos, Span},
+    util::read_file,
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::LinkedList, path::Path};
+
+#[derive(Debug)]
+pub struct Program {
+    pub inputs: Vec<(Var, i32)>,
+    pub outputs: Vec<Var>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Assign(Var, Exp),
+    If(Exp, Vec<Stmt>, Vec<Stmt>),
+    While(Exp, Vec<Stmt>),
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(i32),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    ":=", "&&", "||", "!=", "<=", ">=", "+", "-", "^", "*", "/", "%", "&", "|", "<", ">", "=", ",",
+    ";",
+];
+
+const KEYWORDS: &[&str] = &[
+    "input", "output", "if", "then", "else", "end", "while", "do", "skip",
+];
+
+pub fn parse(source: LinkedList<char>) -> Result<Program> {
+    let tokens = tokenize(&source.into_iter().collect::<Vec<_>>())?;
+    let mut parser = Parser { tokens, next: 0 };
+    parser.program()
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Program> {
+    parse(read_file(path)?)
+}
+
+fn tokenize(source: &[char]) -> Result<Vec<(Token, Span)>> {
+    let mut tokens = Vec::new();
+    let mut pos = Pos::new(1, 1);
+    let mut i = 0;
+    while i < source.len() {
+        let start = pos;
+        let c = source[i];
+        let len = if c == '\n' {
+            pos = Pos::new(pos.line + 1, 1);
+            i += 1;
+            continue;
+        } else if c.is_whitespace() {
+            1
+        } else if c == '/' && source.get(i + 1) == Some(&'/') {
+            source[i..].iter().take_while(|&&c| c != '\n').count()
+        } else if c.is_ascii_digit() {
+            let len = source[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            let text = source[i..i + len].iter().collect::<String>();
+            let n = text
+                .parse()
+                .map_err(|_| anyhow!("{start}: constant {text} is too large"))?;
+            tokens.push((
+                Token::Number(n),
+                Span::new(start, Pos::new(pos.line, pos.column + len)),
+            ));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = source[i..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '_')
+                .count();
+            let word = source[i..i + len].iter().collect();
+            tokens.push((
+                Token::Word(word),
+                Span::new(start, Pos::new(pos.line, pos.column + len)),
+            ));
+            len
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| {
+                    source[i..]
+                        .iter()
+                        .copied()
+                        .take(symbol.len())
+                        .eq(symbol.chars())
+                })
+                .ok_or_else(|| anyhow!("{start}: unexpected character {c:?}"))?;
+            tokens.push((
+                Token::Symbol(symbol),
+                Span::new(start, Pos::new(pos.line, pos.column + symbol.len())),
+            ));
+            symbol.len()
+        };
+        pos.column += len;
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn describe(&self) -> String {
+        match self.tokens.get(self.next) {
+            Some((Token::Word(word), span)) => format!("{span}: {word}"),
+            Some((Token::Number(n), span)) => format!("{span}: {n}"),
+            Some((Token::Symbol(symbol), span)) => format!("{span}: {symbol}"),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.keyword(keyword) {
+            bail!("expected {keyword} found {}", self.describe());
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if !self.symbol(symbol) {
+            bail!("expected {symbol} found {}", self.describe());
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    fn var(&mut self) -> Result<Var> {
+        match self.tokens.get(self.next) {
+            Some((Token::Word(word), span)) if !KEYWORDS.contains(&word.as_str()) => {
+                let x = Var(word.clone(), *span);
+                self.next += 1;
+                Ok(x)
+            }
+            _ => bail!("expected a variable found {}", self.describe()),
+        }
+    }
+
+    fn number(&mut self) -> Result<i32> {
+        match self.peek() {
+            Some(&Token::Number(n)) => {
+                self.next += 1;
+                Ok(n)
+            }
+            _ => bail!("expected a constant found {}", self.describe()),
+        }
+    }
+
+    fn program(&mut self) -> Result<Program> {
+        let mut program = Program {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            body: Vec::new(),
+        };
+        loop {
+            if self.keyword("input") {
+                self.next += 1;
+                loop {
+                    let x = self.var()?;
+                    let n = match self.symbol("=") {
+                        true => {
+                            self.next += 1;
+                            self.number()?
+                        }
+                        false => 0,
+                    };
+                    program.inputs.push((x, n));
+                    if !self.symbol(",") {
+                        break;
+                    }
+                    self.next += 1;
+                }
+            } else if self.keyword("output") {
+                self.next += 1;
+                loop {
+                    program.outputs.push(self.var()?);
+                    if !self.symbol(",") {
+                        break;
+                    }
+                    self.next += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        program.body = self.block()?;
+        if self.next < self.tokens.len() {
+            bail!("expected a statement found {}", self.describe());
+        }
+        Ok(program)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        let mut block = Vec::new();
+        loop {
+            if self.symbol(";") {
+                self.next += 1;
+                continue;
+            }
+            let statement = match self.peek() {
+                Some(Token::Word(word)) => match word.as_str() {
+                    "if" => self.conditional()?,
+                    "while" => self.repetition()?,
+                    "skip" => {
+                        self.next += 1;
+                        Stmt::Skip
+                    }
+                    "else" | "end" => break,
+                    _ => {
+                        let x = self.var()?;
+                        self.expect_symbol(":=")?;
+                        Stmt::Assign(x, self.exp()?)
+                    }
+                },
+                _ => break,
+            };
+            block.push(statement);
+        }
+        Ok(block)
+    }
+
+    fn conditional(&mut self) -> Result<Stmt> {
+        self.expect_keyword("if")?;
+        let e = self.exp()?;
+        self.expect_keyword("then")?;
+        let s_then = self.block()?;
+        let s_else = match self.keyword("else") {
+            true => {
+                self.next += 1;
+                self.block()?
+            }
+            false => Vec::new(),
+        };
+        self.expect_keyword("end")?;
+        Ok(Stmt::If(e, s_then, s_else))
+    }
+
+    fn repetition(&mut self) -> Result<Stmt> {
+        self.expect_keyword("while")?;
+        let e = self.exp()?;
+        self.expect_keyword("do")?;
+        let s = self.block()?;
+        self.expect_keyword("end")?;
+        Ok(Stmt::While(e, s))
+    }
+
+    fn exp(&mut self) -> Result<Exp> {
+        let primary = match self.peek() {
+            Some(Token::Number(_)) => Exp::Constant(Con(self.number()?)),
+            _ => Exp::Variable(self.var()?),
+        };
+        let op = match self.peek() {
+            Some(Token::Symbol(symbol)) => match *symbol {
+                "+" => Op::Add,
+                "-" => Op::Sub,
+                "^" => Op::Xor,
+                "*" => Op::Mul,
+                "/" => Op::Div,
+                "%" => Op::Mod,
+                "&" => Op::And,
+                "|" => Op::Or,
+                "&&" => Op::And2,
+                "||" => Op::Or2,
+                "<" => Op::Less,
+                ">" => Op::Greater,
+                "=" => Op::Equal,
+                "!=" => Op::NotEqual,
+                "<=" => Op::LessEqual,
+                ">=" => Op::GreaterEqual,
+                _ => return Ok(primary),
+            },
+            _ => return Ok(primary),
+        };
+        self.next += 1;
+        Ok(Exp::BinOp(Box::new(primary), op, Box::new(self.exp()?)))
+    }
+}
@@ -0,0 +1,54 @@
+pub mod compiler;
+pub mod vm;
+
+use crate::{
+    ast::ast_node::{ModOp, Op, Type},
+    interpreter::{value::Value, Direction},
+    tokenizer::span::Span,
+};
+
+pub type Slot = usize;
+
+#[derive(Debug, Clone)]
+pub enum Instr {
+    Const(i32),
+    Nil,
+    Load(Slot),
+    LoadIndexed(Slot, Span),
+    Empty(Slot, Span),
+    Top(Slot, Span),
+    BinOp(Op),
+    Update(Slot, ModOp),
+    UpdateIndexed(Slot, ModOp, Span),
+    Push(Slot, Slot, Span),
+    Pop(Slot, Slot, Span),
+    Jump(usize),
+    JumpIf(bool, usize),
+    Assert(bool, &'static str, Span),
+    Alloc(Slot, Type, Span),
+    Free(Slot, &'static str, Span),
+    Call(usize, Vec<Slot>, Direction, Span),
+    Return,
+}
+
+#[derive(Debug)]
+pub struct Code {
+    pub name: String,
+    pub params: Vec<Type>,
+    pub slots: usize,
+    pub forward: Vec<Instr>,
+    pub backward: Vec<Instr>,
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub main: Code,
+    pub globals: Vec<(String, Value)>,
+    pub procs: Vec<Code>,
+}
+
+impl Program {
+    pub fn index(&self, q: &str) -> Option<usize> {
+        self.procs.iter().position(|code| code.name == q)
+    }
+}
@@ -0,0 +1,271 @@
+use super::{Code, Instr, Program, Slot};
+use crate::{
+    ast::ast_node::*,
+    interpreter::{value::Value, Direction},
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+pub fn compile(prog: &Prog) -> Result<Program> {
+    let mut indexes = BTreeMap::new();
+    for (index, p) in prog.ps.iter().enumerate() {
+        if indexes.insert(p.name(), index).is_some() {
+            bail!("procedure {} is defined twice", p.name());
+        }
+    }
+
+    let mut globals = Vec::new();
+    if let Proc::Main { main_stuff, .. } = &prog.p_main {
+        for stuff in main_stuff {
+            let (x, initial) = match stuff {
+                MainStuff::Int(Vdec::Scalar(x)) => (x, Value::Int(0)),
+                MainStuff::Int(Vdec::Array { x, c }) => {
+                    let len = usize::try_from(c.0)
+                        .map_err(|_| anyhow!("{}: negative array size", x.1))?;
+                    (x, Value::Array(vec![0; len]))
+                }
+                MainStuff::Stack(x) => (x, Value::Stack(Vec::new())),
+            };
+            if globals.iter().any(|(y, _)| y == &x.0) {
+                bail!("{}: {} is declared twice", x.1, x.0);
+            }
+            globals.push((x.0.clone(), initial));
+        }
+    }
+
+    let mut compiler = Compiler {
+        indexes,
+        scope: Vec::new(),
+        slots: 0,
+    };
+    let main = compiler.code(
+        "main",
+        globals.iter().map(|(x, _)| x.clone()).collect(),
+        Vec::new(),
+        prog.p_main.body(),
+    )?;
+
+    let mut procs = Vec::new();
+    for p in &prog.ps {
+        if let Proc::Other { q, args, s } = p {
+            let names = args.iter().map(|arg| arg.x.0.clone()).collect();
+            let params = args.iter().map(|arg| arg.t).collect();
+            procs.push(compiler.code(&q.0, names, params, s)?);
+        }
+    }
+
+    Ok(Program {
+        main,
+        globals,
+        procs,
+    })
+}
+
+struct Compiler<'a> {
+    indexes: BTreeMap<&'a str, usize>,
+    scope: Vec<(String, Slot)>,
+    slots: usize,
+}
+
+impl Compiler<'_> {
+    fn code(&mut self, name: &str, names: Vec<String>, params: Vec<Type>, s: &Stm) -> Result<Code> {
+        self.scope = names.into_iter().enumerate().map(|(i, x)| (x, i)).collect();
+        self.slots = self.scope.len();
+
+        let mut forward = Vec::new();
+        self.stm(s, Direction::Forward, &mut forward)?;
+        forward.push(Instr::Return);
+        let mut backward = Vec::new();
+        self.stm(s, Direction::Backward, &mut backward)?;
+        backward.push(Instr::Return);
+
+        Ok(Code {
+            name: name.to_string(),
+            params,
+            slots: self.slots,
+            forward,
+            backward,
+        })
+    }
+
+    fn slot(&self, x: &Var) -> Result<Slot> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(y, _)| y == &x.0)
+            .map(|&(_, slot)| slot)
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn stm(&mut self, s: &Stm, direction: Direction, code: &mut Vec<Instr>) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                self.exp(e, code)?;
+                code.push(Instr::Update(self.slot(x)?, invert(*mod_op, direction)));
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                self.exp(e_index, code)?;
+                self.exp(e, code)?;
+                code.push(Instr::UpdateIndexed(
+                    self.slot(x)?,
+                    invert(*mod_op, direction),
+                    *span,
+                ));
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_if, e_fi, "fi"),
+                    Direction::Backward => (e_fi, e_if, "if"),
+                };
+                self.exp(e_entry, code)?;
+                let to_else = code.len();
+                code.push(Instr::JumpIf(false, 0));
+                self.stm(s_then, direction, code)?;
+                self.exp(e_exit, code)?;
+                code.push(Instr::Assert(true, keyword, *span));
+                let to_end = code.len();
+                code.push(Instr::Jump(0));
+                code[to_else] = Instr::JumpIf(false, code.len());
+                self.stm(s_else, direction, code)?;
+                self.exp(e_exit, code)?;
+                code.push(Instr::Assert(false, keyword, *span));
+                code[to_end] = Instr::Jump(code.len());
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_from, e_until, "from"),
+                    Direction::Backward => (e_until, e_from, "until"),
+                };
+                self.exp(e_entry, code)?;
+                code.push(Instr::Assert(true, keyword, *span));
+                let start = code.len();
+                self.stm(s_do, direction, code)?;
+                self.exp(e_exit, code)?;
+                let to_end = code.len();
+                code.push(Instr::JumpIf(true, 0));
+                self.stm(s_loop, direction, code)?;
+                self.exp(e_entry, code)?;
+                code.push(Instr::Assert(false, keyword, *span));
+                code.push(Instr::Jump(start));
+                code[to_end] = Instr::JumpIf(true, code.len());
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                let (x, xs) = (self.slot(x)?, self.slot(xs)?);
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                code.push(match push {
+                    true => Instr::Push(x, xs, *span),
+                    false => Instr::Pop(x, xs, *span),
+                });
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit, keyword) = match direction {
+                    Direction::Forward => (e_local, e_delocal, "delocal"),
+                    Direction::Backward => (e_delocal, e_local, "local"),
+                };
+                let slot = self.slots;
+                self.slots += 1;
+                self.exp(e_entry, code)?;
+                code.push(Instr::Alloc(slot, *t_local, *span));
+                self.scope.push((x_local.0.clone(), slot));
+                self.stm(s, direction, code)?;
+                self.exp(e_exit, code)?;
+                code.push(Instr::Free(slot, keyword, *span));
+                self.scope.pop();
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let index = *self
+                    .indexes
+                    .get(q.0.as_str())
+                    .ok_or_else(|| anyhow!("{span}: procedure {} is not defined", q.0))?;
+                let slots = xs.iter().map(|x| self.slot(x)).collect::<Result<_>>()?;
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                code.push(Instr::Call(index, slots, direction, *span));
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(s_1, direction, code)?;
+                    self.stm(s_2, direction, code)?;
+                }
+                Direction::Backward => {
+                    self.stm(s_2, direction, code)?;
+                    self.stm(s_1, direction, code)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn exp(&self, e: &Exp, code: &mut Vec<Instr>) -> Result<()> {
+        match e {
+            Exp::Constant(c) => code.push(Instr::Const(c.0)),
+            Exp::Variable(x) => code.push(Instr::Load(self.slot(x)?)),
+            Exp::Indexed { x, e } => {
+                self.exp(e, code)?;
+                code.push(Instr::LoadIndexed(self.slot(x)?, x.1));
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                self.exp(e_1, code)?;
+                self.exp(e_2, code)?;
+                code.push(Instr::BinOp(*op));
+            }
+            Exp::Empty(x) => code.push(Instr::Empty(self.slot(x)?, x.1)),
+            Exp::Top(x) => code.push(Instr::Top(self.slot(x)?, x.1)),
+            Exp::Nil => code.push(Instr::Nil),
+        }
+        Ok(())
+    }
+}
+
+fn invert(mod_op: ModOp, direction: Direction) -> ModOp {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Backward) => ModOp::Sub,
+        (ModOp::Sub, Direction::Backward) => ModOp::Add,
+        (mod_op, _) => mod_op,
+    }
+}
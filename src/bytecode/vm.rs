@@ -0,0 +1,304 @@
+use super::{Code, Instr, Program, Slot};
+use crate::{
+    ast::ast_node::{ModOp, Op, Type},
+    interpreter::{eval_op, update, value::Value, Direction, Exhausted, MAX_DEPTH},
+};
+use anyhow::{anyhow, bail, Result};
+
+const FREE: usize = usize::MAX;
+
+#[derive(Debug)]
+struct Frame<'a> {
+    code: &'a [Instr],
+    pc: usize,
+    slots: Vec<usize>,
+}
+
+pub struct Vm<'a> {
+    program: &'a Program,
+    heap: Vec<Value>,
+    stack: Vec<Value>,
+    frames: Vec<Frame<'a>>,
+    steps: u64,
+    max_steps: Option<u64>,
+    max_depth: usize,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            heap: program
+                .globals
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            steps: 0,
+            max_steps: None,
+            max_depth: MAX_DEPTH,
+        }
+    }
+
+    pub fn limit_steps(&mut self, max_steps: u64) {
+        self.max_steps = Some(self.steps + max_steps);
+    }
+
+    pub fn limit_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn run(&mut self, direction: Direction) -> Result<()> {
+        let slots = (0..self.program.globals.len()).collect();
+        self.execute(&self.program.main, slots, direction)
+    }
+
+    pub fn store(&self) -> Vec<(String, Value)> {
+        self.program
+            .globals
+            .iter()
+            .zip(&self.heap)
+            .map(|((x, _), value)| (x.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn call(
+        &mut self,
+        q: &str,
+        values: Vec<Value>,
+        direction: Direction,
+    ) -> Result<Vec<Value>> {
+        let index = self
+            .program
+            .index(q)
+            .ok_or_else(|| anyhow!("procedure {q} is not defined"))?;
+        let code = &self.program.procs[index];
+        if code.params.len() != values.len() {
+            bail!(
+                "{q} expects {} arguments but {} were given",
+                code.params.len(),
+                values.len()
+            );
+        }
+
+        let base = self.heap.len();
+        self.heap.extend(values);
+        let result = self.execute(code, (base..self.heap.len()).collect(), direction);
+        let values = self.heap.split_off(base);
+        self.stack.clear();
+        self.frames.clear();
+        result.map(|_| values)
+    }
+
+    fn push_frame(
+        &mut self,
+        code: &'a Code,
+        mut slots: Vec<usize>,
+        direction: Direction,
+    ) -> Result<()> {
+        for (t, &location) in code.params.iter().zip(&slots) {
+            match (t, &self.heap[location]) {
+                (Type::Int, Value::Stack(_)) => {
+                    bail!("{} expects an integer found a stack", code.name)
+                }
+                (Type::Stack, Value::Int(_) | Value::Array(_)) => {
+                    bail!("{} expects a stack found an integer", code.name)
+                }
+                _ => {}
+            }
+        }
+        if self.frames.len() > self.max_depth {
+            bail!(Exhausted::Depth {
+                limit: self.max_depth,
+                q: code.name.clone(),
+            });
+        }
+        slots.resize(code.slots, FREE);
+        let code = match direction {
+            Direction::Forward => &code.forward,
+            Direction::Backward => &code.backward,
+        };
+        self.frames.push(Frame { code, pc: 0, slots });
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn cell(&mut self, slot: Slot) -> &mut Value {
+        let location = self.frames.last().expect("no active frame").slots[slot];
+        &mut self.heap[location]
+    }
+
+    fn execute(&mut self, code: &'a Code, slots: Vec<usize>, direction: Direction) -> Result<()> {
+        let depth = self.frames.len();
+        self.push_frame(code, slots, direction)?;
+        let result = self.resume(depth);
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.stack.clear();
+        }
+        result
+    }
+
+    fn resume(&mut self, depth: usize) -> Result<()> {
+        while self.frames.len() > depth {
+            self.steps += 1;
+            if self
+                .max_steps
+                .is_some_and(|max_steps| self.steps > max_steps)
+            {
+                bail!(Exhausted::Steps(None));
+            }
+            let frame = self.frames.last_mut().expect("no active frame");
+            let code = frame.code;
+            let instr = &code[frame.pc];
+            frame.pc += 1;
+
+            match instr {
+                Instr::Const(n) => self.stack.push(Value::Int(*n)),
+                Instr::Nil => self.stack.push(Value::Stack(Vec::new())),
+                Instr::Load(slot) => {
+                    let value = self.cell(*slot).clone();
+                    self.stack.push(value);
+                }
+                Instr::LoadIndexed(slot, span) => {
+                    let index = self.pop().int()?;
+                    let n = match self.cell(*slot) {
+                        Value::Array(ns) => usize::try_from(index)
+                            .ok()
+                            .and_then(|index| ns.get(index))
+                            .copied()
+                            .ok_or_else(|| anyhow!("{span}: index {index} is out of bounds"))?,
+                        _ => bail!("{span}: not an array"),
+                    };
+                    self.stack.push(Value::Int(n));
+                }
+                Instr::Empty(slot, span) => {
+                    let value = match self.cell(*slot) {
+                        Value::Stack(ns) => ns.is_empty().into(),
+                        _ => bail!("{span}: not a stack"),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::Top(slot, span) => {
+                    let n = match self.cell(*slot) {
+                        Value::Stack(ns) => {
+                            *ns.last().ok_or_else(|| anyhow!("{span}: stack is empty"))?
+                        }
+                        _ => bail!("{span}: not a stack"),
+                    };
+                    self.stack.push(Value::Int(n));
+                }
+                Instr::BinOp(op) => {
+                    let v_2 = self.pop();
+                    let v_1 = self.pop();
+                    let value = match op {
+                        Op::Equal => (v_1 == v_2).into(),
+                        Op::NotEqual => (v_1 != v_2).into(),
+                        _ => Value::Int(eval_op(op, v_1.int()?, v_2.int()?)?),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::Update(slot, mod_op) => {
+                    let e = self.pop().int()?;
+                    let cell = self.cell(*slot);
+                    *cell = Value::Int(apply(cell.int()?, mod_op, e));
+                }
+                Instr::UpdateIndexed(slot, mod_op, span) => {
+                    let e = self.pop().int()?;
+                    let index = self.pop().int()?;
+                    let Value::Array(ns) = self.cell(*slot) else {
+                        bail!("{span}: not an array");
+                    };
+                    let n = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| ns.get_mut(index))
+                        .ok_or_else(|| anyhow!("{span}: index {index} is out of bounds"))?;
+                    *n = apply(*n, mod_op, e);
+                }
+                Instr::Push(x, xs, span) => {
+                    let n = self.cell(*x).int()?;
+                    let Value::Stack(ns) = self.cell(*xs) else {
+                        bail!("{span}: not a stack");
+                    };
+                    ns.push(n);
+                    *self.cell(*x) = Value::Int(0);
+                }
+                Instr::Pop(x, xs, span) => {
+                    if self.cell(*x).int()? != 0 {
+                        bail!("{span}: variable must be zero before a pop");
+                    }
+                    let Value::Stack(ns) = self.cell(*xs) else {
+                        bail!("{span}: not a stack");
+                    };
+                    let n = ns.pop().ok_or_else(|| anyhow!("{span}: stack is empty"))?;
+                    *self.cell(*x) = Value::Int(n);
+                }
+                Instr::Jump(target) => {
+                    self.frames.last_mut().expect("no active frame").pc = *target
+                }
+                Instr::JumpIf(expected, target) => {
+                    if self.pop().truth()? == *expected {
+                        self.frames.last_mut().expect("no active frame").pc = *target;
+                    }
+                }
+                Instr::Assert(expected, keyword, span) => {
+                    if self.pop().truth()? != *expected {
+                        bail!("{span}: assertion {keyword} failed");
+                    }
+                }
+                Instr::Alloc(slot, t, span) => {
+                    let value = self.pop();
+                    match (t, &value) {
+                        (Type::Int, Value::Int(_)) | (Type::Stack, Value::Stack(_)) => {}
+                        _ => bail!("{span}: local is initialised with {value}"),
+                    }
+                    self.heap.push(value);
+                    let location = self.heap.len() - 1;
+                    self.frames.last_mut().expect("no active frame").slots[*slot] = location;
+                }
+                Instr::Free(slot, keyword, span) => {
+                    let value = self.pop();
+                    if *self.cell(*slot) != value {
+                        bail!("{span}: assertion {keyword} failed");
+                    }
+                    let frame = self.frames.last_mut().expect("no active frame");
+                    self.heap.truncate(frame.slots[*slot]);
+                    frame.slots[*slot] = FREE;
+                }
+                Instr::Call(index, args, direction, span) => {
+                    let frame = self.frames.last().expect("no active frame");
+                    let mut slots = Vec::with_capacity(args.len());
+                    for &arg in args {
+                        let location = frame.slots[arg];
+                        if slots.contains(&location) {
+                            bail!("{span}: an argument is passed more than once");
+                        }
+                        slots.push(location);
+                    }
+                    let code = &self.program.procs[*index];
+                    if code.params.len() != slots.len() {
+                        bail!(
+                            "{span}: {} expects {} arguments but {} were given",
+                            code.name,
+                            code.params.len(),
+                            slots.len()
+                        );
+                    }
+                    self.push_frame(code, slots, *direction)?;
+                }
+                Instr::Return => {
+                    self.frames.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn apply(n: i32, mod_op: &ModOp, e: i32) -> i32 {
+    update(n, mod_op, e, Direction::Forward)
+}
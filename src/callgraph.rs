@@ -0,0 +1,235 @@
+use crate::{
+    ast::ast_node::*,
+    backend::{quote, Writer},
+    interpreter::Direction,
+    tokenizer::span::Span,
+};
+use serde_json::{json, Value as Json};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub caller: String,
+    pub callee: String,
+    pub direction: Direction,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    pub procs: Vec<String>,
+    pub edges: Vec<Edge>,
+}
+
+impl CallGraph {
+    pub fn new(prog: &Prog) -> Self {
+        let mut edges = Vec::new();
+        for p in prog.procs() {
+            p.body().walk(&mut |s| {
+                let (q, direction, span) = match s {
+                    Stm::Call { q, span, .. } => (q, Direction::Forward, span),
+                    Stm::Uncall { q, span, .. } => (q, Direction::Backward, span),
+                    _ => return,
+                };
+                edges.push(Edge {
+                    caller: p.name().to_string(),
+                    callee: q.0.clone(),
+                    direction,
+                    span: *span,
+                });
+            });
+        }
+        Self {
+            procs: prog.procs().map(|p| p.name().to_string()).collect(),
+            edges,
+        }
+    }
+
+    pub fn defines(&self, q: &str) -> bool {
+        self.procs.iter().any(|p| p == q)
+    }
+
+    pub fn callees(&self, q: &str) -> BTreeSet<&str> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.caller == q)
+            .map(|edge| edge.callee.as_str())
+            .collect()
+    }
+
+    pub fn callers(&self, q: &str) -> BTreeSet<&str> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.callee == q)
+            .map(|edge| edge.caller.as_str())
+            .collect()
+    }
+
+    pub fn reachable(&self, q: &str) -> BTreeSet<&str> {
+        let mut value = BTreeSet::new();
+        let mut pending = vec![q];
+        while let Some(r) = pending.pop() {
+            for callee in self.callees(r) {
+                if value.insert(callee) {
+                    pending.push(callee);
+                }
+            }
+        }
+        value
+    }
+
+    pub fn is_recursive(&self, q: &str) -> bool {
+        self.reachable(q).contains(q)
+    }
+
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: BTreeMap::new(),
+            low: BTreeMap::new(),
+            stack: Vec::new(),
+            components: Vec::new(),
+        };
+        for q in &self.procs {
+            if !tarjan.index.contains_key(q.as_str()) {
+                tarjan.visit(q);
+            }
+        }
+        tarjan
+            .components
+            .into_iter()
+            .filter(|component| match component.as_slice() {
+                [q] => self.callees(q).contains(q),
+                _ => true,
+            })
+            .collect()
+    }
+
+    pub fn dot(&self) -> String {
+        let reached = self.reachable("main");
+        let recursive = self.cycles().into_iter().flatten().collect::<BTreeSet<_>>();
+        let mut out = Writer::default();
+        out.line("digraph callgraph {");
+        out.line("node [fontname=\"monospace\", shape=box]");
+        out.line("edge [fontname=\"monospace\", fontsize=10]");
+        let mut nodes = self.procs.iter().map(String::as_str).collect::<Vec<_>>();
+        for edge in &self.edges {
+            if !nodes.contains(&edge.callee.as_str()) {
+                nodes.push(&edge.callee);
+            }
+        }
+        for q in nodes {
+            let mut attributes = Vec::new();
+            if !self.defines(q) {
+                attributes.push("style=dashed");
+            } else if q != "main" && !reached.contains(q) {
+                attributes.push("color=gray, fontcolor=gray");
+            }
+            if recursive.contains(q) {
+                attributes.push("peripheries=2");
+            }
+            match attributes.is_empty() {
+                true => out.line(format!("\"{}\"", quote(q))),
+                false => out.line(format!("\"{}\" [{}]", quote(q), attributes.join(", "))),
+            }
+        }
+        let mut seen = BTreeSet::new();
+        for edge in &self.edges {
+            let inverse = edge.direction == Direction::Backward;
+            if !seen.insert((&edge.caller, &edge.callee, inverse)) {
+                continue;
+            }
+            let attributes = match inverse {
+                true => "label=\"uncall\", style=dashed",
+                false => "label=\"call\"",
+            };
+            out.line(format!(
+                "\"{}\" -> \"{}\" [{attributes}]",
+                quote(&edge.caller),
+                quote(&edge.callee)
+            ));
+        }
+        out.line("}");
+        out.finish()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut reached = self.reachable("main");
+        reached.insert("main");
+        let cycles = self.cycles();
+        let procedures = self
+            .procs
+            .iter()
+            .map(|q| {
+                json!({
+                    "name": q,
+                    "reachable": reached.contains(q.as_str()),
+                    "recursive": cycles.iter().any(|cycle| cycle.contains(&q.as_str())),
+                })
+            })
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "from": edge.caller,
+                    "to": edge.callee,
+                    "direction": match edge.direction {
+                        Direction::Forward => "forward",
+                        Direction::Backward => "inverse",
+                    },
+                    "defined": self.defines(&edge.callee),
+                    "span": edge.span,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "procedures": procedures,
+            "edges": edges,
+            "reachable": reached,
+            "cycles": cycles,
+        })
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    index: BTreeMap<&'a str, usize>,
+    low: BTreeMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, q: &'a str) {
+        let index = self.index.len();
+        self.index.insert(q, index);
+        self.low.insert(q, index);
+        self.stack.push(q);
+        for r in self.graph.callees(q) {
+            if !self.graph.defines(r) {
+                continue;
+            }
+            if !self.index.contains_key(r) {
+                self.visit(r);
+                let low = self.low[q].min(self.low[r]);
+                self.low.insert(q, low);
+            } else if self.stack.contains(&r) {
+                let low = self.low[q].min(self.index[r]);
+                self.low.insert(q, low);
+            }
+        }
+        if self.low[q] == index {
+            let mut component = Vec::new();
+            while let Some(r) = self.stack.pop() {
+                component.push(r);
+                if r == q {
+                    break;
+                }
+            }
+            component.reverse();
+            self.components.push(component);
+        }
+    }
+}
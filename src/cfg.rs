@@ -0,0 +1,255 @@
+use crate::{
+    ast::ast_node::*,
+    backend::{quote, Writer},
+    interpreter::Direction,
+};
+use anyhow::{anyhow, Result};
+
+pub fn dot(prog: &Prog, q: &str, inverse: bool) -> Result<String> {
+    let p = prog
+        .find(q)
+        .ok_or_else(|| anyhow!("procedure {q} is not defined"))?;
+    let mut graph = Graph {
+        out: Writer::default(),
+        next: 0,
+    };
+    graph.out.line(format!("digraph \"{}\" {{", quote(q)));
+    graph.out.line("node [fontname=\"monospace\"]");
+    graph.out.line("edge [fontname=\"monospace\", fontsize=10]");
+    graph.proc(p, Direction::Forward);
+    if inverse {
+        graph.proc(p, Direction::Backward);
+    }
+    graph.out.line("}");
+    Ok(graph.out.finish())
+}
+
+struct Graph {
+    out: Writer,
+    next: usize,
+}
+
+type Pending = Vec<(String, Option<&'static str>)>;
+
+enum Item<'a> {
+    Line(String),
+    Compound(&'a Stm),
+}
+
+impl Graph {
+    fn proc(&mut self, p: &Proc, direction: Direction) {
+        let (cluster, title) = match direction {
+            Direction::Forward => ("forward", format!("call {}", p.name())),
+            Direction::Backward => ("backward", format!("uncall {}", p.name())),
+        };
+        self.out.line(format!("subgraph cluster_{cluster} {{"));
+        self.out.line(format!("label=\"{}\"", quote(&title)));
+        let begin = self.node("oval", "begin");
+        let pending = self.stm(p.body(), direction, vec![(begin, None)]);
+        let end = self.node("oval", "end");
+        self.connect(pending, &end, None);
+        self.out.line("}");
+    }
+
+    fn node(&mut self, shape: &str, label: &str) -> String {
+        let id = format!("n{}", self.next);
+        self.next += 1;
+        let extra = match shape {
+            "assertion" => "shape=diamond, peripheries=2",
+            "test" => "shape=diamond",
+            "box" => "shape=box",
+            _ => "shape=oval",
+        };
+        self.out.line(format!("{id} [{extra}, label=\"{label}\"]"));
+        id
+    }
+
+    fn connect(&mut self, pending: Pending, to: &str, head: Option<&str>) {
+        for (from, tail) in pending {
+            let mut attributes = Vec::new();
+            if let Some(tail) = tail {
+                attributes.push(format!("taillabel=\"{tail}\""));
+            }
+            if let Some(head) = head {
+                attributes.push(format!("headlabel=\"{head}\""));
+            }
+            match attributes.is_empty() {
+                true => self.out.line(format!("{from} -> {to}")),
+                false => self
+                    .out
+                    .line(format!("{from} -> {to} [{}]", attributes.join(", "))),
+            }
+        }
+    }
+
+    fn stm(&mut self, s: &Stm, direction: Direction, mut pending: Pending) -> Pending {
+        let mut items = Vec::new();
+        flatten(s, direction, &mut items);
+        let mut lines = Vec::new();
+        for item in items {
+            match item {
+                Item::Line(line) => lines.push(line),
+                Item::Compound(s) => {
+                    pending = self.flush(&mut lines, pending);
+                    pending = self.compound(s, direction, pending);
+                }
+            }
+        }
+        self.flush(&mut lines, pending)
+    }
+
+    fn flush(&mut self, lines: &mut Vec<String>, pending: Pending) -> Pending {
+        if lines.is_empty() {
+            return pending;
+        }
+        let label = lines
+            .drain(..)
+            .map(|line| format!("{}\\l", quote(&line)))
+            .collect::<String>();
+        let id = self.node("box", &label);
+        self.connect(pending, &id, None);
+        vec![(id, None)]
+    }
+
+    fn compound(&mut self, s: &Stm, direction: Direction, pending: Pending) -> Pending {
+        match s {
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                ..
+            } => {
+                let (e_if, e_fi) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                let test = self.node("test", &quote(&e_if.to_string()));
+                self.connect(pending, &test, None);
+                let then = self.branch(s_then, direction, vec![(test.clone(), Some("true"))]);
+                let other = self.branch(s_else, direction, vec![(test, Some("false"))]);
+                let join = self.node("assertion", &quote(&e_fi.to_string()));
+                self.connect(then, &join, Some("true"));
+                self.connect(other, &join, Some("false"));
+                vec![(join, None)]
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                ..
+            } => {
+                let (e_from, e_until) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                let from = self.node("assertion", &quote(&e_from.to_string()));
+                self.connect(pending, &from, Some("true"));
+                let done = self.stm(s_do, direction, vec![(from.clone(), None)]);
+                let until = self.node("test", &quote(&e_until.to_string()));
+                self.connect(done, &until, None);
+                let back = self.stm(s_loop, direction, vec![(until.clone(), Some("false"))]);
+                self.connect(back, &from, Some("false"));
+                vec![(until, Some("true"))]
+            }
+            _ => unreachable!("only conditionals and loops are compound"),
+        }
+    }
+
+    fn branch(&mut self, s: &Stm, direction: Direction, pending: Pending) -> Pending {
+        let value = self.stm(s, direction, pending.clone());
+        match value == pending {
+            true => self.flush(&mut vec!["skip".to_string()], pending),
+            false => value,
+        }
+    }
+}
+
+fn flatten<'a>(s: &'a Stm, direction: Direction, items: &mut Vec<Item<'a>>) {
+    let line = match s {
+        Stm::AssignScalar { x, mod_op, e, .. } => {
+            format!("{} {} {e}", x.0, invert(*mod_op, direction).symbol())
+        }
+        Stm::AssignArray {
+            x,
+            e_index,
+            mod_op,
+            e,
+            ..
+        } => format!(
+            "{}[{e_index}] {} {e}",
+            x.0,
+            invert(*mod_op, direction).symbol()
+        ),
+        Stm::Conditional { .. } | Stm::Loop { .. } => {
+            items.push(Item::Compound(s));
+            return;
+        }
+        Stm::Push(x, xs, _) | Stm::Pop(x, xs, _) => {
+            let push = matches!(
+                (s, direction),
+                (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+            );
+            match push {
+                true => format!("push({}, {})", x.0, xs.0),
+                false => format!("pop({}, {})", x.0, xs.0),
+            }
+        }
+        Stm::Local {
+            t_local,
+            x_local,
+            e_local,
+            s,
+            t_delocal,
+            x_delocal,
+            e_delocal,
+            ..
+        } => {
+            let (open, close) = match direction {
+                Direction::Forward => (
+                    format!("local {t_local} {} = {e_local}", x_local.0),
+                    format!("delocal {t_delocal} {} = {e_delocal}", x_delocal.0),
+                ),
+                Direction::Backward => (
+                    format!("local {t_delocal} {} = {e_delocal}", x_delocal.0),
+                    format!("delocal {t_local} {} = {e_local}", x_local.0),
+                ),
+            };
+            items.push(Item::Line(open));
+            flatten(s, direction, items);
+            items.push(Item::Line(close));
+            return;
+        }
+        Stm::Call { q, xs, .. } | Stm::Uncall { q, xs, .. } => {
+            let call = matches!(
+                (s, direction),
+                (Stm::Call { .. }, Direction::Forward) | (Stm::Uncall { .. }, Direction::Backward)
+            );
+            let xs = xs.iter().map(|x| x.0.as_str()).collect::<Vec<_>>();
+            match call {
+                true => format!("call {}({})", q.0, xs.join(", ")),
+                false => format!("uncall {}({})", q.0, xs.join(", ")),
+            }
+        }
+        Stm::Skip(_) => "skip".to_string(),
+        Stm::Sequence(s_1, s_2) => {
+            let (s_1, s_2) = match direction {
+                Direction::Forward => (s_1, s_2),
+                Direction::Backward => (s_2, s_1),
+            };
+            flatten(s_1, direction, items);
+            flatten(s_2, direction, items);
+            return;
+        }
+    };
+    items.push(Item::Line(line));
+}
+
+fn invert(mod_op: ModOp, direction: Direction) -> ModOp {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Backward) => ModOp::Sub,
+        (ModOp::Sub, Direction::Backward) => ModOp::Add,
+        (mod_op, _) => mod_op,
+    }
+}
@@ -0,0 +1,223 @@
+pub mod synth;
+
+use crate::interpreter::{value::Value, Event, Observer};
+use anyhow::{bail, Result};
+use std::fmt::{self, Write};
+
+pub type Line = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gate {
+    pub controls: Vec<Line>,
+    pub target: Line,
+}
+
+#[derive(Debug, Default)]
+pub struct Circuit {
+    pub width: usize,
+    pub names: Vec<String>,
+    pub inputs: Vec<(String, Vec<Line>)>,
+    pub outputs: Vec<(String, Vec<Line>)>,
+    pub gates: Vec<Gate>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub lines: usize,
+    pub ancillas: usize,
+    pub gates: usize,
+    pub not: usize,
+    pub cnot: usize,
+    pub toffoli: usize,
+    pub larger: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lines ({} ancillas), {} gates: {} NOT, {} CNOT, {} Toffoli, {} larger",
+            self.lines, self.ancillas, self.gates, self.not, self.cnot, self.toffoli, self.larger
+        )
+    }
+}
+
+pub fn wrap(n: i32, width: usize) -> i32 {
+    let shift = 32 - width as u32;
+    n.wrapping_shl(shift).wrapping_shr(shift)
+}
+
+pub fn fits(value: &Value, width: usize) -> bool {
+    match value {
+        Value::Int(n) => *n == wrap(*n, width),
+        Value::Array(ns) | Value::Stack(ns) => ns.iter().all(|&n| n == wrap(n, width)),
+    }
+}
+
+pub struct Overflow {
+    pub width: usize,
+    pub overflowed: bool,
+}
+
+impl Observer for Overflow {
+    fn stm(&mut self, event: &Event) -> Result<()> {
+        if !event
+            .changes
+            .iter()
+            .all(|change| fits(&change.new, self.width))
+        {
+            self.overflowed = true;
+        }
+        Ok(())
+    }
+}
+
+impl Circuit {
+    fn is_input(&self, line: Line) -> bool {
+        self.inputs.iter().any(|(_, lines)| lines.contains(&line))
+    }
+
+    fn is_output(&self, line: Line) -> bool {
+        self.outputs.iter().any(|(_, lines)| lines.contains(&line))
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            lines: self.names.len(),
+            ancillas: (0..self.names.len())
+                .filter(|&line| !self.is_input(line))
+                .count(),
+            gates: self.gates.len(),
+            ..Stats::default()
+        };
+        for gate in &self.gates {
+            match gate.controls.len() {
+                0 => stats.not += 1,
+                1 => stats.cnot += 1,
+                2 => stats.toffoli += 1,
+                _ => stats.larger += 1,
+            }
+        }
+        stats
+    }
+
+    fn gate_names(&self, gate: &Gate) -> String {
+        gate.controls
+            .iter()
+            .chain([&gate.target])
+            .map(|&line| self.names[line].as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn real(&self) -> String {
+        let lines = 0..self.names.len();
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}", self.stats());
+        let _ = writeln!(out, ".version 1.0");
+        let _ = writeln!(out, ".numvars {}", self.names.len());
+        let _ = writeln!(out, ".variables {}", self.names.join(" "));
+        let _ = writeln!(out, ".inputs {}", self.names.join(" "));
+        let _ = writeln!(out, ".outputs {}", self.names.join(" "));
+        let constants = lines
+            .clone()
+            .map(|line| if self.is_input(line) { '-' } else { '0' })
+            .collect::<String>();
+        let _ = writeln!(out, ".constants {constants}");
+        let garbage = lines
+            .map(|line| if self.is_output(line) { '-' } else { '1' })
+            .collect::<String>();
+        let _ = writeln!(out, ".garbage {garbage}");
+        let _ = writeln!(out, ".begin");
+        for gate in &self.gates {
+            let _ = writeln!(
+                out,
+                "t{} {}",
+                gate.controls.len() + 1,
+                self.gate_names(gate)
+            );
+        }
+        let _ = writeln!(out, ".end");
+        out
+    }
+
+    pub fn qc(&self) -> String {
+        let names = |lines: &mut dyn Iterator<Item = Line>| {
+            lines
+                .map(|line| self.names[line].as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "# {}", self.stats());
+        let _ = writeln!(out, ".v {}", self.names.join(" "));
+        let _ = writeln!(
+            out,
+            ".i {}",
+            names(
+                &mut self
+                    .inputs
+                    .iter()
+                    .flat_map(|(_, lines)| lines.iter().copied())
+            )
+        );
+        let _ = writeln!(
+            out,
+            ".o {}",
+            names(
+                &mut self
+                    .outputs
+                    .iter()
+                    .flat_map(|(_, lines)| lines.iter().copied())
+            )
+        );
+        let _ = writeln!(out, "BEGIN");
+        for gate in &self.gates {
+            let _ = writeln!(
+                out,
+                "t{} {}",
+                gate.controls.len() + 1,
+                self.gate_names(gate)
+            );
+        }
+        let _ = writeln!(out, "END");
+        out
+    }
+
+    pub fn simulate(&self, inputs: &[i32]) -> Result<Vec<i32>> {
+        if inputs.len() != self.inputs.len() {
+            bail!(
+                "the circuit expects {} inputs but {} were given",
+                self.inputs.len(),
+                inputs.len()
+            );
+        }
+        let mut bits = vec![false; self.names.len()];
+        for ((_, lines), n) in self.inputs.iter().zip(inputs) {
+            for (i, &line) in lines.iter().enumerate() {
+                bits[line] = n >> i & 1 == 1;
+            }
+        }
+        for gate in &self.gates {
+            if gate.controls.iter().all(|&line| bits[line]) {
+                bits[gate.target] ^= true;
+            }
+        }
+        for (line, &bit) in bits.iter().enumerate() {
+            if bit && !self.is_output(line) {
+                bail!("{} is not clean: an assertion failed", self.names[line]);
+            }
+        }
+        Ok(self
+            .outputs
+            .iter()
+            .map(|(_, lines)| {
+                let n = lines
+                    .iter()
+                    .enumerate()
+                    .fold(0, |n, (i, &line)| n | (bits[line] as i32) << i);
+                wrap(n, self.width)
+            })
+            .collect())
+    }
+}
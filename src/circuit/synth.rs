@@ -0,0 +1,697 @@
+use super::{wrap, Circuit, Gate, Line};
+use crate::{
+    ast::ast_node::*,
+    backend::array_params,
+    interpreter::{assertion, eval_op, update, Direction},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::ops::Range;
+
+const MAX_UNROLL: usize = 100_000;
+
+#[derive(Debug, Clone)]
+enum Signal {
+    Known(i32),
+    Wires(Vec<Line>),
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Scalar(usize),
+    Array(Vec<usize>),
+}
+
+#[derive(Debug)]
+struct Operand {
+    lines: Vec<Line>,
+    gates: Range<usize>,
+}
+
+pub fn synthesize(prog: &Prog, q: &str, width: usize) -> Result<Circuit> {
+    if !(1..=32).contains(&width) {
+        bail!("the width must be between 1 and 32");
+    }
+    let mut synth = Synth {
+        prog,
+        width,
+        circuit: Circuit {
+            width,
+            ..Circuit::default()
+        },
+        signals: Vec::new(),
+        env: Vec::new(),
+        free: Vec::new(),
+        control: None,
+        calls: Vec::new(),
+    };
+
+    let (s, direction) = match prog.find(q) {
+        Some(Proc::Main { main_stuff, s, .. }) => {
+            for stuff in main_stuff {
+                let (x, binding) = match stuff {
+                    MainStuff::Int(Vdec::Scalar(x)) => (x, Binding::Scalar(synth.known(0))),
+                    MainStuff::Int(Vdec::Array { x, c }) => {
+                        let len = usize::try_from(c.0)
+                            .map_err(|_| anyhow!("{}: invalid array size", x.1))?;
+                        let cells = (0..len).map(|_| synth.known(0)).collect();
+                        (x, Binding::Array(cells))
+                    }
+                    MainStuff::Stack(x) => {
+                        bail!("{}: stacks are not supported by circuit synthesis", x.1)
+                    }
+                };
+                synth.env.push((x.0.clone(), binding));
+            }
+            (s, Direction::Forward)
+        }
+        Some(Proc::Other { q, args, s }) => {
+            let arrays = &array_params(prog)[q.0.as_str()];
+            for (arg, &array) in args.iter().zip(arrays) {
+                if arg.t == Type::Stack || array {
+                    bail!(
+                        "{}: only integer parameters can become circuit inputs",
+                        arg.x.1
+                    );
+                }
+                let lines = (0..width)
+                    .map(|i| synth.named(format!("{}_{i}", arg.x.0)))
+                    .collect::<Vec<_>>();
+                synth.circuit.inputs.push((arg.x.0.clone(), lines.clone()));
+                synth.signals.push(Signal::Wires(lines));
+                let index = synth.signals.len() - 1;
+                synth.env.push((arg.x.0.clone(), Binding::Scalar(index)));
+            }
+            synth.calls.push(q.0.clone());
+            (s, Direction::Forward)
+        }
+        None => bail!("procedure {q} is not defined"),
+    };
+    synth.stm(s, direction)?;
+
+    let mut outputs = Vec::new();
+    for (x, binding) in std::mem::take(&mut synth.env) {
+        match binding {
+            Binding::Scalar(index) => outputs.push((x, index)),
+            Binding::Array(cells) => outputs.extend(
+                cells
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, index)| (format!("{x}_{i}"), index)),
+            ),
+        }
+    }
+    for (x, index) in outputs {
+        synth.materialize(index);
+        let Signal::Wires(lines) = &synth.signals[index] else {
+            unreachable!("signal was materialized");
+        };
+        for (i, &line) in lines.iter().enumerate() {
+            if !synth.circuit.is_input(line) {
+                synth.circuit.names[line] = format!("{x}_{i}");
+            }
+        }
+        synth.circuit.outputs.push((x, lines.clone()));
+    }
+    Ok(synth.circuit)
+}
+
+struct Synth<'a> {
+    prog: &'a Prog,
+    width: usize,
+    circuit: Circuit,
+    signals: Vec<Signal>,
+    env: Vec<(String, Binding)>,
+    free: Vec<Line>,
+    control: Option<Line>,
+    calls: Vec<String>,
+}
+
+impl Synth<'_> {
+    fn known(&mut self, n: i32) -> usize {
+        self.signals.push(Signal::Known(n));
+        self.signals.len() - 1
+    }
+
+    fn named(&mut self, name: String) -> Line {
+        self.circuit.names.push(name);
+        self.circuit.names.len() - 1
+    }
+
+    fn line(&mut self) -> Line {
+        match self.free.pop() {
+            Some(line) => line,
+            None => {
+                let name = format!("t{}", self.circuit.names.len());
+                self.named(name)
+            }
+        }
+    }
+
+    fn bundle(&mut self) -> Vec<Line> {
+        (0..self.width).map(|_| self.line()).collect()
+    }
+
+    fn release(&mut self, lines: Vec<Line>) {
+        self.free.extend(lines.into_iter().rev());
+    }
+
+    fn gate(&mut self, controls: Vec<Line>, target: Line) {
+        self.circuit.gates.push(Gate { controls, target });
+    }
+
+    fn controlled(&mut self, mut controls: Vec<Line>, target: Line) {
+        controls.extend(self.control);
+        self.gate(controls, target);
+    }
+
+    fn mark(&self) -> usize {
+        self.circuit.gates.len()
+    }
+
+    fn replay(&mut self, range: Range<usize>) {
+        for i in range.rev() {
+            let gate = self.circuit.gates[i].clone();
+            self.circuit.gates.push(gate);
+        }
+    }
+
+    fn discard(&mut self, operand: Operand) {
+        self.replay(operand.gates);
+        self.release(operand.lines);
+    }
+
+    fn materialize(&mut self, index: usize) {
+        if let Signal::Known(n) = self.signals[index] {
+            let lines = self.bundle();
+            for (i, &line) in lines.iter().enumerate() {
+                if n >> i & 1 == 1 {
+                    self.gate(vec![], line);
+                }
+            }
+            self.signals[index] = Signal::Wires(lines);
+        }
+    }
+
+    fn lookup(&self, x: &Var) -> Result<&Binding> {
+        self.env
+            .iter()
+            .rev()
+            .find(|(y, _)| y == &x.0)
+            .map(|(_, binding)| binding)
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn scalar(&self, x: &Var) -> Result<usize> {
+        match self.lookup(x)? {
+            Binding::Scalar(index) => Ok(*index),
+            Binding::Array(_) => bail!("{}: {} is not a scalar", x.1, x.0),
+        }
+    }
+
+    fn element(&self, x: &Var, e: &Exp) -> Result<usize> {
+        let Binding::Array(cells) = self.lookup(x)? else {
+            bail!("{}: {} is not an array", x.1, x.0);
+        };
+        let Some(index) = self.constant(e)? else {
+            bail!("{}: array indexes must not depend on circuit inputs", x.1);
+        };
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| cells.get(index))
+            .copied()
+            .ok_or_else(|| anyhow!("{}: index {index} is out of bounds", x.1))
+    }
+
+    fn constant(&self, e: &Exp) -> Result<Option<i32>> {
+        let value = match e {
+            Exp::Constant(c) => Some(wrap(c.0, self.width)),
+            Exp::Variable(x) => match self.signals[self.scalar(x)?] {
+                Signal::Known(n) => Some(n),
+                Signal::Wires(_) => None,
+            },
+            Exp::Indexed { x, e } => match self.signals[self.element(x, e)?] {
+                Signal::Known(n) => Some(n),
+                Signal::Wires(_) => None,
+            },
+            Exp::BinOp(e_1, op, e_2) => match (self.constant(e_1)?, self.constant(e_2)?) {
+                (Some(n_1), Some(n_2)) => Some(wrap(eval_op(op, n_1, n_2)?, self.width)),
+                _ => None,
+            },
+            Exp::Empty(x) | Exp::Top(x) => {
+                bail!("{}: stacks are not supported by circuit synthesis", x.1)
+            }
+            Exp::Nil => bail!("stacks are not supported by circuit synthesis"),
+        };
+        Ok(value)
+    }
+
+    fn stm(&mut self, s: &Stm, direction: Direction) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let index = self.scalar(x)?;
+                self.update(index, *mod_op, e, direction)?;
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let index = self.element(x, e_index)?;
+                self.update(index, *mod_op, e, direction)?;
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                let keyword = assertion(direction, "fi");
+                match self.constant(e_entry)? {
+                    Some(n) => {
+                        match n != 0 {
+                            true => self.stm(s_then, direction)?,
+                            false => self.stm(s_else, direction)?,
+                        }
+                        self.assert(e_exit, n != 0, keyword, *span)?;
+                    }
+                    None => {
+                        let (c_then, c_else) = (self.line(), self.line());
+                        self.branch(e_entry, c_then, c_else)?;
+                        let control = self.control;
+                        self.control = Some(c_then);
+                        self.stm(s_then, direction)?;
+                        self.control = Some(c_else);
+                        self.stm(s_else, direction)?;
+                        self.control = control;
+                        self.branch(e_exit, c_then, c_else)?;
+                    }
+                }
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                let bounded = |synth: &Self, e: &Exp| {
+                    synth.constant(e)?.map(|n| n != 0).ok_or_else(|| {
+                        anyhow!("{span}: the loop condition depends on circuit inputs")
+                    })
+                };
+                if !bounded(self, e_entry)? {
+                    bail!("{span}: assertion {} failed", assertion(direction, "from"));
+                }
+                for _ in 0..MAX_UNROLL {
+                    self.stm(s_do, direction)?;
+                    if bounded(self, e_exit)? {
+                        return Ok(());
+                    }
+                    self.stm(s_loop, direction)?;
+                    if bounded(self, e_entry)? {
+                        bail!(
+                            "{span}: assertion {} failed on re-entry",
+                            assertion(direction, "from")
+                        );
+                    }
+                }
+                bail!("{span}: the loop runs more than {MAX_UNROLL} iterations");
+            }
+            Stm::Push(_, _, span) | Stm::Pop(_, _, span) => {
+                bail!("{span}: stacks are not supported by circuit synthesis")
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                if *t_local == Type::Stack {
+                    bail!("{span}: stacks are not supported by circuit synthesis");
+                }
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_local, e_delocal),
+                    Direction::Backward => (e_delocal, e_local),
+                };
+                let keyword = assertion(direction, "delocal");
+
+                let index = match (self.control, self.constant(e_entry)?) {
+                    (None, Some(n)) => self.known(n),
+                    _ => {
+                        let lines = (0..self.width).map(|_| self.line()).collect();
+                        self.signals.push(Signal::Wires(lines));
+                        let index = self.signals.len() - 1;
+                        self.update(index, ModOp::Xor, e_entry, Direction::Forward)?;
+                        index
+                    }
+                };
+                self.env.push((x_local.0.clone(), Binding::Scalar(index)));
+                self.stm(s, direction)?;
+                self.env.pop();
+
+                match (&self.signals[index], self.constant(e_exit)?) {
+                    (Signal::Known(n), Some(m)) => {
+                        if *n != m {
+                            bail!("{span}: assertion {keyword} failed");
+                        }
+                    }
+                    _ => {
+                        self.materialize(index);
+                        self.update(index, ModOp::Xor, e_exit, Direction::Forward)?;
+                    }
+                }
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, s: body, .. }) =
+                    self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if self.calls.contains(&q.0) {
+                    bail!("{span}: {} is recursive", q.0);
+                }
+                for (i, x) in xs.iter().enumerate() {
+                    if xs.iter().skip(i + 1).any(|y| y.0 == x.0) {
+                        bail!("{span}: {} is passed to {} more than once", x.0, q.0);
+                    }
+                }
+                if args.len() != xs.len() {
+                    bail!("{span}: {} expects {} arguments", q.0, args.len());
+                }
+                let env = args
+                    .iter()
+                    .zip(xs)
+                    .map(|(arg, x)| Ok((arg.x.0.clone(), self.lookup(x)?.clone())))
+                    .collect::<Result<Vec<_>>>()?;
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                let env = std::mem::replace(&mut self.env, env);
+                self.calls.push(q.0.clone());
+                let result = self.stm(body, direction);
+                self.calls.pop();
+                self.env = env;
+                result.map_err(|e| e.context(format!("{span}: in {}", q.0)))?;
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(s_1, direction)?;
+                    self.stm(s_2, direction)?;
+                }
+                Direction::Backward => {
+                    self.stm(s_2, direction)?;
+                    self.stm(s_1, direction)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, index: usize, mod_op: ModOp, e: &Exp, direction: Direction) -> Result<()> {
+        if let (None, Signal::Known(n), Some(m)) =
+            (self.control, &self.signals[index], self.constant(e)?)
+        {
+            self.signals[index] =
+                Signal::Known(wrap(update(*n, &mod_op, m, direction), self.width));
+            return Ok(());
+        }
+
+        self.materialize(index);
+        let Signal::Wires(xs) = self.signals[index].clone() else {
+            unreachable!("signal was materialized");
+        };
+        let value = self.exp(e)?;
+        let ys = value.lines.clone();
+        let controls = self.control.into_iter().collect::<Vec<_>>();
+        match (mod_op, direction) {
+            (ModOp::Xor, _) => {
+                for (&x, &y) in xs.iter().zip(&ys) {
+                    self.controlled(vec![y], x);
+                }
+            }
+            (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => {
+                self.add(&xs, &ys, &controls, false)
+            }
+            _ => self.add(&xs, &ys, &controls, true),
+        }
+        self.discard(value);
+        Ok(())
+    }
+
+    fn assert(&mut self, e: &Exp, expected: bool, keyword: &str, span: Span) -> Result<()> {
+        if let Some(n) = self.constant(e)? {
+            if (n != 0) != expected {
+                bail!("{span}: assertion {keyword} failed");
+            }
+            return Ok(());
+        }
+        let check = self.line();
+        let value = self.truth(e)?;
+        let bit = value.lines.clone()[0];
+        if expected {
+            self.gate(vec![], bit);
+        }
+        self.controlled(vec![bit], check);
+        if expected {
+            self.gate(vec![], bit);
+        }
+        self.discard(value);
+        Ok(())
+    }
+
+    fn branch(&mut self, e: &Exp, c_then: Line, c_else: Line) -> Result<()> {
+        let value = self.truth(e)?;
+        let bit = value.lines.clone()[0];
+        self.controlled(vec![bit], c_then);
+        self.gate(vec![], bit);
+        self.controlled(vec![bit], c_else);
+        self.gate(vec![], bit);
+        self.discard(value);
+        Ok(())
+    }
+
+    fn add(&mut self, xs: &[Line], ys: &[Line], controls: &[Line], subtract: bool) {
+        let start = self.mark();
+        let carry = self.line();
+        let gate = |synth: &mut Self, mut lines: Vec<Line>| {
+            let target = lines.pop().expect("gate has a target");
+            lines.extend(controls);
+            synth.gate(lines, target);
+        };
+        let mut c = carry;
+        for (&x, &y) in xs.iter().zip(ys) {
+            gate(self, vec![y, x]);
+            gate(self, vec![y, c]);
+            gate(self, vec![c, x, y]);
+            c = y;
+        }
+        for (i, (&x, &y)) in xs.iter().zip(ys).enumerate().rev() {
+            let c = match i {
+                0 => carry,
+                _ => ys[i - 1],
+            };
+            gate(self, vec![c, x, y]);
+            gate(self, vec![y, c]);
+            gate(self, vec![c, x]);
+        }
+        self.release(vec![carry]);
+        if subtract {
+            let end = self.mark();
+            let gates = self.circuit.gates.split_off(start);
+            self.circuit.gates.extend(gates.into_iter().rev());
+            debug_assert_eq!(end, self.mark());
+        }
+    }
+
+    fn exp(&mut self, e: &Exp) -> Result<Operand> {
+        let t = self.bundle();
+        let start = self.mark();
+        if let Some(n) = self.constant(e)? {
+            for (i, &line) in t.iter().enumerate() {
+                if n >> i & 1 == 1 {
+                    self.gate(vec![], line);
+                }
+            }
+            let end = self.mark();
+            return Ok(Operand {
+                lines: t,
+                gates: start..end,
+            });
+        }
+
+        match e {
+            Exp::Variable(_) | Exp::Indexed { .. } => {
+                let index = match e {
+                    Exp::Variable(x) => self.scalar(x)?,
+                    Exp::Indexed { x, e } => self.element(x, e)?,
+                    _ => unreachable!("matched above"),
+                };
+                let Signal::Wires(xs) = self.signals[index].clone() else {
+                    unreachable!("known signals are constant");
+                };
+                for (&x, &y) in xs.iter().zip(&t) {
+                    self.gate(vec![x], y);
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let a = self.exp(e_1)?;
+                let b = self.exp(e_2)?;
+                let (xs, ys) = (a.lines.clone(), b.lines.clone());
+                self.op(*op, &t, &xs, &ys)?;
+                self.discard(b);
+                self.discard(a);
+            }
+            _ => unreachable!("constant covers the remaining expressions"),
+        }
+        let end = self.mark();
+        Ok(Operand {
+            lines: t,
+            gates: start..end,
+        })
+    }
+
+    fn truth(&mut self, e: &Exp) -> Result<Operand> {
+        let bit = self.line();
+        let start = self.mark();
+        let value = self.exp(e)?;
+        let xs = value.lines.clone();
+        self.nonzero(&xs, bit);
+        self.discard(value);
+        let end = self.mark();
+        Ok(Operand {
+            lines: vec![bit],
+            gates: start..end,
+        })
+    }
+
+    fn nonzero(&mut self, xs: &[Line], bit: Line) {
+        for &x in xs {
+            self.gate(vec![], x);
+        }
+        self.gate(xs.to_vec(), bit);
+        for &x in xs {
+            self.gate(vec![], x);
+        }
+        self.gate(vec![], bit);
+    }
+
+    fn extended(&mut self, xs: &[Line]) -> Vec<Line> {
+        let ds = (0..=self.width).map(|_| self.line()).collect::<Vec<_>>();
+        for (&x, &d) in xs.iter().zip(&ds) {
+            self.gate(vec![x], d);
+        }
+        self.gate(vec![xs[self.width - 1]], ds[self.width]);
+        ds
+    }
+
+    fn op(&mut self, op: Op, t: &[Line], xs: &[Line], ys: &[Line]) -> Result<()> {
+        match op {
+            Op::Add | Op::Sub => {
+                for (&x, &z) in xs.iter().zip(t) {
+                    self.gate(vec![x], z);
+                }
+                self.add(t, ys, &[], op == Op::Sub);
+            }
+            Op::Xor => {
+                for ((&x, &y), &z) in xs.iter().zip(ys).zip(t) {
+                    self.gate(vec![x], z);
+                    self.gate(vec![y], z);
+                }
+            }
+            Op::And => {
+                for ((&x, &y), &z) in xs.iter().zip(ys).zip(t) {
+                    self.gate(vec![x, y], z);
+                }
+            }
+            Op::Or => {
+                for ((&x, &y), &z) in xs.iter().zip(ys).zip(t) {
+                    self.gate(vec![x], z);
+                    self.gate(vec![y], z);
+                    self.gate(vec![x, y], z);
+                }
+            }
+            Op::Mul => {
+                for (i, &y) in ys.iter().enumerate() {
+                    self.add(&t[i..], &xs[..self.width - i], &[y], false);
+                }
+            }
+            Op::And2 | Op::Or2 => {
+                let start = self.mark();
+                let (a, b) = (self.line(), self.line());
+                self.nonzero(xs, a);
+                self.nonzero(ys, b);
+                let end = self.mark();
+                if op == Op::Or2 {
+                    self.gate(vec![a], t[0]);
+                    self.gate(vec![b], t[0]);
+                }
+                self.gate(vec![a, b], t[0]);
+                self.replay(start..end);
+                self.release(vec![a, b]);
+            }
+            Op::Equal | Op::NotEqual => {
+                let start = self.mark();
+                let ds = self.bundle();
+                for ((&x, &y), &d) in xs.iter().zip(ys).zip(&ds) {
+                    self.gate(vec![x], d);
+                    self.gate(vec![y], d);
+                    self.gate(vec![], d);
+                }
+                let end = self.mark();
+                self.gate(ds.clone(), t[0]);
+                self.replay(start..end);
+                self.release(ds);
+                if op == Op::NotEqual {
+                    self.gate(vec![], t[0]);
+                }
+            }
+            Op::Less | Op::Greater | Op::LessEqual | Op::GreaterEqual => {
+                let (xs, ys) = match op {
+                    Op::Less | Op::GreaterEqual => (xs, ys),
+                    _ => (ys, xs),
+                };
+                let start = self.mark();
+                let ds = self.extended(xs);
+                let es = self.extended(ys);
+                self.add(&ds, &es, &[], true);
+                let end = self.mark();
+                self.gate(vec![ds[self.width]], t[0]);
+                self.replay(start..end);
+                self.release(es);
+                self.release(ds);
+                if matches!(op, Op::LessEqual | Op::GreaterEqual) {
+                    self.gate(vec![], t[0]);
+                }
+            }
+            Op::Div | Op::Mod => bail!("division of circuit inputs is not supported"),
+        }
+        Ok(())
+    }
+}
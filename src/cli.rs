@@ -0,0 +1,1198 @@
+use anyhow::{anyhow, bail, Result};
+use janus::{
+    absint::{self, Facts},
+    ast::{
+        ast_node::{Proc, Prog},
+        load, sexpr,
+    },
+    backend, bennett,
+    bytecode::{compiler::compile, vm::Vm},
+    callgraph::CallGraph,
+    cfg::dot,
+    circuit::{synth::synthesize, wrap, Overflow},
+    coverage::Coverage,
+    flowchart::{
+        convert::{janus_to_srl, rl_to_srl, srl_to_janus, srl_to_rl},
+        machine::Machine,
+        parser, Rl, Srl,
+    },
+    fuzz::fuzz as fuzz_proc,
+    highlight,
+    interpreter::{exhausted, value::Value, Direction, Interpreter, MAX_DEPTH},
+    lint::{lint, Config, Severity},
+    lsp,
+    optimize::{inline::inline, optimize, partial::specialize},
+    pisa::{self, codegen, emulator::Emulator},
+    prove::{
+        equiv::{equiv, Options as EquivOptions, Outcome, Verdict as EquivVerdict},
+        prove, Injectivity, Method, Verdict as ProofVerdict, COLLISION_BUDGET, UNROLL,
+    },
+    termination::{analyze, Verdict as LoopVerdict},
+    tokenizer::{span::Span, token::Token, Tokenizer},
+    trace::Trace,
+    util::{read_file, Rng},
+    verify::{random_input, show, verify_main, verify_proc, Verdict, MAX_STEPS},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, LinkedList},
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    flags: BTreeSet<String>,
+    options: BTreeMap<String, String>,
+}
+
+impl Args {
+    pub fn parse(args: &[String], with_value: &[&str], flags: &[&str]) -> Result<Self> {
+        let mut value = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if with_value.contains(&name) => {
+                    let option = iter
+                        .next()
+                        .ok_or_else(|| anyhow!("--{name} expects a value"))?;
+                    value.options.insert(name.to_string(), option.clone());
+                }
+                Some(name) if flags.contains(&name) => {
+                    value.flags.insert(name.to_string());
+                }
+                Some(name) => bail!("unknown option --{name}"),
+                None => value.positional.push(arg.clone()),
+            }
+        }
+        Ok(value)
+    }
+
+    pub fn file(&self) -> Result<&str> {
+        self.positional
+            .first()
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("expected a file"))
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        match self.option(name) {
+            Some(option) => option
+                .parse()
+                .map_err(|_| anyhow!("invalid value for --{name}: {option}")),
+            None => Ok(default),
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<()> {
+    let (command, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("expected a command"))?;
+
+    match command.as_str() {
+        "run" => run_program(&Args::parse(
+            args,
+            &["inline-size", "max-steps", "max-depth"],
+            &["inline", "vm", "pisa", "check", "trace", "trace-backward"],
+        )?),
+        "cat" => cat(&Args::parse(args, &[], &[])?),
+        "grammar" => grammar(&Args::parse(args, &["format"], &[])?),
+        "lsp" => lsp::serve(io::stdin().lock(), io::stdout().lock()),
+        "parse" => parse_program(&Args::parse(args, &["emit"], &["tokens"])?),
+        "compile" => compile_program(&Args::parse(
+            args,
+            &["target", "inline-size"],
+            &["inline", "prune-checks", "no-checks"],
+        )?),
+        "fuzz" => fuzz(&Args::parse(args, &["proc", "iterations", "seed"], &[])?),
+        "verify" => verify(&Args::parse(
+            args,
+            &["proc", "input", "samples", "seed"],
+            &[],
+        )?),
+        "bennett" => bennett(&Args::parse(args, &[], &["run"])?),
+        "lint" => lint_program(&Args::parse(args, &["format", "rules"], &[])?),
+        "optimize" => optimize_program(&Args::parse(
+            args,
+            &["samples", "seed", "inline-size"],
+            &["inline", "check"],
+        )?),
+        "specialize" => specialize_program(&Args::parse(
+            args,
+            &["proc", "known", "max-unroll", "samples", "seed"],
+            &["check"],
+        )?),
+        "callgraph" => callgraph(&Args::parse(args, &["format"], &[])?),
+        "termination" => termination(&Args::parse(args, &["format"], &[])?),
+        "prove" => prove_proc(&Args::parse(
+            args,
+            &["proc", "width", "method", "unroll", "format"],
+            &[],
+        )?),
+        "equiv" => equiv_procs(&Args::parse(
+            args,
+            &[
+                "left", "right", "width", "method", "unroll", "samples", "seed", "format",
+            ],
+            &[],
+        )?),
+        "coverage" => coverage(&Args::parse(
+            args,
+            &["format", "max-steps", "max-depth"],
+            &[],
+        )?),
+        "cfg" => cfg(&Args::parse(args, &["proc"], &["inverse"])?),
+        "flowchart" => flowchart(&Args::parse(args, &["from", "to"], &["check"])?),
+        "synth" => synth(&Args::parse(
+            args,
+            &["proc", "width", "format", "samples", "seed"],
+            &["check"],
+        )?),
+        x => bail!("unknown command {x}"),
+    }
+}
+
+fn load_program(args: &Args) -> Result<Prog> {
+    let prog = load(args.file()?)?;
+    match args.flag("inline") {
+        true => Ok(inline(prog, args.parsed("inline-size", 8)?)),
+        false => Ok(prog),
+    }
+}
+
+fn run_program(args: &Args) -> Result<()> {
+    let prog = load_program(args)?;
+    let limits = Limits::parse(args)?;
+    if args.flag("vm") {
+        return run_vm(&prog, args.flag("check"), &limits);
+    }
+    if args.flag("pisa") {
+        return run_pisa(&prog, args.flag("check"), &limits);
+    }
+
+    let mut trace = Trace::new(io::stdout().lock());
+    let mut interpreter = Interpreter::new(&prog)?;
+    limits.apply(&mut interpreter);
+
+    let tracing = if args.flag("trace-backward") {
+        interpreter.run(Direction::Forward)?;
+        interpreter.observe(&mut trace);
+        interpreter.run(Direction::Backward)?;
+        true
+    } else if args.flag("trace") {
+        interpreter.observe(&mut trace);
+        interpreter.run(Direction::Forward)?;
+        true
+    } else {
+        interpreter.run(Direction::Forward)?;
+        false
+    };
+
+    let store = interpreter.store();
+    let mut out: Box<dyn Write> = match tracing {
+        true => Box::new(io::stderr()),
+        false => Box::new(io::stdout()),
+    };
+    for (x, value) in store {
+        writeln!(out, "{x} = {value}")?;
+    }
+    Ok(())
+}
+
+struct Limits {
+    max_steps: Option<u64>,
+    max_depth: usize,
+}
+
+impl Limits {
+    fn parse(args: &Args) -> Result<Self> {
+        let max_depth = args.parsed("max-depth", MAX_DEPTH)?;
+        if max_depth > MAX_DEPTH && !args.flag("vm") {
+            bail!("--max-depth can only exceed {MAX_DEPTH} with --vm");
+        }
+        Ok(Self {
+            max_steps: match args.option("max-steps") {
+                Some(_) => Some(args.parsed("max-steps", 0)?),
+                None => None,
+            },
+            max_depth,
+        })
+    }
+
+    fn apply(&self, interpreter: &mut Interpreter) {
+        if let Some(max_steps) = self.max_steps {
+            interpreter.limit_steps(max_steps);
+        }
+        interpreter.limit_depth(self.max_depth.min(MAX_DEPTH));
+    }
+}
+
+fn run_vm(prog: &Prog, check: bool, limits: &Limits) -> Result<()> {
+    let program = compile(prog)?;
+    let mut vm = Vm::new(&program);
+    if let Some(max_steps) = limits.max_steps {
+        vm.limit_steps(max_steps);
+    }
+    vm.limit_depth(limits.max_depth);
+    vm.run(Direction::Forward)?;
+    let store = vm.store();
+
+    if check {
+        let mut interpreter = Interpreter::new(prog)?;
+        limits.apply(&mut interpreter);
+        interpreter.run(Direction::Forward)?;
+        if interpreter.store() != store {
+            bail!("the vm and the interpreter disagree on main");
+        }
+        vm.run(Direction::Backward)?;
+        interpreter.run(Direction::Backward)?;
+        if interpreter.store() != vm.store() {
+            bail!("the vm and the interpreter disagree on uncalling main");
+        }
+
+        let mut rng = Rng::new(1);
+        let arrays = backend::array_params(prog);
+        for p in &prog.ps {
+            let Proc::Other { q, args, .. } = p else {
+                continue;
+            };
+            for direction in [Direction::Forward, Direction::Backward] {
+                for _ in 0..100 {
+                    let input = random_input(args, &arrays[q.0.as_str()], &mut rng);
+                    let mut interpreter = Interpreter::new(prog)?;
+                    interpreter.limit_steps(MAX_STEPS);
+                    let expected = interpreter.call(&q.0, input.clone(), direction);
+                    if expected.as_ref().is_err_and(exhausted) {
+                        continue;
+                    }
+                    let mut vm = Vm::new(&program);
+                    vm.limit_steps(100 * MAX_STEPS);
+                    let found = vm.call(&q.0, input.clone(), direction);
+                    if found.ok() != expected.ok() {
+                        bail!(
+                            "the vm and the interpreter disagree on {} {} with input {}",
+                            direction.name(),
+                            q.0,
+                            show(&input)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    for (x, value) in store {
+        println!("{x} = {value}");
+    }
+    Ok(())
+}
+
+fn run_pisa(prog: &Prog, check: bool, limits: &Limits) -> Result<()> {
+    let program = codegen::compile(prog)?;
+    let mut emulator = Emulator::new(&program);
+    if let Some(max_steps) = limits.max_steps {
+        emulator.limit_steps(max_steps);
+    }
+    emulator.run(Direction::Forward)?;
+    let store = emulator.store();
+
+    if check {
+        let mut interpreter = Interpreter::new(prog)?;
+        limits.apply(&mut interpreter);
+        interpreter.run(Direction::Forward)?;
+        if interpreter.store() != store {
+            bail!("the emulator and the interpreter disagree on main");
+        }
+        emulator.run(Direction::Backward)?;
+        interpreter.run(Direction::Backward)?;
+        if interpreter.store() != emulator.store() {
+            bail!("the emulator and the interpreter disagree on uncalling main");
+        }
+
+        let mut rng = Rng::new(1);
+        let arrays = backend::array_params(prog);
+        for p in &prog.ps {
+            let Proc::Other { q, args, .. } = p else {
+                continue;
+            };
+            for direction in [Direction::Forward, Direction::Backward] {
+                for _ in 0..100 {
+                    let input = random_input(args, &arrays[q.0.as_str()], &mut rng);
+                    let mut interpreter = Interpreter::new(prog)?;
+                    interpreter.limit_steps(MAX_STEPS);
+                    let expected = interpreter.call(&q.0, input.clone(), direction);
+                    if expected.as_ref().is_err_and(exhausted) {
+                        continue;
+                    }
+                    let found = pisa::call(prog, &q.0, &input, direction, 100 * MAX_STEPS);
+                    if found.ok() != expected.ok() {
+                        bail!(
+                            "the emulator and the interpreter disagree on {} {} with input {}",
+                            direction.name(),
+                            q.0,
+                            show(&input)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    for (x, value) in store {
+        println!("{x} = {value}");
+    }
+    Ok(())
+}
+
+fn parse_program(args: &Args) -> Result<()> {
+    let json = match args.flag("tokens") {
+        true => {
+            let mut tokenizer = Tokenizer::new(read_file(args.file()?)?);
+            tokenizer.tokenize()?;
+            let tokens: LinkedList<(Token, Span)> = tokenizer.into();
+            serde_json::to_value(tokens)?
+        }
+        false => serde_json::to_value(load(args.file()?)?)?,
+    };
+    match args.option("emit").unwrap_or("json") {
+        "json" => println!("{}", serde_json::to_string_pretty(&json)?),
+        "sexpr" => print!("{}", sexpr::to_string(&json)),
+        emit => bail!("unknown format {emit}"),
+    }
+    Ok(())
+}
+
+fn cat(args: &Args) -> Result<()> {
+    let text = std::fs::read_to_string(args.file()?)?;
+    let mut tokenizer = Tokenizer::new(text.chars().collect());
+    tokenizer.tokenize()?;
+    let tokens = tokenizer.tokens().iter().cloned().collect::<Vec<_>>();
+    print!("{}", highlight::ansi(&text, &tokens));
+    Ok(())
+}
+
+fn grammar(args: &Args) -> Result<()> {
+    let grammar = match args.option("format").unwrap_or("textmate") {
+        "textmate" => highlight::textmate(),
+        "spec" => highlight::spec(),
+        format => bail!("unknown format {format}"),
+    };
+    println!("{}", serde_json::to_string_pretty(&grammar)?);
+    Ok(())
+}
+
+fn compile_program(args: &Args) -> Result<()> {
+    let prog = load_program(args)?;
+    let out = match args.option("target") {
+        Some("c") => {
+            let facts = match args.flag("prune-checks") {
+                true => absint::analyze(&prog),
+                false => Facts::default(),
+            };
+            backend::c::emit(&prog, !args.flag("no-checks"), &facts)?
+        }
+        Some("rust") => backend::rust::emit(&prog)?,
+        Some("wat") => backend::wat::emit(&prog)?,
+        Some("pisa") => codegen::compile(&prog)?.to_string(),
+        Some(target) => bail!("unknown target {target}"),
+        None => bail!("expected --target"),
+    };
+    print!("{out}");
+    Ok(())
+}
+
+fn verify(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let samples = args.parsed("samples", 100)?;
+    let mut rng = Rng::new(args.parsed("seed", 1)?);
+    let arrays = backend::array_params(&prog);
+    let mut diverged = false;
+    let mut unchecked = Vec::new();
+
+    if args.option("proc").is_none() {
+        match verify_main(&prog)? {
+            Verdict::Identity => println!("main: ok"),
+            Verdict::Rejected(e) => {
+                println!("main: forward run failed: {e:#}");
+                unchecked.push("main".to_string());
+            }
+            Verdict::Diverged(divergence) => {
+                println!("main: {divergence}");
+                diverged = true;
+            }
+        }
+    }
+
+    for p in &prog.ps {
+        let Proc::Other {
+            q, args: params, ..
+        } = p
+        else {
+            continue;
+        };
+        if args.option("proc").is_some_and(|name| name != q.0) {
+            continue;
+        }
+
+        let inputs = match args.option("input") {
+            Some(input) => vec![parse_input(&prog, &q.0, input)?],
+            None => (0..samples)
+                .map(|_| random_input(params, &arrays[q.0.as_str()], &mut rng))
+                .collect(),
+        };
+        let (mut identities, mut rejected) = (0, 0);
+        for input in inputs {
+            match verify_proc(&prog, &q.0, input.clone())? {
+                Verdict::Identity => identities += 1,
+                Verdict::Rejected(_) => rejected += 1,
+                Verdict::Diverged(divergence) => {
+                    println!("{}: {divergence}", q.0);
+                    println!("  input: {}", show(&input));
+                    diverged = true;
+                    break;
+                }
+            }
+        }
+        println!(
+            "{}: {identities} round trips, {rejected} inputs rejected",
+            q.0
+        );
+        if identities == 0 && !diverged {
+            unchecked.push(q.0.clone());
+        }
+    }
+
+    if diverged {
+        bail!("round trip verification failed");
+    }
+    if !unchecked.is_empty() {
+        bail!(
+            "no input completed a round trip for {}",
+            unchecked.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn bennett(args: &Args) -> Result<()> {
+    let program = bennett::syntax::load(args.file()?)?;
+    let prog = bennett::translate(&program)?;
+    if !args.flag("run") {
+        print!("{prog}");
+        return Ok(());
+    }
+
+    let mut interpreter = Interpreter::new(&prog)?;
+    interpreter.run(Direction::Forward)?;
+    for (x, value) in interpreter.store() {
+        println!("{x} = {value}");
+    }
+    Ok(())
+}
+
+fn lint_program(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let config = Config::parse(args.option("rules").unwrap_or_default())?;
+    let findings = lint(&prog, &config);
+    match args.option("format").unwrap_or("text") {
+        "text" => {
+            for finding in &findings {
+                println!(
+                    "{}: {}: {} [{}]",
+                    finding.span,
+                    finding.severity.name(),
+                    finding.message,
+                    finding.rule.name()
+                );
+            }
+        }
+        "json" => println!("{}", serde_json::to_string_pretty(&findings)?),
+        format => bail!("unknown format {format}"),
+    }
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .count();
+    match errors {
+        0 => {}
+        1 => bail!("1 lint error"),
+        _ => bail!("{errors} lint errors"),
+    }
+    Ok(())
+}
+
+fn optimize_program(args: &Args) -> Result<()> {
+    let original = load(args.file()?)?;
+    let optimized = optimize(load_program(args)?);
+    print!("{optimized}");
+    if !args.flag("check") {
+        return Ok(());
+    }
+
+    let samples = args.parsed("samples", 100)?;
+    let mut rng = Rng::new(args.parsed("seed", 1)?);
+    let (mut agreed, mut rejected) = (0, 0);
+    let run_main = |prog: &Prog| -> Result<Vec<(String, Value)>> {
+        let mut interpreter = Interpreter::new(prog)?;
+        interpreter.limit_steps(MAX_STEPS);
+        interpreter.run(Direction::Forward)?;
+        let store = interpreter.store();
+        interpreter.run(Direction::Backward)?;
+        check_cleared(interpreter.store())?;
+        Ok(store)
+    };
+    match run_main(&original) {
+        Ok(expected) => {
+            let actual = run_main(&optimized).map_err(|e| e.context("the optimized main fails"))?;
+            if let Some(((x, value), (_, v))) = expected.iter().zip(&actual).find(|(a, b)| a != b) {
+                bail!("main: {x} is {value} originally but {v} after optimization");
+            }
+            agreed += 1;
+        }
+        Err(_) => rejected += 1,
+    }
+
+    let call = |prog: &Prog, q: &str, input: Vec<Value>| -> Result<(Vec<Value>, Vec<Value>)> {
+        let mut interpreter = Interpreter::new(prog)?;
+        interpreter.limit_steps(MAX_STEPS);
+        let output = interpreter.call(q, input, Direction::Forward)?;
+        let input = interpreter.call(q, output.clone(), Direction::Backward)?;
+        Ok((output, input))
+    };
+    let arrays = backend::array_params(&original);
+    for p in &original.ps {
+        let Proc::Other { q, args, .. } = p else {
+            continue;
+        };
+        for _ in 0..samples {
+            let input = random_input(args, &arrays[q.0.as_str()], &mut rng);
+            let Ok(expected) = call(&original, &q.0, input.clone()) else {
+                rejected += 1;
+                continue;
+            };
+            let actual = call(&optimized, &q.0, input.clone()).map_err(|e| {
+                e.context(format!("the optimized {} fails on {}", q.0, show(&input)))
+            })?;
+            if actual != expected {
+                bail!(
+                    "{}: on {} the original gives {} but the optimized gives {}",
+                    q.0,
+                    show(&input),
+                    show(&expected.0),
+                    show(&actual.0)
+                );
+            }
+            agreed += 1;
+        }
+    }
+    eprintln!("{agreed} runs agree in both directions, {rejected} rejected by the original");
+    Ok(())
+}
+
+fn specialize_program(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let q = args.option("proc").unwrap_or("main");
+    let mut known = Vec::new();
+    for binding in args.option("known").unwrap_or_default().split(',') {
+        if binding.is_empty() {
+            continue;
+        }
+        let (x, value) = binding
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected NAME=VALUE, found {binding}"))?;
+        let value = value
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| anyhow!("invalid value for {x}: {value}"))?;
+        known.push((x.trim().to_string(), value));
+    }
+    let residual = specialize(&prog, q, &known, args.parsed("max-unroll", 10_000)?)?;
+    let residual_prog = optimize(residual.prog);
+    print!("{residual_prog}");
+    for (x, value) in &residual.preconditions {
+        eprintln!("{x} must be {value} on entry to {}", residual.q);
+    }
+    for (x, value) in &residual.finals {
+        eprintln!("{x} = {value} on exit");
+    }
+    if !args.flag("check") {
+        return Ok(());
+    }
+
+    let (mut agreed, mut rejected) = (0, 0);
+    if q == "main" {
+        let run_main = |prog: &Prog| -> Result<Vec<(String, Value)>> {
+            let mut interpreter = Interpreter::new(prog)?;
+            interpreter.limit_steps(MAX_STEPS);
+            interpreter.run(Direction::Forward)?;
+            let store = interpreter.store();
+            interpreter.run(Direction::Backward)?;
+            check_cleared(interpreter.store())?;
+            Ok(store)
+        };
+        match run_main(&prog) {
+            Ok(expected) => {
+                let actual =
+                    run_main(&residual_prog).map_err(|e| e.context("the residual main fails"))?;
+                if let Some(((x, value), (_, v))) =
+                    expected.iter().zip(&actual).find(|(a, b)| a != b)
+                {
+                    bail!("main: {x} is {value} originally but {v} in the residual program");
+                }
+                agreed += 1;
+            }
+            Err(e) if exhausted(&e) => {}
+            Err(e) => {
+                if run_main(&residual_prog).is_ok() {
+                    bail!("main: the original fails ({e:#}) but the residual program does not");
+                }
+                rejected += 1;
+            }
+        }
+        return report_agreement(agreed, rejected);
+    }
+
+    let Some(Proc::Other { args: params, .. }) = prog.find(q) else {
+        bail!("procedure {q} is not defined");
+    };
+    let mut rng = Rng::new(args.parsed("seed", 1)?);
+    let arrays = backend::array_params(&prog);
+    for _ in 0..args.parsed("samples", 100)? {
+        let mut input = random_input(params, &arrays[q], &mut rng);
+        for (arg, value) in params.iter().zip(input.iter_mut()) {
+            if let Some((_, n)) = known.iter().find(|(x, _)| *x == arg.x.0) {
+                *value = Value::Int(*n);
+            }
+        }
+        let residual_input = residual
+            .params
+            .iter()
+            .map(|x| {
+                let i = params
+                    .iter()
+                    .position(|arg| arg.x.0 == *x)
+                    .unwrap_or_default();
+                input[i].clone()
+            })
+            .collect::<Vec<_>>();
+        let mut original = Interpreter::new(&prog)?;
+        original.limit_steps(MAX_STEPS);
+        let mut interpreter = Interpreter::new(&residual_prog)?;
+        interpreter.limit_steps(MAX_STEPS);
+        let expected = match original.call(q, input.clone(), Direction::Forward) {
+            Ok(expected) => expected,
+            Err(e) if exhausted(&e) => continue,
+            Err(e) => {
+                let residual_result =
+                    interpreter.call(&residual.q, residual_input, Direction::Forward);
+                if residual_result.is_ok() {
+                    bail!(
+                        "{q}: on {} the original fails ({e:#}) but the residual program does not",
+                        show(&input)
+                    );
+                }
+                rejected += 1;
+                continue;
+            }
+        };
+        let actual = interpreter
+            .call(&residual.q, residual_input.clone(), Direction::Forward)
+            .map_err(|e| e.context(format!("the residual program fails on {}", show(&input))))?;
+        for (arg, value) in params.iter().zip(&expected) {
+            let x = &arg.x.0;
+            let v = match residual.params.iter().position(|y| y == x) {
+                Some(i) => actual[i].clone(),
+                None => residual
+                    .finals
+                    .iter()
+                    .find(|(y, _)| y == x)
+                    .map(|(_, n)| Value::Int(*n))
+                    .ok_or_else(|| anyhow!("{x} has no value after specialization"))?,
+            };
+            if v != *value {
+                bail!(
+                    "{q}: on {} {x} is {value} originally but {v} in the residual program",
+                    show(&input)
+                );
+            }
+        }
+        let restored = interpreter
+            .call(&residual.q, actual, Direction::Backward)
+            .map_err(|e| e.context("the residual program fails backward"))?;
+        if restored != residual_input {
+            bail!(
+                "{q}: running the residual program backward gives {} instead of {}",
+                show(&restored),
+                show(&residual_input)
+            );
+        }
+        agreed += 1;
+    }
+    report_agreement(agreed, rejected)
+}
+
+fn report_agreement(agreed: usize, rejected: usize) -> Result<()> {
+    if agreed == 0 {
+        bail!("no run was accepted by the original program, so nothing was checked");
+    }
+    eprintln!("{agreed} runs agree in both directions, {rejected} rejected by both");
+    Ok(())
+}
+
+fn callgraph(args: &Args) -> Result<()> {
+    let graph = CallGraph::new(&load(args.file()?)?);
+    match args.option("format").unwrap_or("dot") {
+        "dot" => print!("{}", graph.dot()),
+        "json" => println!("{}", serde_json::to_string_pretty(&graph.to_json())?),
+        format => bail!("unknown format {format}"),
+    }
+    Ok(())
+}
+
+fn termination(args: &Args) -> Result<()> {
+    let reports = analyze(&load(args.file()?)?);
+    match args.option("format").unwrap_or("text") {
+        "text" => {
+            for report in &reports {
+                let counter = match &report.counter {
+                    Some(i) => format!("counter {i}: "),
+                    None => String::new(),
+                };
+                let verdict = match &report.verdict {
+                    LoopVerdict::Bounded {
+                        iterations,
+                        requires: Some(requires),
+                    } => format!("terminates after {iterations} iterations if {requires}"),
+                    LoopVerdict::Bounded { iterations, .. } => {
+                        format!("terminates after {iterations} iterations")
+                    }
+                    LoopVerdict::Diverges { reason } => format!("cannot terminate: {reason}"),
+                    LoopVerdict::FailsOnReentry { reason } => {
+                        format!("fails on re-entry: {reason}")
+                    }
+                    LoopVerdict::Unknown { reason } => format!("unknown: {reason}"),
+                };
+                println!("{}: {}: {counter}{verdict}", report.span, report.proc);
+            }
+        }
+        "json" => println!("{}", serde_json::to_string_pretty(&reports)?),
+        format => bail!("unknown format {format}"),
+    }
+    Ok(())
+}
+
+fn method(args: &Args) -> Result<Option<Method>> {
+    match args.option("method") {
+        None => Ok(None),
+        Some("random") => Ok(Some(Method::Random)),
+        Some("exhaustive") => Ok(Some(Method::Exhaustive)),
+        Some("symbolic") => Ok(Some(Method::Symbolic)),
+        Some(method) => bail!("unknown method {method}"),
+    }
+}
+
+fn show_assignment(assignment: &[(String, i32)]) -> String {
+    assignment
+        .iter()
+        .map(|(x, n)| format!("{x} = {n}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn prove_proc(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let method = method(args)?;
+    let report = prove(
+        &prog,
+        args.option("proc").unwrap_or("main"),
+        args.parsed("width", 8)?,
+        method,
+        args.parsed("unroll", UNROLL)?,
+    )?;
+    let show = show_assignment;
+    match args.option("format").unwrap_or("text") {
+        "text" => match &report.verdict {
+            ProofVerdict::Proved { injectivity } => {
+                let checked = match report.method {
+                    Method::Symbolic => format!("{} clauses", report.clauses),
+                    _ => format!("{} inputs", 1u64 << report.bits),
+                };
+                println!(
+                    "{}: injective and no assertion fails for any {}-bit input ({}, {checked})",
+                    report.proc,
+                    report.width,
+                    report.method.name()
+                );
+                if *injectivity == Injectivity::Reversibility {
+                    println!(
+                        "  the solver gave up on injectivity after {COLLISION_BUDGET} conflicts; \
+                         it follows from reversibility since no assertion fails"
+                    );
+                }
+            }
+            ProofVerdict::Fails { input, error, path } => {
+                match input.is_empty() {
+                    true => println!("{}: fails", report.proc),
+                    false => println!("{}: fails for {}", report.proc, show(input)),
+                }
+                println!("  {error}");
+                for event in path {
+                    println!("  {event}");
+                }
+            }
+            ProofVerdict::Collides { inputs, output } => println!(
+                "{}: not injective: {} and {} both give {}",
+                report.proc,
+                show(&inputs[0]),
+                show(&inputs[1]),
+                show(output)
+            ),
+            ProofVerdict::Unknown { input, reason } => {
+                println!("{}: undecided for {}: {reason}", report.proc, show(input))
+            }
+        },
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        format => bail!("unknown format {format}"),
+    }
+    match report.verdict {
+        ProofVerdict::Proved { .. } => Ok(()),
+        ProofVerdict::Unknown { .. } => bail!("proof incomplete"),
+        _ => bail!("proof failed"),
+    }
+}
+
+fn equiv_procs(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let (Some(left), Some(right)) = (args.option("left"), args.option("right")) else {
+        bail!("expected --left and --right");
+    };
+    let options = EquivOptions {
+        width: args.parsed("width", 8)?,
+        method: method(args)?,
+        unroll: args.parsed("unroll", UNROLL)?,
+        samples: args.parsed("samples", 1000)?,
+        seed: args.parsed("seed", 1)?,
+    };
+    let report = equiv(&prog, left, right, &options)?;
+    let show = |q: &str, outcome: &Outcome| match outcome {
+        Outcome::Returns { output } => println!("  {q} gives {}", show_assignment(output)),
+        Outcome::Fails { error } => println!("  {q} fails: {error}"),
+    };
+    match args.option("format").unwrap_or("text") {
+        "text" => match &report.verdict {
+            EquivVerdict::Equivalent => {
+                let (scope, checked) = match report.method {
+                    Method::Random => (
+                        format!("{} random {}-bit inputs", report.inputs, report.width),
+                        format!("seed {}", options.seed),
+                    ),
+                    Method::Exhaustive => (
+                        format!("every {}-bit input", report.width),
+                        format!("{} inputs", report.inputs),
+                    ),
+                    Method::Symbolic => (
+                        format!("every {}-bit input", report.width),
+                        format!("{} clauses", report.clauses),
+                    ),
+                };
+                println!(
+                    "{left} and {right} agree on {scope} ({}, {checked})",
+                    report.method.name()
+                );
+            }
+            EquivVerdict::Differ {
+                input,
+                left: left_outcome,
+                right: right_outcome,
+            } => {
+                println!("{left} and {right} differ on {}", show_assignment(input));
+                show(left, left_outcome);
+                show(right, right_outcome);
+            }
+            EquivVerdict::Unknown { input, reason } => println!(
+                "{left} and {right}: undecided for {}: {reason}",
+                show_assignment(input)
+            ),
+        },
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        format => bail!("unknown format {format}"),
+    }
+    match report.verdict {
+        EquivVerdict::Equivalent => Ok(()),
+        EquivVerdict::Unknown { .. } => bail!("equivalence check incomplete"),
+        EquivVerdict::Differ { .. } => bail!("procedures differ"),
+    }
+}
+
+fn coverage(args: &Args) -> Result<()> {
+    let path = args.file()?;
+    let format = args.option("format").unwrap_or("text");
+    let extension = Path::new(path).extension().and_then(|x| x.to_str());
+    if format == "text" && matches!(extension, Some("json" | "sexpr")) {
+        bail!("--format text annotates Janus source, which {path} is not; use --format lcov");
+    }
+    let prog = load(path)?;
+    let limits = Limits::parse(args)?;
+    let mut coverage = Coverage::default();
+    let mut interpreter = Interpreter::new(&prog)?;
+    limits.apply(&mut interpreter);
+    interpreter.observe(&mut coverage);
+    let result = interpreter
+        .run(Direction::Forward)
+        .and_then(|_| interpreter.run(Direction::Backward));
+    drop(interpreter);
+
+    match format {
+        "text" => print!(
+            "{}",
+            coverage.annotate(&prog, &std::fs::read_to_string(path)?)
+        ),
+        "lcov" => print!("{}", coverage.lcov(&prog, path)),
+        format => bail!("unknown format {format}"),
+    }
+    result
+}
+
+fn cfg(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let q = args.option("proc").unwrap_or("main");
+    print!("{}", dot(&prog, q, args.flag("inverse"))?);
+    Ok(())
+}
+
+enum Flowchart {
+    Janus(Box<Prog>),
+    Srl(Srl),
+    Rl(Rl),
+}
+
+impl Flowchart {
+    fn run(&self) -> Result<Vec<(String, Value)>> {
+        match self {
+            Flowchart::Janus(prog) => {
+                let mut interpreter = Interpreter::new(prog)?;
+                interpreter.run(Direction::Forward)?;
+                let store = interpreter.store();
+                interpreter.run(Direction::Backward)?;
+                check_cleared(interpreter.store())?;
+                Ok(store)
+            }
+            Flowchart::Srl(srl) => Flowchart::Janus(Box::new(srl_to_janus(srl))).run(),
+            Flowchart::Rl(rl) => {
+                let mut machine = Machine::new(rl)?;
+                machine.run(Direction::Forward)?;
+                let store = machine.store();
+                machine.run(Direction::Backward)?;
+                check_cleared(machine.store())?;
+                Ok(store)
+            }
+        }
+    }
+}
+
+fn check_cleared(store: Vec<(String, Value)>) -> Result<()> {
+    match store.iter().find(|(_, value)| !value.is_zero()) {
+        Some((x, value)) => bail!("running backward leaves {x} = {value}"),
+        None => Ok(()),
+    }
+}
+
+fn flowchart(args: &Args) -> Result<()> {
+    let source = match args.option("from").unwrap_or("janus") {
+        "janus" => Flowchart::Janus(Box::new(load(args.file()?)?)),
+        "rl" => Flowchart::Rl(parser::load(args.file()?)?),
+        from => bail!("unknown source language {from}"),
+    };
+    let srl = match &source {
+        Flowchart::Janus(prog) => janus_to_srl(prog)?,
+        Flowchart::Rl(rl) => rl_to_srl(rl)?,
+        Flowchart::Srl(_) => unreachable!("SRL is never read directly"),
+    };
+    let target = match args.option("to").unwrap_or("srl") {
+        "janus" => Flowchart::Janus(Box::new(srl_to_janus(&srl))),
+        "srl" => Flowchart::Srl(srl),
+        "rl" => Flowchart::Rl(srl_to_rl(&srl)),
+        to => bail!("unknown target language {to}"),
+    };
+    match &target {
+        Flowchart::Janus(prog) => print!("{prog}"),
+        Flowchart::Srl(srl) => print!("{srl}"),
+        Flowchart::Rl(rl) => print!("{rl}"),
+    }
+    if !args.flag("check") {
+        return Ok(());
+    }
+
+    let expected = source
+        .run()
+        .map_err(|e| e.context("the source program fails"))?;
+    let actual = target
+        .run()
+        .map_err(|e| e.context("the translation fails"))?;
+    for (x, value) in &expected {
+        match actual.iter().find(|(y, _)| y == x) {
+            Some((_, v)) if v == value => {}
+            Some((_, v)) => bail!("{x} is {value} in the source but {v} in the translation"),
+            None => bail!("{x} is missing from the translation"),
+        }
+    }
+    let extra = actual
+        .iter()
+        .filter(|(x, _)| !expected.iter().any(|(y, _)| y == x))
+        .collect::<Vec<_>>();
+    if let Some((x, value)) = extra.iter().find(|(_, value)| !value.is_zero()) {
+        bail!("{x} is left at {value} by the translation");
+    }
+    eprintln!(
+        "{} variables agree, {} auxiliary variables cleared, both run backward to zero",
+        expected.len(),
+        extra.len()
+    );
+    Ok(())
+}
+
+fn synth(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let q = args.option("proc").unwrap_or("main");
+    let width = args.parsed("width", 8)?;
+    let circuit = synthesize(&prog, q, width)?;
+    match args.option("format").unwrap_or("real") {
+        "real" => print!("{}", circuit.real()),
+        "qc" => print!("{}", circuit.qc()),
+        format => bail!("unknown format {format}"),
+    }
+    if !args.flag("check") {
+        return Ok(());
+    }
+
+    let flatten = |values: Vec<Value>| {
+        values
+            .into_iter()
+            .flat_map(|value| match value {
+                Value::Int(n) => vec![n],
+                Value::Array(ns) | Value::Stack(ns) => ns,
+            })
+            .collect::<Vec<_>>()
+    };
+    let samples = match q {
+        "main" => 1,
+        _ => args.parsed("samples", 100)?,
+    };
+    let mut rng = Rng::new(args.parsed("seed", 1)?);
+    let low = wrap(1i32.wrapping_shl(width as u32 - 1), width);
+    let high = !low;
+    let (mut agreed, mut skipped) = (0, 0);
+    let attempts = match q {
+        "main" => 1,
+        _ => samples * 10,
+    };
+    while agreed < samples && agreed + skipped < attempts {
+        let inputs = (0..circuit.inputs.len())
+            .map(|_| rng.range(low, high))
+            .collect::<Vec<_>>();
+        let mut overflow = Overflow {
+            width,
+            overflowed: false,
+        };
+        let mut interpreter = Interpreter::new(&prog)?;
+        interpreter.limit_steps(MAX_STEPS);
+        interpreter.observe(&mut overflow);
+        let expected = match q {
+            "main" => interpreter.run(Direction::Forward).map(|_| {
+                interpreter
+                    .store()
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect()
+            }),
+            _ => interpreter.call(
+                q,
+                inputs.iter().map(|&n| Value::Int(n)).collect(),
+                Direction::Forward,
+            ),
+        };
+        let expected = match expected.map(flatten) {
+            Ok(ns) if !overflow.overflowed && ns.iter().all(|&n| n == wrap(n, width)) => ns,
+            _ => {
+                skipped += 1;
+                continue;
+            }
+        };
+        match circuit.simulate(&inputs) {
+            Ok(actual) if actual == expected => agreed += 1,
+            actual => {
+                let show = |ns: &[i32]| {
+                    ns.iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                eprintln!("{q}: the circuit disagrees with the interpreter");
+                eprintln!("  input: {}", show(&inputs));
+                eprintln!("  interpreter: {}", show(&expected));
+                match actual {
+                    Ok(actual) => eprintln!("  circuit: {}", show(&actual)),
+                    Err(e) => eprintln!("  circuit: {e:#}"),
+                }
+                bail!("circuit check failed");
+            }
+        }
+    }
+    eprintln!("{q}: {agreed} samples agree, {skipped} skipped");
+    if agreed * 2 < samples {
+        bail!(
+            "only {agreed} of {} samples ran without overflow or a failed assertion",
+            agreed + skipped
+        );
+    }
+    Ok(())
+}
+
+fn parse_input(prog: &Prog, q: &str, input: &str) -> Result<Vec<Value>> {
+    let bindings = input
+        .split_whitespace()
+        .map(|binding| {
+            let (x, value) = binding
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected name=value found {binding}"))?;
+            Ok((x.to_string(), value.parse()?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Interpreter::new(prog)?.arg_store(q, bindings)
+}
+
+fn fuzz(args: &Args) -> Result<()> {
+    let prog = load(args.file()?)?;
+    let q = args
+        .option("proc")
+        .ok_or_else(|| anyhow!("expected --proc"))?;
+    let iterations = args.parsed("iterations", 1000)?;
+    let mut rng = Rng::new(args.parsed("seed", 1)?);
+
+    let summary = fuzz_proc(&prog, q, iterations, &mut rng)?;
+    if let Some(failure) = summary.failure {
+        println!("{q}: failed at iteration {}", failure.iteration);
+        println!("  input: {}", show(&failure.input));
+        println!("  shrunk: {}", show(&failure.shrunk));
+        println!("  reason: {}", failure.reason);
+        bail!("reversibility fuzzing failed");
+    }
+    println!(
+        "{q}: {} iterations passed, {} inputs rejected by the forward run",
+        summary.passed, summary.rejected
+    );
+    if summary.passed == 0 {
+        bail!("no input completed a round trip for {q}");
+    }
+    Ok(())
+}
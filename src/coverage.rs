@@ -0,0 +1,235 @@
+use crate::{
+    ast::ast_node::*,
+    interpreter::{Branch, Direction, Event, Observer},
+    tokenizer::span::Span,
+};
+use anyhow::Result;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+const DIRECTIONS: [Direction; 2] = [Direction::Forward, Direction::Backward];
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    stms: BTreeMap<(Span, Direction), u64>,
+    branches: BTreeMap<(Span, Branch, Direction), u64>,
+}
+
+impl Observer for Coverage {
+    fn stm(&mut self, _event: &Event) -> Result<()> {
+        Ok(())
+    }
+
+    fn enter(&mut self, stm: &Stm, direction: Direction) -> Result<()> {
+        *self.stms.entry((stm.span(), direction)).or_default() += 1;
+        Ok(())
+    }
+
+    fn branch(&mut self, stm: &Stm, branch: Branch, direction: Direction) -> Result<()> {
+        *self
+            .branches
+            .entry((stm.span(), branch, direction))
+            .or_default() += 1;
+        Ok(())
+    }
+}
+
+fn sites(prog: &Prog) -> Vec<&Stm> {
+    let mut sites = Vec::new();
+    for proc in prog.procs() {
+        proc.body().walk(&mut |stm| {
+            if !matches!(stm, Stm::Sequence(..)) {
+                sites.push(stm);
+            }
+        });
+    }
+    sites
+}
+
+fn branches(stm: &Stm) -> &'static [Branch] {
+    match stm {
+        Stm::Conditional { .. } => &[Branch::Then, Branch::Else],
+        Stm::Loop { .. } => &[Branch::Exit, Branch::Loop],
+        _ => &[],
+    }
+}
+
+fn first(stm: &Stm, direction: Direction) -> &Stm {
+    match (stm, direction) {
+        (Stm::Sequence(s_1, _), Direction::Forward) => first(s_1, direction),
+        (Stm::Sequence(_, s_2), Direction::Backward) => first(s_2, direction),
+        _ => stm,
+    }
+}
+
+fn line(proc: &Proc) -> usize {
+    match proc {
+        Proc::Main { span, .. } => span.start.line,
+        Proc::Other { q, .. } => q.1.start.line,
+    }
+}
+
+struct Summary {
+    hit: usize,
+    total: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {}", self.hit, self.total)
+    }
+}
+
+impl Coverage {
+    pub fn hits(&self, stm: &Stm, direction: Direction) -> u64 {
+        self.stms
+            .get(&(stm.span(), direction))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn taken(&self, stm: &Stm, branch: Branch, direction: Direction) -> u64 {
+        self.branches
+            .get(&(stm.span(), branch, direction))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn calls(&self, proc: &Proc, direction: Direction) -> u64 {
+        self.hits(first(proc.body(), direction), direction)
+    }
+
+    fn lines(&self, sites: &[&Stm], direction: Direction) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+        for site in sites {
+            let count = lines.entry(site.span().start.line).or_default();
+            *count = self.hits(site, direction).max(*count);
+        }
+        lines
+    }
+
+    fn statements(&self, sites: &[&Stm], direction: Direction) -> Summary {
+        Summary {
+            hit: sites
+                .iter()
+                .filter(|site| self.hits(site, direction) > 0)
+                .count(),
+            total: sites.len(),
+        }
+    }
+
+    fn branch_summary(&self, sites: &[&Stm], direction: Direction) -> Summary {
+        let mut summary = Summary { hit: 0, total: 0 };
+        for site in sites {
+            for &branch in branches(site) {
+                summary.total += 1;
+                if self.taken(site, branch, direction) > 0 {
+                    summary.hit += 1;
+                }
+            }
+        }
+        summary
+    }
+
+    pub fn lcov(&self, prog: &Prog, path: &str) -> String {
+        let sites = sites(prog);
+        let mut out = String::new();
+        for direction in DIRECTIONS {
+            let _ = writeln!(out, "TN:{}", direction.name());
+            let _ = writeln!(out, "SF:{path}");
+            for proc in prog.procs() {
+                let _ = writeln!(out, "FN:{},{}", line(proc), proc.name());
+            }
+            let mut called = 0;
+            for proc in prog.procs() {
+                let calls = self.calls(proc, direction);
+                if calls > 0 {
+                    called += 1;
+                }
+                let _ = writeln!(out, "FNDA:{calls},{}", proc.name());
+            }
+            let _ = writeln!(out, "FNF:{}", prog.procs().count());
+            let _ = writeln!(out, "FNH:{called}");
+
+            let branch_sites = sites.iter().filter(|site| !branches(site).is_empty());
+            for (block, site) in branch_sites.enumerate() {
+                let entered = self.hits(site, direction) > 0;
+                for (i, &branch) in branches(site).iter().enumerate() {
+                    let taken = match entered {
+                        true => self.taken(site, branch, direction).to_string(),
+                        false => "-".to_string(),
+                    };
+                    let line = site.span().start.line;
+                    let _ = writeln!(out, "BRDA:{line},{block},{i},{taken}");
+                }
+            }
+            let summary = self.branch_summary(&sites, direction);
+            let _ = writeln!(out, "BRF:{}", summary.total);
+            let _ = writeln!(out, "BRH:{}", summary.hit);
+
+            let lines = self.lines(&sites, direction);
+            for (line, count) in &lines {
+                let _ = writeln!(out, "DA:{line},{count}");
+            }
+            let _ = writeln!(out, "LF:{}", lines.len());
+            let hit = lines.values().filter(|&&count| count > 0).count();
+            let _ = writeln!(out, "LH:{hit}");
+            let _ = writeln!(out, "end_of_record");
+        }
+        out
+    }
+
+    pub fn annotate(&self, prog: &Prog, source: &str) -> String {
+        let sites = sites(prog);
+        let [statements, branches_taken] = [Self::statements, Self::branch_summary]
+            .map(|summary| DIRECTIONS.map(|direction| summary(self, &sites, direction)));
+        let [forward, backward] = DIRECTIONS.map(|direction| self.lines(&sites, direction));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "statements: {} run forward, {} backward",
+            statements[0], statements[1]
+        );
+        let _ = writeln!(
+            out,
+            "branches: {} taken forward, {} backward",
+            branches_taken[0], branches_taken[1]
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(out, " forward backward");
+
+        let count = |lines: &BTreeMap<usize, u64>, line| match lines.get(&line) {
+            None => String::new(),
+            Some(0) => "#####".to_string(),
+            Some(count) => count.to_string(),
+        };
+        for (i, text) in source.lines().enumerate() {
+            let line = i + 1;
+            let _ = writeln!(
+                out,
+                "{:>8} {:>8} | {text}",
+                count(&forward, line),
+                count(&backward, line)
+            );
+            for site in &sites {
+                if site.span().start.line != line || branches(site).is_empty() {
+                    continue;
+                }
+                let taken = branches(site)
+                    .iter()
+                    .map(|&branch| {
+                        let [f, b] =
+                            DIRECTIONS.map(|direction| self.taken(site, branch, direction));
+                        format!("{}: {f} forward, {b} backward", branch.name())
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                let _ = writeln!(out, "{:>17} | {taken}", "");
+            }
+        }
+        out
+    }
+}
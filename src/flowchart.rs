@@ -0,0 +1,295 @@
+pub mod convert;
+pub mod machine;
+pub mod parser;
+
+use crate::{
+    ast::ast_node::{Exp, ModOp},
+    backend::{Kind, Writer},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    Update {
+        x: String,
+        index: Option<Exp>,
+        mod_op: ModOp,
+        e: Exp,
+    },
+    Push(String, String),
+    Pop(String, String),
+    Assert(Exp),
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub enum Structured {
+    Step(Step),
+    Sequence(Vec<Structured>),
+    Conditional {
+        e_if: Exp,
+        s_then: Box<Structured>,
+        s_else: Box<Structured>,
+        e_fi: Exp,
+    },
+    Loop {
+        e_from: Exp,
+        s_do: Box<Structured>,
+        s_loop: Box<Structured>,
+        e_until: Exp,
+    },
+}
+
+#[derive(Debug)]
+pub struct Srl {
+    pub vars: Vec<(String, Kind)>,
+    pub body: Structured,
+}
+
+#[derive(Debug, Clone)]
+pub enum ComeFrom {
+    Entry,
+    From(String),
+    Fi(Exp, String, String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Jump {
+    Exit,
+    Goto(String),
+    If(Exp, String, String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub label: String,
+    pub from: ComeFrom,
+    pub steps: Vec<Step>,
+    pub jump: Jump,
+}
+
+#[derive(Debug)]
+pub struct Rl {
+    pub vars: Vec<(String, Kind)>,
+    pub blocks: Vec<Block>,
+}
+
+impl Step {
+    pub fn inverse(&self) -> Self {
+        match self {
+            Step::Update {
+                x,
+                index,
+                mod_op,
+                e,
+            } => Step::Update {
+                x: x.clone(),
+                index: index.clone(),
+                mod_op: match mod_op {
+                    ModOp::Add => ModOp::Sub,
+                    ModOp::Sub => ModOp::Add,
+                    ModOp::Xor => ModOp::Xor,
+                },
+                e: e.clone(),
+            },
+            Step::Push(x, xs) => Step::Pop(x.clone(), xs.clone()),
+            Step::Pop(x, xs) => Step::Push(x.clone(), xs.clone()),
+            Step::Assert(e) => Step::Assert(e.clone()),
+            Step::Skip => Step::Skip,
+        }
+    }
+}
+
+impl Structured {
+    pub fn inverse(&self) -> Self {
+        match self {
+            Structured::Step(step) => Structured::Step(step.inverse()),
+            Structured::Sequence(ss) => {
+                Structured::Sequence(ss.iter().rev().map(Structured::inverse).collect())
+            }
+            Structured::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+            } => Structured::Conditional {
+                e_if: e_fi.clone(),
+                s_then: Box::new(s_then.inverse()),
+                s_else: Box::new(s_else.inverse()),
+                e_fi: e_if.clone(),
+            },
+            Structured::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+            } => Structured::Loop {
+                e_from: e_until.clone(),
+                s_do: Box::new(s_do.inverse()),
+                s_loop: Box::new(s_loop.inverse()),
+                e_until: e_from.clone(),
+            },
+        }
+    }
+}
+
+impl Rl {
+    pub fn labels(&self) -> Result<BTreeMap<&str, usize>> {
+        let mut labels = BTreeMap::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if labels.insert(block.label.as_str(), i).is_some() {
+                bail!("label {} is defined twice", block.label);
+            }
+        }
+        for block in &self.blocks {
+            let mut targets = Vec::new();
+            match &block.from {
+                ComeFrom::Entry => {}
+                ComeFrom::From(l) => targets.push(l),
+                ComeFrom::Fi(_, l_1, l_2) => targets.extend([l_1, l_2]),
+            }
+            match &block.jump {
+                Jump::Exit => {}
+                Jump::Goto(l) => targets.push(l),
+                Jump::If(_, l_1, l_2) => targets.extend([l_1, l_2]),
+            }
+            if let Some(l) = targets.iter().find(|l| !labels.contains_key(l.as_str())) {
+                bail!("{}: label {l} is not defined", block.label);
+            }
+        }
+        Ok(labels)
+    }
+
+    pub fn entry(&self) -> Result<usize> {
+        self.unique(|block| matches!(block.from, ComeFrom::Entry), "entry")
+    }
+
+    pub fn exit(&self) -> Result<usize> {
+        self.unique(|block| matches!(block.jump, Jump::Exit), "exit")
+    }
+
+    fn unique(&self, f: impl Fn(&Block) -> bool, name: &str) -> Result<usize> {
+        let mut found = self.blocks.iter().enumerate().filter(|(_, block)| f(block));
+        let (i, _) = found
+            .next()
+            .ok_or_else(|| anyhow!("the program has no {name} block"))?;
+        if let Some((_, block)) = found.next() {
+            bail!(
+                "{}: the program has more than one {name} block",
+                block.label
+            );
+        }
+        Ok(i)
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Update {
+                x,
+                index: None,
+                mod_op,
+                e,
+            } => write!(f, "{x} {} {e}", mod_op.symbol()),
+            Step::Update {
+                x,
+                index: Some(index),
+                mod_op,
+                e,
+            } => write!(f, "{x}[{index}] {} {e}", mod_op.symbol()),
+            Step::Push(x, xs) => write!(f, "push({x}, {xs})"),
+            Step::Pop(x, xs) => write!(f, "pop({x}, {xs})"),
+            Step::Assert(e) => write!(f, "assert {e}"),
+            Step::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+fn declarations(out: &mut Writer, vars: &[(String, Kind)]) {
+    for (x, kind) in vars {
+        match kind {
+            Kind::Scalar => out.line(format!("int {x}")),
+            Kind::Array(len) => out.line(format!("int {x}[{}]", len.unwrap_or_default())),
+            Kind::Stack => out.line(format!("stack {x}")),
+        }
+    }
+}
+
+fn structured(out: &mut Writer, s: &Structured) {
+    match s {
+        Structured::Step(step) => out.line(step.to_string()),
+        Structured::Sequence(ss) if ss.is_empty() => out.line("skip"),
+        Structured::Sequence(ss) => ss.iter().for_each(|s| structured(out, s)),
+        Structured::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+        } => {
+            out.line(format!("if {e_if} then"));
+            out.indent();
+            structured(out, s_then);
+            out.dedent();
+            out.line("else");
+            out.indent();
+            structured(out, s_else);
+            out.dedent();
+            out.line(format!("fi {e_fi}"));
+        }
+        Structured::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+        } => {
+            out.line(format!("from {e_from} do"));
+            out.indent();
+            structured(out, s_do);
+            out.dedent();
+            out.line("loop");
+            out.indent();
+            structured(out, s_loop);
+            out.dedent();
+            out.line(format!("until {e_until}"));
+        }
+    }
+}
+
+impl fmt::Display for Srl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = Writer::default();
+        declarations(&mut out, &self.vars);
+        out.line("");
+        structured(&mut out, &self.body);
+        write!(f, "{}", out.finish())
+    }
+}
+
+impl fmt::Display for Rl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = Writer::default();
+        declarations(&mut out, &self.vars);
+        for block in &self.blocks {
+            out.line("");
+            out.line(&block.label);
+            out.indent();
+            match &block.from {
+                ComeFrom::Entry => out.line("entry"),
+                ComeFrom::From(l) => out.line(format!("from {l}")),
+                ComeFrom::Fi(e, l_1, l_2) => out.line(format!("fi {e} {l_1} {l_2}")),
+            }
+            for step in &block.steps {
+                out.line(step.to_string());
+            }
+            match &block.jump {
+                Jump::Exit => out.line("exit"),
+                Jump::Goto(l) => out.line(format!("goto {l}")),
+                Jump::If(e, l_1, l_2) => out.line(format!("if {e} {l_1} {l_2}")),
+            }
+            out.dedent();
+        }
+        write!(f, "{}", out.finish())
+    }
+}
@@ -0,0 +1,505 @@
+use super::{Block, ComeFrom, Jump, Rl, Srl, Step, Structured};
+use crate::{
+    ast::ast_node::*,
+    backend::{fresh, Kind},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeSet, LinkedList};
+
+pub fn janus_to_srl(prog: &Prog) -> Result<Srl> {
+    let Proc::Main { main_stuff, s, .. } = &prog.p_main else {
+        bail!("the program has no main procedure");
+    };
+    let mut lowering = Lowering {
+        prog,
+        vars: Vec::new(),
+        used: BTreeSet::new(),
+        calls: Vec::new(),
+    };
+    let mut env = Vec::new();
+    for stuff in main_stuff {
+        let (x, kind) = match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => (x, Kind::Scalar),
+            MainStuff::Int(Vdec::Array { x, c }) => {
+                let len =
+                    usize::try_from(c.0).map_err(|_| anyhow!("{}: negative array size", x.1))?;
+                (x, Kind::Array(Some(len)))
+            }
+            MainStuff::Stack(x) => (x, Kind::Stack),
+        };
+        if !lowering.used.insert(x.0.clone()) {
+            bail!("{}: {} is declared twice", x.1, x.0);
+        }
+        lowering.vars.push((x.0.clone(), kind));
+        env.push((x.0.clone(), x.0.clone()));
+    }
+    let body = lowering.stm(s, &mut env)?;
+    Ok(Srl {
+        vars: lowering.vars,
+        body,
+    })
+}
+
+struct Lowering<'a> {
+    prog: &'a Prog,
+    vars: Vec<(String, Kind)>,
+    used: BTreeSet<String>,
+    calls: Vec<String>,
+}
+
+impl Lowering<'_> {
+    fn fresh(&mut self, x: &str, kind: Kind) -> String {
+        let name = fresh(&mut self.used, x);
+        self.vars.push((name.clone(), kind));
+        name
+    }
+
+    fn stm(&mut self, s: &Stm, env: &mut Vec<(String, String)>) -> Result<Structured> {
+        let value = match s {
+            Stm::AssignScalar { x, mod_op, e, .. } => Structured::Step(Step::Update {
+                x: rename(x, env)?.0,
+                index: None,
+                mod_op: *mod_op,
+                e: exp(e, env)?,
+            }),
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                ..
+            } => Structured::Step(Step::Update {
+                x: rename(x, env)?.0,
+                index: Some(exp(e_index, env)?),
+                mod_op: *mod_op,
+                e: exp(e, env)?,
+            }),
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                ..
+            } => Structured::Conditional {
+                e_if: exp(e_if, env)?,
+                s_then: Box::new(self.stm(s_then, env)?),
+                s_else: Box::new(self.stm(s_else, env)?),
+                e_fi: exp(e_fi, env)?,
+            },
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                ..
+            } => Structured::Loop {
+                e_from: exp(e_from, env)?,
+                s_do: Box::new(self.stm(s_do, env)?),
+                s_loop: Box::new(self.stm(s_loop, env)?),
+                e_until: exp(e_until, env)?,
+            },
+            Stm::Push(x, xs, _) => {
+                Structured::Step(Step::Push(rename(x, env)?.0, rename(xs, env)?.0))
+            }
+            Stm::Pop(x, xs, _) => {
+                Structured::Step(Step::Pop(rename(x, env)?.0, rename(xs, env)?.0))
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let e_local = exp(e_local, env)?;
+                let kind = match t_local {
+                    Type::Int => Kind::Scalar,
+                    Type::Stack => Kind::Stack,
+                };
+                let x = self.fresh(&x_local.0, kind);
+                env.push((x_local.0.clone(), x.clone()));
+                let body = self.stm(s, env);
+                env.pop();
+                let body = body?;
+                let e_delocal = exp(e_delocal, env)?;
+
+                let is = |e: Exp| {
+                    Structured::Step(Step::Assert(Exp::BinOp(
+                        Box::new(Exp::Variable(var(&x))),
+                        Op::Equal,
+                        Box::new(e),
+                    )))
+                };
+                let steps = match t_local {
+                    Type::Int => vec![
+                        Structured::Step(Step::Update {
+                            x: x.clone(),
+                            index: None,
+                            mod_op: ModOp::Add,
+                            e: e_local.clone(),
+                        }),
+                        is(e_local),
+                        body,
+                        is(e_delocal.clone()),
+                        Structured::Step(Step::Update {
+                            x: x.clone(),
+                            index: None,
+                            mod_op: ModOp::Sub,
+                            e: e_delocal,
+                        }),
+                    ],
+                    Type::Stack => vec![is(e_local), body, is(e_delocal)],
+                };
+                Structured::Sequence(steps)
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, s: body, .. }) =
+                    self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if self.calls.contains(&q.0) {
+                    bail!("{span}: {} is recursive and cannot become a flowchart", q.0);
+                }
+                if args.len() != xs.len() {
+                    bail!(
+                        "{span}: {} expects {} arguments but {} were given",
+                        q.0,
+                        args.len(),
+                        xs.len()
+                    );
+                }
+                let mut inner = Vec::new();
+                for (arg, x) in args.iter().zip(xs) {
+                    let x = rename(x, env)?;
+                    if inner.iter().any(|(_, y)| *y == x.0) {
+                        bail!("{span}: {} is passed to {} more than once", x.0, q.0);
+                    }
+                    inner.push((arg.x.0.clone(), x.0));
+                }
+                self.calls.push(q.0.clone());
+                let body = self.stm(body, &mut inner);
+                self.calls.pop();
+                let body = body.map_err(|e| e.context(format!("{span}: in {}", q.0)))?;
+                match s {
+                    Stm::Call { .. } => body,
+                    _ => body.inverse(),
+                }
+            }
+            Stm::Skip(_) => Structured::Step(Step::Skip),
+            Stm::Sequence(s_1, s_2) => {
+                let mut ss = Vec::new();
+                for s in [s_1, s_2] {
+                    match self.stm(s, env)? {
+                        Structured::Sequence(inner) => ss.extend(inner),
+                        s => ss.push(s),
+                    }
+                }
+                Structured::Sequence(ss)
+            }
+        };
+        Ok(value)
+    }
+}
+
+fn rename(x: &Var, env: &[(String, String)]) -> Result<Var> {
+    env.iter()
+        .rev()
+        .find(|(y, _)| *y == x.0)
+        .map(|(_, name)| Var(name.clone(), x.1))
+        .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+}
+
+fn exp(e: &Exp, env: &[(String, String)]) -> Result<Exp> {
+    let value = match e {
+        Exp::Constant(c) => Exp::Constant(c.clone()),
+        Exp::Variable(x) => Exp::Variable(rename(x, env)?),
+        Exp::Indexed { x, e } => Exp::Indexed {
+            x: rename(x, env)?,
+            e: Box::new(exp(e, env)?),
+        },
+        Exp::BinOp(e_1, op, e_2) => {
+            Exp::BinOp(Box::new(exp(e_1, env)?), *op, Box::new(exp(e_2, env)?))
+        }
+        Exp::Empty(x) => Exp::Empty(rename(x, env)?),
+        Exp::Top(x) => Exp::Top(rename(x, env)?),
+        Exp::Nil => Exp::Nil,
+    };
+    Ok(value)
+}
+
+fn var(x: &str) -> Var {
+    Var(x.to_string(), Span::default())
+}
+
+fn int(n: usize) -> Exp {
+    Exp::Constant(Con(n as i32))
+}
+
+pub fn srl_to_janus(srl: &Srl) -> Prog {
+    let main_stuff = srl
+        .vars
+        .iter()
+        .map(|(x, kind)| match kind {
+            Kind::Scalar => MainStuff::Int(Vdec::Scalar(var(x))),
+            Kind::Array(len) => MainStuff::Int(Vdec::Array {
+                x: var(x),
+                c: Con(len.unwrap_or_default() as i32),
+            }),
+            Kind::Stack => MainStuff::Stack(var(x)),
+        })
+        .collect();
+    Prog {
+        p_main: Proc::Main {
+            span: Span::default(),
+            main_stuff,
+            s: janus(&srl.body),
+        },
+        ps: LinkedList::new(),
+    }
+}
+
+fn janus(s: &Structured) -> Stm {
+    let span = Span::default();
+    match s {
+        Structured::Step(Step::Update {
+            x,
+            index: None,
+            mod_op,
+            e,
+        }) => Stm::AssignScalar {
+            x: var(x),
+            mod_op: *mod_op,
+            e: e.clone(),
+            span,
+        },
+        Structured::Step(Step::Update {
+            x,
+            index: Some(index),
+            mod_op,
+            e,
+        }) => Stm::AssignArray {
+            x: var(x),
+            e_index: index.clone(),
+            mod_op: *mod_op,
+            e: e.clone(),
+            span,
+        },
+        Structured::Step(Step::Push(x, xs)) => Stm::Push(var(x), var(xs), span),
+        Structured::Step(Step::Pop(x, xs)) => Stm::Pop(var(x), var(xs), span),
+        Structured::Step(Step::Assert(e)) => Stm::Conditional {
+            e_if: e.clone(),
+            s_then: Box::new(Stm::Skip(span)),
+            s_else: Box::new(Stm::Skip(span)),
+            e_fi: int(1),
+            span,
+        },
+        Structured::Step(Step::Skip) => Stm::Skip(span),
+        Structured::Sequence(ss) => ss
+            .iter()
+            .map(janus)
+            .reduce(|s_1, s_2| Stm::Sequence(Box::new(s_1), Box::new(s_2)))
+            .unwrap_or(Stm::Skip(span)),
+        Structured::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+        } => Stm::Conditional {
+            e_if: e_if.clone(),
+            s_then: Box::new(janus(s_then)),
+            s_else: Box::new(janus(s_else)),
+            e_fi: e_fi.clone(),
+            span,
+        },
+        Structured::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+        } => Stm::Loop {
+            e_from: e_from.clone(),
+            s_do: Box::new(janus(s_do)),
+            s_loop: Box::new(janus(s_loop)),
+            e_until: e_until.clone(),
+            span,
+        },
+    }
+}
+
+pub fn srl_to_rl(srl: &Srl) -> Rl {
+    let mut builder = Builder {
+        blocks: Vec::new(),
+        current: Block {
+            label: "l0".to_string(),
+            from: ComeFrom::Entry,
+            steps: Vec::new(),
+            jump: Jump::Exit,
+        },
+        next: 1,
+    };
+    builder.structured(&srl.body);
+    builder.close(Jump::Exit);
+    Rl {
+        vars: srl.vars.clone(),
+        blocks: builder.blocks,
+    }
+}
+
+struct Builder {
+    blocks: Vec<Block>,
+    current: Block,
+    next: usize,
+}
+
+impl Builder {
+    fn label(&mut self) -> String {
+        self.next += 1;
+        format!("l{}", self.next - 1)
+    }
+
+    fn close(&mut self, jump: Jump) -> String {
+        let label = self.current.label.clone();
+        let block = std::mem::replace(
+            &mut self.current,
+            Block {
+                label: String::new(),
+                from: ComeFrom::Entry,
+                steps: Vec::new(),
+                jump: Jump::Exit,
+            },
+        );
+        self.blocks.push(Block { jump, ..block });
+        label
+    }
+
+    fn open(&mut self, label: String, from: ComeFrom) {
+        self.current.label = label;
+        self.current.from = from;
+    }
+
+    fn structured(&mut self, s: &Structured) {
+        match s {
+            Structured::Step(step) => self.current.steps.push(step.clone()),
+            Structured::Sequence(ss) => ss.iter().for_each(|s| self.structured(s)),
+            Structured::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+            } => {
+                let (then, other, join) = (self.label(), self.label(), self.label());
+                let test = self.close(Jump::If(e_if.clone(), then.clone(), other.clone()));
+                self.open(then, ComeFrom::From(test.clone()));
+                self.structured(s_then);
+                let then_end = self.close(Jump::Goto(join.clone()));
+                self.open(other, ComeFrom::From(test));
+                self.structured(s_else);
+                let other_end = self.close(Jump::Goto(join.clone()));
+                self.open(join, ComeFrom::Fi(e_fi.clone(), then_end, other_end));
+            }
+            Structured::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+            } => {
+                let (head, body, exit) = (self.label(), self.label(), self.label());
+                let before = self.close(Jump::Goto(head.clone()));
+                let index = self.blocks.len();
+                self.open(head, ComeFrom::Fi(e_from.clone(), before, String::new()));
+                self.structured(s_do);
+                let test = self.close(Jump::If(e_until.clone(), exit.clone(), body.clone()));
+                self.open(body, ComeFrom::From(test.clone()));
+                self.structured(s_loop);
+                let back = self.close(Jump::Goto(self.blocks[index].label.clone()));
+                if let ComeFrom::Fi(_, _, l) = &mut self.blocks[index].from {
+                    *l = back;
+                }
+                self.open(exit, ComeFrom::From(test));
+            }
+        }
+    }
+}
+
+pub fn rl_to_srl(rl: &Rl) -> Result<Srl> {
+    let labels = rl.labels()?;
+    let (entry, exit) = (rl.entry()?, rl.exit()?);
+    let mut used = rl
+        .vars
+        .iter()
+        .map(|(x, _)| x.clone())
+        .collect::<BTreeSet<_>>();
+    let (pc, prev) = (fresh(&mut used, "pc"), fresh(&mut used, "prev"));
+    let id = |l: &str| labels[l] + 1;
+    let update = |x: &str, mod_op: ModOp, n: usize| {
+        Structured::Step(Step::Update {
+            x: x.to_string(),
+            index: None,
+            mod_op,
+            e: int(n),
+        })
+    };
+    let is = |x: &str, n: usize| {
+        Exp::BinOp(Box::new(Exp::Variable(var(x))), Op::Equal, Box::new(int(n)))
+    };
+    let goto = |from: usize, to: usize| match to > from {
+        true => update(&pc, ModOp::Add, to - from),
+        false => update(&pc, ModOp::Sub, from - to),
+    };
+
+    let mut dispatch = Structured::Sequence(Vec::new());
+    for (i, block) in rl.blocks.iter().enumerate().rev() {
+        let i = i + 1;
+        let mut body = Vec::new();
+        match &block.from {
+            ComeFrom::Entry => {}
+            ComeFrom::From(l) => body.push(update(&prev, ModOp::Sub, id(l))),
+            ComeFrom::Fi(e, l_1, l_2) => body.push(Structured::Conditional {
+                e_if: e.clone(),
+                s_then: Box::new(update(&prev, ModOp::Sub, id(l_1))),
+                s_else: Box::new(update(&prev, ModOp::Sub, id(l_2))),
+                e_fi: e.clone(),
+            }),
+        }
+        body.extend(block.steps.iter().cloned().map(Structured::Step));
+        body.push(update(&prev, ModOp::Add, i));
+        match &block.jump {
+            Jump::Exit => body.push(update(&pc, ModOp::Sub, i)),
+            Jump::Goto(l) => body.push(goto(i, id(l))),
+            Jump::If(e, l_1, l_2) => body.push(Structured::Conditional {
+                e_if: e.clone(),
+                s_then: Box::new(goto(i, id(l_1))),
+                s_else: Box::new(goto(i, id(l_2))),
+                e_fi: is(&pc, id(l_1)),
+            }),
+        }
+        dispatch = Structured::Conditional {
+            e_if: is(&pc, i),
+            s_then: Box::new(Structured::Sequence(body)),
+            s_else: Box::new(dispatch),
+            e_fi: is(&prev, i),
+        };
+    }
+
+    let body = Structured::Sequence(vec![
+        update(&pc, ModOp::Add, entry + 1),
+        Structured::Loop {
+            e_from: is(&prev, 0),
+            s_do: Box::new(dispatch),
+            s_loop: Box::new(Structured::Sequence(Vec::new())),
+            e_until: is(&pc, 0),
+        },
+        update(&prev, ModOp::Sub, exit + 1),
+    ]);
+    let mut vars = rl.vars.clone();
+    vars.extend([(pc, Kind::Scalar), (prev, Kind::Scalar)]);
+    Ok(Srl { vars, body })
+}
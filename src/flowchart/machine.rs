@@ -0,0 +1,241 @@
+use super::{ComeFrom, Jump, Rl, Step};
+use crate::{
+    ast::ast_node::{Exp, Op, Var},
+    backend::Kind,
+    interpreter::{eval_op, update, value::Value, Direction, Exhausted},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+pub struct Machine<'a> {
+    rl: &'a Rl,
+    labels: BTreeMap<&'a str, usize>,
+    entry: usize,
+    exit: usize,
+    store: Vec<(String, Value)>,
+    steps: u64,
+    max_steps: Option<u64>,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(rl: &'a Rl) -> Result<Self> {
+        let mut store = Vec::new();
+        for (x, kind) in &rl.vars {
+            if store.iter().any(|(y, _)| y == x) {
+                bail!("{x} is declared twice");
+            }
+            let value = match kind {
+                Kind::Scalar => Value::Int(0),
+                Kind::Array(len) => Value::Array(vec![0; len.unwrap_or_default()]),
+                Kind::Stack => Value::Stack(Vec::new()),
+            };
+            store.push((x.clone(), value));
+        }
+        Ok(Self {
+            rl,
+            labels: rl.labels()?,
+            entry: rl.entry()?,
+            exit: rl.exit()?,
+            store,
+            steps: 0,
+            max_steps: None,
+        })
+    }
+
+    pub fn limit_steps(&mut self, max_steps: u64) {
+        self.max_steps = Some(self.steps + max_steps);
+    }
+
+    pub fn store(&self) -> Vec<(String, Value)> {
+        self.store.clone()
+    }
+
+    pub fn run(&mut self, direction: Direction) -> Result<()> {
+        let (mut current, mut previous) = match direction {
+            Direction::Forward => (self.entry, None),
+            Direction::Backward => (self.exit, None),
+        };
+        loop {
+            self.steps += 1;
+            if self
+                .max_steps
+                .is_some_and(|max_steps| self.steps > max_steps)
+            {
+                bail!(Exhausted::Steps(None));
+            }
+            let block = &self.rl.blocks[current];
+            let from = match direction {
+                Direction::Forward => self.from(&block.from)?,
+                Direction::Backward => self.to(&block.jump)?,
+            };
+            if from != previous {
+                let keyword = match direction {
+                    Direction::Forward => "come-from",
+                    Direction::Backward => "jump",
+                };
+                bail!("{}: {keyword} assertion failed", block.label);
+            }
+            match direction {
+                Direction::Forward => {
+                    for step in &block.steps {
+                        self.step(step, direction)
+                            .map_err(|e| e.context(format!("{}: {step}", block.label)))?;
+                    }
+                }
+                Direction::Backward => {
+                    for step in block.steps.iter().rev() {
+                        self.step(step, direction)
+                            .map_err(|e| e.context(format!("{}: {step}", block.label)))?;
+                    }
+                }
+            }
+            let next = match direction {
+                Direction::Forward => self.to(&block.jump)?,
+                Direction::Backward => self.from(&block.from)?,
+            };
+            match next {
+                Some(next) => {
+                    previous = Some(current);
+                    current = next;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn from(&self, from: &ComeFrom) -> Result<Option<usize>> {
+        let label = match from {
+            ComeFrom::Entry => return Ok(None),
+            ComeFrom::From(l) => l,
+            ComeFrom::Fi(e, l_1, l_2) => match self.eval(e)?.truth()? {
+                true => l_1,
+                false => l_2,
+            },
+        };
+        Ok(Some(self.labels[label.as_str()]))
+    }
+
+    fn to(&self, jump: &Jump) -> Result<Option<usize>> {
+        let label = match jump {
+            Jump::Exit => return Ok(None),
+            Jump::Goto(l) => l,
+            Jump::If(e, l_1, l_2) => match self.eval(e)?.truth()? {
+                true => l_1,
+                false => l_2,
+            },
+        };
+        Ok(Some(self.labels[label.as_str()]))
+    }
+
+    fn location(&self, x: &str) -> Result<usize> {
+        self.store
+            .iter()
+            .position(|(y, _)| y == x)
+            .ok_or_else(|| anyhow!("{x} is not declared"))
+    }
+
+    fn read(&self, x: &str) -> Result<&Value> {
+        Ok(&self.store[self.location(x)?].1)
+    }
+
+    fn step(&mut self, step: &Step, direction: Direction) -> Result<()> {
+        match step {
+            Step::Update {
+                x,
+                index,
+                mod_op,
+                e,
+            } => {
+                let var = Var(x.clone(), Span::default());
+                if e.mentions(&var) || index.as_ref().is_some_and(|e| e.mentions(&var)) {
+                    bail!("{x} appears on both sides of an update");
+                }
+                let index = index.as_ref().map(|e| self.eval(e)?.int()).transpose()?;
+                let e = self.eval(e)?.int()?;
+                let location = self.location(x)?;
+                let n = match (&mut self.store[location].1, index) {
+                    (Value::Int(n), None) => n,
+                    (Value::Array(ns), Some(index)) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| ns.get_mut(index))
+                        .ok_or_else(|| anyhow!("index {index} is out of bounds"))?,
+                    (value, _) => bail!("{x} cannot be updated: it holds {value}"),
+                };
+                *n = update(*n, mod_op, e, direction);
+            }
+            Step::Push(x, xs) | Step::Pop(x, xs) => {
+                let push = matches!(
+                    (step, direction),
+                    (Step::Push(..), Direction::Forward) | (Step::Pop(..), Direction::Backward)
+                );
+                if x == xs {
+                    bail!("{x} cannot be pushed onto itself");
+                }
+                let (x, xs) = (self.location(x)?, self.location(xs)?);
+                let n = self.store[x].1.int()?;
+                let Value::Stack(ns) = &mut self.store[xs].1 else {
+                    bail!("{} is not a stack", self.store[xs].0);
+                };
+                let n = match push {
+                    true => {
+                        ns.push(n);
+                        0
+                    }
+                    false => {
+                        if n != 0 {
+                            bail!("{} must be zero before a pop", self.store[x].0);
+                        }
+                        ns.pop().ok_or_else(|| anyhow!("the stack is empty"))?
+                    }
+                };
+                self.store[x].1 = Value::Int(n);
+            }
+            Step::Assert(e) => {
+                if !self.eval(e)?.truth()? {
+                    bail!("assertion failed");
+                }
+            }
+            Step::Skip => {}
+        }
+        Ok(())
+    }
+
+    fn eval(&self, e: &Exp) -> Result<Value> {
+        match e {
+            Exp::Constant(c) => Ok(Value::Int(c.0)),
+            Exp::Variable(x) => Ok(self.read(&x.0)?.clone()),
+            Exp::Indexed { x, e } => {
+                let index = self.eval(e)?.int()?;
+                match self.read(&x.0)? {
+                    Value::Array(ns) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| ns.get(index))
+                        .map(|&n| Value::Int(n))
+                        .ok_or_else(|| anyhow!("index {index} is out of bounds")),
+                    _ => bail!("{} is not an array", x.0),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (v_1, v_2) = (self.eval(e_1)?, self.eval(e_2)?);
+                match op {
+                    Op::Equal => Ok((v_1 == v_2).into()),
+                    Op::NotEqual => Ok((v_1 != v_2).into()),
+                    _ => Ok(Value::Int(eval_op(op, v_1.int()?, v_2.int()?)?)),
+                }
+            }
+            Exp::Empty(x) => match self.read(&x.0)? {
+                Value::Stack(ns) => Ok(ns.is_empty().into()),
+                _ => bail!("{} is not a stack", x.0),
+            },
+            Exp::Top(x) => match self.read(&x.0)? {
+                Value::Stack(ns) => ns
+                    .last()
+                    .map(|&n| Value::Int(n))
+                    .ok_or_else(|| anyhow!("{} is empty", x.0)),
+                _ => bail!("{} is not a stack", x.0),
+            },
+            Exp::Nil => Ok(Value::Stack(Vec::new())),
+        }
+    }
+}
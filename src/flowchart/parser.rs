@@ -0,0 +1,132 @@
+use super::{Block, ComeFrom, Jump, Rl, Step};
+use crate::{
+    ast::{
+        ast_node::{Stm, Vdec},
+        Ast,
+    },
+    backend::Kind,
+    tokenizer::{token::Token, Tokenizer},
+    util::read_file,
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::LinkedList, path::Path};
+
+pub fn parse(source: LinkedList<char>) -> Result<Rl> {
+    let mut tokenizer = Tokenizer::new(source);
+    tokenizer.tokenize()?;
+    let mut ast = Ast::new(tokenizer);
+
+    let mut vars = Vec::new();
+    loop {
+        match ast.peek() {
+            Some(Token::Int) => {
+                ast.next();
+                match ast.d()? {
+                    Vdec::Scalar(x) => vars.push((x.0, Kind::Scalar)),
+                    Vdec::Array { x, c } => {
+                        let len = usize::try_from(c.0)
+                            .map_err(|_| anyhow!("{}: negative array size", x.1))?;
+                        vars.push((x.0, Kind::Array(Some(len))));
+                    }
+                }
+            }
+            Some(Token::Stack) => {
+                ast.next();
+                vars.push((ast.x()?.0, Kind::Stack));
+            }
+            _ => break,
+        }
+    }
+
+    let mut blocks = Vec::new();
+    while ast.peek().is_some() {
+        blocks.push(block(&mut ast)?);
+    }
+    Ok(Rl { vars, blocks })
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Rl> {
+    parse(read_file(path)?)
+}
+
+fn is(ast: &Ast, word: &str) -> bool {
+    matches!(ast.peek(), Some(Token::Identifier(x)) if x == word)
+}
+
+fn block(ast: &mut Ast) -> Result<Block> {
+    let label = ast.x()?;
+    let from = match ast.peek() {
+        Some(Token::From) => {
+            ast.next();
+            ComeFrom::From(ast.x()?.0)
+        }
+        Some(Token::Fi) => {
+            ast.next();
+            ComeFrom::Fi(ast.e()?, ast.x()?.0, ast.x()?.0)
+        }
+        _ if is(ast, "entry") => {
+            ast.next();
+            ComeFrom::Entry
+        }
+        x => bail!("{}: expected entry, from or fi found {x:?}", label.1),
+    };
+
+    let mut steps = Vec::new();
+    let jump = loop {
+        match ast.peek() {
+            Some(Token::If) => {
+                ast.next();
+                break Jump::If(ast.e()?, ast.x()?.0, ast.x()?.0);
+            }
+            _ if is(ast, "goto") => {
+                ast.next();
+                break Jump::Goto(ast.x()?.0);
+            }
+            _ if is(ast, "exit") => {
+                ast.next();
+                break Jump::Exit;
+            }
+            _ if is(ast, "assert") => {
+                ast.next();
+                steps.push(Step::Assert(ast.e()?));
+            }
+            Some(Token::Identifier(_) | Token::Push | Token::Pop | Token::Skip) => {
+                steps.push(step(ast.s_non_recursive()?));
+            }
+            x => bail!("{}: expected a step or a jump found {x:?}", label.1),
+        }
+    };
+
+    Ok(Block {
+        label: label.0,
+        from,
+        steps,
+        jump,
+    })
+}
+
+fn step(s: Stm) -> Step {
+    match s {
+        Stm::AssignScalar { x, mod_op, e, .. } => Step::Update {
+            x: x.0,
+            index: None,
+            mod_op,
+            e,
+        },
+        Stm::AssignArray {
+            x,
+            e_index,
+            mod_op,
+            e,
+            ..
+        } => Step::Update {
+            x: x.0,
+            index: Some(e_index),
+            mod_op,
+            e,
+        },
+        Stm::Push(x, xs, _) => Step::Push(x.0, xs.0),
+        Stm::Pop(x, xs, _) => Step::Pop(x.0, xs.0),
+        _ => Step::Skip,
+    }
+}
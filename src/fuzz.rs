@@ -0,0 +1,147 @@
+use crate::{
+    ast::ast_node::{Prog, Type},
+    backend::array_params,
+    interpreter::{value::Value, Interpreter},
+    util::Rng,
+    verify::{verify_proc, Verdict},
+};
+use anyhow::Result;
+
+const MAX_SHRINKS: usize = 1000;
+const MAX_ARRAY_LEN: i32 = 8;
+const MAX_STACK_LEN: i32 = 4;
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub passed: usize,
+    pub rejected: usize,
+    pub failure: Option<Failure>,
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub iteration: usize,
+    pub input: Vec<Value>,
+    pub shrunk: Vec<Value>,
+    pub reason: String,
+}
+
+enum Checked {
+    Passed,
+    Rejected,
+    Failed(String),
+}
+
+pub fn fuzz(prog: &Prog, q: &str, iterations: usize, rng: &mut Rng) -> Result<Summary> {
+    let interpreter = Interpreter::new(prog)?;
+    let params = interpreter.params(q)?;
+    let arrays = array_params(prog)
+        .remove(q)
+        .unwrap_or_else(|| vec![false; params.len()]);
+    let mut summary = Summary::default();
+
+    for iteration in 0..iterations {
+        let input = params
+            .iter()
+            .zip(&arrays)
+            .map(|(arg, &array)| match (&arg.t, array) {
+                (Type::Int, false) => Value::Int(random_int(rng)),
+                (Type::Int, true) => {
+                    let len = rng.range(1, MAX_ARRAY_LEN);
+                    Value::Array((0..len).map(|_| random_int(rng)).collect())
+                }
+                (Type::Stack, _) => {
+                    let len = rng.range(0, MAX_STACK_LEN);
+                    Value::Stack((0..len).map(|_| random_int(rng)).collect())
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match check(prog, q, &input)? {
+            Checked::Passed => summary.passed += 1,
+            Checked::Rejected => summary.rejected += 1,
+            Checked::Failed(reason) => {
+                let (shrunk, reason) = shrink(prog, q, input.clone(), reason)?;
+                summary.failure = Some(Failure {
+                    iteration,
+                    input,
+                    shrunk,
+                    reason,
+                });
+                break;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn random_int(rng: &mut Rng) -> i32 {
+    match rng.range(0, 7) {
+        0 => rng.range(-1000, 1000),
+        _ => rng.range(-8, 8),
+    }
+}
+
+fn check(prog: &Prog, q: &str, input: &[Value]) -> Result<Checked> {
+    let checked = match verify_proc(prog, q, input.to_vec())? {
+        Verdict::Identity => Checked::Passed,
+        Verdict::Rejected(_) => Checked::Rejected,
+        Verdict::Diverged(divergence) => Checked::Failed(divergence.to_string()),
+    };
+    Ok(checked)
+}
+
+fn shrink(
+    prog: &Prog,
+    q: &str,
+    mut input: Vec<Value>,
+    mut reason: String,
+) -> Result<(Vec<Value>, String)> {
+    'shrinking: for _ in 0..MAX_SHRINKS {
+        for index in 0..input.len() {
+            for candidate in candidates(&input[index]) {
+                let mut next = input.clone();
+                next[index] = candidate;
+                if let Checked::Failed(next_reason) = check(prog, q, &next)? {
+                    input = next;
+                    reason = next_reason;
+                    continue 'shrinking;
+                }
+            }
+        }
+        break;
+    }
+    Ok((input, reason))
+}
+
+fn candidates(value: &Value) -> Vec<Value> {
+    let ints = |n: i32| {
+        let mut ns = vec![0, n / 2, n - n.signum()];
+        ns.dedup();
+        ns.into_iter().filter(move |&m| m != n)
+    };
+    let lists = |ns: &Vec<i32>, resize: bool| {
+        let mut value = Vec::new();
+        if resize {
+            for index in 0..ns.len() {
+                let mut next = ns.clone();
+                next.remove(index);
+                value.push(next);
+            }
+        }
+        for (index, &n) in ns.iter().enumerate() {
+            for m in ints(n) {
+                let mut next = ns.clone();
+                next[index] = m;
+                value.push(next);
+            }
+        }
+        value
+    };
+
+    match value {
+        Value::Int(n) => ints(*n).map(Value::Int).collect(),
+        Value::Array(ns) => lists(ns, false).into_iter().map(Value::Array).collect(),
+        Value::Stack(ns) => lists(ns, true).into_iter().map(Value::Stack).collect(),
+    }
+}
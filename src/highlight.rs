@@ -0,0 +1,365 @@
+use crate::tokenizer::{
+    span::Span, token::Token, KEYWORDS, LITERAL_START, SYMBOLS_1, SYMBOLS_2, WHITESPACE,
+};
+use serde_json::{json, Value as Json};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Keyword,
+    Type,
+    Builtin,
+    Constant,
+    Number,
+    Operator,
+    Assignment,
+    Punctuation,
+    Procedure,
+    Identifier,
+}
+
+pub const CLASSES: &[Class] = &[
+    Class::Keyword,
+    Class::Type,
+    Class::Builtin,
+    Class::Constant,
+    Class::Number,
+    Class::Operator,
+    Class::Assignment,
+    Class::Punctuation,
+    Class::Procedure,
+    Class::Identifier,
+];
+
+pub const INTRODUCERS: &[Token] = &[Token::Procedure, Token::Call, Token::Uncall];
+
+pub const SEMANTIC_TYPES: &[&str] = &[
+    "keyword",
+    "type",
+    "function",
+    "enumMember",
+    "number",
+    "operator",
+    "variable",
+];
+
+impl Class {
+    pub fn of(token: &Token) -> Self {
+        match token {
+            Token::Int | Token::Stack => Class::Type,
+            Token::Empty | Token::Top => Class::Builtin,
+            Token::Nil => Class::Constant,
+            Token::Procedure
+            | Token::If
+            | Token::Then
+            | Token::Else
+            | Token::Fi
+            | Token::From
+            | Token::Do
+            | Token::Loop
+            | Token::Until
+            | Token::Push
+            | Token::Pop
+            | Token::Local
+            | Token::Delocal
+            | Token::Call
+            | Token::Uncall
+            | Token::Skip => Class::Keyword,
+            Token::PlusEqual | Token::MinusEqual | Token::CaretEqual => Class::Assignment,
+            Token::Plus
+            | Token::Minus
+            | Token::Caret
+            | Token::Asterisk
+            | Token::Slash
+            | Token::Percent
+            | Token::Ampersand
+            | Token::VerticalBar
+            | Token::Ampersand2
+            | Token::VerticalBar2
+            | Token::Less
+            | Token::Greater
+            | Token::Equal
+            | Token::ExclamationEqual
+            | Token::LessEqual
+            | Token::GreaterEqual => Class::Operator,
+            Token::LParen
+            | Token::RParen
+            | Token::LSquareBracket
+            | Token::RSquareBracket
+            | Token::Comma => Class::Punctuation,
+            Token::Identifier(_) => Class::Identifier,
+            Token::Constant(_) => Class::Number,
+        }
+    }
+
+    pub fn scope(self) -> &'static str {
+        match self {
+            Class::Keyword => "keyword.control.janus",
+            Class::Type => "storage.type.janus",
+            Class::Builtin => "support.function.janus",
+            Class::Constant => "constant.language.janus",
+            Class::Number => "constant.numeric.janus",
+            Class::Operator => "keyword.operator.janus",
+            Class::Assignment => "keyword.operator.assignment.janus",
+            Class::Punctuation => "punctuation.janus",
+            Class::Procedure => "entity.name.function.janus",
+            Class::Identifier => "variable.other.janus",
+        }
+    }
+
+    pub fn semantic(self) -> Option<usize> {
+        let name = match self {
+            Class::Keyword => "keyword",
+            Class::Type => "type",
+            Class::Builtin | Class::Procedure => "function",
+            Class::Constant => "enumMember",
+            Class::Number => "number",
+            Class::Operator | Class::Assignment => "operator",
+            Class::Identifier => "variable",
+            Class::Punctuation => return None,
+        };
+        SEMANTIC_TYPES.iter().position(|x| *x == name)
+    }
+
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Class::Keyword => Some("1;35"),
+            Class::Type => Some("36"),
+            Class::Builtin => Some("34"),
+            Class::Constant | Class::Number => Some("33"),
+            Class::Assignment => Some("1;31"),
+            Class::Operator => Some("31"),
+            Class::Procedure => Some("1;32"),
+            Class::Punctuation | Class::Identifier => None,
+        }
+    }
+}
+
+pub fn classify(tokens: &[(Token, Span)]) -> Vec<(Span, Class)> {
+    let mut previous = None;
+    let mut value = Vec::new();
+    for (token, span) in tokens {
+        let class = match (previous, token) {
+            (Some(previous), Token::Identifier(_)) if INTRODUCERS.contains(previous) => {
+                Class::Procedure
+            }
+            _ => Class::of(token),
+        };
+        value.push((*span, class));
+        previous = Some(token);
+    }
+    value
+}
+
+fn words(class: Class) -> Vec<&'static str> {
+    KEYWORDS
+        .iter()
+        .filter(|(_, token)| Class::of(token) == class)
+        .map(|(keyword, _)| *keyword)
+        .collect()
+}
+
+fn symbols(class: Class) -> Vec<String> {
+    let mut value = SYMBOLS_2
+        .iter()
+        .filter(|(_, token)| Class::of(token) == class)
+        .map(|((a, b), _)| format!("{a}{b}"))
+        .collect::<Vec<_>>();
+    value.extend(
+        SYMBOLS_1
+            .iter()
+            .filter(|(_, token)| Class::of(token) == class)
+            .map(|(a, _)| a.to_string()),
+    );
+    value
+}
+
+fn introducers() -> Vec<&'static str> {
+    KEYWORDS
+        .iter()
+        .filter(|(_, token)| INTRODUCERS.contains(token))
+        .map(|(keyword, _)| *keyword)
+        .collect()
+}
+
+fn escape(x: &str) -> String {
+    x.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '_' {
+            true => c.to_string(),
+            false => format!("\\{c}"),
+        })
+        .collect()
+}
+
+fn separators() -> String {
+    let mut value = WHITESPACE
+        .iter()
+        .map(|c| match c {
+            ' ' => " ".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            '\r' => "\\r".to_string(),
+            c => escape(&c.to_string()),
+        })
+        .collect::<String>();
+    for (c, _) in SYMBOLS_1 {
+        value.push_str(&escape(&c.to_string()));
+    }
+    value
+}
+
+fn pairs() -> Vec<(char, char)> {
+    SYMBOLS_2
+        .iter()
+        .map(|(pair, _)| *pair)
+        .filter(|(a, _)| !SYMBOLS_1.iter().any(|(c, _)| c == a))
+        .collect()
+}
+
+fn word_pattern(alternatives: &[&str]) -> String {
+    let separators = separators();
+    let alternatives = alternatives
+        .iter()
+        .map(|x| escape(x))
+        .collect::<Vec<_>>()
+        .join("|");
+    let pairs = pairs()
+        .into_iter()
+        .map(|(a, b)| format!("|{}", escape(&format!("{a}{b}"))))
+        .collect::<String>();
+    format!("(?<![^{separators}])(?:{alternatives})(?=[{separators}]{pairs}|$)")
+}
+
+pub fn identifier_pattern() -> String {
+    let mut excluded = separators();
+    let mut alternatives = String::new();
+    for (a, b) in pairs() {
+        excluded.push_str(&escape(&a.to_string()));
+        alternatives.push_str(&format!(
+            "|{}(?!{})",
+            escape(&a.to_string()),
+            escape(&b.to_string())
+        ));
+    }
+    format!("(?:[^{excluded}]{alternatives})+")
+}
+
+pub fn number_pattern() -> String {
+    let starts = LITERAL_START
+        .iter()
+        .map(|c| escape(&c.to_string()))
+        .collect::<String>();
+    format!("(?<![^{0}])[{starts}][^{0}]*", separators())
+}
+
+pub fn textmate() -> Json {
+    let mut patterns = Vec::new();
+    let separators = separators();
+    patterns.push(json!({
+        "match": format!(
+            "(?<![^{separators}])({})\\s+({})",
+            introducers().join("|"),
+            identifier_pattern(),
+        ),
+        "captures": {
+            "1": { "name": Class::Keyword.scope() },
+            "2": { "name": Class::Procedure.scope() },
+        },
+    }));
+    for &class in CLASSES {
+        let words = words(class);
+        if !words.is_empty() {
+            patterns.push(json!({ "match": word_pattern(&words), "name": class.scope() }));
+        }
+    }
+    for width in [2, 1] {
+        for &class in CLASSES {
+            let alternatives = symbols(class)
+                .iter()
+                .filter(|x| x.chars().count() == width)
+                .map(|x| escape(x))
+                .collect::<Vec<_>>();
+            if !alternatives.is_empty() {
+                patterns.push(json!({
+                    "match": alternatives.join("|"),
+                    "name": class.scope(),
+                }));
+            }
+        }
+    }
+    patterns.push(json!({ "match": number_pattern(), "name": Class::Number.scope() }));
+    patterns.push(json!({ "match": identifier_pattern(), "name": Class::Identifier.scope() }));
+    json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "Janus",
+        "scopeName": "source.janus",
+        "fileTypes": ["janus"],
+        "patterns": patterns,
+    })
+}
+
+pub fn spec() -> Json {
+    let classes = CLASSES
+        .iter()
+        .filter_map(|&class| {
+            let words = words(class);
+            let symbols = symbols(class);
+            match words.is_empty() && symbols.is_empty() {
+                true => None,
+                false => Some(json!({
+                    "scope": class.scope(),
+                    "words": words,
+                    "symbols": symbols,
+                })),
+            }
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "language": "janus",
+        "whitespace": WHITESPACE.iter().collect::<String>(),
+        "classes": classes,
+        "procedure": {
+            "after": introducers(),
+            "scope": Class::Procedure.scope(),
+        },
+        "number": { "pattern": number_pattern(), "scope": Class::Number.scope() },
+        "identifier": { "pattern": identifier_pattern(), "scope": Class::Identifier.scope() },
+    })
+}
+
+pub fn ansi(text: &str, tokens: &[(Token, Span)]) -> String {
+    let classes = classify(tokens);
+    let mut value = String::new();
+    let mut classes = classes.iter().peekable();
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let mut open = None;
+        for (j, c) in line.chars().enumerate() {
+            let column = j + 1;
+            if let Some(end) = open {
+                if column == end {
+                    value.push_str("\x1b[0m");
+                    open = None;
+                }
+            }
+            while let Some((span, class)) = classes.peek() {
+                if (span.start.line, span.start.column) > (i + 1, column) {
+                    break;
+                }
+                if (span.start.line, span.start.column) == (i + 1, column) {
+                    if let Some(code) = class.ansi() {
+                        value.push_str(&format!("\x1b[{code}m"));
+                        open = Some(span.end.column);
+                    }
+                }
+                classes.next();
+            }
+            if c == '\n' && open.take().is_some() {
+                value.push_str("\x1b[0m");
+            }
+            value.push(c);
+        }
+        if open.is_some() {
+            value.push_str("\x1b[0m");
+        }
+    }
+    value
+}
@@ -0,0 +1,638 @@
+pub mod value;
+
+use crate::{ast::ast_node::*, tokenizer::span::Span};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::{BTreeMap, LinkedList},
+    fmt,
+};
+use value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    pub fn flip(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Forward => "forward",
+            Direction::Backward => "backward",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Exhausted {
+    Steps(Option<Span>),
+    Depth { limit: usize, q: String },
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exhausted::Steps(Some(span)) => write!(f, "{span}: step limit exceeded"),
+            Exhausted::Steps(None) => write!(f, "step limit exceeded"),
+            Exhausted::Depth { limit, q } => {
+                write!(f, "call depth exceeds {limit} when calling {q}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+#[derive(Debug)]
+pub struct Change {
+    pub x: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug)]
+pub struct Event<'a> {
+    pub proc: &'a str,
+    pub stm: &'a Stm,
+    pub direction: Direction,
+    pub depth: usize,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Branch {
+    Then,
+    Else,
+    Exit,
+    Loop,
+}
+
+impl Branch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Branch::Then => "then",
+            Branch::Else => "else",
+            Branch::Exit => "exit",
+            Branch::Loop => "loop",
+        }
+    }
+}
+
+pub trait Observer {
+    fn stm(&mut self, event: &Event) -> Result<()>;
+
+    fn enter(&mut self, _stm: &Stm, _direction: Direction) -> Result<()> {
+        Ok(())
+    }
+
+    fn branch(&mut self, _stm: &Stm, _branch: Branch, _direction: Direction) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Frame<'a> {
+    proc: &'a str,
+    env: BTreeMap<String, usize>,
+}
+
+pub struct Interpreter<'a> {
+    prog: &'a Prog,
+    heap: Vec<Value>,
+    frames: Vec<Frame<'a>>,
+    observers: Vec<&'a mut dyn Observer>,
+    steps: u64,
+    max_steps: Option<u64>,
+    max_depth: usize,
+}
+
+pub const MAX_DEPTH: usize = 10_000;
+
+impl<'a> Interpreter<'a> {
+    pub fn new(prog: &'a Prog) -> Result<Self> {
+        let mut value = Self {
+            prog,
+            heap: Vec::new(),
+            frames: Vec::new(),
+            observers: Vec::new(),
+            steps: 0,
+            max_steps: None,
+            max_depth: MAX_DEPTH,
+        };
+
+        let mut frame = Frame {
+            proc: "main",
+            env: BTreeMap::new(),
+        };
+        if let Proc::Main { main_stuff, .. } = &prog.p_main {
+            for stuff in main_stuff {
+                let (x, initial) = match stuff {
+                    MainStuff::Int(Vdec::Scalar(x)) => (x, Value::Int(0)),
+                    MainStuff::Int(Vdec::Array { x, c }) => {
+                        let len = usize::try_from(c.0)
+                            .map_err(|_| anyhow!("{}: negative array size", x.1))?;
+                        (x, Value::Array(vec![0; len]))
+                    }
+                    MainStuff::Stack(x) => (x, Value::Stack(Vec::new())),
+                };
+                if frame.env.contains_key(&x.0) {
+                    bail!("{}: {} is declared twice", x.1, x.0);
+                }
+                frame.env.insert(x.0.clone(), value.alloc(initial));
+            }
+        }
+        value.frames.push(frame);
+
+        Ok(value)
+    }
+
+    pub fn observe(&mut self, observer: &'a mut dyn Observer) {
+        self.observers.push(observer);
+    }
+
+    pub fn limit_steps(&mut self, max_steps: u64) {
+        self.max_steps = Some(self.steps + max_steps);
+    }
+
+    pub fn limit_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn run(&mut self, direction: Direction) -> Result<()> {
+        self.exec(self.prog.p_main.body(), direction)
+    }
+
+    pub fn store(&self) -> Vec<(String, Value)> {
+        let mut value = self.frames[0]
+            .env
+            .iter()
+            .map(|(x, &location)| (x.clone(), self.heap[location].clone()))
+            .collect::<Vec<_>>();
+        value.sort_by_key(|(x, _)| self.frames[0].env[x]);
+        value
+    }
+
+    pub fn call(
+        &mut self,
+        q: &str,
+        values: Vec<Value>,
+        direction: Direction,
+    ) -> Result<Vec<Value>> {
+        let base = self.heap.len();
+        let locations = values
+            .into_iter()
+            .map(|value| self.alloc(value))
+            .collect::<Vec<_>>();
+        let result = self.invoke(q, &locations, direction);
+        let values = self.heap.split_off(base);
+        result.map(|_| values)
+    }
+
+    fn alloc(&mut self, value: Value) -> usize {
+        self.heap.push(value);
+        self.heap.len() - 1
+    }
+
+    fn frame(&self) -> &Frame<'a> {
+        self.frames.last().expect("no active frame")
+    }
+
+    fn location(&self, x: &Var) -> Result<usize> {
+        self.frame()
+            .env
+            .get(&x.0)
+            .copied()
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn read(&self, x: &Var) -> Result<&Value> {
+        Ok(&self.heap[self.location(x)?])
+    }
+
+    fn write(&mut self, x: &Var, value: Value) -> Result<()> {
+        let location = self.location(x)?;
+        self.heap[location] = value;
+        Ok(())
+    }
+
+    fn notify(&mut self, stm: &'a Stm, direction: Direction, changes: Vec<Change>) -> Result<()> {
+        if self.observers.is_empty() {
+            return Ok(());
+        }
+        let event = Event {
+            proc: self.frame().proc,
+            stm,
+            direction,
+            depth: self.frames.len() - 1,
+            changes,
+        };
+        for observer in self.observers.iter_mut() {
+            observer.stm(&event)?;
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, stm: &Stm, direction: Direction) -> Result<()> {
+        for observer in self.observers.iter_mut() {
+            observer.enter(stm, direction)?;
+        }
+        Ok(())
+    }
+
+    fn branch(&mut self, stm: &Stm, branch: Branch, direction: Direction) -> Result<()> {
+        for observer in self.observers.iter_mut() {
+            observer.branch(stm, branch, direction)?;
+        }
+        Ok(())
+    }
+
+    fn changed(&self, x: &Var, old: Value) -> Result<Change> {
+        Ok(Change {
+            x: x.0.clone(),
+            old,
+            new: self.read(x)?.clone(),
+        })
+    }
+
+    pub fn params(&self, q: &str) -> Result<&'a LinkedList<Arg>> {
+        Ok(self.lookup(q)?.1)
+    }
+
+    pub fn arg_store(
+        &self,
+        q: &str,
+        bindings: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Vec<Value>> {
+        let params = self.params(q)?;
+        let mut values = params
+            .iter()
+            .map(|arg| match arg.t {
+                Type::Int => Value::Int(0),
+                Type::Stack => Value::Stack(Vec::new()),
+            })
+            .collect::<Vec<_>>();
+
+        for (x, value) in bindings {
+            let index = params
+                .iter()
+                .position(|arg| arg.x.0 == x)
+                .ok_or_else(|| anyhow!("{x} is not a parameter of {q}"))?;
+            values[index] = value;
+        }
+        Ok(values)
+    }
+
+    fn lookup(&self, q: &str) -> Result<(&'a PId, &'a LinkedList<Arg>, &'a Stm)> {
+        match self.prog.ps.iter().find(|p| p.name() == q) {
+            Some(Proc::Other { q, args, s }) => Ok((q, args, s)),
+            _ => bail!("procedure {q} is not defined"),
+        }
+    }
+
+    fn invoke(&mut self, q: &str, locations: &[usize], direction: Direction) -> Result<()> {
+        let (q, args, s) = self.lookup(q)?;
+        if args.len() != locations.len() {
+            bail!(
+                "{} expects {} arguments but {} were given",
+                q.0,
+                args.len(),
+                locations.len()
+            );
+        }
+
+        let mut env = BTreeMap::new();
+        for (arg, &location) in args.iter().zip(locations) {
+            match (&arg.t, &self.heap[location]) {
+                (Type::Int, Value::Stack(_)) => {
+                    bail!("{}: {} expects an integer found a stack", arg.x.1, arg.x.0)
+                }
+                (Type::Stack, Value::Int(_) | Value::Array(_)) => {
+                    bail!("{}: {} expects a stack found an integer", arg.x.1, arg.x.0)
+                }
+                _ => {}
+            }
+            if env.insert(arg.x.0.clone(), location).is_some() {
+                bail!("{}: parameter {} is declared twice", arg.x.1, arg.x.0);
+            }
+        }
+
+        if self.frames.len() > self.max_depth {
+            bail!(Exhausted::Depth {
+                limit: self.max_depth,
+                q: q.0.clone(),
+            });
+        }
+        self.frames.push(Frame { proc: &q.0, env });
+        let result = self.exec(s, direction);
+        self.frames.pop();
+        result
+    }
+
+    fn exec(&mut self, s: &'a Stm, direction: Direction) -> Result<()> {
+        self.steps += 1;
+        if self
+            .max_steps
+            .is_some_and(|max_steps| self.steps > max_steps)
+        {
+            bail!(Exhausted::Steps(Some(s.span())));
+        }
+        if !matches!(s, Stm::Sequence(..)) {
+            self.enter(s, direction)?;
+        }
+
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let old = self.read(x)?.clone();
+                let new = update(old.int()?, mod_op, self.eval(e)?.int()?, direction);
+                self.write(x, Value::Int(new))?;
+                let change = self.changed(x, old)?;
+                self.notify(s, direction, vec![change])
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let index = self.eval(e_index)?.int()?;
+                let e = self.eval(e)?.int()?;
+                let location = self.location(x)?;
+                let old = self.heap[location].clone();
+                let Value::Array(ns) = &mut self.heap[location] else {
+                    bail!("{span}: {} is not an array", x.0);
+                };
+                let n = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| ns.get_mut(index))
+                    .ok_or_else(|| anyhow!("{span}: index {index} is out of bounds"))?;
+                *n = update(*n, mod_op, e, direction);
+                let change = self.changed(x, old)?;
+                self.notify(s, direction, vec![change])
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                let entry = self.eval(e_entry)?.truth()?;
+                let branch = if entry { Branch::Then } else { Branch::Else };
+                self.branch(s, branch, direction)?;
+                self.exec(if entry { s_then } else { s_else }, direction)?;
+                if self.eval(e_exit)?.truth()? != entry {
+                    bail!("{span}: assertion {} failed", assertion(direction, "fi"));
+                }
+                self.notify(s, direction, Vec::new())
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                if !self.eval(e_entry)?.truth()? {
+                    bail!("{span}: assertion {} failed", assertion(direction, "from"));
+                }
+                self.exec(s_do, direction)?;
+                loop {
+                    if self.eval(e_exit)?.truth()? {
+                        self.branch(s, Branch::Exit, direction)?;
+                        break;
+                    }
+                    self.branch(s, Branch::Loop, direction)?;
+                    self.exec(s_loop, direction)?;
+                    if self.eval(e_entry)?.truth()? {
+                        bail!(
+                            "{span}: assertion {} failed on re-entry",
+                            assertion(direction, "from")
+                        );
+                    }
+                    self.exec(s_do, direction)?;
+                }
+                self.notify(s, direction, Vec::new())
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                let (old_x, old_xs) = (self.read(x)?.clone(), self.read(xs)?.clone());
+                let Value::Stack(mut ns) = old_xs.clone() else {
+                    bail!("{span}: {} is not a stack", xs.0);
+                };
+                if push {
+                    ns.push(old_x.int()?);
+                    self.write(x, Value::Int(0))?;
+                } else {
+                    if old_x.int()? != 0 {
+                        bail!("{span}: {} must be zero before a pop", x.0);
+                    }
+                    let n = ns
+                        .pop()
+                        .ok_or_else(|| anyhow!("{span}: {} is empty", xs.0))?;
+                    self.write(x, Value::Int(n))?;
+                }
+                self.write(xs, Value::Stack(ns))?;
+                let changes = vec![self.changed(x, old_x)?, self.changed(xs, old_xs)?];
+                self.notify(s, direction, changes)
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s: s_local,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_local, e_delocal),
+                    Direction::Backward => (e_delocal, e_local),
+                };
+                let initial = self.eval(e_entry)?;
+                match (t_local, &initial) {
+                    (Type::Int, Value::Int(_)) | (Type::Stack, Value::Stack(_)) => {}
+                    _ => bail!("{span}: {} is initialised with {initial}", x_local.0),
+                }
+
+                let location = self.alloc(initial);
+                let shadowed = self
+                    .frames
+                    .last_mut()
+                    .expect("no active frame")
+                    .env
+                    .insert(x_local.0.clone(), location);
+                let result = self.exec(s_local, direction).and_then(|_| {
+                    if self.eval(e_exit)? != self.heap[location] {
+                        bail!(
+                            "{span}: assertion {} {} failed",
+                            assertion(direction, "delocal"),
+                            x_local.0
+                        );
+                    }
+                    Ok(())
+                });
+
+                let env = &mut self.frames.last_mut().expect("no active frame").env;
+                match shadowed {
+                    Some(shadowed) => env.insert(x_local.0.clone(), shadowed),
+                    None => env.remove(&x_local.0),
+                };
+                self.heap.truncate(location);
+                result?;
+                self.notify(s, direction, Vec::new())
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let inner = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                let mut locations = Vec::new();
+                for x in xs {
+                    let location = self.location(x)?;
+                    if locations.contains(&location) {
+                        bail!("{span}: {} is passed to {} more than once", x.0, q.0);
+                    }
+                    locations.push(location);
+                }
+                let old = locations
+                    .iter()
+                    .map(|&location| self.heap[location].clone())
+                    .collect::<Vec<_>>();
+                self.invoke(&q.0, &locations, inner)
+                    .map_err(|e| e.context(format!("{span}: in {}", q.0)))?;
+                let changes = xs
+                    .iter()
+                    .zip(old)
+                    .map(|(x, old)| self.changed(x, old))
+                    .collect::<Result<_>>()?;
+                self.notify(s, direction, changes)
+            }
+            Stm::Skip(_) => self.notify(s, direction, Vec::new()),
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.exec(s_1, direction)?;
+                    self.exec(s_2, direction)
+                }
+                Direction::Backward => {
+                    self.exec(s_2, direction)?;
+                    self.exec(s_1, direction)
+                }
+            },
+        }
+    }
+
+    fn eval(&self, e: &Exp) -> Result<Value> {
+        match e {
+            Exp::Constant(c) => Ok(Value::Int(c.0)),
+            Exp::Variable(x) => Ok(self.read(x)?.clone()),
+            Exp::Indexed { x, e } => {
+                let index = self.eval(e)?.int()?;
+                match self.read(x)? {
+                    Value::Array(ns) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| ns.get(index))
+                        .map(|&n| Value::Int(n))
+                        .ok_or_else(|| anyhow!("{}: index {index} is out of bounds", x.1)),
+                    _ => bail!("{}: {} is not an array", x.1, x.0),
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (v_1, v_2) = (self.eval(e_1)?, self.eval(e_2)?);
+                match op {
+                    Op::Equal => Ok((v_1 == v_2).into()),
+                    Op::NotEqual => Ok((v_1 != v_2).into()),
+                    _ => Ok(Value::Int(eval_op(op, v_1.int()?, v_2.int()?)?)),
+                }
+            }
+            Exp::Empty(x) => match self.read(x)? {
+                Value::Stack(ns) => Ok(ns.is_empty().into()),
+                _ => bail!("{}: {} is not a stack", x.1, x.0),
+            },
+            Exp::Top(x) => match self.read(x)? {
+                Value::Stack(ns) => ns
+                    .last()
+                    .map(|&n| Value::Int(n))
+                    .ok_or_else(|| anyhow!("{}: {} is empty", x.1, x.0)),
+                _ => bail!("{}: {} is not a stack", x.1, x.0),
+            },
+            Exp::Nil => Ok(Value::Stack(Vec::new())),
+        }
+    }
+}
+
+pub fn eval_op(op: &Op, n_1: i32, n_2: i32) -> Result<i32> {
+    let value = match op {
+        Op::Add => n_1.wrapping_add(n_2),
+        Op::Sub => n_1.wrapping_sub(n_2),
+        Op::Xor => n_1 ^ n_2,
+        Op::Mul => n_1.wrapping_mul(n_2),
+        Op::Div | Op::Mod if n_2 == 0 => bail!("division by zero"),
+        Op::Div => n_1.wrapping_div(n_2),
+        Op::Mod => n_1.wrapping_rem(n_2),
+        Op::And => n_1 & n_2,
+        Op::Or => n_1 | n_2,
+        Op::And2 => (n_1 != 0 && n_2 != 0) as i32,
+        Op::Or2 => (n_1 != 0 || n_2 != 0) as i32,
+        Op::Less => (n_1 < n_2) as i32,
+        Op::Greater => (n_1 > n_2) as i32,
+        Op::Equal => (n_1 == n_2) as i32,
+        Op::NotEqual => (n_1 != n_2) as i32,
+        Op::LessEqual => (n_1 <= n_2) as i32,
+        Op::GreaterEqual => (n_1 >= n_2) as i32,
+    };
+    Ok(value)
+}
+
+pub fn update(n: i32, mod_op: &ModOp, e: i32, direction: Direction) -> i32 {
+    match (mod_op, direction) {
+        (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => n.wrapping_add(e),
+        (ModOp::Sub, Direction::Forward) | (ModOp::Add, Direction::Backward) => n.wrapping_sub(e),
+        (ModOp::Xor, _) => n ^ e,
+    }
+}
+
+pub fn exhausted(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| cause.is::<Exhausted>())
+}
+
+pub(crate) fn assertion(direction: Direction, keyword: &'static str) -> &'static str {
+    match (direction, keyword) {
+        (Direction::Forward, keyword) => keyword,
+        (Direction::Backward, "fi") => "if",
+        (Direction::Backward, "from") => "until",
+        (Direction::Backward, "delocal") => "local",
+        (Direction::Backward, keyword) => keyword,
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, bail, Error, Result};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i32),
+    Array(Vec<i32>),
+    Stack(Vec<i32>),
+}
+
+impl Value {
+    pub fn int(&self) -> Result<i32> {
+        match self {
+            Value::Int(n) => Ok(*n),
+            x => bail!("expected an integer found {x}"),
+        }
+    }
+
+    pub fn truth(&self) -> Result<bool> {
+        Ok(self.int()? != 0)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Value::Int(n) => *n == 0,
+            Value::Array(ns) => ns.iter().all(|&n| n == 0),
+            Value::Stack(ns) => ns.is_empty(),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Int(value as i32)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |ns: &[i32]| {
+            ns.iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Array(ns) => write!(f, "[{}]", join(ns)),
+            Value::Stack(ns) if ns.is_empty() => write!(f, "nil"),
+            Value::Stack(ns) => write!(f, "<{}>", join(ns)),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let list = |inner: &str| {
+            inner
+                .split(',')
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(|n| n.parse().map_err(|_| anyhow!("invalid integer {n}")))
+                .collect::<Result<Vec<i32>>>()
+        };
+        let s = s.trim();
+        if s == "nil" {
+            Ok(Value::Stack(Vec::new()))
+        } else if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Ok(Value::Array(list(inner)?))
+        } else if let Some(inner) = s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            Ok(Value::Stack(list(inner)?))
+        } else {
+            s.parse()
+                .map(Value::Int)
+                .map_err(|_| anyhow!("invalid value {s}"))
+        }
+    }
+}
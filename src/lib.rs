@@ -0,0 +1,23 @@
+pub mod absint;
+pub mod ast;
+pub mod backend;
+pub mod bennett;
+pub mod bytecode;
+pub mod callgraph;
+pub mod cfg;
+pub mod circuit;
+pub mod coverage;
+pub mod flowchart;
+pub mod fuzz;
+pub mod highlight;
+pub mod interpreter;
+pub mod lint;
+pub mod lsp;
+pub mod optimize;
+pub mod pisa;
+pub mod prove;
+pub mod termination;
+pub mod tokenizer;
+pub mod trace;
+pub mod util;
+pub mod verify;
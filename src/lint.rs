@@ -0,0 +1,316 @@
+use crate::{
+    absint::{self, Check, Outcome},
+    ast::ast_node::*,
+    callgraph::CallGraph,
+    interpreter::{assertion, Direction},
+    optimize::inline::walk_vars,
+    termination::{analyze, Verdict},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    UnusedVariable,
+    UnreachableProcedure,
+    UnusedParameter,
+    UnusedLocal,
+    NonTerminatingLoop,
+    LoopReentry,
+    AssertionFails,
+    AssertionHolds,
+    IndexOutOfBounds,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule::UnusedVariable,
+    Rule::UnreachableProcedure,
+    Rule::UnusedParameter,
+    Rule::UnusedLocal,
+    Rule::NonTerminatingLoop,
+    Rule::LoopReentry,
+    Rule::AssertionFails,
+    Rule::AssertionHolds,
+    Rule::IndexOutOfBounds,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Info,
+    Warning,
+    Error,
+}
+
+pub const SEVERITIES: &[Severity] = &[
+    Severity::Off,
+    Severity::Info,
+    Severity::Warning,
+    Severity::Error,
+];
+
+impl Rule {
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreachableProcedure => "unreachable-procedure",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::UnusedLocal => "unused-local",
+            Rule::NonTerminatingLoop => "non-terminating-loop",
+            Rule::LoopReentry => "loop-reentry",
+            Rule::AssertionFails => "assertion-fails",
+            Rule::AssertionHolds => "assertion-holds",
+            Rule::IndexOutOfBounds => "index-out-of-bounds",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Rule::UnusedVariable
+            | Rule::UnusedParameter
+            | Rule::UnusedLocal
+            | Rule::NonTerminatingLoop
+            | Rule::LoopReentry
+            | Rule::AssertionFails
+            | Rule::IndexOutOfBounds => Severity::Warning,
+            Rule::UnreachableProcedure | Rule::AssertionHolds => Severity::Info,
+        }
+    }
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Off => "off",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config(BTreeMap<Rule, Severity>);
+
+impl Default for Config {
+    fn default() -> Self {
+        Self(RULES.iter().map(|&rule| (rule, rule.severity())).collect())
+    }
+}
+
+impl Config {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut value = Self::default();
+        for setting in spec.split(',').filter(|setting| !setting.is_empty()) {
+            let (name, level) = setting
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected RULE=SEVERITY, found {setting}"))?;
+            let rule = RULES
+                .iter()
+                .find(|rule| rule.name() == name.trim())
+                .ok_or_else(|| anyhow!("unknown rule {name}"))?;
+            let severity = SEVERITIES
+                .iter()
+                .copied()
+                .find(|severity| severity.name() == level.trim())
+                .ok_or_else(|| anyhow!("unknown severity {level}"))?;
+            value.0.insert(*rule, severity);
+        }
+        Ok(value)
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.0.get(&rule).copied().unwrap_or(rule.severity())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+pub fn lint(prog: &Prog, config: &Config) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut report = |rule: Rule, span: Span, message: String| {
+        let severity = config.severity(rule);
+        if severity != Severity::Off {
+            findings.push(Finding {
+                rule,
+                severity,
+                span,
+                message,
+            });
+        }
+    };
+
+    if let Proc::Main { main_stuff, s, .. } = &prog.p_main {
+        for stuff in main_stuff {
+            let (MainStuff::Int(Vdec::Scalar(x))
+            | MainStuff::Int(Vdec::Array { x, .. })
+            | MainStuff::Stack(x)) = stuff;
+            if !touches(s, &x.0) {
+                report(
+                    Rule::UnusedVariable,
+                    x.1,
+                    format!("{} is declared but never used", x.0),
+                );
+            }
+        }
+    }
+
+    let graph = CallGraph::new(prog);
+    let reached = graph.reachable("main");
+    for p in &prog.ps {
+        let Proc::Other { q, args, s } = p else {
+            continue;
+        };
+        if !reached.contains(q.0.as_str()) {
+            report(
+                Rule::UnreachableProcedure,
+                q.1,
+                format!("{} is never reached from main", q.0),
+            );
+        }
+        for arg in args {
+            if !touches(s, &arg.x.0) {
+                report(
+                    Rule::UnusedParameter,
+                    arg.x.1,
+                    format!("{} never reads or modifies its parameter {}", q.0, arg.x.0),
+                );
+            }
+        }
+    }
+
+    for p in prog.procs() {
+        p.body().walk(&mut |s| {
+            let Stm::Local { x_local, s, .. } = s else {
+                return;
+            };
+            if !touches(s, &x_local.0) {
+                report(
+                    Rule::UnusedLocal,
+                    x_local.1,
+                    format!("the body of local {} never touches it", x_local.0),
+                );
+            }
+        });
+    }
+
+    for found in analyze(prog) {
+        match found.verdict {
+            Verdict::Diverges { reason } => report(
+                Rule::NonTerminatingLoop,
+                found.span,
+                format!("loop in {} cannot terminate: {reason}", found.proc),
+            ),
+            Verdict::FailsOnReentry { reason } => report(
+                Rule::LoopReentry,
+                found.span,
+                format!("loop in {} cannot repeat: {reason}", found.proc),
+            ),
+            _ => {}
+        }
+    }
+
+    for (site, observation) in absint::analyze(prog).0 {
+        let when = match site.direction {
+            Direction::Forward => "",
+            Direction::Backward => " when run backward",
+        };
+        let subject = &observation.subject;
+        let check = match site.check {
+            Check::Fi => format!("assertion {}", assertion(site.direction, "fi")),
+            Check::From => format!("assertion {}", assertion(site.direction, "from")),
+            Check::Reentry => format!(
+                "assertion {} on re-entry",
+                assertion(site.direction, "from")
+            ),
+            Check::Delocal => format!(
+                "assertion {} {subject}",
+                assertion(site.direction, "delocal")
+            ),
+            Check::Pop => format!("pop from {subject}"),
+            Check::Index => {
+                let index = observation.index.map(|index| index.to_string());
+                let len = match observation.len {
+                    Some(len) => format!(" of length {len}"),
+                    None => String::new(),
+                };
+                format!("index {} into {subject}{len}", index.unwrap_or_default())
+            }
+        };
+        match (site.check, observation.outcome) {
+            (Check::Index, Outcome::Fails) => report(
+                Rule::IndexOutOfBounds,
+                site.span,
+                format!("{check} is always out of bounds{when}"),
+            ),
+            (Check::Index, Outcome::Unknown) => report(
+                Rule::IndexOutOfBounds,
+                site.span,
+                format!("{check} may be out of bounds{when}"),
+            ),
+            (_, Outcome::Fails) => report(
+                Rule::AssertionFails,
+                site.span,
+                format!("{check} always fails{when}"),
+            ),
+            (Check::Fi | Check::From | Check::Reentry | Check::Delocal, Outcome::Holds) => report(
+                Rule::AssertionHolds,
+                site.span,
+                format!("{check} always holds{when}"),
+            ),
+            _ => {}
+        }
+    }
+
+    findings.sort_by_key(|finding| finding.span);
+    findings
+}
+
+fn touches(s: &Stm, x: &str) -> bool {
+    let reads = |e: &Exp| {
+        let mut value = false;
+        walk_vars(e, &mut |y| value |= y.0 == x);
+        value
+    };
+    match s {
+        Stm::AssignScalar { x: y, e, .. } => y.0 == x || reads(e),
+        Stm::AssignArray {
+            x: y, e_index, e, ..
+        } => y.0 == x || reads(e_index) || reads(e),
+        Stm::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+            ..
+        } => reads(e_if) || reads(e_fi) || touches(s_then, x) || touches(s_else, x),
+        Stm::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+            ..
+        } => reads(e_from) || reads(e_until) || touches(s_do, x) || touches(s_loop, x),
+        Stm::Push(y, ys, _) | Stm::Pop(y, ys, _) => y.0 == x || ys.0 == x,
+        Stm::Local {
+            x_local,
+            e_local,
+            s,
+            e_delocal,
+            ..
+        } => reads(e_local) || (x_local.0 != x && (reads(e_delocal) || touches(s, x))),
+        Stm::Call { xs, .. } | Stm::Uncall { xs, .. } => xs.iter().any(|y| y.0 == x),
+        Stm::Skip(_) => false,
+        Stm::Sequence(s_1, s_2) => touches(s_1, x) || touches(s_2, x),
+    }
+}
@@ -0,0 +1,436 @@
+pub mod analysis;
+
+use crate::{
+    highlight::{classify, SEMANTIC_TYPES},
+    tokenizer::{
+        span::{Pos, Span},
+        KEYWORDS,
+    },
+};
+use analysis::{Document, SymbolKind};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value as Json};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+};
+
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+    let mut server = Server {
+        documents: BTreeMap::new(),
+        shutdown: false,
+        encoding: Encoding::Utf16,
+    };
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> Result<Option<Json>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+    let len = len.ok_or_else(|| anyhow!("message has no Content-Length header"))?;
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    fn negotiate(params: &Json) -> Self {
+        let offered = params["capabilities"]["general"]["positionEncodings"].as_array();
+        match offered.is_some_and(|encodings| encodings.iter().any(|x| x == "utf-32")) {
+            true => Encoding::Utf32,
+            false => Encoding::Utf16,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf16 => "utf-16",
+            Encoding::Utf32 => "utf-32",
+        }
+    }
+
+    fn len(self, c: char) -> usize {
+        match self {
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+struct Positions<'a> {
+    lines: Vec<&'a str>,
+    encoding: Encoding,
+}
+
+impl<'a> Positions<'a> {
+    fn new(text: &'a str, encoding: Encoding) -> Self {
+        Self {
+            lines: text.split('\n').collect(),
+            encoding,
+        }
+    }
+
+    fn character(&self, pos: Pos) -> usize {
+        let line = pos
+            .line
+            .checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .unwrap_or(&"");
+        let column = pos.column.saturating_sub(1);
+        let units = line
+            .chars()
+            .take(column)
+            .map(|c| self.encoding.len(c))
+            .sum::<usize>();
+        units + column.saturating_sub(line.chars().count())
+    }
+
+    fn pos(&self, line: usize, character: usize) -> Pos {
+        let text = self.lines.get(line).unwrap_or(&"");
+        let mut units = 0;
+        let mut column = 1;
+        for c in text.chars() {
+            if units >= character {
+                break;
+            }
+            units += self.encoding.len(c);
+            column += 1;
+        }
+        Pos::new(line + 1, column + character.saturating_sub(units))
+    }
+
+    fn position(&self, pos: Pos) -> Json {
+        json!({
+            "line": pos.line.saturating_sub(1),
+            "character": self.character(pos),
+        })
+    }
+
+    fn range(&self, span: Span) -> Json {
+        json!({ "start": self.position(span.start), "end": self.position(span.end) })
+    }
+
+    fn location(&self, uri: &str, span: Span) -> Json {
+        json!({ "uri": uri, "range": self.range(span) })
+    }
+}
+
+struct Server {
+    documents: BTreeMap<String, Document>,
+    shutdown: bool,
+    encoding: Encoding,
+}
+
+impl Server {
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params);
+        };
+        let result = match method {
+            "initialize" => {
+                self.encoding = Encoding::negotiate(params);
+                Ok(json!({
+                    "capabilities": {
+                        "positionEncoding": self.encoding.name(),
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "hoverProvider": true,
+                        "documentSymbolProvider": true,
+                        "renameProvider": true,
+                        "documentFormattingProvider": true,
+                        "semanticTokensProvider": {
+                            "legend": { "tokenTypes": SEMANTIC_TYPES, "tokenModifiers": [] },
+                            "full": true,
+                        },
+                    },
+                    "serverInfo": { "name": "janus" },
+                }))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            _ if self.shutdown => Err((-32600, "the server is shutting down".to_string())),
+            "textDocument/definition" => self.definition(params).map_err(internal),
+            "textDocument/references" => self.references(params).map_err(internal),
+            "textDocument/hover" => self.hover(params).map_err(internal),
+            "textDocument/documentSymbol" => self.symbols(params).map_err(internal),
+            "textDocument/rename" => self.rename(params).map_err(internal),
+            "textDocument/formatting" => self.format(params).map_err(internal),
+            "textDocument/semanticTokens/full" => self.highlight(params).map_err(internal),
+            _ => Err((-32601, format!("method {method} is not supported"))),
+        };
+        let reply = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        vec![reply]
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![diagnostics(uri, None, self.encoding)];
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+        let document = Document::new(text);
+        let reply = diagnostics(uri, Some(&document), self.encoding);
+        self.documents.insert(uri.to_string(), document);
+        vec![reply]
+    }
+
+    fn document<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a Document)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| anyhow!("the request names no document"))?;
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| anyhow!("{uri} is not open"))?;
+        Ok((uri, document))
+    }
+
+    fn positions<'a>(&self, document: &'a Document) -> Positions<'a> {
+        Positions::new(&document.text, self.encoding)
+    }
+
+    fn symbol<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a Document, Option<usize>)> {
+        let (uri, document) = self.document(params)?;
+        let line = params["position"]["line"].as_u64().unwrap_or_default();
+        let character = params["position"]["character"].as_u64().unwrap_or_default();
+        let pos = self
+            .positions(document)
+            .pos(line as usize, character as usize);
+        Ok((uri, document, document.symbol_at(pos)))
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json> {
+        let (uri, document, id) = self.symbol(params)?;
+        Ok(match id {
+            Some(id) => self
+                .positions(document)
+                .location(uri, document.symbols[id].definition),
+            None => Json::Null,
+        })
+    }
+
+    fn references(&self, params: &Json) -> Result<Json> {
+        let (uri, document, id) = self.symbol(params)?;
+        let Some(id) = id else {
+            return Ok(json!([]));
+        };
+        let declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let definition = document.symbols[id].definition;
+        let positions = self.positions(document);
+        let locations = document
+            .references(id)
+            .into_iter()
+            .filter(|&span| declaration || span != definition)
+            .map(|span| positions.location(uri, span))
+            .collect();
+        Ok(Json::Array(locations))
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json> {
+        let (_, document, id) = self.symbol(params)?;
+        let Some(id) = id else {
+            return Ok(Json::Null);
+        };
+        let symbol = &document.symbols[id];
+        Ok(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```janus\n{}\n```", symbol.detail),
+            },
+        }))
+    }
+
+    fn symbols(&self, params: &Json) -> Result<Json> {
+        let (_, document) = self.document(params)?;
+        let positions = self.positions(document);
+        let symbols = document
+            .procedures()
+            .into_iter()
+            .map(|(q, span, name)| {
+                let detail = document
+                    .prog
+                    .as_ref()
+                    .and_then(|prog| prog.find(&q))
+                    .map(analysis::signature)
+                    .unwrap_or_default();
+                json!({
+                    "name": q,
+                    "detail": detail,
+                    "kind": 12,
+                    "range": positions.range(span),
+                    "selectionRange": positions.range(name),
+                })
+            })
+            .collect();
+        Ok(Json::Array(symbols))
+    }
+
+    fn rename(&self, params: &Json) -> Result<Json> {
+        let (uri, document, id) = self.symbol(params)?;
+        let Some(id) = id else {
+            bail!("there is no symbol at this position");
+        };
+        let name = params["newName"]
+            .as_str()
+            .ok_or_else(|| anyhow!("the request has no new name"))?;
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || KEYWORDS.iter().any(|(keyword, _)| *keyword == name) || name == "main" {
+            bail!("{name} is not a valid name");
+        }
+        let symbol = &document.symbols[id];
+        let clash = document.symbols.iter().any(|other| {
+            other.name == name
+                && other.kind == symbol.kind
+                && (symbol.kind == SymbolKind::Procedure
+                    || document.procedure_of(other.definition)
+                        == document.procedure_of(symbol.definition))
+        });
+        if clash {
+            bail!("{name} is already used");
+        }
+        let positions = self.positions(document);
+        let edits = document
+            .references(id)
+            .into_iter()
+            .map(|span| json!({ "range": positions.range(span), "newText": name }))
+            .collect::<Vec<_>>();
+        Ok(json!({ "changes": { uri: edits } }))
+    }
+
+    fn highlight(&self, params: &Json) -> Result<Json> {
+        let (_, document) = self.document(params)?;
+        let positions = self.positions(document);
+        let mut data = Vec::new();
+        let mut last = Pos::new(1, 1);
+        for (span, class) in classify(&document.tokens) {
+            let Some(kind) = class.semantic() else {
+                continue;
+            };
+            let line = span.start.line - last.line;
+            let start = positions.character(span.start);
+            let delta = match line {
+                0 => start - positions.character(last),
+                _ => start,
+            };
+            let len = positions.character(span.end) - start;
+            data.extend([line, delta, len, kind, 0]);
+            last = span.start;
+        }
+        Ok(json!({ "data": data }))
+    }
+
+    fn format(&self, params: &Json) -> Result<Json> {
+        let (_, document) = self.document(params)?;
+        let Some(prog) = &document.prog else {
+            return Ok(json!([]));
+        };
+        let positions = self.positions(document);
+        let lines = &positions.lines;
+        let end = Pos::new(
+            lines.len(),
+            lines
+                .last()
+                .map(|line| line.chars().count())
+                .unwrap_or_default()
+                + 1,
+        );
+        Ok(json!([{
+            "range": positions.range(Span::new(Pos::new(1, 1), end)),
+            "newText": prog.to_string(),
+        }]))
+    }
+}
+
+fn internal(e: anyhow::Error) -> (i64, String) {
+    (-32603, format!("{e:#}"))
+}
+
+fn diagnostics(uri: &str, document: Option<&Document>, encoding: Encoding) -> Json {
+    let diagnostics = document
+        .map(|document| {
+            let positions = Positions::new(&document.text, encoding);
+            document
+                .diagnostics
+                .iter()
+                .map(|(span, message)| {
+                    json!({
+                        "range": positions.range(*span),
+                        "severity": 1,
+                        "source": "janus",
+                        "message": message,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": Json::Array(diagnostics) },
+    })
+}
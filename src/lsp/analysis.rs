@@ -0,0 +1,344 @@
+use crate::{
+    ast::{ast_node::*, Ast},
+    tokenizer::{
+        span::{Pos, Span},
+        token::Token,
+        Tokenizer,
+    },
+    util::char_list,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Procedure,
+    Variable,
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub definition: Span,
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct Document {
+    pub text: String,
+    pub tokens: Vec<(Token, Span)>,
+    pub prog: Option<Prog>,
+    pub diagnostics: Vec<(Span, String)>,
+    pub symbols: Vec<Symbol>,
+    pub occurrences: Vec<(Span, usize)>,
+}
+
+impl Document {
+    pub fn new(text: &str) -> Self {
+        let mut value = Self {
+            text: text.to_string(),
+            tokens: Vec::new(),
+            prog: None,
+            diagnostics: Vec::new(),
+            symbols: Vec::new(),
+            occurrences: Vec::new(),
+        };
+        let mut tokenizer = Tokenizer::new(char_list(text));
+        if let Err(e) = tokenizer.tokenize() {
+            value
+                .diagnostics
+                .push((tokenizer.position(), format!("{e:#}")));
+            return value;
+        }
+        value.tokens = tokenizer.tokens().iter().cloned().collect();
+        let mut ast = Ast::new(tokenizer);
+        if let Err(e) = ast.build() {
+            value.diagnostics.push((ast.position(), format!("{e:#}")));
+            return value;
+        }
+        let Some(prog) = ast.into_tree() else {
+            return value;
+        };
+        let mut checker = Checker {
+            prog: &prog,
+            diagnostics: Vec::new(),
+            symbols: Vec::new(),
+            occurrences: Vec::new(),
+            scope: Vec::new(),
+        };
+        checker.prog();
+        value.diagnostics = checker.diagnostics;
+        value.symbols = checker.symbols;
+        value.occurrences = checker.occurrences;
+        value.prog = Some(prog);
+        value
+    }
+
+    pub fn symbol_at(&self, pos: Pos) -> Option<usize> {
+        self.occurrences
+            .iter()
+            .find(|(span, _)| span.start <= pos && pos < span.end)
+            .map(|&(_, id)| id)
+    }
+
+    pub fn references(&self, id: usize) -> Vec<Span> {
+        self.occurrences
+            .iter()
+            .filter(|&&(_, other)| other == id)
+            .map(|&(span, _)| span)
+            .collect()
+    }
+
+    pub fn procedures(&self) -> Vec<(String, Span, Span)> {
+        let mut value = Vec::new();
+        for (i, window) in self.tokens.windows(2).enumerate() {
+            if let [(Token::Procedure, start), (Token::Identifier(q), name)] = window {
+                let end = self.tokens[i + 2..]
+                    .iter()
+                    .take_while(|(token, _)| *token != Token::Procedure)
+                    .last()
+                    .map(|(_, span)| span.end)
+                    .unwrap_or(name.end);
+                value.push((q.clone(), Span::new(start.start, end), *name));
+            }
+        }
+        value
+    }
+
+    pub fn procedure_of(&self, span: Span) -> Option<String> {
+        self.procedures()
+            .into_iter()
+            .find(|(_, range, _)| range.start <= span.start && span.end <= range.end)
+            .map(|(q, _, _)| q)
+    }
+}
+
+struct Checker<'a> {
+    prog: &'a Prog,
+    diagnostics: Vec<(Span, String)>,
+    symbols: Vec<Symbol>,
+    occurrences: Vec<(Span, usize)>,
+    scope: Vec<(String, usize)>,
+}
+
+pub fn signature(p: &Proc) -> String {
+    match p {
+        Proc::Main { .. } => "procedure main()".to_string(),
+        Proc::Other { q, args, .. } => {
+            let args = args
+                .iter()
+                .map(|arg| format!("{} {}", arg.t, arg.x.0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("procedure {}({args})", q.0)
+        }
+    }
+}
+
+impl Checker<'_> {
+    fn define(&mut self, x: &Var, kind: SymbolKind, detail: String) -> usize {
+        let id = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: x.0.clone(),
+            kind,
+            definition: x.1,
+            detail,
+        });
+        self.occurrences.push((x.1, id));
+        id
+    }
+
+    fn declare(&mut self, x: &Var, detail: String) {
+        if self.scope.iter().any(|(y, _)| *y == x.0) {
+            self.diagnostics
+                .push((x.1, format!("{} is declared twice", x.0)));
+        }
+        let id = self.define(x, SymbolKind::Variable, detail);
+        self.scope.push((x.0.clone(), id));
+    }
+
+    fn use_var(&mut self, x: &Var) {
+        match self.scope.iter().rev().find(|(y, _)| *y == x.0) {
+            Some(&(_, id)) => self.occurrences.push((x.1, id)),
+            None => self
+                .diagnostics
+                .push((x.1, format!("{} is not declared", x.0))),
+        }
+    }
+
+    fn prog(&mut self) {
+        let mut procedures = BTreeMap::new();
+        for p in &self.prog.ps {
+            let Proc::Other { q, .. } = p else {
+                continue;
+            };
+            if q.0 == "main" || procedures.contains_key(&q.0) {
+                self.diagnostics
+                    .push((q.1, format!("procedure {} is defined twice", q.0)));
+                continue;
+            }
+            let id = self.define(&Var(q.0.clone(), q.1), SymbolKind::Procedure, signature(p));
+            procedures.insert(q.0.clone(), id);
+        }
+
+        for p in self.prog.procs() {
+            self.scope.clear();
+            match p {
+                Proc::Main { main_stuff, .. } => {
+                    for stuff in main_stuff {
+                        let (x, detail) = match stuff {
+                            MainStuff::Int(Vdec::Scalar(x)) => (x, format!("int {}", x.0)),
+                            MainStuff::Int(Vdec::Array { x, c }) => {
+                                (x, format!("int {}[{}]", x.0, c.0))
+                            }
+                            MainStuff::Stack(x) => (x, format!("stack {}", x.0)),
+                        };
+                        self.declare(x, format!("{detail} (main)"));
+                    }
+                }
+                Proc::Other { q, args, .. } => {
+                    for arg in args {
+                        self.declare(
+                            &arg.x,
+                            format!("{} {} (parameter of {})", arg.t, arg.x.0, q.0),
+                        );
+                    }
+                }
+            }
+            self.stm(p.body(), &procedures);
+        }
+    }
+
+    fn exp(&mut self, e: &Exp) {
+        match e {
+            Exp::Constant(_) | Exp::Nil => {}
+            Exp::Variable(x) | Exp::Empty(x) | Exp::Top(x) => self.use_var(x),
+            Exp::Indexed { x, e } => {
+                self.use_var(x);
+                self.exp(e);
+            }
+            Exp::BinOp(e_1, _, e_2) => {
+                self.exp(e_1);
+                self.exp(e_2);
+            }
+        }
+    }
+
+    fn stm(&mut self, s: &Stm, procedures: &BTreeMap<String, usize>) {
+        match s {
+            Stm::AssignScalar { x, e, .. } => {
+                self.use_var(x);
+                self.exp(e);
+                if e.mentions(x) {
+                    self.diagnostics
+                        .push((x.1, format!("{} appears on both sides of the update", x.0)));
+                }
+            }
+            Stm::AssignArray { x, e_index, e, .. } => {
+                self.use_var(x);
+                self.exp(e_index);
+                self.exp(e);
+                if e_index.mentions(x) || e.mentions(x) {
+                    self.diagnostics
+                        .push((x.1, format!("{} appears on both sides of the update", x.0)));
+                }
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                ..
+            } => {
+                self.exp(e_if);
+                self.stm(s_then, procedures);
+                self.stm(s_else, procedures);
+                self.exp(e_fi);
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                ..
+            } => {
+                self.exp(e_from);
+                self.stm(s_do, procedures);
+                self.stm(s_loop, procedures);
+                self.exp(e_until);
+            }
+            Stm::Push(x, xs, _) | Stm::Pop(x, xs, _) => {
+                self.use_var(x);
+                self.use_var(xs);
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                ..
+            } => {
+                self.exp(e_local);
+                let id = self.symbols.len();
+                self.scope.push((x_local.0.clone(), id));
+                self.define(
+                    x_local,
+                    SymbolKind::Variable,
+                    format!("{t_local} {} (local)", x_local.0),
+                );
+                self.stm(s, procedures);
+                self.exp(e_delocal);
+                if x_delocal.0 != x_local.0 || t_delocal != t_local {
+                    self.diagnostics.push((
+                        x_delocal.1,
+                        format!(
+                            "delocal {t_delocal} {} does not match local {t_local} {}",
+                            x_delocal.0, x_local.0
+                        ),
+                    ));
+                } else {
+                    self.occurrences.push((x_delocal.1, id));
+                }
+                self.scope.pop();
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                for x in xs {
+                    self.use_var(x);
+                }
+                for (i, x) in xs.iter().enumerate() {
+                    if xs.iter().take(i).any(|y| y.0 == x.0) {
+                        self.diagnostics
+                            .push((x.1, format!("{} is passed more than once", x.0)));
+                    }
+                }
+                let Some(&id) = procedures.get(&q.0) else {
+                    self.diagnostics
+                        .push((q.1, format!("procedure {} is not defined", q.0)));
+                    return;
+                };
+                self.occurrences.push((q.1, id));
+                if let Some(Proc::Other { args, .. }) = self.prog.find(&q.0) {
+                    if args.len() != xs.len() {
+                        self.diagnostics.push((
+                            *span,
+                            format!(
+                                "{} expects {} arguments but {} were given",
+                                q.0,
+                                args.len(),
+                                xs.len()
+                            ),
+                        ));
+                    }
+                }
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => {
+                self.stm(s_1, procedures);
+                self.stm(s_2, procedures);
+            }
+        }
+    }
+}
@@ -1,13 +1,49 @@
-mod ast;
-mod tokenizer;
-mod util;
+mod cli;
 
-use ast::Ast;
-use std::{fs::File, io::Write};
-use tokenizer::Tokenizer;
-use util::read_file;
+use janus::{ast::Ast, tokenizer::Tokenizer, util::read_file};
+use std::{
+    fs::File,
+    io::{self, Write},
+    panic,
+};
+
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+const BACKTRACE: usize = 8;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !info.payload_as_str().is_some_and(broken_pipe) {
+                hook(info);
+            }
+        }));
+        let result = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || cli::run(&args))
+            .expect("failed to spawn interpreter thread")
+            .join();
+        let result = match result {
+            Ok(result) => result,
+            Err(payload) if payload_str(&*payload).is_some_and(broken_pipe) => return,
+            Err(payload) => panic::resume_unwind(payload),
+        };
+        if let Err(e) = result {
+            if e.chain().any(|cause| {
+                cause
+                    .downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+            }) {
+                return;
+            }
+            let _ = io::stdout().flush();
+            eprintln!("{}", report(&e));
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let characters = read_file("program.txt").expect("failed to read program");
 
     let mut tokenizer = Tokenizer::new(characters);
@@ -27,3 +63,28 @@ fn main() {
     let mut file = File::create("ast.txt").expect("failed to create file");
     write!(file, "{ast:#?}").expect("failed to write to file");
 }
+
+fn payload_str(payload: &(dyn std::any::Any + Send)) -> Option<&str> {
+    match payload.downcast_ref::<String>() {
+        Some(message) => Some(message),
+        None => payload.downcast_ref::<&str>().copied(),
+    }
+}
+
+fn broken_pipe(message: &str) -> bool {
+    message.contains("Broken pipe")
+}
+
+fn report(e: &anyhow::Error) -> String {
+    let chain = e.chain().map(ToString::to_string).collect::<Vec<_>>();
+    if chain.len() <= 2 * BACKTRACE {
+        return chain.join(": ");
+    }
+    let (head, tail) = (&chain[..BACKTRACE], &chain[chain.len() - BACKTRACE..]);
+    format!(
+        "{}: ... {} more ...: {}",
+        head.join(": "),
+        chain.len() - 2 * BACKTRACE,
+        tail.join(": ")
+    )
+}
@@ -0,0 +1,265 @@
+pub mod inline;
+pub mod partial;
+
+use crate::{
+    ast::ast_node::*,
+    backend::{array_params, Kind, Scope},
+    interpreter::eval_op,
+};
+use std::collections::BTreeMap;
+
+pub fn optimize(prog: Prog) -> Prog {
+    let arrays = array_params(&prog)
+        .into_iter()
+        .map(|(q, arrays)| (q.to_string(), arrays))
+        .collect::<BTreeMap<_, _>>();
+    let Prog { p_main, ps } = prog;
+    let p_main = match p_main {
+        Proc::Main {
+            span,
+            main_stuff,
+            s,
+        } => {
+            let mut scope = Scope::default();
+            for stuff in &main_stuff {
+                match stuff {
+                    MainStuff::Int(Vdec::Scalar(x)) => scope.push(&x.0, Kind::Scalar, false),
+                    MainStuff::Int(Vdec::Array { x, c }) => {
+                        scope.push(&x.0, Kind::Array(usize::try_from(c.0).ok()), false)
+                    }
+                    MainStuff::Stack(x) => scope.push(&x.0, Kind::Stack, false),
+                }
+            }
+            let s = stm(s, &mut scope);
+            Proc::Main {
+                span,
+                main_stuff,
+                s,
+            }
+        }
+        p => p,
+    };
+    let ps = ps
+        .into_iter()
+        .map(|p| match p {
+            Proc::Other { q, args, s } => {
+                let mut scope = Scope::params(&args, &arrays[&q.0]);
+                let s = stm(s, &mut scope);
+                Proc::Other { q, args, s }
+            }
+            p => p,
+        })
+        .collect();
+    Prog { p_main, ps }
+}
+
+fn constant(e: &Exp) -> Option<i32> {
+    match e {
+        Exp::Constant(c) => Some(c.0),
+        Exp::BinOp(e_1, op @ (Op::Sub | Op::Mul), e_2) => {
+            eval_op(op, constant(e_1)?, constant(e_2)?).ok()
+        }
+        _ => None,
+    }
+}
+
+fn int(n: i32) -> Exp {
+    match n {
+        0.. => Exp::Constant(Con(n)),
+        i32::MIN => Exp::BinOp(
+            Box::new(int(0)),
+            Op::Sub,
+            Box::new(Exp::BinOp(
+                Box::new(int(1 << 30)),
+                Op::Mul,
+                Box::new(int(2)),
+            )),
+        ),
+        _ => Exp::BinOp(Box::new(int(0)), Op::Sub, Box::new(int(-n))),
+    }
+}
+
+fn is_int(e: &Exp, scope: &Scope) -> bool {
+    match e {
+        Exp::Variable(x) => scope
+            .get(&x.0)
+            .is_some_and(|binding| binding.kind == Kind::Scalar),
+        Exp::Nil => false,
+        _ => true,
+    }
+}
+
+fn is_safe(e: &Exp, scope: &Scope) -> bool {
+    let kind = |x: &Var| scope.get(&x.0).map(|binding| binding.kind);
+    match e {
+        Exp::Constant(_) | Exp::Nil => true,
+        Exp::Variable(x) => kind(x) == Some(Kind::Scalar),
+        Exp::Empty(x) => kind(x) == Some(Kind::Stack),
+        Exp::Indexed { .. } | Exp::Top(_) => false,
+        Exp::BinOp(_, Op::Div | Op::Mod, _) => false,
+        Exp::BinOp(e_1, Op::Equal | Op::NotEqual, e_2) => {
+            is_safe(e_1, scope) && is_safe(e_2, scope)
+        }
+        Exp::BinOp(e_1, _, e_2) => [e_1, e_2]
+            .iter()
+            .all(|e| is_int(e, scope) && is_safe(e, scope)),
+    }
+}
+
+pub fn exp(e: Exp, scope: &Scope) -> Exp {
+    match e {
+        Exp::Indexed { x, e } => Exp::Indexed {
+            x,
+            e: Box::new(exp(*e, scope)),
+        },
+        Exp::BinOp(e_1, op, e_2) => binop(exp(*e_1, scope), op, exp(*e_2, scope), scope),
+        e => e,
+    }
+}
+
+fn binop(e_1: Exp, op: Op, e_2: Exp, scope: &Scope) -> Exp {
+    let (n_1, n_2) = (constant(&e_1), constant(&e_2));
+    if let (Some(n_1), Some(n_2)) = (n_1, n_2) {
+        if let Ok(n) = eval_op(&op, n_1, n_2) {
+            return int(n);
+        }
+    }
+    match (n_1, op, n_2) {
+        (_, Op::Add | Op::Sub | Op::Xor | Op::Or, Some(0)) | (_, Op::Mul | Op::Div, Some(1))
+            if is_int(&e_1, scope) =>
+        {
+            e_1
+        }
+        (Some(0), Op::Add | Op::Xor | Op::Or, _) | (Some(1), Op::Mul, _) if is_int(&e_2, scope) => {
+            e_2
+        }
+        (_, Op::Mul | Op::And, Some(0)) if is_int(&e_1, scope) && is_safe(&e_1, scope) => {
+            Exp::Constant(Con(0))
+        }
+        (Some(0), Op::Mul | Op::And, _) if is_int(&e_2, scope) && is_safe(&e_2, scope) => {
+            Exp::Constant(Con(0))
+        }
+        _ => Exp::BinOp(Box::new(e_1), op, Box::new(e_2)),
+    }
+}
+
+pub fn stm(s: Stm, scope: &mut Scope) -> Stm {
+    let kind = |x: &Var, scope: &Scope| scope.get(&x.0).map(|binding| binding.kind);
+    match s {
+        Stm::AssignScalar { x, mod_op, e, span } => {
+            let e = exp(e, scope);
+            match constant(&e) {
+                Some(0) if kind(&x, scope) == Some(Kind::Scalar) => Stm::Skip(span),
+                _ => Stm::AssignScalar { x, mod_op, e, span },
+            }
+        }
+        Stm::AssignArray {
+            x,
+            e_index,
+            mod_op,
+            e,
+            span,
+        } => {
+            let e_index = exp(e_index, scope);
+            let e = exp(e, scope);
+            let in_bounds = match (kind(&x, scope), constant(&e_index)) {
+                (Some(Kind::Array(Some(len))), Some(i)) => {
+                    usize::try_from(i).is_ok_and(|i| i < len)
+                }
+                _ => false,
+            };
+            match constant(&e) {
+                Some(0) if in_bounds => Stm::Skip(span),
+                _ => Stm::AssignArray {
+                    x,
+                    e_index,
+                    mod_op,
+                    e,
+                    span,
+                },
+            }
+        }
+        Stm::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+            span,
+        } => {
+            let e_if = exp(e_if, scope);
+            let s_then = stm(*s_then, scope);
+            let s_else = stm(*s_else, scope);
+            let e_fi = exp(e_fi, scope);
+            match (constant(&e_if), constant(&e_fi)) {
+                (Some(n_if), Some(n_fi)) if (n_if != 0) == (n_fi != 0) => match n_if != 0 {
+                    true => s_then,
+                    false => s_else,
+                },
+                _ => Stm::Conditional {
+                    e_if,
+                    s_then: Box::new(s_then),
+                    s_else: Box::new(s_else),
+                    e_fi,
+                    span,
+                },
+            }
+        }
+        Stm::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+            span,
+        } => {
+            let e_from = exp(e_from, scope);
+            let s_do = stm(*s_do, scope);
+            let s_loop = stm(*s_loop, scope);
+            let e_until = exp(e_until, scope);
+            match (constant(&e_from), constant(&e_until)) {
+                (Some(n_from), Some(n_until)) if n_from != 0 && n_until != 0 => s_do,
+                _ => Stm::Loop {
+                    e_from,
+                    s_do: Box::new(s_do),
+                    s_loop: Box::new(s_loop),
+                    e_until,
+                    span,
+                },
+            }
+        }
+        Stm::Local {
+            t_local,
+            x_local,
+            e_local,
+            s,
+            t_delocal,
+            x_delocal,
+            e_delocal,
+            span,
+        } => {
+            let e_local = exp(e_local, scope);
+            let kind = match t_local {
+                Type::Int => Kind::Scalar,
+                Type::Stack => Kind::Stack,
+            };
+            scope.push(&x_local.0, kind, false);
+            let s = stm(*s, scope);
+            let e_delocal = exp(e_delocal, scope);
+            scope.pop();
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s: Box::new(s),
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            }
+        }
+        Stm::Sequence(s_1, s_2) => match (stm(*s_1, scope), stm(*s_2, scope)) {
+            (Stm::Skip(_), s) | (s, Stm::Skip(_)) => s,
+            (s_1, s_2) => Stm::Sequence(Box::new(s_1), Box::new(s_2)),
+        },
+        s => s,
+    }
+}
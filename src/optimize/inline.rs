@@ -0,0 +1,371 @@
+use crate::{ast::ast_node::*, backend::fresh, callgraph::CallGraph};
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
+
+pub fn inline(prog: Prog, max_size: usize) -> Prog {
+    let inlinable = inlinable(&prog, max_size);
+    let mut bodies = prog
+        .procs()
+        .map(|p| {
+            let mut used = declared(p);
+            let mut inliner = Inliner {
+                inlinable: &inlinable,
+                used: &mut used,
+                depth: 0,
+            };
+            inliner.stm(p.body(), &[], false)
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+    let Prog { p_main, ps } = prog;
+    let mut replace = |p: Proc| {
+        let s = bodies.next().expect("one body per procedure");
+        match p {
+            Proc::Main {
+                span, main_stuff, ..
+            } => Proc::Main {
+                span,
+                main_stuff,
+                s,
+            },
+            Proc::Other { q, args, .. } => Proc::Other { q, args, s },
+        }
+    };
+    let p_main = replace(p_main);
+    let ps = ps.into_iter().map(replace).collect();
+    Prog { p_main, ps }
+}
+
+pub fn instantiate(
+    body: &Stm,
+    env: &[(String, String)],
+    inverse: bool,
+    used: &mut BTreeSet<String>,
+) -> Stm {
+    let mut inliner = Inliner {
+        inlinable: &BTreeMap::new(),
+        used,
+        depth: 1,
+    };
+    inliner.stm(body, env, inverse)
+}
+
+pub fn size(s: &Stm) -> usize {
+    let mut value = 0;
+    s.walk(&mut |s| {
+        if !matches!(s, Stm::Sequence(..)) {
+            value += 1;
+        }
+    });
+    value
+}
+
+fn closed(s: &Stm, scope: &mut Vec<String>) -> bool {
+    let bound = |x: &Var, scope: &[String]| scope.contains(&x.0);
+    let exp_closed = |e: &Exp, scope: &[String]| {
+        let mut value = true;
+        walk_vars(e, &mut |x| value &= bound(x, scope));
+        value
+    };
+    match s {
+        Stm::AssignScalar { x, e, .. } => bound(x, scope) && exp_closed(e, scope),
+        Stm::AssignArray { x, e_index, e, .. } => {
+            bound(x, scope) && exp_closed(e_index, scope) && exp_closed(e, scope)
+        }
+        Stm::Conditional {
+            e_if,
+            s_then,
+            s_else,
+            e_fi,
+            ..
+        } => {
+            exp_closed(e_if, scope)
+                && closed(s_then, scope)
+                && closed(s_else, scope)
+                && exp_closed(e_fi, scope)
+        }
+        Stm::Loop {
+            e_from,
+            s_do,
+            s_loop,
+            e_until,
+            ..
+        } => {
+            exp_closed(e_from, scope)
+                && closed(s_do, scope)
+                && closed(s_loop, scope)
+                && exp_closed(e_until, scope)
+        }
+        Stm::Push(x, xs, _) | Stm::Pop(x, xs, _) => bound(x, scope) && bound(xs, scope),
+        Stm::Local {
+            t_local,
+            x_local,
+            e_local,
+            s,
+            t_delocal,
+            x_delocal,
+            e_delocal,
+            ..
+        } => {
+            if x_local.0 != x_delocal.0 || t_local != t_delocal || !exp_closed(e_local, scope) {
+                return false;
+            }
+            scope.push(x_local.0.clone());
+            let value = closed(s, scope) && exp_closed(e_delocal, scope);
+            scope.pop();
+            value
+        }
+        Stm::Call { xs, .. } | Stm::Uncall { xs, .. } => xs.iter().all(|x| bound(x, scope)),
+        Stm::Skip(_) => true,
+        Stm::Sequence(s_1, s_2) => closed(s_1, scope) && closed(s_2, scope),
+    }
+}
+
+pub fn walk_vars(e: &Exp, f: &mut impl FnMut(&Var)) {
+    match e {
+        Exp::Constant(_) | Exp::Nil => {}
+        Exp::Variable(x) | Exp::Empty(x) | Exp::Top(x) => f(x),
+        Exp::Indexed { x, e } => {
+            f(x);
+            walk_vars(e, f);
+        }
+        Exp::BinOp(e_1, _, e_2) => {
+            walk_vars(e_1, f);
+            walk_vars(e_2, f);
+        }
+    }
+}
+
+fn inlinable(prog: &Prog, max_size: usize) -> BTreeMap<String, &Proc> {
+    let graph = CallGraph::new(prog);
+    prog.ps
+        .iter()
+        .filter(|p| {
+            let Proc::Other { q, args, s } = p else {
+                return false;
+            };
+            let mut scope = args.iter().map(|arg| arg.x.0.clone()).collect::<Vec<_>>();
+            let distinct = scope.iter().collect::<BTreeSet<_>>().len() == scope.len();
+            let unique = prog.ps.iter().filter(|p| p.name() == q.0).count() == 1;
+            unique
+                && distinct
+                && size(s) <= max_size
+                && !graph.is_recursive(&q.0)
+                && closed(s, &mut scope)
+        })
+        .map(|p| (p.name().to_string(), p))
+        .collect()
+}
+
+pub fn declared(p: &Proc) -> BTreeSet<String> {
+    let mut value: BTreeSet<String> = match p {
+        Proc::Main { main_stuff, .. } => main_stuff
+            .iter()
+            .map(|stuff| match stuff {
+                MainStuff::Int(Vdec::Scalar(x))
+                | MainStuff::Int(Vdec::Array { x, .. })
+                | MainStuff::Stack(x) => x.0.clone(),
+            })
+            .collect(),
+        Proc::Other { args, .. } => args.iter().map(|arg| arg.x.0.clone()).collect(),
+    };
+    p.body().walk(&mut |s| {
+        let xs = match s {
+            Stm::AssignScalar { x, .. } | Stm::AssignArray { x, .. } => vec![x],
+            Stm::Local { x_local, .. } => vec![x_local],
+            Stm::Push(x, xs, _) | Stm::Pop(x, xs, _) => vec![x, xs],
+            Stm::Call { xs, .. } | Stm::Uncall { xs, .. } => xs.iter().collect(),
+            _ => Vec::new(),
+        };
+        value.extend(xs.into_iter().map(|x| x.0.clone()));
+        for e in s.exps() {
+            walk_vars(e, &mut |x| {
+                value.insert(x.0.clone());
+            });
+        }
+    });
+    value
+}
+
+struct Inliner<'a> {
+    inlinable: &'a BTreeMap<String, &'a Proc>,
+    used: &'a mut BTreeSet<String>,
+    depth: usize,
+}
+
+impl Inliner<'_> {
+    fn stm(&mut self, s: &Stm, env: &[(String, String)], inverse: bool) -> Stm {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => Stm::AssignScalar {
+                x: rename(x, env),
+                mod_op: invert(*mod_op, inverse),
+                e: exp(e, env),
+                span: *span,
+            },
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => Stm::AssignArray {
+                x: rename(x, env),
+                e_index: exp(e_index, env),
+                mod_op: invert(*mod_op, inverse),
+                e: exp(e, env),
+                span: *span,
+            },
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_if, e_fi) = swap(exp(e_if, env), exp(e_fi, env), inverse);
+                Stm::Conditional {
+                    e_if,
+                    s_then: Box::new(self.stm(s_then, env, inverse)),
+                    s_else: Box::new(self.stm(s_else, env, inverse)),
+                    e_fi,
+                    span: *span,
+                }
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_from, e_until) = swap(exp(e_from, env), exp(e_until, env), inverse);
+                Stm::Loop {
+                    e_from,
+                    s_do: Box::new(self.stm(s_do, env, inverse)),
+                    s_loop: Box::new(self.stm(s_loop, env, inverse)),
+                    e_until,
+                    span: *span,
+                }
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                let (x, xs) = (rename(x, env), rename(xs, env));
+                match matches!(s, Stm::Push(..)) != inverse {
+                    true => Stm::Push(x, xs, *span),
+                    false => Stm::Pop(x, xs, *span),
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                let e_local = exp(e_local, env);
+                let x = match self.depth {
+                    0 => x_local.0.clone(),
+                    _ => fresh(self.used, &x_local.0),
+                };
+                let mut inner = env.to_vec();
+                inner.push((x_local.0.clone(), x.clone()));
+                let s = self.stm(s, &inner, inverse);
+                let e_delocal = exp(e_delocal, &inner);
+                let (e_local, e_delocal) = swap(e_local, e_delocal, inverse);
+                Stm::Local {
+                    t_local: *t_local,
+                    x_local: Var(x.clone(), x_local.1),
+                    e_local,
+                    s: Box::new(s),
+                    t_delocal: *t_delocal,
+                    x_delocal: Var(
+                        match self.depth {
+                            0 => x_delocal.0.clone(),
+                            _ => x,
+                        },
+                        x_delocal.1,
+                    ),
+                    e_delocal,
+                    span: *span,
+                }
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let xs = xs.iter().map(|x| rename(x, env)).collect::<LinkedList<_>>();
+                let backward = matches!(s, Stm::Uncall { .. }) != inverse;
+                let distinct = xs.iter().map(|x| &x.0).collect::<BTreeSet<_>>().len() == xs.len();
+                match self.inlinable.get(&q.0) {
+                    Some(Proc::Other { args, s: body, .. })
+                        if distinct && args.len() == xs.len() =>
+                    {
+                        let inner = args
+                            .iter()
+                            .zip(&xs)
+                            .map(|(arg, x)| (arg.x.0.clone(), x.0.clone()))
+                            .collect::<Vec<_>>();
+                        self.depth += 1;
+                        let value = self.stm(body, &inner, backward);
+                        self.depth -= 1;
+                        value
+                    }
+                    _ => {
+                        let q = PId(q.0.clone(), q.1);
+                        match backward {
+                            false => Stm::Call { q, xs, span: *span },
+                            true => Stm::Uncall { q, xs, span: *span },
+                        }
+                    }
+                }
+            }
+            Stm::Skip(span) => Stm::Skip(*span),
+            Stm::Sequence(s_1, s_2) => {
+                let (s_1, s_2) = swap(s_1, s_2, inverse);
+                Stm::Sequence(
+                    Box::new(self.stm(s_1, env, inverse)),
+                    Box::new(self.stm(s_2, env, inverse)),
+                )
+            }
+        }
+    }
+}
+
+fn swap<T>(a: T, b: T, inverse: bool) -> (T, T) {
+    match inverse {
+        true => (b, a),
+        false => (a, b),
+    }
+}
+
+fn invert(mod_op: ModOp, inverse: bool) -> ModOp {
+    match (mod_op, inverse) {
+        (ModOp::Add, true) => ModOp::Sub,
+        (ModOp::Sub, true) => ModOp::Add,
+        (mod_op, _) => mod_op,
+    }
+}
+
+fn rename(x: &Var, env: &[(String, String)]) -> Var {
+    let name = env
+        .iter()
+        .rev()
+        .find(|(y, _)| *y == x.0)
+        .map_or(&x.0, |(_, name)| name);
+    Var(name.clone(), x.1)
+}
+
+fn exp(e: &Exp, env: &[(String, String)]) -> Exp {
+    match e {
+        Exp::Constant(c) => Exp::Constant(c.clone()),
+        Exp::Variable(x) => Exp::Variable(rename(x, env)),
+        Exp::Indexed { x, e } => Exp::Indexed {
+            x: rename(x, env),
+            e: Box::new(exp(e, env)),
+        },
+        Exp::BinOp(e_1, op, e_2) => {
+            Exp::BinOp(Box::new(exp(e_1, env)), *op, Box::new(exp(e_2, env)))
+        }
+        Exp::Empty(x) => Exp::Empty(rename(x, env)),
+        Exp::Top(x) => Exp::Top(rename(x, env)),
+        Exp::Nil => Exp::Nil,
+    }
+}
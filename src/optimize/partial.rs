@@ -0,0 +1,676 @@
+use super::{
+    inline::{declared, instantiate, walk_vars},
+    int,
+};
+use crate::{
+    ast::ast_node::*,
+    backend::{array_params, sequence},
+    callgraph::CallGraph,
+    interpreter::{eval_op, update, Direction},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
+
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    value: i32,
+    residual: i32,
+}
+
+type State = BTreeMap<String, Slot>;
+
+#[derive(Debug)]
+pub struct Residual {
+    pub prog: Prog,
+    pub q: String,
+    pub params: Vec<String>,
+    pub finals: Vec<(String, i32)>,
+    pub preconditions: Vec<(String, i32)>,
+}
+
+pub fn specialize(
+    prog: &Prog,
+    q: &str,
+    known: &[(String, i32)],
+    max_unroll: usize,
+) -> Result<Residual> {
+    let p = prog
+        .find(q)
+        .ok_or_else(|| anyhow!("procedure {q} is not defined"))?;
+    let mut specializer = Specializer {
+        prog,
+        graph: CallGraph::new(prog),
+        state: State::new(),
+        used: declared(p),
+        depth: 0,
+        dynamic: 0,
+        max_unroll,
+    };
+    let mut out = Vec::new();
+    let (main_stuff, args) = match p {
+        Proc::Main { main_stuff, .. } => (main_stuff, None),
+        Proc::Other { args, .. } => (&LinkedList::new(), Some(args)),
+    };
+    let Some(args) = args else {
+        if let Some((x, _)) = known.first() {
+            bail!("main has no inputs, but {x} was given");
+        }
+        for stuff in main_stuff {
+            if let MainStuff::Int(Vdec::Scalar(x)) = stuff {
+                specializer.state.insert(
+                    x.0.clone(),
+                    Slot {
+                        value: 0,
+                        residual: 0,
+                    },
+                );
+            }
+        }
+        specializer.stm(p.body(), &mut out);
+        let xs = specializer.state.keys().cloned().collect::<Vec<_>>();
+        for x in xs {
+            specializer.lift(&x, &mut out);
+        }
+        let s = sequence(out);
+        let ps = reachable(prog, &s);
+        return Ok(Residual {
+            prog: Prog {
+                p_main: Proc::Main {
+                    span: Span::default(),
+                    main_stuff: main_stuff.clone(),
+                    s,
+                },
+                ps,
+            },
+            q: q.to_string(),
+            params: Vec::new(),
+            finals: Vec::new(),
+            preconditions: Vec::new(),
+        });
+    };
+
+    let arrays = &array_params(prog)[q];
+    for (x, value) in known {
+        match args.iter().zip(arrays).find(|(arg, _)| arg.x.0 == *x) {
+            None => bail!("{q} has no parameter {x}"),
+            Some((arg, &array)) if array || arg.t != Type::Int => {
+                bail!("{x} is not an integer parameter of {q}")
+            }
+            Some(_) => {
+                specializer.state.insert(
+                    x.clone(),
+                    Slot {
+                        value: *value,
+                        residual: *value,
+                    },
+                );
+            }
+        }
+    }
+    specializer.stm(p.body(), &mut out);
+
+    let mut params = LinkedList::new();
+    let mut finals = Vec::new();
+    let mut preconditions = Vec::new();
+    let mut wrapped = Vec::new();
+    for arg in args {
+        let x = &arg.x.0;
+        match known.iter().find(|(y, _)| y == x) {
+            Some(&(_, initial)) => match specializer.state.get(x).copied() {
+                Some(slot) => {
+                    finals.push((x.clone(), slot.value));
+                    wrapped.push((x.clone(), initial, slot));
+                }
+                None => {
+                    preconditions.push((x.clone(), initial));
+                    params.push_back(arg.clone());
+                }
+            },
+            None => params.push_back(arg.clone()),
+        }
+    }
+    for (x, initial, slot) in wrapped.into_iter().rev() {
+        if out.iter().any(|s| mentions(s, &x)) {
+            if slot.residual != slot.value {
+                out.push(adjust(&x, slot.value.wrapping_sub(slot.residual)));
+            }
+            out = vec![Stm::Local {
+                t_local: Type::Int,
+                x_local: var(&x),
+                e_local: int(initial),
+                s: Box::new(sequence(out)),
+                t_delocal: Type::Int,
+                x_delocal: var(&x),
+                e_delocal: int(slot.value),
+                span: Span::default(),
+            }];
+        }
+    }
+
+    let mut name = format!("{q}_spec");
+    let mut i = 1;
+    while prog.find(&name).is_some() {
+        name = format!("{q}_spec_{i}");
+        i += 1;
+    }
+    let s = sequence(out);
+    let mut ps = reachable(prog, &s);
+    let residual_params = params.iter().map(|arg| arg.x.0.clone()).collect();
+    ps.push_front(Proc::Other {
+        q: PId(name.clone(), Span::default()),
+        args: params,
+        s,
+    });
+    Ok(Residual {
+        prog: Prog {
+            p_main: Proc::Main {
+                span: Span::default(),
+                main_stuff: LinkedList::new(),
+                s: Stm::Skip(Span::default()),
+            },
+            ps,
+        },
+        q: name,
+        params: residual_params,
+        finals,
+        preconditions,
+    })
+}
+
+struct Specializer<'a> {
+    prog: &'a Prog,
+    graph: CallGraph,
+    state: State,
+    used: BTreeSet<String>,
+    depth: usize,
+    dynamic: usize,
+    max_unroll: usize,
+}
+
+impl Specializer<'_> {
+    fn eval(&self, e: &Exp) -> Option<i32> {
+        match e {
+            Exp::Constant(c) => Some(c.0),
+            Exp::Variable(x) => self.state.get(&x.0).map(|slot| slot.value),
+            Exp::BinOp(e_1, op, e_2) => eval_op(op, self.eval(e_1)?, self.eval(e_2)?).ok(),
+            _ => None,
+        }
+    }
+
+    fn residual(&mut self, e: &Exp, out: &mut Vec<Stm>) -> Exp {
+        if let Some(n) = self.eval(e) {
+            return int(n);
+        }
+        match e {
+            Exp::Indexed { x, e } => Exp::Indexed {
+                x: x.clone(),
+                e: Box::new(self.residual(e, out)),
+            },
+            Exp::BinOp(e_1, op, e_2) => {
+                let e_1 = match &**e_1 {
+                    Exp::Variable(x) if self.eval(e_1).is_some_and(|n| n < 0) => {
+                        self.sync(&x.0, out);
+                        Exp::Variable(x.clone())
+                    }
+                    e_1 => self.residual(e_1, out),
+                };
+                Exp::BinOp(Box::new(e_1), *op, Box::new(self.residual(e_2, out)))
+            }
+            e => e.clone(),
+        }
+    }
+
+    fn sync(&mut self, x: &str, out: &mut Vec<Stm>) {
+        if let Some(slot) = self.state.get_mut(x) {
+            if slot.value != slot.residual {
+                out.push(adjust(x, slot.value.wrapping_sub(slot.residual)));
+                slot.residual = slot.value;
+            }
+        }
+    }
+
+    fn lift(&mut self, x: &str, out: &mut Vec<Stm>) {
+        self.sync(x, out);
+        self.state.remove(x);
+    }
+
+    fn reconcile(&mut self, target: &State, out: &mut Vec<Stm>) {
+        for (x, slot) in self.state.clone() {
+            match target.get(&x) {
+                Some(t) if t.value == slot.value => {
+                    if t.residual != slot.residual {
+                        out.push(adjust(&x, t.residual.wrapping_sub(slot.residual)));
+                        self.state.insert(x, *t);
+                    }
+                }
+                _ => self.lift(&x, out),
+            }
+        }
+    }
+
+    fn drift(&self, head: &State) -> Vec<String> {
+        head.iter()
+            .filter(|(x, slot)| self.state.get(*x).is_none_or(|s| s.value != slot.value))
+            .map(|(x, _)| x.clone())
+            .collect()
+    }
+
+    fn stm(&mut self, s: &Stm, out: &mut Vec<Stm>) {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                match (self.state.get(&x.0), self.eval(e)) {
+                    (Some(slot), Some(n)) if !e.mentions(x) => {
+                        let value = update(slot.value, mod_op, n, Direction::Forward);
+                        self.state.insert(
+                            x.0.clone(),
+                            Slot {
+                                value,
+                                residual: slot.residual,
+                            },
+                        );
+                    }
+                    (_, Some(n)) if *mod_op != ModOp::Xor && !e.mentions(x) => {
+                        self.lift(&x.0, out);
+                        let n = match mod_op {
+                            ModOp::Sub => n.wrapping_neg(),
+                            _ => n,
+                        };
+                        out.push(adjust(&x.0, n));
+                    }
+                    _ => {
+                        self.lift(&x.0, out);
+                        let e = self.residual(e, out);
+                        out.push(Stm::AssignScalar {
+                            x: x.clone(),
+                            mod_op: *mod_op,
+                            e,
+                            span: *span,
+                        });
+                    }
+                }
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                let e_index = self.residual(e_index, out);
+                let e = self.residual(e, out);
+                out.push(Stm::AssignArray {
+                    x: x.clone(),
+                    e_index,
+                    mod_op: *mod_op,
+                    e,
+                    span: *span,
+                });
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => match self.eval(e_if) {
+                Some(c) => {
+                    let mut branch = Vec::new();
+                    self.stm(if c != 0 { s_then } else { s_else }, &mut branch);
+                    match self.eval(e_fi) {
+                        Some(d) => {
+                            out.extend(branch);
+                            if (c != 0) != (d != 0) {
+                                out.push(fail());
+                            }
+                        }
+                        None => {
+                            let e_fi = self.residual(e_fi, &mut branch);
+                            let (s_then, s_else) = match c != 0 {
+                                true => (sequence(branch), skip()),
+                                false => (skip(), sequence(branch)),
+                            };
+                            out.push(Stm::Conditional {
+                                e_if: int((c != 0) as i32),
+                                s_then: Box::new(s_then),
+                                s_else: Box::new(s_else),
+                                e_fi,
+                                span: *span,
+                            });
+                        }
+                    }
+                }
+                None => {
+                    let e_if = self.residual(e_if, out);
+                    let entry = self.state.clone();
+                    self.dynamic += 1;
+                    let mut out_then = Vec::new();
+                    self.stm(s_then, &mut out_then);
+                    let state_then = std::mem::replace(&mut self.state, entry);
+                    let mut out_else = Vec::new();
+                    self.stm(s_else, &mut out_else);
+                    self.dynamic -= 1;
+                    let merged = state_then
+                        .iter()
+                        .filter(|(x, slot)| {
+                            self.state
+                                .get(*x)
+                                .is_some_and(|other| other.value == slot.value)
+                        })
+                        .map(|(x, slot)| (x.clone(), *slot))
+                        .collect::<State>();
+                    self.reconcile(&merged, &mut out_else);
+                    self.state = state_then;
+                    self.reconcile(&merged, &mut out_then);
+                    let mut tail = Vec::new();
+                    let e_fi = self.residual(e_fi, &mut tail);
+                    out_then.extend(tail.iter().cloned());
+                    out_else.extend(tail);
+                    out.push(Stm::Conditional {
+                        e_if,
+                        s_then: Box::new(sequence(out_then)),
+                        s_else: Box::new(sequence(out_else)),
+                        e_fi,
+                        span: *span,
+                    });
+                }
+            },
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                if self.unroll(e_from, s_do, s_loop, e_until, out) {
+                    return;
+                }
+                let mut lifted = targets(s_do);
+                lifted.extend(targets(s_loop));
+                let (mark, entry) = (out.len(), self.state.clone());
+                loop {
+                    out.truncate(mark);
+                    self.state = entry.clone();
+                    for x in &lifted {
+                        self.lift(x, out);
+                    }
+                    let e_from = self.residual(e_from, out);
+                    let e_until = self.residual(e_until, out);
+                    let head = self.state.clone();
+                    self.dynamic += 1;
+                    let mut out_do = Vec::new();
+                    self.stm(s_do, &mut out_do);
+                    let mut drift = self.drift(&head);
+                    self.reconcile(&head, &mut out_do);
+                    let mut out_loop = Vec::new();
+                    self.stm(s_loop, &mut out_loop);
+                    drift.extend(self.drift(&head));
+                    self.reconcile(&head, &mut out_loop);
+                    self.dynamic -= 1;
+                    if drift.is_empty() {
+                        out.push(Stm::Loop {
+                            e_from,
+                            s_do: Box::new(sequence(out_do)),
+                            s_loop: Box::new(sequence(out_loop)),
+                            e_until,
+                            span: *span,
+                        });
+                        break;
+                    }
+                    lifted.extend(drift);
+                }
+            }
+            Stm::Push(x, xs, span) => {
+                let tracked = self.state.contains_key(&x.0);
+                self.lift(&x.0, out);
+                out.push(Stm::Push(x.clone(), xs.clone(), *span));
+                if tracked {
+                    self.state.insert(
+                        x.0.clone(),
+                        Slot {
+                            value: 0,
+                            residual: 0,
+                        },
+                    );
+                }
+            }
+            Stm::Pop(x, xs, span) => {
+                self.lift(&x.0, out);
+                out.push(Stm::Pop(x.clone(), xs.clone(), *span));
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                let x = &x_local.0;
+                let n = match t_local {
+                    Type::Int => self.eval(e_local),
+                    Type::Stack => None,
+                };
+                let e_local = self.residual(e_local, out);
+                let outer = self.state.remove(x);
+                if let Some(n) = n {
+                    self.state.insert(
+                        x.clone(),
+                        Slot {
+                            value: n,
+                            residual: n,
+                        },
+                    );
+                }
+                let mut inner = Vec::new();
+                self.stm(s, &mut inner);
+                let matching = x_delocal.0 == *x && t_delocal == t_local;
+                let mut closing = None;
+                let e_delocal = match (self.state.get(x).copied(), self.eval(e_delocal)) {
+                    (Some(slot), Some(w)) if matching => {
+                        if slot.value != w {
+                            inner.push(fail());
+                        }
+                        if slot.residual != w {
+                            closing = Some(adjust(x, w.wrapping_sub(slot.residual)));
+                        }
+                        int(w)
+                    }
+                    _ => {
+                        self.lift(x, &mut inner);
+                        self.residual(e_delocal, &mut inner)
+                    }
+                };
+                self.state.remove(x);
+                if let Some(slot) = outer {
+                    self.state.insert(x.clone(), slot);
+                }
+                if matching
+                    && *t_local == Type::Int
+                    && is_constant(&e_local)
+                    && is_constant(&e_delocal)
+                    && !inner.iter().any(|s| mentions(s, x))
+                {
+                    out.extend(inner);
+                    return;
+                }
+                inner.extend(closing);
+                out.push(Stm::Local {
+                    t_local: *t_local,
+                    x_local: x_local.clone(),
+                    e_local,
+                    s: Box::new(sequence(inner)),
+                    t_delocal: *t_delocal,
+                    x_delocal: x_delocal.clone(),
+                    e_delocal,
+                    span: *span,
+                });
+            }
+            Stm::Call { q, xs, .. } | Stm::Uncall { q, xs, .. } => {
+                let distinct = xs.iter().map(|x| &x.0).collect::<BTreeSet<_>>().len() == xs.len();
+                match self.prog.find(&q.0) {
+                    Some(Proc::Other { args, s: body, .. })
+                        if distinct
+                            && args.len() == xs.len()
+                            && self.depth < MAX_DEPTH
+                            && (self.dynamic == 0 || !self.graph.is_recursive(&q.0)) =>
+                    {
+                        let env = args
+                            .iter()
+                            .zip(xs)
+                            .map(|(arg, x)| (arg.x.0.clone(), x.0.clone()))
+                            .collect::<Vec<_>>();
+                        let backward = matches!(s, Stm::Uncall { .. });
+                        let body = instantiate(body, &env, backward, &mut self.used);
+                        self.depth += 1;
+                        self.stm(&body, out);
+                        self.depth -= 1;
+                    }
+                    _ => {
+                        for x in xs {
+                            self.lift(&x.0, out);
+                        }
+                        out.push(s.clone());
+                    }
+                }
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => {
+                self.stm(s_1, out);
+                self.stm(s_2, out);
+            }
+        }
+    }
+
+    fn unroll(
+        &mut self,
+        e_from: &Exp,
+        s_do: &Stm,
+        s_loop: &Stm,
+        e_until: &Exp,
+        out: &mut Vec<Stm>,
+    ) -> bool {
+        match self.eval(e_from) {
+            None => return false,
+            Some(0) => {
+                out.push(fail());
+                return true;
+            }
+            Some(_) => {}
+        }
+        let (mark, entry) = (out.len(), self.state.clone());
+        for _ in 0..self.max_unroll {
+            self.stm(s_do, out);
+            match self.eval(e_until) {
+                Some(0) => {}
+                Some(_) => return true,
+                None => break,
+            }
+            self.stm(s_loop, out);
+            match self.eval(e_from) {
+                Some(0) => {}
+                Some(_) => {
+                    out.push(fail());
+                    return true;
+                }
+                None => break,
+            }
+        }
+        out.truncate(mark);
+        self.state = entry;
+        false
+    }
+}
+
+pub fn targets(s: &Stm) -> BTreeSet<String> {
+    let mut value = BTreeSet::new();
+    s.walk(&mut |s| match s {
+        Stm::AssignScalar { x, .. } | Stm::AssignArray { x, .. } => {
+            value.insert(x.0.clone());
+        }
+        Stm::Push(x, xs, _) | Stm::Pop(x, xs, _) => {
+            value.insert(x.0.clone());
+            value.insert(xs.0.clone());
+        }
+        Stm::Call { xs, .. } | Stm::Uncall { xs, .. } => {
+            value.extend(xs.iter().map(|x| x.0.clone()));
+        }
+        _ => {}
+    });
+    value
+}
+
+fn is_constant(e: &Exp) -> bool {
+    let mut value = true;
+    walk_vars(e, &mut |_| value = false);
+    value
+}
+
+fn mentions(s: &Stm, x: &str) -> bool {
+    let mut value = targets(s).contains(x);
+    s.walk(&mut |s| {
+        if let Stm::Local { x_local, .. } = s {
+            value |= x_local.0 == x;
+        }
+        for e in s.exps() {
+            walk_vars(e, &mut |y| value |= y.0 == x);
+        }
+    });
+    value
+}
+
+fn reachable(prog: &Prog, s: &Stm) -> LinkedList<Proc> {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![s];
+    while let Some(s) = pending.pop() {
+        s.walk(&mut |s| {
+            if let Stm::Call { q, .. } | Stm::Uncall { q, .. } = s {
+                if seen.insert(q.0.clone()) {
+                    if let Some(p) = prog.find(&q.0) {
+                        pending.push(p.body());
+                    }
+                }
+            }
+        });
+    }
+    prog.ps
+        .iter()
+        .filter(|p| seen.contains(p.name()))
+        .cloned()
+        .collect()
+}
+
+fn var(x: &str) -> Var {
+    Var(x.to_string(), Span::default())
+}
+
+fn adjust(x: &str, n: i32) -> Stm {
+    let (mod_op, e) = match n {
+        0.. | i32::MIN => (ModOp::Add, int(n)),
+        _ => (ModOp::Sub, int(-n)),
+    };
+    Stm::AssignScalar {
+        x: var(x),
+        mod_op,
+        e,
+        span: Span::default(),
+    }
+}
+
+fn skip() -> Stm {
+    Stm::Skip(Span::default())
+}
+
+fn fail() -> Stm {
+    Stm::Conditional {
+        e_if: int(0),
+        s_then: Box::new(skip()),
+        s_else: Box::new(skip()),
+        e_fi: int(1),
+        span: Span::default(),
+    }
+}
@@ -0,0 +1,202 @@
+pub mod codegen;
+pub mod emulator;
+
+use crate::{
+    ast::ast_node::*,
+    backend::Kind,
+    interpreter::{value::Value, Direction},
+    tokenizer::span::Span,
+};
+use anyhow::Result;
+use emulator::Emulator;
+use std::{
+    collections::{BTreeMap, LinkedList},
+    fmt,
+};
+
+pub type Reg = usize;
+
+pub const ZERO: Reg = 0;
+pub const SP: Reg = 1;
+pub const RO: Reg = 2;
+pub const REGISTERS: usize = 32;
+pub const STACK_CAPACITY: usize = 1024;
+pub const MEMORY: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    Add(Reg, Reg),
+    Sub(Reg, Reg),
+    Xor(Reg, Reg),
+    Addi(Reg, i32),
+    Xori(Reg, i32),
+    Neg(Reg),
+    OpX(Op, Reg, Reg, Reg),
+    Exch(Reg, Reg),
+    Bra(isize),
+    Rbra(isize),
+    Beq(Reg, Reg, isize),
+    Bne(Reg, Reg, isize),
+    Swapbr(Reg),
+    Check(Reg, usize),
+    Start,
+    Finish,
+}
+
+impl Instr {
+    pub fn inverse(self) -> Self {
+        match self {
+            Instr::Add(rd, rs) => Instr::Sub(rd, rs),
+            Instr::Sub(rd, rs) => Instr::Add(rd, rs),
+            Instr::Addi(rd, n) => Instr::Addi(rd, n.wrapping_neg()),
+            instr => instr,
+        }
+    }
+
+    pub fn target(&self, pc: usize) -> Option<usize> {
+        match self {
+            Instr::Bra(offset)
+            | Instr::Rbra(offset)
+            | Instr::Beq(_, _, offset)
+            | Instr::Bne(_, _, offset) => Some(pc.wrapping_add_signed(*offset)),
+            _ => None,
+        }
+    }
+}
+
+pub fn call(
+    prog: &Prog,
+    q: &str,
+    input: &[Value],
+    direction: Direction,
+    max_steps: u64,
+) -> Result<Vec<Value>> {
+    let xs = (0..input.len())
+        .map(|i| Var(format!("a{i}"), Span::default()))
+        .collect::<LinkedList<_>>();
+    let main_stuff = xs
+        .iter()
+        .zip(input)
+        .map(|(x, value)| match value {
+            Value::Int(_) => MainStuff::Int(Vdec::Scalar(x.clone())),
+            Value::Array(ns) => MainStuff::Int(Vdec::Array {
+                x: x.clone(),
+                c: Con(ns.len() as i32),
+            }),
+            Value::Stack(_) => MainStuff::Stack(x.clone()),
+        })
+        .collect();
+    let q = PId(q.to_string(), Span::default());
+    let s = match direction {
+        Direction::Forward => Stm::Call {
+            q,
+            xs: xs.clone(),
+            span: Span::default(),
+        },
+        Direction::Backward => Stm::Uncall {
+            q,
+            xs: xs.clone(),
+            span: Span::default(),
+        },
+    };
+    let harness = Prog {
+        p_main: Proc::Main {
+            span: Span::default(),
+            main_stuff,
+            s,
+        },
+        ps: prog.ps.clone(),
+    };
+    let program = codegen::compile(&harness)?;
+    let mut emulator = Emulator::new(&program);
+    emulator.limit_steps(max_steps);
+    for (x, value) in xs.iter().zip(input) {
+        emulator.set(&x.0, value)?;
+    }
+    emulator.run(Direction::Forward)?;
+    Ok(emulator
+        .store()
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect())
+}
+
+fn mnemonic(op: Op) -> &'static str {
+    match op {
+        Op::Add => "ADDX",
+        Op::Sub => "SUBX",
+        Op::Xor => "XORX",
+        Op::Mul => "MULX",
+        Op::Div => "DIVX",
+        Op::Mod => "MODX",
+        Op::And => "ANDX",
+        Op::Or => "ORX",
+        Op::And2 => "LANDX",
+        Op::Or2 => "LORX",
+        Op::Less => "SLTX",
+        Op::Greater => "SGTX",
+        Op::Equal => "SEQX",
+        Op::NotEqual => "SNEX",
+        Op::LessEqual => "SLEX",
+        Op::GreaterEqual => "SGEX",
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Add(rd, rs) => write!(f, "ADD r{rd}, r{rs}"),
+            Instr::Sub(rd, rs) => write!(f, "SUB r{rd}, r{rs}"),
+            Instr::Xor(rd, rs) => write!(f, "XOR r{rd}, r{rs}"),
+            Instr::Addi(rd, n) => write!(f, "ADDI r{rd}, {n}"),
+            Instr::Xori(rd, n) => write!(f, "XORI r{rd}, {n}"),
+            Instr::Neg(rd) => write!(f, "NEG r{rd}"),
+            Instr::OpX(op, rd, rs, rt) => write!(f, "{} r{rd}, r{rs}, r{rt}", mnemonic(*op)),
+            Instr::Exch(rd, ra) => write!(f, "EXCH r{rd}, r{ra}"),
+            Instr::Bra(offset) => write!(f, "BRA {offset}"),
+            Instr::Rbra(offset) => write!(f, "RBRA {offset}"),
+            Instr::Beq(ra, rb, offset) => write!(f, "BEQ r{ra}, r{rb}, {offset}"),
+            Instr::Bne(ra, rb, offset) => write!(f, "BNE r{ra}, r{rb}, {offset}"),
+            Instr::Swapbr(rd) => write!(f, "SWAPBR r{rd}"),
+            Instr::Check(rd, message) => write!(f, "CHECK r{rd}, {message}"),
+            Instr::Start => write!(f, "START"),
+            Instr::Finish => write!(f, "FINISH"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub code: Vec<Instr>,
+    pub labels: BTreeMap<usize, String>,
+    pub globals: Vec<(String, Kind, usize)>,
+    pub messages: Vec<String>,
+    pub heap: usize,
+    pub finish: usize,
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (pc, instr) in self.code.iter().enumerate() {
+            if let Some(label) = self.labels.get(&pc) {
+                writeln!(f, "{label}:")?;
+            }
+            if let Instr::Check(rd, message) = instr {
+                writeln!(f, "    CHECK r{rd}, {:?}", self.messages[*message])?;
+                continue;
+            }
+            let Some(label) = instr.target(pc).and_then(|target| self.labels.get(&target)) else {
+                writeln!(f, "    {instr}")?;
+                continue;
+            };
+            match instr {
+                Instr::Bra(_) => writeln!(f, "    BRA {label}")?,
+                Instr::Rbra(_) => writeln!(f, "    RBRA {label}")?,
+                Instr::Beq(ra, rb, _) => writeln!(f, "    BEQ r{ra}, r{rb}, {label}")?,
+                Instr::Bne(ra, rb, _) => writeln!(f, "    BNE r{ra}, r{rb}, {label}")?,
+                _ => writeln!(f, "    {instr}")?,
+            }
+        }
+        Ok(())
+    }
+}
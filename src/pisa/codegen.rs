@@ -0,0 +1,689 @@
+use super::{Instr, Program, Reg, REGISTERS, RO, SP, STACK_CAPACITY, ZERO};
+use crate::{
+    ast::ast_node::*,
+    backend::{array_params, Kind},
+    tokenizer::span::Span,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, LinkedList};
+
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Global(usize),
+    Direct(usize),
+    Indirect(usize),
+}
+
+pub fn compile(prog: &Prog) -> Result<Program> {
+    let Proc::Main { main_stuff, s, .. } = &prog.p_main else {
+        bail!("main must be the first procedure");
+    };
+
+    let mut globals = Vec::new();
+    let mut heap = 0;
+    for stuff in main_stuff {
+        let (x, kind, size) = match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => (x, Kind::Scalar, 1),
+            MainStuff::Int(Vdec::Array { x, c }) => {
+                let len = usize::try_from(c.0)
+                    .ok()
+                    .filter(|&len| len > 0)
+                    .ok_or_else(|| anyhow!("{}: invalid array size", x.1))?;
+                (x, Kind::Array(Some(len)), len)
+            }
+            MainStuff::Stack(x) => (x, Kind::Stack, 1 + STACK_CAPACITY),
+        };
+        if globals.iter().any(|(y, _, _)| y == &x.0) {
+            bail!("{}: {} is declared twice", x.1, x.0);
+        }
+        globals.push((x.0.clone(), kind, heap));
+        heap += size;
+    }
+
+    let mut compiler = Compiler {
+        prog,
+        arrays: array_params(prog),
+        scope: globals
+            .iter()
+            .map(|(x, kind, address)| (x.clone(), *kind, Location::Global(*address)))
+            .collect(),
+        depth: 0,
+        next: RO + 1,
+        calls: Vec::new(),
+        proc: "main".to_string(),
+        messages: Vec::new(),
+    };
+
+    let mut code = vec![Instr::Start];
+    compiler.stm(s, &mut code)?;
+    let finish = code.len();
+    code.push(Instr::Finish);
+
+    let mut labels = BTreeMap::new();
+    let mut entries = BTreeMap::new();
+    for p in &prog.ps {
+        if let Proc::Other { q, args, s } = p {
+            if entries.contains_key(q.0.as_str()) {
+                bail!("procedure {} is defined twice", q.0);
+            }
+            let (top, entry, bot) = compiler.proc(&q.0, args, s, &mut code)?;
+            labels.insert(top, format!("{}_top", q.0));
+            labels.insert(entry, q.0.clone());
+            labels.insert(bot, format!("{}_bot", q.0));
+            entries.insert(q.0.as_str(), entry);
+        }
+    }
+
+    for (pc, q) in compiler.calls {
+        let entry = *entries
+            .get(q.as_str())
+            .ok_or_else(|| anyhow!("procedure {q} is not defined"))?;
+        let offset = entry as isize - pc as isize;
+        code[pc] = match code[pc] {
+            Instr::Rbra(_) => Instr::Rbra(offset),
+            _ => Instr::Bra(offset),
+        };
+    }
+    for (pc, instr) in code.iter().enumerate() {
+        if let Some(target) = instr.target(pc) {
+            labels.entry(target).or_insert_with(|| format!("L{target}"));
+        }
+    }
+
+    Ok(Program {
+        code,
+        labels,
+        globals,
+        messages: compiler.messages,
+        heap,
+        finish,
+    })
+}
+
+fn undo(code: &mut Vec<Instr>, p: &[Instr]) {
+    code.extend(p.iter().rev().map(|instr| instr.inverse()));
+}
+
+struct Compiler<'a> {
+    prog: &'a Prog,
+    arrays: BTreeMap<&'a str, Vec<bool>>,
+    scope: Vec<(String, Kind, Location)>,
+    depth: usize,
+    next: Reg,
+    calls: Vec<(usize, String)>,
+    proc: String,
+    messages: Vec<String>,
+}
+
+impl Compiler<'_> {
+    fn alloc(&mut self) -> Result<Reg> {
+        if self.next == REGISTERS {
+            bail!("expression needs more than {REGISTERS} registers");
+        }
+        self.next += 1;
+        Ok(self.next - 1)
+    }
+
+    fn release(&mut self, n: usize) {
+        self.next -= n;
+    }
+
+    fn lookup(&self, x: &Var) -> Result<(Kind, Location)> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(y, _, _)| y == &x.0)
+            .map(|&(_, kind, location)| (kind, location))
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn offset(&self, cell: usize) -> i32 {
+        -((self.depth - cell) as i32)
+    }
+
+    fn proc(
+        &mut self,
+        q: &str,
+        args: &LinkedList<Arg>,
+        s: &Stm,
+        code: &mut Vec<Instr>,
+    ) -> Result<(usize, usize, usize)> {
+        let top = code.len();
+        code.push(Instr::Bra(0));
+        code.push(Instr::Addi(SP, -1));
+        code.push(Instr::Exch(RO, SP));
+        let entry = code.len();
+        code.push(Instr::Swapbr(RO));
+        code.push(Instr::Neg(RO));
+        code.push(Instr::Exch(RO, SP));
+        code.push(Instr::Addi(SP, 1));
+
+        let arrays = &self.arrays[q];
+        let mut cells = 0;
+        self.scope = args
+            .iter()
+            .zip(arrays)
+            .map(|(arg, &array)| {
+                let kind = match arg.t {
+                    Type::Int if array => Kind::Array(None),
+                    Type::Int => Kind::Scalar,
+                    Type::Stack => Kind::Stack,
+                };
+                cells += 1 + array as usize;
+                (
+                    arg.x.0.clone(),
+                    kind,
+                    Location::Indirect(cells - 1 - array as usize),
+                )
+            })
+            .collect();
+        self.depth = cells + 1;
+        self.proc = q.to_string();
+        self.stm(s, code)?;
+
+        let bot = code.len();
+        code.push(Instr::Bra(top as isize - bot as isize));
+        code[top] = Instr::Bra(bot as isize - top as isize);
+        Ok((top, entry, bot))
+    }
+
+    fn address(&mut self, x: &Var, ra: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        match self.lookup(x)?.1 {
+            Location::Global(address) => code.push(Instr::Xori(ra, address as i32)),
+            Location::Direct(cell) => {
+                code.push(Instr::Xor(ra, SP));
+                code.push(Instr::Addi(ra, self.offset(cell)));
+            }
+            Location::Indirect(cell) => {
+                let (rb, rt) = (self.alloc()?, self.alloc()?);
+                let p = [Instr::Xor(rb, SP), Instr::Addi(rb, self.offset(cell))];
+                code.extend(p);
+                self.copy(ra, rb, rt, code);
+                undo(code, &p);
+                self.release(2);
+            }
+        }
+        Ok(())
+    }
+
+    fn length(&mut self, x: &Var, rl: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        match self.lookup(x)? {
+            (Kind::Array(Some(len)), _) => code.push(Instr::Xori(rl, len as i32)),
+            (Kind::Array(None), Location::Indirect(cell)) => {
+                let (rb, rt) = (self.alloc()?, self.alloc()?);
+                let p = [Instr::Xor(rb, SP), Instr::Addi(rb, self.offset(cell + 1))];
+                code.extend(p);
+                self.copy(rl, rb, rt, code);
+                undo(code, &p);
+                self.release(2);
+            }
+            _ => bail!("{}: {} is not an array", x.1, x.0),
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, rc: Reg, message: String, code: &mut Vec<Instr>) {
+        self.messages.push(format!("{}: {message}", self.proc));
+        code.push(Instr::Check(rc, self.messages.len() - 1));
+    }
+
+    fn expect_true(&mut self, rc: Reg, message: String, code: &mut Vec<Instr>) {
+        code.push(Instr::Xori(rc, 1));
+        self.check(rc, message, code);
+        code.push(Instr::Xori(rc, 1));
+    }
+
+    fn check_index(&mut self, x: &Var, ri: Reg, span: Span, code: &mut Vec<Instr>) -> Result<()> {
+        let (rl, rb, rn, rc) = (self.alloc()?, self.alloc()?, self.alloc()?, self.alloc()?);
+        let mut p = Vec::new();
+        self.length(x, rl, &mut p)?;
+        p.push(Instr::OpX(Op::GreaterEqual, rb, ri, rl));
+        p.push(Instr::OpX(Op::Less, rn, ri, ZERO));
+        p.push(Instr::OpX(Op::Or, rc, rb, rn));
+        code.extend(&p);
+        self.check(rc, format!("index out of bounds at {span}"), code);
+        undo(code, &p);
+        self.release(4);
+        Ok(())
+    }
+
+    fn check_stack(
+        &mut self,
+        xs: &Var,
+        len: usize,
+        message: String,
+        code: &mut Vec<Instr>,
+    ) -> Result<()> {
+        let (ra, rl, rt, rn, rc) = (
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+        );
+        let mut p = Vec::new();
+        self.address(xs, ra, &mut p)?;
+        self.copy(rl, ra, rt, &mut p);
+        p.push(Instr::Xori(rn, len as i32));
+        p.push(Instr::OpX(Op::Equal, rc, rl, rn));
+        code.extend(&p);
+        self.check(rc, message, code);
+        undo(code, &p);
+        self.release(5);
+        Ok(())
+    }
+
+    fn copy(&self, rd: Reg, ra: Reg, rt: Reg, code: &mut Vec<Instr>) {
+        code.push(Instr::Exch(rt, ra));
+        code.push(Instr::Xor(rd, rt));
+        code.push(Instr::Exch(rt, ra));
+    }
+
+    fn expect(&self, x: &Var, kind: Kind) -> Result<()> {
+        match (self.lookup(x)?.0, kind) {
+            (Kind::Scalar, Kind::Scalar)
+            | (Kind::Array(_), Kind::Array(_))
+            | (Kind::Stack, Kind::Stack) => Ok(()),
+            (_, Kind::Scalar) => bail!("{}: {} is not a scalar", x.1, x.0),
+            (_, Kind::Array(_)) => bail!("{}: {} is not an array", x.1, x.0),
+            (_, Kind::Stack) => bail!("{}: {} is not a stack", x.1, x.0),
+        }
+    }
+
+    fn stm(&mut self, s: &Stm, code: &mut Vec<Instr>) -> Result<()> {
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                self.expect(x, Kind::Scalar)?;
+                let (te, ra, rv) = (self.alloc()?, self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                self.exp(e, te, &mut p)?;
+                self.address(x, ra, &mut p)?;
+                code.extend(&p);
+                self.update(*mod_op, ra, rv, te, code);
+                undo(code, &p);
+                self.release(3);
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                self.expect(x, Kind::Array(None))?;
+                let (te, ra, ri, rv) = (self.alloc()?, self.alloc()?, self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                self.exp(e, te, &mut p)?;
+                self.address(x, ra, &mut p)?;
+                self.exp(e_index, ri, &mut p)?;
+                self.check_index(x, ri, *span, &mut p)?;
+                p.push(Instr::Add(ra, ri));
+                code.extend(&p);
+                self.update(*mod_op, ra, rv, te, code);
+                undo(code, &p);
+                self.release(4);
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let rt = self.alloc()?;
+                self.check(rt, format!("assertion if failed at {span}"), code);
+                self.condition(e_if, rt, code)?;
+                let test = code.len();
+                code.push(Instr::Beq(rt, ZERO, 0));
+                code.push(Instr::Xori(rt, 1));
+                self.stm(s_then, code)?;
+                code.push(Instr::Xori(rt, 1));
+                let assert_t = code.len();
+                code.push(Instr::Bra(0));
+                let test_f = code.len();
+                code.push(Instr::Bra(test as isize - test_f as isize));
+                code[test] = Instr::Beq(rt, ZERO, test_f as isize - test as isize);
+                self.stm(s_else, code)?;
+                let assert = code.len();
+                code.push(Instr::Bne(rt, ZERO, assert_t as isize - assert as isize));
+                code[assert_t] = Instr::Bra(assert as isize - assert_t as isize);
+                self.condition(e_fi, rt, code)?;
+                self.check(rt, format!("assertion fi failed at {span}"), code);
+                self.release(1);
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let rt = self.alloc()?;
+                self.condition(e_from, rt, code)?;
+                self.expect_true(rt, format!("assertion from failed at {span}"), code);
+                let entry = code.len();
+                code.push(Instr::Beq(rt, ZERO, 0));
+                self.condition(e_from, rt, code)?;
+                self.stm(s_do, code)?;
+                let message = format!("assertion until failed on re-entry at {span}");
+                self.check(rt, message, code);
+                self.condition(e_until, rt, code)?;
+                let test = code.len();
+                code.push(Instr::Bne(rt, ZERO, 0));
+                self.stm(s_loop, code)?;
+                self.condition(e_from, rt, code)?;
+                let message = format!("assertion from failed on re-entry at {span}");
+                self.check(rt, message, code);
+                let back = code.len();
+                code.push(Instr::Bra(entry as isize - back as isize));
+                let exit = code.len();
+                code.push(Instr::Bra(test as isize - exit as isize));
+                code[entry] = Instr::Beq(rt, ZERO, back as isize - entry as isize);
+                code[test] = Instr::Bne(rt, ZERO, exit as isize - test as isize);
+                self.expect_true(rt, format!("assertion until failed at {span}"), code);
+                self.condition(e_until, rt, code)?;
+                self.release(1);
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                self.expect(x, Kind::Scalar)?;
+                self.expect(xs, Kind::Stack)?;
+                let mut full = Vec::new();
+                let message = format!("push onto a full stack at {span}");
+                self.check_stack(xs, STACK_CAPACITY, message, &mut full)?;
+                let mut empty = Vec::new();
+                let message = format!("pop from an empty stack at {span}");
+                self.check_stack(xs, 0, message, &mut empty)?;
+                let mut p = Vec::new();
+                let message = format!("{} must be zero before a pop at {span}", x.0);
+                self.push(x, xs, message, &mut p)?;
+                match s {
+                    Stm::Push(..) => {
+                        code.extend(full);
+                        code.extend(p);
+                        code.extend(empty);
+                    }
+                    _ => {
+                        code.extend(empty);
+                        undo(code, &p);
+                        code.extend(full);
+                    }
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                if e_delocal.mentions(x_local) {
+                    bail!("{span}: delocal {} mentions itself", x_local.0);
+                }
+                let (kind, size) = match t_local {
+                    Type::Int => (Kind::Scalar, 1),
+                    Type::Stack => {
+                        if !matches!(e_local, Exp::Nil) || !matches!(e_delocal, Exp::Nil) {
+                            bail!("{span}: stack locals must be initialised with nil");
+                        }
+                        (Kind::Stack, 1 + STACK_CAPACITY)
+                    }
+                };
+
+                let rv = self.alloc()?;
+                let message = format!("assertion local {} failed at {span}", x_local.0);
+                self.check(rv, message, code);
+                let mut p = Vec::new();
+                if kind == Kind::Scalar {
+                    self.initial(e_local, rv, &mut p)?;
+                }
+                p.push(Instr::Exch(rv, SP));
+                p.push(Instr::Addi(SP, size as i32));
+                code.extend(p);
+                self.release(1);
+
+                self.scope
+                    .push((x_local.0.clone(), kind, Location::Direct(self.depth)));
+                self.depth += size;
+                self.stm(s, code)?;
+                self.depth -= size;
+                self.scope.pop();
+
+                let rv = self.alloc()?;
+                let mut p = Vec::new();
+                if kind == Kind::Scalar {
+                    self.initial(e_delocal, rv, &mut p)?;
+                }
+                p.push(Instr::Exch(rv, SP));
+                p.push(Instr::Addi(SP, size as i32));
+                undo(code, &p);
+                let message = format!("assertion delocal {} failed at {span}", x_local.0);
+                self.check(rv, message, code);
+                self.release(1);
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, .. }) = self.prog.ps.iter().find(|p| p.name() == q.0)
+                else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                if args.len() != xs.len() {
+                    bail!("{span}: {} expects {} arguments", q.0, args.len());
+                }
+                for (i, (x, arg)) in xs.iter().zip(args).enumerate() {
+                    if xs.iter().skip(i + 1).any(|y| y.0 == x.0) {
+                        bail!("{span}: {} is passed more than once", x.0);
+                    }
+                    match (self.lookup(x)?.0, arg.t) {
+                        (Kind::Stack, Type::Int) => {
+                            bail!("{span}: {} expects an integer found a stack", q.0)
+                        }
+                        (Kind::Scalar | Kind::Array(_), Type::Stack) => {
+                            bail!("{span}: {} expects a stack found an integer", q.0)
+                        }
+                        _ => {}
+                    }
+                }
+
+                let (ra, rc) = (self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                let mut cells = 0;
+                let arrays = self.arrays[q.0.as_str()].clone();
+                for (x, array) in xs.iter().zip(arrays) {
+                    self.address(x, ra, &mut p)?;
+                    let cell = [Instr::Xor(rc, SP), Instr::Addi(rc, cells)];
+                    p.extend(cell);
+                    p.push(Instr::Exch(ra, rc));
+                    undo(&mut p, &cell);
+                    cells += 1;
+                    if array {
+                        self.length(x, ra, &mut p)?;
+                        let cell = [Instr::Xor(rc, SP), Instr::Addi(rc, cells)];
+                        p.extend(cell);
+                        p.push(Instr::Exch(ra, rc));
+                        undo(&mut p, &cell);
+                        cells += 1;
+                    }
+                }
+                self.release(2);
+
+                code.extend(&p);
+                code.push(Instr::Addi(SP, cells));
+                self.calls.push((code.len(), q.0.clone()));
+                code.push(match s {
+                    Stm::Call { .. } => Instr::Bra(0),
+                    _ => Instr::Rbra(0),
+                });
+                code.push(Instr::Addi(SP, -cells));
+                undo(code, &p);
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => {
+                self.stm(s_1, code)?;
+                self.stm(s_2, code)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&self, mod_op: ModOp, ra: Reg, rv: Reg, te: Reg, code: &mut Vec<Instr>) {
+        code.push(Instr::Exch(rv, ra));
+        code.push(match mod_op {
+            ModOp::Add => Instr::Add(rv, te),
+            ModOp::Sub => Instr::Sub(rv, te),
+            ModOp::Xor => Instr::Xor(rv, te),
+        });
+        code.push(Instr::Exch(rv, ra));
+    }
+
+    fn initial(&mut self, e: &Exp, rv: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        let te = self.alloc()?;
+        let mut p = Vec::new();
+        self.exp(e, te, &mut p)?;
+        code.extend(&p);
+        code.push(Instr::Xor(rv, te));
+        undo(code, &p);
+        self.release(1);
+        Ok(())
+    }
+
+    fn push(&mut self, x: &Var, xs: &Var, message: String, code: &mut Vec<Instr>) -> Result<()> {
+        let (rx, rv, rs, rl, rp, rt) = (
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+            self.alloc()?,
+        );
+        let mut px = Vec::new();
+        self.address(x, rx, &mut px)?;
+        let mut ps = Vec::new();
+        self.address(xs, rs, &mut ps)?;
+        self.copy(rl, rs, rt, &mut ps);
+        let pp = [Instr::Xor(rp, rs), Instr::Add(rp, rl), Instr::Addi(rp, 1)];
+
+        code.extend(&px);
+        self.check(rv, message, code);
+        code.push(Instr::Exch(rv, rx));
+        code.extend(&ps);
+        code.extend(pp);
+        code.push(Instr::Exch(rv, rp));
+        undo(code, &pp);
+        self.copy(rl, rs, rt, code);
+        code.push(Instr::Exch(rl, rs));
+        code.push(Instr::Addi(rl, 1));
+        code.push(Instr::Exch(rl, rs));
+        undo(code, &ps[..ps.len() - 3]);
+        undo(code, &px);
+        self.release(6);
+        Ok(())
+    }
+
+    fn condition(&mut self, e: &Exp, rt: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        let r = self.alloc()?;
+        let mut p = Vec::new();
+        self.exp(e, r, &mut p)?;
+        code.extend(&p);
+        code.push(Instr::OpX(Op::NotEqual, rt, r, ZERO));
+        undo(code, &p);
+        self.release(1);
+        Ok(())
+    }
+
+    fn empty(&mut self, x: &Var, t: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        self.expect(x, Kind::Stack)?;
+        let (ra, rl, rt) = (self.alloc()?, self.alloc()?, self.alloc()?);
+        let mut p = Vec::new();
+        self.address(x, ra, &mut p)?;
+        self.copy(rl, ra, rt, &mut p);
+        code.extend(&p);
+        code.push(Instr::OpX(Op::Equal, t, rl, ZERO));
+        undo(code, &p);
+        self.release(3);
+        Ok(())
+    }
+
+    fn exp(&mut self, e: &Exp, t: Reg, code: &mut Vec<Instr>) -> Result<()> {
+        match e {
+            Exp::Constant(c) => code.push(Instr::Xori(t, c.0)),
+            Exp::Variable(x) => {
+                self.expect(x, Kind::Scalar)?;
+                let (ra, rt) = (self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                self.address(x, ra, &mut p)?;
+                code.extend(&p);
+                self.copy(t, ra, rt, code);
+                undo(code, &p);
+                self.release(2);
+            }
+            Exp::Indexed { x, e } => {
+                self.expect(x, Kind::Array(None))?;
+                let (ra, ri, rt) = (self.alloc()?, self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                self.address(x, ra, &mut p)?;
+                self.exp(e, ri, &mut p)?;
+                self.check_index(x, ri, x.1, &mut p)?;
+                p.push(Instr::Add(ra, ri));
+                code.extend(&p);
+                self.copy(t, ra, rt, code);
+                undo(code, &p);
+                self.release(3);
+            }
+            Exp::BinOp(e_1, op @ (Op::Equal | Op::NotEqual), e_2)
+                if matches!(**e_1, Exp::Nil) || matches!(**e_2, Exp::Nil) =>
+            {
+                match (&**e_1, &**e_2) {
+                    (Exp::Nil, Exp::Nil) => {}
+                    (Exp::Variable(x), Exp::Nil) | (Exp::Nil, Exp::Variable(x)) => {
+                        self.empty(x, t, code)?;
+                    }
+                    _ => bail!("stacks can only be compared with nil"),
+                }
+                if *op == Op::NotEqual {
+                    code.push(Instr::Xori(t, 1));
+                }
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (t_1, t_2) = (self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                self.exp(e_1, t_1, &mut p)?;
+                self.exp(e_2, t_2, &mut p)?;
+                code.extend(&p);
+                code.push(Instr::OpX(*op, t, t_1, t_2));
+                undo(code, &p);
+                self.release(2);
+            }
+            Exp::Empty(x) => self.empty(x, t, code)?,
+            Exp::Top(x) => {
+                self.expect(x, Kind::Stack)?;
+                let (ra, rl, rt) = (self.alloc()?, self.alloc()?, self.alloc()?);
+                let mut p = Vec::new();
+                let message = format!("top of an empty stack at {}", x.1);
+                self.check_stack(x, 0, message, &mut p)?;
+                self.address(x, ra, &mut p)?;
+                self.copy(rl, ra, rt, &mut p);
+                p.push(Instr::Add(ra, rl));
+                code.extend(&p);
+                self.copy(t, ra, rt, code);
+                undo(code, &p);
+                self.release(3);
+            }
+            Exp::Nil => bail!("nil is not an integer"),
+        }
+        Ok(())
+    }
+}
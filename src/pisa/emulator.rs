@@ -0,0 +1,192 @@
+use super::{Instr, Program, MEMORY, REGISTERS, SP, STACK_CAPACITY};
+use crate::{
+    backend::Kind,
+    interpreter::{eval_op, value::Value, Direction},
+};
+use anyhow::{anyhow, bail, Result};
+
+pub struct Emulator<'a> {
+    program: &'a Program,
+    registers: [i32; REGISTERS],
+    memory: Vec<i32>,
+    pc: usize,
+    br: isize,
+    dir: isize,
+    steps: u64,
+    max_steps: Option<u64>,
+}
+
+impl<'a> Emulator<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        let mut registers = [0; REGISTERS];
+        registers[SP] = program.heap as i32;
+        Self {
+            program,
+            registers,
+            memory: vec![0; MEMORY],
+            pc: 0,
+            br: 0,
+            dir: 1,
+            steps: 0,
+            max_steps: None,
+        }
+    }
+
+    pub fn limit_steps(&mut self, max_steps: u64) {
+        self.max_steps = Some(self.steps + max_steps);
+    }
+
+    pub fn run(&mut self, direction: Direction) -> Result<()> {
+        let (start, end) = match direction {
+            Direction::Forward => (1, self.program.finish),
+            Direction::Backward => (self.program.finish - 1, 0),
+        };
+        self.pc = start;
+        self.dir = match direction {
+            Direction::Forward => 1,
+            Direction::Backward => -1,
+        };
+        while self.pc != end {
+            self.steps += 1;
+            if self
+                .max_steps
+                .is_some_and(|max_steps| self.steps > max_steps)
+            {
+                bail!("step limit exceeded at pc {}", self.pc);
+            }
+            self.step()?;
+        }
+
+        for (r, &n) in self.registers.iter().enumerate() {
+            let expected = match r {
+                SP => self.program.heap as i32,
+                _ => 0,
+            };
+            if n != expected {
+                bail!(
+                    "r{r} holds {n} after running {}: an assertion failed",
+                    direction.name()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn store(&self) -> Vec<(String, Value)> {
+        self.program
+            .globals
+            .iter()
+            .map(|(x, kind, address)| {
+                let value = match kind {
+                    Kind::Scalar => Value::Int(self.memory[*address]),
+                    Kind::Array(len) => {
+                        let len = len.unwrap_or_default();
+                        Value::Array(self.memory[*address..address + len].to_vec())
+                    }
+                    Kind::Stack => {
+                        let len = self.memory[*address] as usize;
+                        Value::Stack(self.memory[address + 1..address + 1 + len].to_vec())
+                    }
+                };
+                (x.clone(), value)
+            })
+            .collect()
+    }
+
+    pub fn set(&mut self, x: &str, value: &Value) -> Result<()> {
+        let &(_, kind, address) = self
+            .program
+            .globals
+            .iter()
+            .find(|(y, _, _)| y == x)
+            .ok_or_else(|| anyhow!("{x} is not a global"))?;
+        match (kind, value) {
+            (Kind::Scalar, Value::Int(n)) => self.memory[address] = *n,
+            (Kind::Array(Some(len)), Value::Array(ns)) if ns.len() == len => {
+                self.memory[address..address + len].copy_from_slice(ns);
+            }
+            (Kind::Stack, Value::Stack(ns)) if ns.len() <= STACK_CAPACITY => {
+                self.memory[address] = ns.len() as i32;
+                self.memory[address + 1..address + 1 + ns.len()].copy_from_slice(ns);
+            }
+            _ => bail!("{x} cannot hold {value}"),
+        }
+        Ok(())
+    }
+
+    fn cell(&mut self, pc: usize, ra: usize) -> Result<&mut i32> {
+        let address = self.registers[ra];
+        usize::try_from(address)
+            .ok()
+            .and_then(|address| self.memory.get_mut(address))
+            .ok_or_else(|| anyhow!("{pc}: address {address} is out of bounds"))
+    }
+
+    fn step(&mut self) -> Result<()> {
+        let pc = self.pc;
+        let instr = *self
+            .program
+            .code
+            .get(pc)
+            .ok_or_else(|| anyhow!("{pc}: jumped outside the program"))?;
+        let instr = match self.dir {
+            1 => instr,
+            _ => instr.inverse(),
+        };
+        let landing = self.br != 0;
+        let r = &mut self.registers;
+
+        match instr {
+            Instr::Add(rd, rs) => r[rd] = r[rd].wrapping_add(r[rs]),
+            Instr::Sub(rd, rs) => r[rd] = r[rd].wrapping_sub(r[rs]),
+            Instr::Xor(rd, rs) => r[rd] ^= r[rs],
+            Instr::Addi(rd, n) => r[rd] = r[rd].wrapping_add(n),
+            Instr::Xori(rd, n) => r[rd] ^= n,
+            Instr::Neg(rd) => r[rd] = r[rd].wrapping_neg(),
+            Instr::OpX(op, rd, rs, rt) => {
+                r[rd] ^= eval_op(&op, r[rs], r[rt]).map_err(|e| anyhow!("{pc}: {e}"))?
+            }
+            Instr::Exch(rd, ra) => {
+                let n = self.registers[rd];
+                let cell = self.cell(pc, ra)?;
+                let old = std::mem::replace(cell, n);
+                self.registers[rd] = old;
+            }
+            Instr::Bra(offset) => self.br += offset,
+            Instr::Rbra(offset) => {
+                self.br += offset;
+                self.dir = -self.dir;
+            }
+            Instr::Beq(ra, rb, offset) => {
+                if r[ra] == r[rb] {
+                    self.br += offset;
+                }
+            }
+            Instr::Bne(ra, rb, offset) => {
+                if r[ra] != r[rb] {
+                    self.br += offset;
+                }
+            }
+            Instr::Swapbr(rd) => {
+                let br = self.br;
+                self.br = r[rd] as isize;
+                r[rd] = br as i32;
+            }
+            Instr::Check(rd, message) => {
+                if r[rd] != 0 {
+                    bail!("{pc}: {}", self.program.messages[message]);
+                }
+            }
+            Instr::Start | Instr::Finish => bail!("{pc}: {instr} reached from inside the program"),
+        }
+
+        if landing && self.br != 0 {
+            bail!("{pc}: jump does not land on its paired branch");
+        }
+        self.pc = match self.br {
+            0 => pc.wrapping_add_signed(self.dir),
+            br => pc.wrapping_add_signed(br),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,938 @@
+pub mod bits;
+pub mod equiv;
+pub mod sat;
+
+use crate::{
+    ast::ast_node::*,
+    backend::array_params,
+    circuit::wrap,
+    interpreter::{assertion, eval_op, update, Direction, MAX_DEPTH},
+};
+use anyhow::{anyhow, bail, Error, Result};
+use bits::{Blaster, FALSE, TRUE};
+use sat::Lit;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+pub const UNROLL: usize = 256;
+pub const MAX_UNROLL: usize = 100_000;
+pub const MAX_EXHAUSTIVE: usize = 20;
+pub const COLLISION_BUDGET: u64 = 2_000;
+const AUTO_EXHAUSTIVE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Random,
+    Exhaustive,
+    Symbolic,
+}
+
+impl Method {
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Random => "random",
+            Method::Exhaustive => "exhaustive",
+            Method::Symbolic => "symbolic",
+        }
+    }
+}
+
+pub type Assignment = Vec<(String, i32)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Injectivity {
+    Checked,
+    Reversibility,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "verdict", rename_all = "kebab-case")]
+pub enum Verdict {
+    Proved {
+        injectivity: Injectivity,
+    },
+    Fails {
+        input: Assignment,
+        error: String,
+        path: Vec<String>,
+    },
+    Collides {
+        inputs: [Assignment; 2],
+        output: Assignment,
+    },
+    Unknown {
+        input: Assignment,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub proc: String,
+    pub width: usize,
+    pub method: Method,
+    pub bits: usize,
+    pub clauses: usize,
+    pub verdict: Verdict,
+}
+
+pub fn prove(
+    prog: &Prog,
+    q: &str,
+    width: usize,
+    method: Option<Method>,
+    unroll: usize,
+) -> Result<Report> {
+    if !(1..=32).contains(&width) {
+        bail!("the width must be between 1 and 32");
+    }
+    let params = params(prog, q)?;
+    let bits = params.len() * width;
+    let method = method.unwrap_or(default_method(bits));
+    let prover = Prover {
+        prog,
+        q,
+        width,
+        unroll,
+        params,
+    };
+    let (verdict, clauses) = match method {
+        Method::Random => bail!("random testing cannot prove {q}; use exhaustive or symbolic"),
+        Method::Exhaustive => (prover.exhaustive()?, 0),
+        Method::Symbolic => prover.symbolic()?,
+    };
+    Ok(Report {
+        proc: q.to_string(),
+        width,
+        method,
+        bits,
+        clauses,
+        verdict,
+    })
+}
+
+fn params(prog: &Prog, q: &str) -> Result<Vec<String>> {
+    match prog.find(q) {
+        Some(Proc::Main { .. }) => Ok(Vec::new()),
+        Some(Proc::Other { q, args, .. }) => {
+            let arrays = &array_params(prog)[q.0.as_str()];
+            let mut params = Vec::new();
+            for (arg, &array) in args.iter().zip(arrays) {
+                if arg.t == Type::Stack || array {
+                    bail!("{}: only integer parameters can be proved", arg.x.1);
+                }
+                params.push(arg.x.0.clone());
+            }
+            Ok(params)
+        }
+        None => bail!("procedure {q} is not defined"),
+    }
+}
+
+fn default_method(bits: usize) -> Method {
+    match bits <= AUTO_EXHAUSTIVE {
+        true => Method::Exhaustive,
+        false => Method::Symbolic,
+    }
+}
+
+fn exhaustive_input(counter: u64, params: usize, width: usize) -> Vec<i32> {
+    let mask = (1u64 << width) - 1;
+    (0..params)
+        .map(|i| wrap((counter >> (i * width) & mask) as i32, width))
+        .collect()
+}
+
+fn check_exhaustive(q: &str, bits: usize) -> Result<()> {
+    if bits > MAX_EXHAUSTIVE {
+        bail!("{q} has {bits} input bits, too many to check exhaustively; use --method symbolic");
+    }
+    Ok(())
+}
+
+struct Prover<'a> {
+    prog: &'a Prog,
+    q: &'a str,
+    width: usize,
+    unroll: usize,
+    params: Vec<String>,
+}
+
+impl Prover<'_> {
+    fn executor(&self, record: bool) -> Executor<'_> {
+        Executor::new(self.prog, self.width, self.unroll, record)
+    }
+
+    fn assignment(&self, ns: &[i32]) -> Assignment {
+        self.params
+            .iter()
+            .cloned()
+            .zip(ns.iter().copied())
+            .collect()
+    }
+
+    fn exhaustive(&self) -> Result<Verdict> {
+        let bits = self.params.len() * self.width;
+        check_exhaustive(self.q, bits)?;
+        let mut executor = self.executor(false);
+        let mut seen = HashMap::new();
+        let (mut unknown, mut collision) = (None, None);
+        for counter in 0..1u64 << bits {
+            let input = exhaustive_input(counter, self.params.len(), self.width);
+            let words = input.iter().map(|&n| Word::Known(n)).collect::<Vec<_>>();
+            match executor.run(self.q, &words) {
+                Ok(cells) => {
+                    let output = cells
+                        .iter()
+                        .take(self.params.len())
+                        .map(|cell| match cell {
+                            Cell::Int(Word::Known(n)) => *n,
+                            _ => unreachable!("concrete runs only produce known integers"),
+                        })
+                        .collect::<Vec<_>>();
+                    if let Some(other) = seen.insert(output.clone(), input.clone()) {
+                        collision.get_or_insert(Verdict::Collides {
+                            inputs: [self.assignment(&other), self.assignment(&input)],
+                            output: self.assignment(&output),
+                        });
+                    }
+                }
+                Err(_) if executor.stop == Some(Stop::Incomplete) && unknown.is_some() => {}
+                Err(e) => match self.stopped(&executor, e, &input)? {
+                    verdict @ Verdict::Unknown { .. } => unknown = Some(verdict),
+                    verdict => return Ok(verdict),
+                },
+            }
+        }
+        Ok(unknown.or(collision).unwrap_or(Verdict::Proved {
+            injectivity: Injectivity::Checked,
+        }))
+    }
+
+    fn symbolic(&self) -> Result<(Verdict, usize)> {
+        let mut executor = self.executor(false);
+        let mut inputs = Vec::new();
+        for _ in &self.params {
+            let xs = (0..self.width)
+                .map(|_| executor.blaster.input())
+                .collect::<Vec<_>>();
+            inputs.push(xs);
+        }
+        let words = inputs.iter().cloned().map(Word::Bits).collect::<Vec<_>>();
+        let first = match executor.run(self.q, &words) {
+            Ok(cells) => cells,
+            Err(e) => {
+                let input = vec![0; self.params.len()];
+                return Ok((self.stopped(&executor, e, &input)?, executor.clauses()));
+            }
+        };
+
+        let failures = std::mem::take(&mut executor.failures);
+        let failed = executor.blaster.any(&failures);
+        if executor.blaster.solver.solve(&[failed]) {
+            let input = executor.model(&inputs);
+            return Ok((self.replay(&input)?, executor.clauses()));
+        }
+        for (reached, reason) in std::mem::take(&mut executor.incomplete) {
+            if executor.blaster.solver.solve(&[reached]) {
+                let input = executor.model(&inputs);
+                let verdict = Verdict::Unknown {
+                    input: self.assignment(&input),
+                    reason,
+                };
+                return Ok((verdict, executor.clauses()));
+            }
+        }
+        if self.params.is_empty() {
+            let verdict = Verdict::Proved {
+                injectivity: Injectivity::Checked,
+            };
+            return Ok((verdict, executor.clauses()));
+        }
+
+        let mut others = Vec::new();
+        for _ in &self.params {
+            let ys = (0..self.width)
+                .map(|_| executor.blaster.input())
+                .collect::<Vec<_>>();
+            others.push(ys);
+        }
+        let words = others.iter().cloned().map(Word::Bits).collect::<Vec<_>>();
+        let second = executor.run(self.q, &words)?;
+        let mut differences = Vec::new();
+        for (xs, ys) in inputs.iter().zip(&others) {
+            for (&x, &y) in xs.iter().zip(ys) {
+                differences.push(executor.blaster.xor(x, y));
+            }
+        }
+        let mut outputs = Vec::new();
+        let mut same = Vec::new();
+        for (cell_1, cell_2) in first.iter().zip(&second).take(self.params.len()) {
+            let (Cell::Int(word_1), Cell::Int(word_2)) = (cell_1, cell_2) else {
+                unreachable!("parameters are integers");
+            };
+            let (xs, ys) = (executor.bits(word_1), executor.bits(word_2));
+            same.push(executor.blaster.equal(&xs, &ys));
+            outputs.push(xs);
+        }
+        let differ = executor.blaster.any(&differences);
+        let same = executor.blaster.all(&same);
+        let collision = executor.blaster.and(differ, same);
+        let solver = &mut executor.blaster.solver;
+        let verdict = match solver.solve_within(&[collision], COLLISION_BUDGET) {
+            Some(true) => Verdict::Collides {
+                inputs: [
+                    self.assignment(&executor.model(&inputs)),
+                    self.assignment(&executor.model(&others)),
+                ],
+                output: self.assignment(&executor.model(&outputs)),
+            },
+            Some(false) => Verdict::Proved {
+                injectivity: Injectivity::Checked,
+            },
+            None => Verdict::Proved {
+                injectivity: Injectivity::Reversibility,
+            },
+        };
+        Ok((verdict, executor.clauses()))
+    }
+
+    fn stopped(&self, executor: &Executor, e: Error, input: &[i32]) -> Result<Verdict> {
+        match executor.stop {
+            Some(Stop::Failed) => self.replay(input),
+            Some(Stop::Incomplete) => Ok(Verdict::Unknown {
+                input: self.assignment(input),
+                reason: format!("{e:#}"),
+            }),
+            None => Err(e),
+        }
+    }
+
+    fn replay(&self, input: &[i32]) -> Result<Verdict> {
+        let mut executor = self.executor(true);
+        let words = input.iter().map(|&n| Word::Known(n)).collect::<Vec<_>>();
+        let e = match executor.run(self.q, &words) {
+            Ok(_) => bail!("{} does not fail on the input the solver found", self.q),
+            Err(e) => e,
+        };
+        match executor.stop {
+            Some(Stop::Failed) => Ok(Verdict::Fails {
+                input: self.assignment(input),
+                error: format!("{e:#}"),
+                path: executor.path,
+            }),
+            Some(Stop::Incomplete) => Ok(Verdict::Unknown {
+                input: self.assignment(input),
+                reason: format!("{e:#}"),
+            }),
+            None => Err(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Word {
+    Known(i32),
+    Bits(Vec<Lit>),
+}
+
+#[derive(Debug, Clone)]
+enum Cell {
+    Int(Word),
+    Array(Vec<Word>),
+    Stack(Vec<Word>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+    Failed,
+    Incomplete,
+}
+
+struct Executor<'a> {
+    prog: &'a Prog,
+    width: usize,
+    unroll: usize,
+    record: bool,
+    blaster: Blaster,
+    heap: Vec<Cell>,
+    frames: Vec<BTreeMap<String, usize>>,
+    guard: Lit,
+    abandoned: Lit,
+    failures: Vec<Lit>,
+    incomplete: Vec<(Lit, String)>,
+    stop: Option<Stop>,
+    path: Vec<String>,
+}
+
+impl<'a> Executor<'a> {
+    fn new(prog: &'a Prog, width: usize, unroll: usize, record: bool) -> Self {
+        Self {
+            prog,
+            width,
+            unroll,
+            record,
+            blaster: Blaster::default(),
+            heap: Vec::new(),
+            frames: Vec::new(),
+            guard: TRUE,
+            abandoned: FALSE,
+            failures: Vec::new(),
+            incomplete: Vec::new(),
+            stop: None,
+            path: Vec::new(),
+        }
+    }
+
+    fn clauses(&self) -> usize {
+        self.blaster.solver.clauses()
+    }
+
+    fn model(&self, words: &[Vec<Lit>]) -> Vec<i32> {
+        words.iter().map(|xs| self.blaster.model(xs)).collect()
+    }
+
+    fn run(&mut self, q: &str, input: &[Word]) -> Result<Vec<Cell>> {
+        self.heap.clear();
+        self.frames.clear();
+        self.guard = TRUE;
+        self.abandoned = FALSE;
+        self.failures.clear();
+        self.incomplete.clear();
+        self.stop = None;
+        self.path.clear();
+
+        let mut frame = BTreeMap::new();
+        let s = match self.prog.find(q) {
+            Some(Proc::Main { main_stuff, s, .. }) => {
+                for stuff in main_stuff {
+                    let (x, cell) = match stuff {
+                        MainStuff::Int(Vdec::Scalar(x)) => (x, Cell::Int(Word::Known(0))),
+                        MainStuff::Int(Vdec::Array { x, c }) => {
+                            let len = usize::try_from(c.0)
+                                .map_err(|_| anyhow!("{}: negative array size", x.1))?;
+                            (x, Cell::Array(vec![Word::Known(0); len]))
+                        }
+                        MainStuff::Stack(x) => (x, Cell::Stack(Vec::new())),
+                    };
+                    if frame.insert(x.0.clone(), self.heap.len()).is_some() {
+                        bail!("{}: {} is declared twice", x.1, x.0);
+                    }
+                    self.heap.push(cell);
+                }
+                s
+            }
+            Some(Proc::Other { args, s, .. }) => {
+                for (arg, word) in args.iter().zip(input) {
+                    if frame.insert(arg.x.0.clone(), self.heap.len()).is_some() {
+                        bail!("{}: parameter {} is declared twice", arg.x.1, arg.x.0);
+                    }
+                    self.heap.push(Cell::Int(word.clone()));
+                }
+                s
+            }
+            None => bail!("procedure {q} is not defined"),
+        };
+        self.frames.push(frame);
+        self.stm(s, Direction::Forward)?;
+        Ok(std::mem::take(&mut self.heap))
+    }
+
+    fn fail(&mut self, violated: Lit, message: impl FnOnce() -> String) -> Result<()> {
+        let reached = self.blaster.and(self.guard, violated);
+        let reached = self.blaster.and(reached, !self.abandoned);
+        match reached {
+            FALSE => Ok(()),
+            TRUE => {
+                self.stop = Some(Stop::Failed);
+                bail!(message())
+            }
+            _ => {
+                self.failures.push(reached);
+                Ok(())
+            }
+        }
+    }
+
+    fn give_up(&mut self, reason: impl FnOnce() -> String) -> Result<()> {
+        let reached = self.blaster.and(self.guard, !self.abandoned);
+        match reached {
+            FALSE => Ok(()),
+            TRUE => {
+                self.stop = Some(Stop::Incomplete);
+                bail!(reason())
+            }
+            _ => {
+                self.incomplete.push((reached, reason()));
+                self.abandoned = self.blaster.or(self.abandoned, reached);
+                Ok(())
+            }
+        }
+    }
+
+    fn log(&mut self, event: impl FnOnce() -> String) {
+        if self.record && self.guard == TRUE {
+            self.path.push(event());
+        }
+    }
+
+    fn location(&self, x: &Var) -> Result<usize> {
+        self.frames
+            .last()
+            .expect("no active frame")
+            .get(&x.0)
+            .copied()
+            .ok_or_else(|| anyhow!("{}: {} is not declared", x.1, x.0))
+    }
+
+    fn scalar(&self, x: &Var) -> Result<Word> {
+        match &self.heap[self.location(x)?] {
+            Cell::Int(word) => Ok(word.clone()),
+            _ => bail!("{}: {} is not an integer", x.1, x.0),
+        }
+    }
+
+    fn bits(&self, word: &Word) -> Vec<Lit> {
+        match word {
+            Word::Known(n) => self.blaster.word(*n, self.width),
+            Word::Bits(xs) => xs.clone(),
+        }
+    }
+
+    fn word(&self, xs: Vec<Lit>) -> Word {
+        match self.blaster.known(&xs) {
+            Some(n) => Word::Known(n),
+            None => Word::Bits(xs),
+        }
+    }
+
+    fn truth(&mut self, word: &Word) -> Lit {
+        match word {
+            Word::Known(0) => FALSE,
+            Word::Known(_) => TRUE,
+            Word::Bits(xs) => self.blaster.nonzero(xs),
+        }
+    }
+
+    fn boolean(&self, lit: Lit) -> Word {
+        let mut xs = vec![FALSE; self.width];
+        xs[0] = lit;
+        self.word(xs)
+    }
+
+    fn guarded(&mut self, new: Word, old: &Word) -> Word {
+        if self.guard == TRUE || new == *old {
+            return new;
+        }
+        let (xs, ys) = (self.bits(&new), self.bits(old));
+        let zs = self.blaster.select(self.guard, &xs, &ys);
+        self.word(zs)
+    }
+
+    fn update(&mut self, old: &Word, mod_op: ModOp, e: &Word, direction: Direction) -> Word {
+        let new = match (old, e) {
+            (Word::Known(n), Word::Known(m)) => {
+                Word::Known(wrap(update(*n, &mod_op, *m, direction), self.width))
+            }
+            _ => {
+                let (xs, ys) = (self.bits(old), self.bits(e));
+                let zs = match (mod_op, direction) {
+                    (ModOp::Xor, _) => self.blaster.bitwise(&xs, &ys, Blaster::xor),
+                    (ModOp::Add, Direction::Forward) | (ModOp::Sub, Direction::Backward) => {
+                        self.blaster.add(&xs, &ys)
+                    }
+                    _ => self.blaster.sub(&xs, &ys),
+                };
+                self.word(zs)
+            }
+        };
+        self.guarded(new, old)
+    }
+
+    fn binop(&mut self, op: Op, a: &Word, b: &Word) -> Word {
+        if let (Word::Known(n_1), Word::Known(n_2)) = (a, b) {
+            return Word::Known(wrap(eval_op(&op, *n_1, *n_2).unwrap_or(0), self.width));
+        }
+        let (xs, ys) = (self.bits(a), self.bits(b));
+        let zs = self.blaster.binop(op, &xs, &ys);
+        self.word(zs)
+    }
+
+    fn same(&mut self, a: &Cell, b: &Cell) -> Lit {
+        let mut pair = |a: &Word, b: &Word| match (a, b) {
+            (Word::Known(n_1), Word::Known(n_2)) => match n_1 == n_2 {
+                true => TRUE,
+                false => FALSE,
+            },
+            _ => {
+                let (xs, ys) = (self.bits(a), self.bits(b));
+                self.blaster.equal(&xs, &ys)
+            }
+        };
+        let lits = match (a, b) {
+            (Cell::Int(a), Cell::Int(b)) => vec![pair(a, b)],
+            (Cell::Array(xs), Cell::Array(ys)) | (Cell::Stack(xs), Cell::Stack(ys))
+                if xs.len() == ys.len() =>
+            {
+                xs.iter().zip(ys).map(|(a, b)| pair(a, b)).collect()
+            }
+            _ => vec![FALSE],
+        };
+        self.blaster.all(&lits)
+    }
+
+    fn int(&mut self, e: &Exp) -> Result<Word> {
+        match self.eval(e)? {
+            Cell::Int(word) => Ok(word),
+            _ => bail!("expected an integer found a {}", e),
+        }
+    }
+
+    fn condition(&mut self, e: &Exp) -> Result<Lit> {
+        let word = self.int(e)?;
+        Ok(self.truth(&word))
+    }
+
+    fn index(&mut self, x: &Var, e: &Exp, len: usize) -> Result<Option<usize>> {
+        let index = match self.int(e)? {
+            Word::Known(index) => index,
+            Word::Bits(_) => bail!("{}: array indexes must not depend on the input", x.1),
+        };
+        let found = usize::try_from(index).ok().filter(|&index| index < len);
+        if found.is_none() {
+            self.fail(TRUE, || format!("{}: index {index} is out of bounds", x.1))?;
+        }
+        Ok(found)
+    }
+
+    fn eval(&mut self, e: &Exp) -> Result<Cell> {
+        match e {
+            Exp::Constant(c) => Ok(Cell::Int(Word::Known(wrap(c.0, self.width)))),
+            Exp::Variable(x) => Ok(self.heap[self.location(x)?].clone()),
+            Exp::Indexed { x, e } => {
+                let Cell::Array(words) = &self.heap[self.location(x)?] else {
+                    bail!("{}: {} is not an array", x.1, x.0);
+                };
+                let len = words.len();
+                let word = match self.index(x, e, len)? {
+                    Some(index) => match &self.heap[self.location(x)?] {
+                        Cell::Array(words) => words[index].clone(),
+                        _ => unreachable!("checked above"),
+                    },
+                    None => Word::Known(0),
+                };
+                Ok(Cell::Int(word))
+            }
+            Exp::BinOp(e_1, op, e_2) => {
+                let (v_1, v_2) = (self.eval(e_1)?, self.eval(e_2)?);
+                if let Op::Equal | Op::NotEqual = op {
+                    let same = self.same(&v_1, &v_2);
+                    return Ok(Cell::Int(self.boolean(match op {
+                        Op::Equal => same,
+                        _ => !same,
+                    })));
+                }
+                let (Cell::Int(a), Cell::Int(b)) = (v_1, v_2) else {
+                    bail!("expected integers in {e}");
+                };
+                if let Op::Div | Op::Mod = op {
+                    let zero = !self.truth(&b);
+                    self.fail(zero, || "division by zero".to_string())?;
+                }
+                Ok(Cell::Int(self.binop(*op, &a, &b)))
+            }
+            Exp::Empty(x) => match &self.heap[self.location(x)?] {
+                Cell::Stack(words) => Ok(Cell::Int(Word::Known(words.is_empty().into()))),
+                _ => bail!("{}: {} is not a stack", x.1, x.0),
+            },
+            Exp::Top(x) => {
+                let top = match &self.heap[self.location(x)?] {
+                    Cell::Stack(words) => words.last().cloned(),
+                    _ => bail!("{}: {} is not a stack", x.1, x.0),
+                };
+                match top {
+                    Some(word) => Ok(Cell::Int(word)),
+                    None => {
+                        self.fail(TRUE, || format!("{}: {} is empty", x.1, x.0))?;
+                        Ok(Cell::Int(Word::Known(0)))
+                    }
+                }
+            }
+            Exp::Nil => Ok(Cell::Stack(Vec::new())),
+        }
+    }
+
+    fn stm(&mut self, s: &'a Stm, direction: Direction) -> Result<()> {
+        if self.guard == FALSE {
+            return Ok(());
+        }
+        match s {
+            Stm::AssignScalar { x, mod_op, e, span } => {
+                if e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let old = self.scalar(x)?;
+                let value = self.int(e)?;
+                let new = self.update(&old, *mod_op, &value, direction);
+                let location = self.location(x)?;
+                self.heap[location] = Cell::Int(new);
+            }
+            Stm::AssignArray {
+                x,
+                e_index,
+                mod_op,
+                e,
+                span,
+            } => {
+                if e_index.mentions(x) || e.mentions(x) {
+                    bail!("{span}: {} appears on both sides of an update", x.0);
+                }
+                let location = self.location(x)?;
+                let Cell::Array(words) = &self.heap[location] else {
+                    bail!("{span}: {} is not an array", x.0);
+                };
+                let len = words.len();
+                let Some(index) = self.index(x, e_index, len)? else {
+                    return Ok(());
+                };
+                let value = self.int(e)?;
+                let Cell::Array(words) = &self.heap[location] else {
+                    unreachable!("checked above");
+                };
+                let old = words[index].clone();
+                let new = self.update(&old, *mod_op, &value, direction);
+                if let Cell::Array(words) = &mut self.heap[location] {
+                    words[index] = new;
+                }
+            }
+            Stm::Conditional {
+                e_if,
+                s_then,
+                s_else,
+                e_fi,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_if, e_fi),
+                    Direction::Backward => (e_fi, e_if),
+                };
+                let entry = self.condition(e_entry)?;
+                self.log(|| match entry {
+                    TRUE => format!("{span}: takes the then branch"),
+                    _ => format!("{span}: takes the else branch"),
+                });
+                let guard = self.guard;
+                self.guard = self.blaster.and(guard, entry);
+                self.stm(s_then, direction)?;
+                self.guard = self.blaster.and(guard, !entry);
+                self.stm(s_else, direction)?;
+                self.guard = guard;
+                let exit = self.condition(e_exit)?;
+                let violated = self.blaster.xor(exit, entry);
+                self.fail(violated, || {
+                    format!("{span}: assertion {} failed", assertion(direction, "fi"))
+                })?;
+            }
+            Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } => {
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_from, e_until),
+                    Direction::Backward => (e_until, e_from),
+                };
+                let entry = self.condition(e_entry)?;
+                self.fail(!entry, || {
+                    format!("{span}: assertion {} failed", assertion(direction, "from"))
+                })?;
+                let guard = self.guard;
+                let mut iterations = 0;
+                loop {
+                    self.stm(s_do, direction)?;
+                    let exit = self.condition(e_exit)?;
+                    self.guard = self.blaster.and(self.guard, !exit);
+                    if self.guard == FALSE {
+                        break;
+                    }
+                    let limit = match self.guard {
+                        TRUE => MAX_UNROLL,
+                        _ => self.unroll,
+                    };
+                    if iterations == limit {
+                        self.give_up(|| {
+                            format!("{span}: the loop runs more than {limit} iterations")
+                        })?;
+                        break;
+                    }
+                    iterations += 1;
+                    self.stm(s_loop, direction)?;
+                    let entry = self.condition(e_entry)?;
+                    self.fail(entry, || {
+                        format!(
+                            "{span}: assertion {} failed on re-entry",
+                            assertion(direction, "from")
+                        )
+                    })?;
+                }
+                self.guard = guard;
+                self.log(|| format!("{span}: the loop repeats {iterations} times"));
+            }
+            Stm::Push(x, xs, span) | Stm::Pop(x, xs, span) => {
+                let push = matches!(
+                    (s, direction),
+                    (Stm::Push(..), Direction::Forward) | (Stm::Pop(..), Direction::Backward)
+                );
+                if x.0 == xs.0 {
+                    bail!("{span}: {} cannot be pushed onto itself", x.0);
+                }
+                if self.guard != TRUE {
+                    bail!("{span}: stack operations must not depend on the input");
+                }
+                let (location, stack) = (self.location(x)?, self.location(xs)?);
+                let word = self.scalar(x)?;
+                if !matches!(self.heap[stack], Cell::Stack(_)) {
+                    bail!("{span}: {} is not a stack", xs.0);
+                }
+                if push {
+                    if let Cell::Stack(words) = &mut self.heap[stack] {
+                        words.push(word);
+                    }
+                    self.heap[location] = Cell::Int(Word::Known(0));
+                } else {
+                    let nonzero = self.truth(&word);
+                    self.fail(nonzero, || {
+                        format!("{span}: {} must be zero before a pop", x.0)
+                    })?;
+                    let top = match &mut self.heap[stack] {
+                        Cell::Stack(words) => words.pop(),
+                        _ => unreachable!("checked above"),
+                    };
+                    match top {
+                        Some(word) => self.heap[location] = Cell::Int(word),
+                        None => self.fail(TRUE, || format!("{span}: {} is empty", xs.0))?,
+                    }
+                }
+            }
+            Stm::Local {
+                t_local,
+                x_local,
+                e_local,
+                s: s_local,
+                t_delocal,
+                x_delocal,
+                e_delocal,
+                span,
+            } => {
+                if x_local.0 != x_delocal.0 || t_local != t_delocal {
+                    bail!("{span}: local {} does not match its delocal", x_local.0);
+                }
+                let (e_entry, e_exit) = match direction {
+                    Direction::Forward => (e_local, e_delocal),
+                    Direction::Backward => (e_delocal, e_local),
+                };
+                let initial = self.eval(e_entry)?;
+                match (t_local, &initial) {
+                    (Type::Int, Cell::Int(_)) | (Type::Stack, Cell::Stack(_)) => {}
+                    _ => bail!("{span}: {} is initialised with {e_entry}", x_local.0),
+                }
+
+                let location = self.heap.len();
+                self.heap.push(initial);
+                let frame = self.frames.last_mut().expect("no active frame");
+                let shadowed = frame.insert(x_local.0.clone(), location);
+                let result = self.stm(s_local, direction).and_then(|_| {
+                    let exit = self.eval(e_exit)?;
+                    let current = self.heap[location].clone();
+                    let same = self.same(&exit, &current);
+                    self.fail(!same, || {
+                        format!(
+                            "{span}: assertion {} {} failed",
+                            assertion(direction, "delocal"),
+                            x_local.0
+                        )
+                    })
+                });
+
+                let frame = self.frames.last_mut().expect("no active frame");
+                match shadowed {
+                    Some(shadowed) => frame.insert(x_local.0.clone(), shadowed),
+                    None => frame.remove(&x_local.0),
+                };
+                self.heap.truncate(location);
+                result?;
+            }
+            Stm::Call { q, xs, span } | Stm::Uncall { q, xs, span } => {
+                let Some(Proc::Other { args, s: body, .. }) = self.prog.find(&q.0) else {
+                    bail!("{span}: procedure {} is not defined", q.0);
+                };
+                let direction = match s {
+                    Stm::Call { .. } => direction,
+                    _ => direction.flip(),
+                };
+                if args.len() != xs.len() {
+                    bail!(
+                        "{span}: {} expects {} arguments but {} were given",
+                        q.0,
+                        args.len(),
+                        xs.len()
+                    );
+                }
+                let mut frame = BTreeMap::new();
+                let mut locations = Vec::new();
+                for (arg, x) in args.iter().zip(xs) {
+                    let location = self.location(x)?;
+                    if locations.contains(&location) {
+                        bail!("{span}: {} is passed to {} more than once", x.0, q.0);
+                    }
+                    locations.push(location);
+                    match (&arg.t, &self.heap[location]) {
+                        (Type::Int, Cell::Stack(_)) => {
+                            bail!("{}: {} expects an integer found a stack", arg.x.1, arg.x.0)
+                        }
+                        (Type::Stack, Cell::Int(_) | Cell::Array(_)) => {
+                            bail!("{}: {} expects a stack found an integer", arg.x.1, arg.x.0)
+                        }
+                        _ => {}
+                    }
+                    if frame.insert(arg.x.0.clone(), location).is_some() {
+                        bail!("{}: parameter {} is declared twice", arg.x.1, arg.x.0);
+                    }
+                }
+                let limit = match self.guard {
+                    TRUE => MAX_DEPTH,
+                    _ => self.unroll,
+                };
+                if self.frames.len() > limit {
+                    return self.give_up(|| {
+                        format!("{span}: call depth exceeds {limit} when calling {}", q.0)
+                    });
+                }
+                self.log(|| match s {
+                    Stm::Call { .. } => format!("{span}: calls {}", q.0),
+                    _ => format!("{span}: uncalls {}", q.0),
+                });
+                self.frames.push(frame);
+                let result = self.stm(body, direction);
+                self.frames.pop();
+                result.map_err(|e| e.context(format!("{span}: in {}", q.0)))?;
+            }
+            Stm::Skip(_) => {}
+            Stm::Sequence(s_1, s_2) => match direction {
+                Direction::Forward => {
+                    self.stm(s_1, direction)?;
+                    self.stm(s_2, direction)?;
+                }
+                Direction::Backward => {
+                    self.stm(s_2, direction)?;
+                    self.stm(s_1, direction)?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
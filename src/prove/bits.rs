@@ -0,0 +1,280 @@
+use super::sat::{Lit, Solver};
+use crate::ast::ast_node::Op;
+use std::collections::HashMap;
+
+pub const TRUE: Lit = Lit::new(0, false);
+pub const FALSE: Lit = Lit::new(0, true);
+
+#[derive(Debug)]
+pub struct Blaster {
+    pub solver: Solver,
+    ands: HashMap<(Lit, Lit), Lit>,
+    xors: HashMap<(Lit, Lit), Lit>,
+}
+
+impl Default for Blaster {
+    fn default() -> Self {
+        let mut solver = Solver::new();
+        let truth = solver.var();
+        solver.clause(&[truth]);
+        Self {
+            solver,
+            ands: HashMap::new(),
+            xors: HashMap::new(),
+        }
+    }
+}
+
+impl Blaster {
+    pub fn input(&mut self) -> Lit {
+        self.solver.var()
+    }
+
+    pub fn constant(&self, lit: Lit) -> Option<bool> {
+        match lit {
+            TRUE => Some(true),
+            FALSE => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let (a, b) = (a.min(b), a.max(b));
+        match (a, b) {
+            (FALSE, _) | (_, FALSE) => return FALSE,
+            (TRUE, lit) | (lit, TRUE) => return lit,
+            _ if a == b => return a,
+            _ if a == !b => return FALSE,
+            _ => {}
+        }
+        if let Some(&lit) = self.ands.get(&(a, b)) {
+            return lit;
+        }
+        let lit = self.solver.var();
+        self.solver.clause(&[!lit, a]);
+        self.solver.clause(&[!lit, b]);
+        self.solver.clause(&[lit, !a, !b]);
+        self.ands.insert((a, b), lit);
+        lit
+    }
+
+    pub fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        !self.and(!a, !b)
+    }
+
+    pub fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let negated = a.negated() != b.negated();
+        let (a, b) = (Lit::new(a.var(), false), Lit::new(b.var(), false));
+        let (a, b) = (a.min(b), a.max(b));
+        let lit = match (a, b) {
+            (TRUE, lit) => !lit,
+            _ if a == b => FALSE,
+            _ => match self.xors.get(&(a, b)) {
+                Some(&lit) => lit,
+                None => {
+                    let lit = self.solver.var();
+                    self.solver.clause(&[!lit, a, b]);
+                    self.solver.clause(&[!lit, !a, !b]);
+                    self.solver.clause(&[lit, !a, b]);
+                    self.solver.clause(&[lit, a, !b]);
+                    self.xors.insert((a, b), lit);
+                    lit
+                }
+            },
+        };
+        match negated {
+            true => !lit,
+            false => lit,
+        }
+    }
+
+    pub fn ite(&mut self, c: Lit, a: Lit, b: Lit) -> Lit {
+        match (c, a == b) {
+            (TRUE, _) => a,
+            (FALSE, _) => b,
+            (_, true) => a,
+            _ => {
+                let a = self.and(c, a);
+                let b = self.and(!c, b);
+                self.or(a, b)
+            }
+        }
+    }
+
+    pub fn any(&mut self, lits: &[Lit]) -> Lit {
+        lits.iter().fold(FALSE, |value, &lit| self.or(value, lit))
+    }
+
+    pub fn all(&mut self, lits: &[Lit]) -> Lit {
+        lits.iter().fold(TRUE, |value, &lit| self.and(value, lit))
+    }
+
+    pub fn word(&self, n: i32, width: usize) -> Vec<Lit> {
+        (0..width)
+            .map(|i| match n >> i & 1 {
+                1 => TRUE,
+                _ => FALSE,
+            })
+            .collect()
+    }
+
+    pub fn known(&self, xs: &[Lit]) -> Option<i32> {
+        let mut n = 0;
+        for (i, &x) in xs.iter().enumerate() {
+            n |= i32::from(self.constant(x)?) << i;
+        }
+        let shift = 32 - xs.len() as u32;
+        Some(n.wrapping_shl(shift).wrapping_shr(shift))
+    }
+
+    pub fn model(&self, xs: &[Lit]) -> i32 {
+        let n = xs
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, &x)| n | i32::from(self.solver.model(x)) << i);
+        let shift = 32 - xs.len() as u32;
+        n.wrapping_shl(shift).wrapping_shr(shift)
+    }
+
+    pub fn select(&mut self, c: Lit, xs: &[Lit], ys: &[Lit]) -> Vec<Lit> {
+        xs.iter()
+            .zip(ys)
+            .map(|(&x, &y)| self.ite(c, x, y))
+            .collect()
+    }
+
+    fn carry(&mut self, xs: &[Lit], ys: &[Lit], carry: Lit) -> (Vec<Lit>, Lit) {
+        let mut c = carry;
+        let mut sum = Vec::with_capacity(xs.len());
+        for (&x, &y) in xs.iter().zip(ys) {
+            let t = self.xor(x, y);
+            sum.push(self.xor(t, c));
+            let generate = self.and(x, y);
+            let propagate = self.and(t, c);
+            c = self.or(generate, propagate);
+        }
+        (sum, c)
+    }
+
+    pub fn add(&mut self, xs: &[Lit], ys: &[Lit]) -> Vec<Lit> {
+        self.carry(xs, ys, FALSE).0
+    }
+
+    pub fn sub(&mut self, xs: &[Lit], ys: &[Lit]) -> Vec<Lit> {
+        let ys = ys.iter().map(|&y| !y).collect::<Vec<_>>();
+        self.carry(xs, &ys, TRUE).0
+    }
+
+    pub fn neg(&mut self, xs: &[Lit]) -> Vec<Lit> {
+        let zero = vec![FALSE; xs.len()];
+        self.sub(&zero, xs)
+    }
+
+    pub fn mul(&mut self, xs: &[Lit], ys: &[Lit]) -> Vec<Lit> {
+        let width = xs.len();
+        let mut product = vec![FALSE; width];
+        for (i, &y) in ys.iter().enumerate() {
+            let partial = (0..width)
+                .map(|k| match k >= i {
+                    true => self.and(xs[k - i], y),
+                    false => FALSE,
+                })
+                .collect::<Vec<_>>();
+            product = self.add(&product, &partial);
+        }
+        product
+    }
+
+    fn at_least(&mut self, xs: &[Lit], ys: &[Lit]) -> Lit {
+        let ys = ys.iter().map(|&y| !y).collect::<Vec<_>>();
+        self.carry(xs, &ys, TRUE).1
+    }
+
+    pub fn less(&mut self, xs: &[Lit], ys: &[Lit]) -> Lit {
+        let flip = |zs: &[Lit]| {
+            let mut zs = zs.to_vec();
+            let last = zs.len() - 1;
+            zs[last] = !zs[last];
+            zs
+        };
+        !self.at_least(&flip(xs), &flip(ys))
+    }
+
+    pub fn equal(&mut self, xs: &[Lit], ys: &[Lit]) -> Lit {
+        let same = xs
+            .iter()
+            .zip(ys)
+            .map(|(&x, &y)| !self.xor(x, y))
+            .collect::<Vec<_>>();
+        self.all(&same)
+    }
+
+    pub fn nonzero(&mut self, xs: &[Lit]) -> Lit {
+        self.any(xs)
+    }
+
+    pub fn divide(&mut self, xs: &[Lit], ys: &[Lit]) -> (Vec<Lit>, Vec<Lit>) {
+        let width = xs.len();
+        let (sign_x, sign_y) = (xs[width - 1], ys[width - 1]);
+        let negated = self.neg(xs);
+        let xs = self.select(sign_x, &negated, xs);
+        let negated = self.neg(ys);
+        let ys = self.select(sign_y, &negated, ys);
+
+        let mut quotient = vec![FALSE; width];
+        let mut remainder = vec![FALSE; width];
+        for i in (0..width).rev() {
+            remainder.rotate_right(1);
+            remainder[0] = xs[i];
+            let fits = self.at_least(&remainder, &ys);
+            let reduced = self.sub(&remainder, &ys);
+            remainder = self.select(fits, &reduced, &remainder);
+            quotient[i] = fits;
+        }
+
+        let sign = self.xor(sign_x, sign_y);
+        let negated = self.neg(&quotient);
+        let quotient = self.select(sign, &negated, &quotient);
+        let negated = self.neg(&remainder);
+        let remainder = self.select(sign_x, &negated, &remainder);
+        (quotient, remainder)
+    }
+
+    pub fn bitwise(
+        &mut self,
+        xs: &[Lit],
+        ys: &[Lit],
+        f: fn(&mut Blaster, Lit, Lit) -> Lit,
+    ) -> Vec<Lit> {
+        xs.iter().zip(ys).map(|(&x, &y)| f(self, x, y)).collect()
+    }
+
+    pub fn binop(&mut self, op: Op, xs: &[Lit], ys: &[Lit]) -> Vec<Lit> {
+        let lit = match op {
+            Op::Add => return self.add(xs, ys),
+            Op::Sub => return self.sub(xs, ys),
+            Op::Mul => return self.mul(xs, ys),
+            Op::Div => return self.divide(xs, ys).0,
+            Op::Mod => return self.divide(xs, ys).1,
+            Op::Xor => return self.bitwise(xs, ys, Blaster::xor),
+            Op::And => return self.bitwise(xs, ys, Blaster::and),
+            Op::Or => return self.bitwise(xs, ys, Blaster::or),
+            Op::And2 | Op::Or2 => {
+                let (a, b) = (self.nonzero(xs), self.nonzero(ys));
+                match op {
+                    Op::And2 => self.and(a, b),
+                    _ => self.or(a, b),
+                }
+            }
+            Op::Less => self.less(xs, ys),
+            Op::Greater => self.less(ys, xs),
+            Op::LessEqual => !self.less(ys, xs),
+            Op::GreaterEqual => !self.less(xs, ys),
+            Op::Equal => self.equal(xs, ys),
+            Op::NotEqual => !self.equal(xs, ys),
+        };
+        let mut zs = vec![FALSE; xs.len()];
+        zs[0] = lit;
+        zs
+    }
+}
@@ -0,0 +1,338 @@
+use super::{
+    bits::{FALSE, TRUE},
+    check_exhaustive, default_method, exhaustive_input, params,
+    sat::Lit,
+    Assignment, Cell, Executor, Method, Stop, Word,
+};
+use crate::{ast::ast_node::*, backend::array_params, circuit::wrap, util::Rng};
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "kebab-case")]
+pub enum Outcome {
+    Returns { output: Assignment },
+    Fails { error: String },
+}
+
+impl Outcome {
+    fn agrees(&self, other: &Outcome) -> bool {
+        match (self, other) {
+            (Outcome::Returns { output: xs }, Outcome::Returns { output: ys }) => {
+                xs.iter().map(|(_, n)| n).eq(ys.iter().map(|(_, n)| n))
+            }
+            (Outcome::Fails { .. }, Outcome::Fails { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "verdict", rename_all = "kebab-case")]
+pub enum Verdict {
+    Equivalent,
+    Differ {
+        input: Assignment,
+        left: Outcome,
+        right: Outcome,
+    },
+    Unknown {
+        input: Assignment,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub left: String,
+    pub right: String,
+    pub width: usize,
+    pub method: Method,
+    pub bits: usize,
+    pub inputs: u64,
+    pub clauses: usize,
+    pub verdict: Verdict,
+}
+
+pub struct Options {
+    pub width: usize,
+    pub method: Option<Method>,
+    pub unroll: usize,
+    pub samples: u64,
+    pub seed: u64,
+}
+
+pub fn equiv(prog: &Prog, left: &str, right: &str, options: &Options) -> Result<Report> {
+    if !(1..=32).contains(&options.width) {
+        bail!("the width must be between 1 and 32");
+    }
+    compare_args(prog, left, right)?;
+    let params = [params(prog, left)?, params(prog, right)?];
+    let bits = params[0].len() * options.width;
+    let method = options.method.unwrap_or(default_method(bits));
+    let checker = Checker {
+        prog,
+        left,
+        right,
+        width: options.width,
+        unroll: options.unroll,
+        params,
+    };
+    let (verdict, inputs, clauses) = match method {
+        Method::Random => {
+            let mut rng = Rng::new(options.seed);
+            let (verdict, inputs) = checker.random(options.samples, &mut rng)?;
+            (verdict, inputs, 0)
+        }
+        Method::Exhaustive => {
+            let (verdict, inputs) = checker.exhaustive()?;
+            (verdict, inputs, 0)
+        }
+        Method::Symbolic => {
+            let (verdict, clauses) = checker.symbolic()?;
+            (verdict, 0, clauses)
+        }
+    };
+    Ok(Report {
+        left: left.to_string(),
+        right: right.to_string(),
+        width: options.width,
+        method,
+        bits,
+        inputs,
+        clauses,
+        verdict,
+    })
+}
+
+fn compare_args(prog: &Prog, left: &str, right: &str) -> Result<()> {
+    let arrays = array_params(prog);
+    let mut lists = Vec::new();
+    for q in [left, right] {
+        match prog.find(q) {
+            Some(Proc::Other { q, args, .. }) => {
+                let kinds = args
+                    .iter()
+                    .zip(&arrays[q.0.as_str()])
+                    .map(|(arg, &array)| match (arg.t, array) {
+                        (Type::Stack, _) => "stack",
+                        (Type::Int, true) => "array",
+                        (Type::Int, false) => "int",
+                    })
+                    .collect::<Vec<_>>();
+                lists.push(kinds);
+            }
+            Some(Proc::Main { .. }) => bail!("main has no parameters to compare"),
+            None => bail!("procedure {q} is not defined"),
+        }
+    }
+    if lists[0].len() != lists[1].len() {
+        bail!(
+            "{left} has {} parameters but {right} has {}",
+            lists[0].len(),
+            lists[1].len()
+        );
+    }
+    for (i, (l, r)) in lists[0].iter().zip(&lists[1]).enumerate() {
+        if l != r {
+            bail!("parameter {} is {l} in {left} but {r} in {right}", i + 1);
+        }
+    }
+    Ok(())
+}
+
+enum Run {
+    Done(Outcome),
+    Incomplete(String),
+}
+
+struct Side {
+    failed: Lit,
+    incomplete: Vec<(Lit, String)>,
+    outputs: Option<Vec<Vec<Lit>>>,
+}
+
+struct Checker<'a> {
+    prog: &'a Prog,
+    left: &'a str,
+    right: &'a str,
+    width: usize,
+    unroll: usize,
+    params: [Vec<String>; 2],
+}
+
+impl Checker<'_> {
+    fn assignment(&self, side: usize, ns: &[i32]) -> Assignment {
+        self.params[side]
+            .iter()
+            .cloned()
+            .zip(ns.iter().copied())
+            .collect()
+    }
+
+    fn concrete(&self, side: usize, input: &[i32]) -> Result<Run> {
+        let q = [self.left, self.right][side];
+        let mut executor = Executor::new(self.prog, self.width, self.unroll, false);
+        let words = input.iter().map(|&n| Word::Known(n)).collect::<Vec<_>>();
+        let e = match executor.run(q, &words) {
+            Ok(cells) => {
+                let output = cells
+                    .iter()
+                    .take(input.len())
+                    .map(|cell| match cell {
+                        Cell::Int(Word::Known(n)) => *n,
+                        _ => unreachable!("concrete runs only produce known integers"),
+                    })
+                    .collect::<Vec<_>>();
+                let output = self.assignment(side, &output);
+                return Ok(Run::Done(Outcome::Returns { output }));
+            }
+            Err(e) => e,
+        };
+        match executor.stop {
+            Some(Stop::Failed) => Ok(Run::Done(Outcome::Fails {
+                error: format!("{e:#}"),
+            })),
+            Some(Stop::Incomplete) => Ok(Run::Incomplete(format!("{q}: {e:#}"))),
+            None => Err(e),
+        }
+    }
+
+    fn compare(&self, input: &[i32]) -> Result<Option<Verdict>> {
+        let (left, right) = match (self.concrete(0, input)?, self.concrete(1, input)?) {
+            (Run::Incomplete(reason), _) | (_, Run::Incomplete(reason)) => {
+                return Ok(Some(Verdict::Unknown {
+                    input: self.assignment(0, input),
+                    reason,
+                }));
+            }
+            (Run::Done(left), Run::Done(right)) => (left, right),
+        };
+        match left.agrees(&right) {
+            true => Ok(None),
+            false => Ok(Some(Verdict::Differ {
+                input: self.assignment(0, input),
+                left,
+                right,
+            })),
+        }
+    }
+
+    fn random(&self, samples: u64, rng: &mut Rng) -> Result<(Verdict, u64)> {
+        for sample in 0..samples {
+            let input = self.params[0]
+                .iter()
+                .map(|_| wrap(rng.next_u64() as i32, self.width))
+                .collect::<Vec<_>>();
+            if let Some(verdict) = self.compare(&input)? {
+                return Ok((verdict, sample + 1));
+            }
+        }
+        Ok((Verdict::Equivalent, samples))
+    }
+
+    fn exhaustive(&self) -> Result<(Verdict, u64)> {
+        let bits = self.params[0].len() * self.width;
+        check_exhaustive(self.left, bits)?;
+        for counter in 0..1u64 << bits {
+            let input = exhaustive_input(counter, self.params[0].len(), self.width);
+            if let Some(verdict) = self.compare(&input)? {
+                return Ok((verdict, counter + 1));
+            }
+        }
+        Ok((Verdict::Equivalent, 1 << bits))
+    }
+
+    fn side(&self, executor: &mut Executor, side: usize, words: &[Word]) -> Result<Side> {
+        let q = [self.left, self.right][side];
+        match executor.run(q, words) {
+            Ok(cells) => {
+                let failures = std::mem::take(&mut executor.failures);
+                let outputs = cells
+                    .iter()
+                    .take(words.len())
+                    .map(|cell| match cell {
+                        Cell::Int(word) => executor.bits(word),
+                        _ => unreachable!("parameters are integers"),
+                    })
+                    .collect();
+                Ok(Side {
+                    failed: executor.blaster.any(&failures),
+                    incomplete: std::mem::take(&mut executor.incomplete)
+                        .into_iter()
+                        .map(|(reached, reason)| (reached, format!("{q}: {reason}")))
+                        .collect(),
+                    outputs: Some(outputs),
+                })
+            }
+            Err(e) => match executor.stop {
+                Some(Stop::Failed) => Ok(Side {
+                    failed: TRUE,
+                    incomplete: Vec::new(),
+                    outputs: None,
+                }),
+                Some(Stop::Incomplete) => Ok(Side {
+                    failed: FALSE,
+                    incomplete: vec![(TRUE, format!("{q}: {e:#}"))],
+                    outputs: None,
+                }),
+                None => Err(e),
+            },
+        }
+    }
+
+    fn symbolic(&self) -> Result<(Verdict, usize)> {
+        let mut executor = Executor::new(self.prog, self.width, self.unroll, false);
+        let mut inputs = Vec::new();
+        for _ in &self.params[0] {
+            let xs = (0..self.width)
+                .map(|_| executor.blaster.input())
+                .collect::<Vec<_>>();
+            inputs.push(xs);
+        }
+        let words = inputs.iter().cloned().map(Word::Bits).collect::<Vec<_>>();
+        let left = self.side(&mut executor, 0, &words)?;
+        let right = self.side(&mut executor, 1, &words)?;
+
+        for (reached, reason) in left.incomplete.iter().chain(&right.incomplete) {
+            if executor.blaster.solver.solve(&[*reached]) {
+                let input = executor.model(&inputs);
+                let verdict = match self.compare(&input)? {
+                    Some(verdict) => verdict,
+                    None => Verdict::Unknown {
+                        input: self.assignment(0, &input),
+                        reason: reason.clone(),
+                    },
+                };
+                return Ok((verdict, executor.clauses()));
+            }
+        }
+
+        let one_fails = executor.blaster.xor(left.failed, right.failed);
+        let mismatched = match (&left.outputs, &right.outputs) {
+            (Some(xss), Some(yss)) => {
+                let mut same = Vec::new();
+                for (xs, ys) in xss.iter().zip(yss) {
+                    same.push(executor.blaster.equal(xs, ys));
+                }
+                let same = executor.blaster.all(&same);
+                let succeed = executor.blaster.and(!left.failed, !right.failed);
+                executor.blaster.and(succeed, !same)
+            }
+            _ => FALSE,
+        };
+        let differ = executor.blaster.or(one_fails, mismatched);
+        if !executor.blaster.solver.solve(&[differ]) {
+            return Ok((Verdict::Equivalent, executor.clauses()));
+        }
+        let input = executor.model(&inputs);
+        match self.compare(&input)? {
+            Some(verdict) => Ok((verdict, executor.clauses())),
+            None => bail!(
+                "{} and {} agree on the input the solver found",
+                self.left,
+                self.right
+            ),
+        }
+    }
+}
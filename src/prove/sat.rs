@@ -0,0 +1,357 @@
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Not};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub const fn new(var: usize, negated: bool) -> Self {
+        Self((var as u32) << 1 | negated as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Activity(f64, usize);
+
+impl Eq for Activity {}
+
+impl PartialOrd for Activity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Activity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(other.1.cmp(&self.1))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    phases: Vec<bool>,
+    activity: Vec<f64>,
+    heap: BinaryHeap<Activity>,
+    queued: Vec<bool>,
+    bump: f64,
+    trail: Vec<Lit>,
+    limits: Vec<usize>,
+    head: usize,
+    model: Vec<bool>,
+    unsat: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            bump: 1.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn var(&mut self) -> Lit {
+        let var = self.values.len();
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.phases.push(false);
+        self.activity.push(0.0);
+        self.watches.extend([Vec::new(), Vec::new()]);
+        self.heap.push(Activity(0.0, var));
+        self.queued.push(true);
+        Lit::new(var, false)
+    }
+
+    pub fn vars(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn clauses(&self) -> usize {
+        self.clauses.len()
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|value| value != lit.negated())
+    }
+
+    fn level(&self) -> usize {
+        self.limits.len()
+    }
+
+    pub fn clause(&mut self, lits: &[Lit]) {
+        if self.unsat {
+            return;
+        }
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        if lits.windows(2).any(|pair| pair[0] == !pair[1])
+            || lits.iter().any(|&lit| self.value(lit) == Some(true))
+        {
+            return;
+        }
+        lits.retain(|&lit| self.value(lit).is_none());
+        match lits.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.assign(lits[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            }
+            _ => {
+                self.attach(lits);
+            }
+        }
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[lits[0].index()].push(index);
+        self.watches[lits[1].index()].push(index);
+        self.clauses.push(lits);
+        index
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(!lit.negated());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let falsified = !self.trail[self.head];
+            self.head += 1;
+            let mut watching = std::mem::take(&mut self.watches[falsified.index()]);
+            let mut i = 0;
+            while i < watching.len() {
+                let index = watching[i];
+                if self.clauses[index][0] == falsified {
+                    self.clauses[index].swap(0, 1);
+                }
+                let first = self.clauses[index][0];
+                if self.value(first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let replacement = (2..self.clauses[index].len())
+                    .find(|&k| self.value(self.clauses[index][k]) != Some(false));
+                if let Some(k) = replacement {
+                    self.clauses[index].swap(1, k);
+                    let watch = self.clauses[index][1];
+                    self.watches[watch.index()].push(index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+                if self.value(first) == Some(false) {
+                    self.watches[falsified.index()] = watching;
+                    return Some(index);
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            self.watches[falsified.index()] = watching;
+        }
+        None
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let limit = self.limits[level];
+        for lit in self.trail.drain(limit..) {
+            let var = lit.var();
+            self.phases[var] = !lit.negated();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            if !self.queued[var] {
+                self.queued[var] = true;
+                self.heap.push(Activity(self.activity[var], var));
+            }
+        }
+        self.limits.truncate(level);
+        self.head = self.trail.len();
+    }
+
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut reason = conflict;
+        let mut implied = None;
+        loop {
+            let skip = usize::from(implied.is_some());
+            for k in skip..self.clauses[reason].len() {
+                let lit = self.clauses[reason][k];
+                let var = lit.var();
+                if seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                match self.levels[var] == self.level() {
+                    true => pending += 1,
+                    false => learnt.push(lit),
+                }
+            }
+            let lit = loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            implied = Some(lit);
+            reason = self.reasons[lit.var()].expect("implied literals have a reason");
+        }
+
+        let mut level = 0;
+        if let Some(k) = (1..learnt.len()).max_by_key(|&k| self.levels[learnt[k].var()]) {
+            learnt.swap(1, k);
+            level = self.levels[learnt[1].var()];
+        }
+        (learnt, level)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+            self.heap = (0..self.values.len())
+                .filter(|&var| self.queued[var])
+                .map(|var| Activity(self.activity[var], var))
+                .collect();
+        } else if self.queued[var] {
+            self.heap.push(Activity(self.activity[var], var));
+        }
+    }
+
+    fn decide(&mut self) -> Option<Lit> {
+        while let Some(Activity(activity, var)) = self.heap.pop() {
+            if activity != self.activity[var] {
+                continue;
+            }
+            self.queued[var] = false;
+            if self.values[var].is_none() {
+                return Some(Lit::new(var, !self.phases[var]));
+            }
+        }
+        None
+    }
+
+    pub fn solve(&mut self, assumptions: &[Lit]) -> bool {
+        self.solve_within(assumptions, u64::MAX)
+            .expect("an unlimited search always finishes")
+    }
+
+    pub fn solve_within(&mut self, assumptions: &[Lit], budget: u64) -> Option<bool> {
+        if self.unsat {
+            return Some(false);
+        }
+        let mut total = 0;
+        let mut conflicts = 0;
+        let mut restart = 100;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    self.unsat = true;
+                    return Some(false);
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                match learnt.len() {
+                    1 => self.assign(learnt[0], None),
+                    _ => {
+                        let lit = learnt[0];
+                        let index = self.attach(learnt);
+                        self.assign(lit, Some(index));
+                    }
+                }
+                self.bump /= 0.95;
+                conflicts += 1;
+                total += 1;
+                if total == budget {
+                    self.backtrack(0);
+                    return None;
+                }
+                continue;
+            }
+            if conflicts >= restart {
+                conflicts = 0;
+                restart += restart / 2;
+                self.backtrack(0);
+                continue;
+            }
+            if let Some(&assumption) = assumptions.get(self.level()) {
+                match self.value(assumption) {
+                    Some(true) => self.limits.push(self.trail.len()),
+                    Some(false) => {
+                        self.backtrack(0);
+                        return Some(false);
+                    }
+                    None => {
+                        self.limits.push(self.trail.len());
+                        self.assign(assumption, None);
+                    }
+                }
+                continue;
+            }
+            match self.decide() {
+                Some(lit) => {
+                    self.limits.push(self.trail.len());
+                    self.assign(lit, None);
+                }
+                None => {
+                    self.model = self
+                        .values
+                        .iter()
+                        .map(|value| value.expect("every variable is assigned"))
+                        .collect();
+                    self.backtrack(0);
+                    return Some(true);
+                }
+            }
+        }
+    }
+
+    pub fn model(&self, lit: Lit) -> bool {
+        self.model.get(lit.var()).copied().unwrap_or(false) != lit.negated()
+    }
+}
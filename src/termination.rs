@@ -0,0 +1,384 @@
+use crate::{
+    ast::ast_node::*,
+    backend::Scope,
+    optimize::{self, inline::walk_vars, partial::targets},
+    tokenizer::span::Span,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "verdict", rename_all = "kebab-case")]
+pub enum Verdict {
+    Bounded {
+        iterations: String,
+        requires: Option<String>,
+    },
+    Diverges {
+        reason: String,
+    },
+    FailsOnReentry {
+        reason: String,
+    },
+    Unknown {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub proc: String,
+    pub span: Span,
+    pub counter: Option<String>,
+    pub verdict: Verdict,
+}
+
+pub fn analyze(prog: &Prog) -> Vec<Report> {
+    let mut reports = Vec::new();
+    for p in prog.procs() {
+        p.body().walk(&mut |s| {
+            let Stm::Loop {
+                e_from,
+                s_do,
+                s_loop,
+                e_until,
+                span,
+            } = s
+            else {
+                return;
+            };
+            let mut modified = targets(s_do);
+            modified.extend(targets(s_loop));
+            let analysis = Analysis {
+                modified,
+                s_do,
+                s_loop,
+            };
+            let (counter, verdict) = analysis.verdict(e_from, e_until);
+            reports.push(Report {
+                proc: p.name().to_string(),
+                span: *span,
+                counter,
+                verdict,
+            });
+        });
+    }
+    reports
+}
+
+struct Analysis<'a> {
+    modified: BTreeSet<String>,
+    s_do: &'a Stm,
+    s_loop: &'a Stm,
+}
+
+impl Analysis<'_> {
+    fn changes(&self, e: &Exp) -> bool {
+        let mut value = false;
+        walk_vars(e, &mut |x| value |= self.modified.contains(&x.0));
+        value
+    }
+
+    fn equation<'e>(&self, e: &'e Exp) -> Option<(&'e str, &'e Exp)> {
+        match e {
+            Exp::BinOp(e_1, Op::Equal, e_2) => match (&**e_1, &**e_2) {
+                (Exp::Variable(x), e) | (e, Exp::Variable(x))
+                    if self.modified.contains(&x.0) && !e.mentions(x) =>
+                {
+                    Some((&x.0, e))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn verdict(&self, e_from: &Exp, e_until: &Exp) -> (Option<String>, Verdict) {
+        if constant(e_until).is_some_and(|n| n != 0) {
+            return (None, bounded(0));
+        }
+        if !self.changes(e_from) {
+            return (
+                None,
+                Verdict::FailsOnReentry {
+                    reason: "nothing in the from condition changes inside the loop, so it fails \
+                             its from assertion on re-entry unless it exits on the first test"
+                        .to_string(),
+                },
+            );
+        }
+        if !self.changes(e_until) {
+            let reason = match constant(e_until) {
+                Some(_) => "the until condition is always false".to_string(),
+                None => "nothing in the until condition changes inside the loop, so once the \
+                         loop repeats it never exits"
+                    .to_string(),
+            };
+            return (None, Verdict::Diverges { reason });
+        }
+
+        let Some((i, start)) = self.equation(e_from) else {
+            return (None, unknown("the from condition is not of the form i = e"));
+        };
+        let counter = Some(i.to_string());
+        let Some((rel, bound)) = comparison(e_until, i) else {
+            return (
+                counter,
+                unknown(&format!(
+                    "the until condition does not compare {i} with a bound"
+                )),
+            );
+        };
+        if self.changes(start) || self.changes(bound) {
+            return (
+                counter,
+                unknown(&format!("the bounds on {i} change inside the loop")),
+            );
+        }
+        let (Some(d_do), Some(d_loop)) = (step(self.s_do, i), step(self.s_loop, i)) else {
+            return (
+                counter,
+                unknown(&format!(
+                    "{i} is not updated by a constant step on every iteration"
+                )),
+            );
+        };
+        (counter, progress(i, start, d_do, d_do + d_loop, rel, bound))
+    }
+}
+
+fn progress(i: &str, start: &Exp, d_do: i64, d: i64, rel: Op, bound: &Exp) -> Verdict {
+    let first = Linear::of(start).offset(d_do);
+    let bound = Linear::of(bound);
+    let holds = first.minus(&bound).value().map(|n| compare(n, rel, 0));
+    if holds == Some(true) {
+        return bounded(0);
+    }
+    if d == 0 {
+        return Verdict::FailsOnReentry {
+            reason: format!(
+                "{i} is back where it started after every iteration, so the loop fails its from \
+                 assertion on re-entry unless it exits on the first test"
+            ),
+        };
+    }
+
+    let increasing = d > 0;
+    let (target, exact) = match (rel, increasing) {
+        (Op::Equal, _) => (bound.clone(), true),
+        (Op::GreaterEqual, true) | (Op::LessEqual, false) => (bound.clone(), false),
+        (Op::Greater, true) => (bound.offset(1), false),
+        (Op::Less, false) => (bound.offset(-1), false),
+        (Op::NotEqual, _) => {
+            return Verdict::Bounded {
+                iterations: match holds {
+                    Some(_) => "1".to_string(),
+                    None => "at most 1".to_string(),
+                },
+                requires: None,
+            };
+        }
+        _ => {
+            return Verdict::Diverges {
+                reason: format!(
+                    "{i} only {} but the loop waits for {i} {} {bound}, so once the loop \
+                     repeats it never exits",
+                    match increasing {
+                        true => "grows",
+                        false => "shrinks",
+                    },
+                    rel.symbol()
+                ),
+            };
+        }
+    };
+    let distance = match increasing {
+        true => target.minus(&first),
+        false => first.minus(&target),
+    };
+    let step = d.abs();
+    match (distance.value(), exact) {
+        (Some(n), true) if n >= 0 && n % step == 0 => bounded(n / step),
+        (Some(_), true) => Verdict::Diverges {
+            reason: format!(
+                "{i} takes the values {first}, {}, {}, ... and never equals {bound} without \
+                 overflowing",
+                first.offset(d),
+                first.offset(2 * d)
+            ),
+        },
+        (Some(n), false) => bounded((n.max(0) + step - 1) / step),
+        (None, true) => Verdict::Bounded {
+            iterations: divide(&distance, step),
+            requires: Some(match step {
+                1 => format!("{distance} >= 0"),
+                _ => format!("{distance} >= 0 and a multiple of {step}"),
+            }),
+        },
+        (None, false) => Verdict::Bounded {
+            iterations: match step {
+                1 => format!("max(0, {distance})"),
+                _ => format!("max(0, ceil({}))", divide(&distance, step)),
+            },
+            requires: None,
+        },
+    }
+}
+
+fn bounded(n: i64) -> Verdict {
+    Verdict::Bounded {
+        iterations: n.to_string(),
+        requires: None,
+    }
+}
+
+fn unknown(reason: &str) -> Verdict {
+    Verdict::Unknown {
+        reason: reason.to_string(),
+    }
+}
+
+fn constant(e: &Exp) -> Option<i32> {
+    match optimize::exp(e.clone(), &Scope::default()) {
+        Exp::Constant(c) => Some(c.0),
+        _ => None,
+    }
+}
+
+fn compare(n_1: i64, rel: Op, n_2: i64) -> bool {
+    match rel {
+        Op::Less => n_1 < n_2,
+        Op::Greater => n_1 > n_2,
+        Op::Equal => n_1 == n_2,
+        Op::NotEqual => n_1 != n_2,
+        Op::LessEqual => n_1 <= n_2,
+        _ => n_1 >= n_2,
+    }
+}
+
+fn comparison<'a>(e: &'a Exp, i: &str) -> Option<(Op, &'a Exp)> {
+    let Exp::BinOp(e_1, op, e_2) = e else {
+        return None;
+    };
+    let flipped = match op {
+        Op::Less => Op::Greater,
+        Op::Greater => Op::Less,
+        Op::LessEqual => Op::GreaterEqual,
+        Op::GreaterEqual => Op::LessEqual,
+        Op::Equal | Op::NotEqual => *op,
+        _ => return None,
+    };
+    let counter = |e: &Exp| matches!(e, Exp::Variable(x) if x.0 == i);
+    let free = |e: &Exp| {
+        let mut value = true;
+        walk_vars(e, &mut |x| value &= x.0 != i);
+        value
+    };
+    match (&**e_1, &**e_2) {
+        (e_1, e_2) if counter(e_1) && free(e_2) => Some((*op, e_2)),
+        (e_1, e_2) if counter(e_2) && free(e_1) => Some((flipped, e_1)),
+        _ => None,
+    }
+}
+
+fn step(s: &Stm, i: &str) -> Option<i64> {
+    match s {
+        Stm::AssignScalar { x, mod_op, e, .. } if x.0 == i => {
+            let n = i64::from(constant(e)?);
+            match mod_op {
+                ModOp::Add => Some(n),
+                ModOp::Sub => Some(-n),
+                ModOp::Xor => None,
+            }
+        }
+        Stm::Sequence(s_1, s_2) => Some(step(s_1, i)? + step(s_2, i)?),
+        Stm::Local { x_local, s, .. } if x_local.0 != i => step(s, i),
+        s if !targets(s).contains(i) => Some(0),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Linear {
+    terms: BTreeMap<String, i64>,
+    constant: i64,
+}
+
+impl Linear {
+    fn of(e: &Exp) -> Self {
+        match constant(e) {
+            Some(n) => Self {
+                terms: BTreeMap::new(),
+                constant: n.into(),
+            },
+            None => {
+                let term = match e {
+                    Exp::BinOp(..) => format!("({e})"),
+                    _ => e.to_string(),
+                };
+                Self {
+                    terms: BTreeMap::from([(term, 1)]),
+                    constant: 0,
+                }
+            }
+        }
+    }
+
+    fn offset(&self, n: i64) -> Self {
+        let mut value = self.clone();
+        value.constant += n;
+        value
+    }
+
+    fn minus(&self, other: &Self) -> Self {
+        let mut value = self.clone();
+        for (term, k) in &other.terms {
+            *value.terms.entry(term.clone()).or_default() -= k;
+        }
+        value.terms.retain(|_, k| *k != 0);
+        value.constant -= other.constant;
+        value
+    }
+
+    fn value(&self) -> Option<i64> {
+        self.terms.is_empty().then_some(self.constant)
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (term, &k) in &self.terms {
+            let sign = match (first, k < 0) {
+                (true, false) => "",
+                (true, true) => "-",
+                (false, false) => " + ",
+                (false, true) => " - ",
+            };
+            match k.abs() {
+                1 => write!(f, "{sign}{term}")?,
+                k => write!(f, "{sign}{k} * {term}")?,
+            }
+            first = false;
+        }
+        match (first, self.constant) {
+            (true, n) => write!(f, "{n}"),
+            (false, 0) => Ok(()),
+            (false, n) if n < 0 => write!(f, " - {}", -n),
+            (false, n) => write!(f, " + {n}"),
+        }
+    }
+}
+
+fn divide(distance: &Linear, step: i64) -> String {
+    match step {
+        1 => distance.to_string(),
+        _ if distance.terms.len() + usize::from(distance.constant != 0) > 1 => {
+            format!("({distance}) / {step}")
+        }
+        _ => format!("{distance} / {step}"),
+    }
+}
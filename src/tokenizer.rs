@@ -1,16 +1,78 @@
+pub mod span;
 pub mod token;
 
 use crate::util::char_list;
 use anyhow::Result;
 use lazy_static::lazy_static;
+use span::{Pos, Span};
 use std::collections::{BTreeMap, LinkedList};
 use token::Token;
 
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("int", Token::Int),
+    ("stack", Token::Stack),
+    ("procedure", Token::Procedure),
+    ("if", Token::If),
+    ("then", Token::Then),
+    ("else", Token::Else),
+    ("fi", Token::Fi),
+    ("from", Token::From),
+    ("do", Token::Do),
+    ("loop", Token::Loop),
+    ("until", Token::Until),
+    ("push", Token::Push),
+    ("pop", Token::Pop),
+    ("local", Token::Local),
+    ("delocal", Token::Delocal),
+    ("call", Token::Call),
+    ("uncall", Token::Uncall),
+    ("skip", Token::Skip),
+    ("empty", Token::Empty),
+    ("top", Token::Top),
+    ("nil", Token::Nil),
+];
+
+pub const SYMBOLS_1: &[(char, Token)] = &[
+    ('+', Token::Plus),
+    ('-', Token::Minus),
+    ('^', Token::Caret),
+    ('*', Token::Asterisk),
+    ('/', Token::Slash),
+    ('%', Token::Percent),
+    ('&', Token::Ampersand),
+    ('|', Token::VerticalBar),
+    ('<', Token::Less),
+    ('>', Token::Greater),
+    ('=', Token::Equal),
+    ('(', Token::LParen),
+    (')', Token::RParen),
+    ('[', Token::LSquareBracket),
+    (']', Token::RSquareBracket),
+    (',', Token::Comma),
+];
+
+pub const SYMBOLS_2: &[((char, char), Token)] = &[
+    (('&', '&'), Token::Ampersand2),
+    (('|', '|'), Token::VerticalBar2),
+    (('!', '='), Token::ExclamationEqual),
+    (('<', '='), Token::LessEqual),
+    (('>', '='), Token::GreaterEqual),
+    (('+', '='), Token::PlusEqual),
+    (('-', '='), Token::MinusEqual),
+    (('^', '='), Token::CaretEqual),
+];
+
+pub const WHITESPACE: &[char] = &[' ', '\n', '\t', '\r'];
+
+pub const LITERAL_START: &[char] = &['\"', '\'', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
 #[derive(Debug)]
 pub struct Tokenizer {
     source: LinkedList<char>,
-    tokens: LinkedList<Token>,
+    tokens: LinkedList<(Token, Span)>,
     buffer: LinkedList<char>,
+    buffer_start: Pos,
+    pos: Pos,
 }
 
 impl Tokenizer {
@@ -19,47 +81,35 @@ impl Tokenizer {
             source,
             tokens: Default::default(),
             buffer: Default::default(),
+            buffer_start: Pos::new(1, 1),
+            pos: Pos::new(1, 1),
         }
     }
 
+    pub fn tokens(&self) -> &LinkedList<(Token, Span)> {
+        &self.tokens
+    }
+
+    pub fn position(&self) -> Span {
+        Span::new(self.buffer_start, self.pos)
+    }
+
     fn take_buffer(&mut self) -> String {
         std::mem::take(&mut self.buffer).into_iter().collect()
     }
 
     fn push_buffer(&mut self) -> Result<()> {
         lazy_static! {
-            static ref keywords: BTreeMap<LinkedList<char>, Token> = {
-                let mut value = BTreeMap::new();
-                value.insert(char_list("int"), Token::Int);
-                value.insert(char_list("stack"), Token::Stack);
-                value.insert(char_list("procedure"), Token::Procedure);
-                value.insert(char_list("if"), Token::If);
-                value.insert(char_list("then"), Token::Then);
-                value.insert(char_list("else"), Token::Else);
-                value.insert(char_list("fi"), Token::Fi);
-                value.insert(char_list("from"), Token::From);
-                value.insert(char_list("do"), Token::Do);
-                value.insert(char_list("loop"), Token::Loop);
-                value.insert(char_list("until"), Token::Until);
-                value.insert(char_list("push"), Token::Push);
-                value.insert(char_list("pop"), Token::Pop);
-                value.insert(char_list("local"), Token::Local);
-                value.insert(char_list("delocal"), Token::Delocal);
-                value.insert(char_list("call"), Token::Call);
-                value.insert(char_list("uncall"), Token::Uncall);
-                value.insert(char_list("skip"), Token::Skip);
-                value.insert(char_list("empty"), Token::Empty);
-                value.insert(char_list("top"), Token::Top);
-                value.insert(char_list("nil"), Token::Nil);
-                value
-            };
+            static ref keywords: BTreeMap<LinkedList<char>, Token> = KEYWORDS
+                .iter()
+                .map(|(keyword, token)| (char_list(keyword), token.clone()))
+                .collect();
         }
 
         if let Some(character) = self.buffer.front() {
-            let is_literal = ['\"', '\'', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9']
-                .iter()
-                .any(|c| c == character);
+            let is_literal = LITERAL_START.contains(character);
 
+            let span = Span::new(self.buffer_start, self.pos);
             let token = match (is_literal, keywords.get(&self.buffer)) {
                 (true, _) => Token::Constant(self.take_buffer().parse()?),
                 (_, None) => Token::Identifier(self.take_buffer()),
@@ -68,19 +118,24 @@ impl Tokenizer {
                     token.clone()
                 }
             };
-            self.push_token(token);
+            self.tokens.push_back((token, span));
         }
 
         Ok(())
     }
 
-    fn push_token(&mut self, token: Token) {
-        self.tokens.push_back(token);
+    fn push_token(&mut self, token: Token, len: usize) {
+        let end = Pos::new(self.pos.line, self.pos.column + len);
+        self.tokens.push_back((token, Span::new(self.pos, end)));
     }
 
     fn advance(&mut self, len: usize) {
         for _ in 0..len {
-            self.source.pop_front();
+            match self.source.pop_front() {
+                Some('\n') => self.pos = Pos::new(self.pos.line + 1, 1),
+                Some(_) => self.pos.column += 1,
+                None => {}
+            }
         }
     }
 
@@ -94,38 +149,8 @@ impl Tokenizer {
 
     pub fn tokenize(&mut self) -> Result<()> {
         lazy_static! {
-            static ref table_1: BTreeMap<char, Token> = {
-                let mut value = BTreeMap::new();
-                value.insert('+', Token::Plus);
-                value.insert('-', Token::Minus);
-                value.insert('^', Token::Caret);
-                value.insert('*', Token::Asterisk);
-                value.insert('/', Token::Slash);
-                value.insert('%', Token::Percent);
-                value.insert('&', Token::Ampersand);
-                value.insert('|', Token::VerticalBar);
-                value.insert('<', Token::Less);
-                value.insert('>', Token::Greater);
-                value.insert('=', Token::Equal);
-                value.insert('(', Token::LParen);
-                value.insert(')', Token::RParen);
-                value.insert('[', Token::LSquareBracket);
-                value.insert(']', Token::RSquareBracket);
-                value.insert(',', Token::Comma);
-                value
-            };
-            static ref table_2: BTreeMap<(char, char), Token> = {
-                let mut value = BTreeMap::new();
-                value.insert(('&', '&'), Token::Ampersand2);
-                value.insert(('|', '|'), Token::VerticalBar2);
-                value.insert(('!', '='), Token::ExclamationEqual);
-                value.insert(('<', '='), Token::LessEqual);
-                value.insert(('>', '='), Token::GreaterEqual);
-                value.insert(('+', '='), Token::PlusEqual);
-                value.insert(('-', '='), Token::MinusEqual);
-                value.insert(('^', '='), Token::CaretEqual);
-                value
-            };
+            static ref table_1: BTreeMap<char, Token> = SYMBOLS_1.iter().cloned().collect();
+            static ref table_2: BTreeMap<(char, char), Token> = SYMBOLS_2.iter().cloned().collect();
         }
 
         let front = match self.front() {
@@ -136,7 +161,7 @@ impl Tokenizer {
             Some(character) => character,
         };
 
-        if [' ', '\n', '\t', '\r'].iter().any(|&c| c == front) {
+        if WHITESPACE.contains(&front) {
             self.push_buffer()?;
             self.advance(1);
             self.tokenize()?;
@@ -146,7 +171,7 @@ impl Tokenizer {
         if let Some(second) = self.get(1) {
             if let Some(token) = table_2.get(&(front, second)) {
                 self.push_buffer()?;
-                self.push_token(token.clone());
+                self.push_token(token.clone(), 2);
                 self.advance(2);
                 self.tokenize()?;
                 return Ok(());
@@ -155,12 +180,15 @@ impl Tokenizer {
 
         if let Some(token) = table_1.get(&front) {
             self.push_buffer()?;
-            self.push_token(token.clone());
+            self.push_token(token.clone(), 1);
             self.advance(1);
             self.tokenize()?;
             return Ok(());
         }
 
+        if self.buffer.is_empty() {
+            self.buffer_start = self.pos;
+        }
         self.buffer.push_back(front);
         self.advance(1);
         self.tokenize()?;
@@ -168,7 +196,7 @@ impl Tokenizer {
     }
 }
 
-impl From<Tokenizer> for LinkedList<Token> {
+impl From<Tokenizer> for LinkedList<(Token, Span)> {
     fn from(value: Tokenizer) -> Self {
         value.tokens
     }
@@ -0,0 +1,56 @@
+use crate::{
+    interpreter::{value::Value, Event, Observer},
+    tokenizer::span::{Pos, Span},
+};
+use anyhow::Result;
+use serde_json::{json, Value as Json};
+use std::io::Write;
+
+pub struct Trace<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Trace<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> Observer for Trace<W> {
+    fn stm(&mut self, event: &Event) -> Result<()> {
+        let changes = event
+            .changes
+            .iter()
+            .map(|change| {
+                json!({
+                    "var": change.x,
+                    "old": value_json(&change.old),
+                    "new": value_json(&change.new),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let record = json!({
+            "proc": event.proc,
+            "span": span_json(event.stm.span()),
+            "kind": event.stm.kind(),
+            "direction": event.direction.name(),
+            "depth": event.depth,
+            "changes": changes,
+        });
+        writeln!(self.out, "{record}")?;
+        Ok(())
+    }
+}
+
+pub fn value_json(value: &Value) -> Json {
+    match value {
+        Value::Int(n) => json!(n),
+        Value::Array(ns) | Value::Stack(ns) => json!(ns),
+    }
+}
+
+pub fn span_json(span: Span) -> Json {
+    let pos = |pos: Pos| json!({ "line": pos.line, "column": pos.column });
+    json!({ "start": pos(span.start), "end": pos(span.end) })
+}
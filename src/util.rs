@@ -16,3 +16,24 @@ pub fn read_file(path: impl AsRef<Path>) -> Result<LinkedList<char>> {
     file.read_to_string(&mut buf)?;
     Ok(buf.chars().collect())
 }
+
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        let width = (high as i64 - low as i64 + 1) as u64;
+        (low as i64 + (self.next_u64() % width) as i64) as i32
+    }
+}
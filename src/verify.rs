@@ -0,0 +1,217 @@
+use crate::{
+    ast::ast_node::{Arg, Prog, Stm, Type},
+    interpreter::{value::Value, Direction, Event, Interpreter, Observer},
+    tokenizer::span::Span,
+    util::Rng,
+};
+use anyhow::{Error, Result};
+use std::{collections::LinkedList, fmt};
+
+pub const MAX_STEPS: u64 = 1_000_000;
+
+#[derive(Debug, PartialEq)]
+struct Record {
+    proc: String,
+    span: Span,
+    kind: &'static str,
+    changes: Vec<(String, Value, Value)>,
+}
+
+#[derive(Debug, Default)]
+struct Recorder {
+    forward: Vec<Record>,
+    backward: Vec<Record>,
+}
+
+impl Observer for Recorder {
+    fn stm(&mut self, event: &Event) -> Result<()> {
+        if matches!(
+            event.stm,
+            Stm::Conditional { .. }
+                | Stm::Loop { .. }
+                | Stm::Local { .. }
+                | Stm::Call { .. }
+                | Stm::Uncall { .. }
+        ) {
+            return Ok(());
+        }
+        let changes = event
+            .changes
+            .iter()
+            .map(|change| (change.x.clone(), change.old.clone(), change.new.clone()))
+            .collect();
+        let record = Record {
+            proc: event.proc.to_string(),
+            span: event.stm.span(),
+            kind: event.stm.kind(),
+            changes,
+        };
+        match event.direction {
+            Direction::Forward => self.forward.push(record),
+            Direction::Backward => self.backward.push(record),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub proc: String,
+    pub span: Span,
+    pub kind: &'static str,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Identity,
+    Rejected(Error),
+    Diverged(Divergence),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "round trip diverges in {} at {} ({}): {}",
+            self.proc, self.span, self.kind, self.reason
+        )
+    }
+}
+
+impl Recorder {
+    fn divergence(&self, q: &str, reason: impl FnOnce() -> String) -> Divergence {
+        let matched = self
+            .backward
+            .iter()
+            .zip(self.forward.iter().rev())
+            .take_while(|(backward, forward)| undoes(backward, forward))
+            .count();
+        let pending = self
+            .forward
+            .len()
+            .checked_sub(matched + 1)
+            .map(|index| &self.forward[index]);
+
+        let reason = match (pending, self.backward.get(matched)) {
+            (Some(_), Some(backward)) => format!(
+                "{} in {} at {} changed {} instead",
+                backward.kind,
+                backward.proc,
+                backward.span,
+                show_changes(&backward.changes)
+            ),
+            _ => reason(),
+        };
+        match pending {
+            Some(forward) => Divergence {
+                proc: forward.proc.clone(),
+                span: forward.span,
+                kind: forward.kind,
+                reason,
+            },
+            None => Divergence {
+                proc: q.to_string(),
+                span: Span::default(),
+                kind: "Sequence",
+                reason,
+            },
+        }
+    }
+}
+
+fn undoes(backward: &Record, forward: &Record) -> bool {
+    backward.proc == forward.proc
+        && backward.span == forward.span
+        && backward.changes.len() == forward.changes.len()
+        && backward.changes.iter().zip(&forward.changes).all(
+            |((x_b, old_b, new_b), (x_f, old_f, new_f))| {
+                x_b == x_f && old_b == new_f && new_b == old_f
+            },
+        )
+}
+
+pub fn verify_main(prog: &Prog) -> Result<Verdict> {
+    let q = "main";
+    let mut recorder = Recorder::default();
+    let result = {
+        let mut interpreter = Interpreter::new(prog)?;
+        interpreter.observe(&mut recorder);
+        interpreter.limit_steps(MAX_STEPS);
+        match interpreter.run(Direction::Forward) {
+            Err(e) => return Ok(Verdict::Rejected(e)),
+            Ok(()) => interpreter
+                .run(Direction::Backward)
+                .map(|_| interpreter.store()),
+        }
+    };
+
+    let verdict = match result {
+        Err(e) => Verdict::Diverged(recorder.divergence(q, || format!("{e:#}"))),
+        Ok(store) => match store.iter().find(|(_, value)| !value.is_zero()) {
+            Some((x, value)) => Verdict::Diverged(
+                recorder.divergence(q, || format!("{x} is {value} instead of zero")),
+            ),
+            None => Verdict::Identity,
+        },
+    };
+    Ok(verdict)
+}
+
+pub fn verify_proc(prog: &Prog, q: &str, input: Vec<Value>) -> Result<Verdict> {
+    let mut recorder = Recorder::default();
+    let result = {
+        let mut interpreter = Interpreter::new(prog)?;
+        interpreter.observe(&mut recorder);
+        interpreter.limit_steps(MAX_STEPS);
+        match interpreter.call(q, input.clone(), Direction::Forward) {
+            Err(e) => return Ok(Verdict::Rejected(e)),
+            Ok(output) => interpreter.call(q, output, Direction::Backward),
+        }
+    };
+
+    let verdict = match result {
+        Err(e) => Verdict::Diverged(recorder.divergence(q, || format!("{e:#}"))),
+        Ok(values) if values == input => Verdict::Identity,
+        Ok(values) => Verdict::Diverged(recorder.divergence(q, || {
+            format!("expected {} found {}", show(&input), show(&values))
+        })),
+    };
+    Ok(verdict)
+}
+
+pub fn random_input(args: &LinkedList<Arg>, arrays: &[bool], rng: &mut Rng) -> Vec<Value> {
+    args.iter()
+        .zip(arrays)
+        .map(|(arg, &array)| match (arg.t, array) {
+            (Type::Int, false) => Value::Int(rng.range(0, 15)),
+            (Type::Int, true) => {
+                let len = rng.range(1, 8);
+                Value::Array((0..len).map(|_| rng.range(0, 15)).collect())
+            }
+            (Type::Stack, _) => {
+                let len = rng.range(0, 3);
+                Value::Stack((0..len).map(|_| rng.range(-15, 15)).collect())
+            }
+        })
+        .collect()
+}
+
+pub fn show(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn show_changes(changes: &[(String, Value, Value)]) -> String {
+    match changes.is_empty() {
+        true => "nothing".to_string(),
+        false => changes
+            .iter()
+            .map(|(x, old, new)| format!("{x}: {old} -> {new}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{interpret_main, STEPS};
+use janus::{
+    ast::ast_node::{Exp, Proc},
+    bennett::{
+        self,
+        syntax::{self, Program, Stmt},
+    },
+    interpreter::{eval_op, value::Value, Direction, Interpreter},
+    util::char_list,
+};
+use std::collections::BTreeMap;
+
+const SOURCE: &str = "
+input n = 5, k = 7
+output f, s
+f := 1
+s := 0
+while n > 0 do
+    f := f * n
+    if n % 2 then
+        k := k ^ n
+    else
+        s := s + n
+    end
+    n := n - 1
+end
+";
+
+fn eval(e: &Exp, env: &BTreeMap<String, i32>) -> i32 {
+    match e {
+        Exp::Constant(c) => c.0,
+        Exp::Variable(x) => env.get(&x.0).copied().unwrap_or(0),
+        Exp::BinOp(e_1, op, e_2) => eval_op(op, eval(e_1, env), eval(e_2, env)).unwrap(),
+        _ => unreachable!("the irreversible language only has integer expressions"),
+    }
+}
+
+fn exec(block: &[Stmt], env: &mut BTreeMap<String, i32>) {
+    for s in block {
+        match s {
+            Stmt::Assign(x, e) => {
+                let n = eval(e, env);
+                env.insert(x.0.clone(), n);
+            }
+            Stmt::If(e, s_then, s_else) => match eval(e, env) {
+                0 => exec(s_else, env),
+                _ => exec(s_then, env),
+            },
+            Stmt::While(e, s) => {
+                while eval(e, env) != 0 {
+                    exec(s, env);
+                }
+            }
+            Stmt::Skip => {}
+        }
+    }
+}
+
+fn expected(program: &Program, inputs: &[i32]) -> Vec<i32> {
+    let mut env = program
+        .inputs
+        .iter()
+        .zip(inputs)
+        .map(|((x, _), &n)| (x.0.clone(), n))
+        .collect();
+    exec(&program.body, &mut env);
+    program.outputs.iter().map(|x| env[&x.0]).collect()
+}
+
+#[test]
+fn translation_preserves_outputs_and_clears_garbage() {
+    let program = syntax::parse(char_list(SOURCE)).unwrap();
+    let prog = bennett::translate(&program).unwrap();
+    let Some(Proc::Other { args, .. }) = prog.find("run") else {
+        panic!("the translation has no run procedure");
+    };
+    assert_eq!(args.len(), program.inputs.len() + program.outputs.len());
+    for inputs in [[5, 7], [0, 0], [6, -3], [1, 1], [9, 100]] {
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_steps(STEPS);
+        let input = inputs
+            .iter()
+            .map(|&n| Value::Int(n))
+            .chain(program.outputs.iter().map(|_| Value::Int(0)))
+            .collect::<Vec<_>>();
+        let output = interpreter
+            .call("run", input.clone(), Direction::Forward)
+            .unwrap_or_else(|e| panic!("{inputs:?}: garbage was left behind: {e:#}"));
+        let (kept, results) = output.split_at(inputs.len());
+        assert_eq!(kept, &input[..inputs.len()], "{inputs:?}: inputs changed");
+        let results = results
+            .iter()
+            .map(|value| match value {
+                Value::Int(n) => *n,
+                _ => unreachable!("results are integers"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results, expected(&program, &inputs), "{inputs:?}");
+
+        let restored = interpreter
+            .call("run", output, Direction::Backward)
+            .unwrap();
+        assert_eq!(restored, input, "{inputs:?}: uncalling run");
+    }
+
+    let store = interpret_main(&prog, Direction::Forward).unwrap();
+    let names = store.iter().map(|(x, _)| x.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["n", "k", "f_out", "s_out"]);
+    let outputs = store[2..]
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect::<Vec<_>>();
+    let expected = expected(&program, &[5, 7])
+        .into_iter()
+        .map(Value::Int)
+        .collect::<Vec<_>>();
+    assert_eq!(outputs, expected);
+}
@@ -0,0 +1,301 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, Case};
+use janus::{
+    absint::{self, Facts},
+    ast::{self, ast_node::Prog},
+    backend,
+    interpreter::{value::Value, Direction},
+    util::char_list,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+fn compile(name: &str, source: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{name}.c"));
+    let binary = dir.join(name);
+    fs::write(&path, source).unwrap();
+    let status = Command::new("cc")
+        .arg("-Dmain=janus_main")
+        .arg("-o")
+        .arg(&binary)
+        .arg(&path)
+        .status()
+        .expect("cc is installed");
+    assert!(status.success(), "{name}: cc failed");
+    binary
+}
+
+fn run(binary: &Path, args: &[String]) -> Output {
+    Command::new(binary).args(args).output().unwrap()
+}
+
+fn check_main(name: &str, prog: &Prog, checks: bool, facts: &Facts) {
+    let source = backend::c::emit(prog, checks, facts).unwrap();
+    let binary = compile(
+        name,
+        &format!("{source}\n#undef main\nint main(void) {{ return janus_main(); }}\n"),
+    );
+    let output = run(&binary, &[]);
+    match interpret_main(prog, Direction::Forward) {
+        Ok(store) => {
+            assert!(
+                output.status.success(),
+                "{name}: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            let expected = store
+                .iter()
+                .map(|(x, value)| format!("{x} = {value}\n"))
+                .collect::<String>();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{name}");
+        }
+        Err(e) if checks => assert_eq!(output.status.code(), Some(1), "{name}: expected {e:#}"),
+        Err(_) => {}
+    }
+}
+
+fn driver(cases: &[Case]) -> String {
+    let mut out = String::from("\n#undef main\nint main(int argc, char **argv) {\n");
+    out.push_str("    switch (argc > 1 ? atoi(argv[1]) : -1) {\n");
+    for (i, case) in cases.iter().enumerate() {
+        out.push_str(&format!("    case {i}: {{\n"));
+        let mut params = Vec::new();
+        for (j, value) in case.input.iter().enumerate() {
+            match value {
+                Value::Int(n) => {
+                    out.push_str(&format!("        int32_t a{j} = {n};\n"));
+                    params.push(format!("&a{j}"));
+                }
+                Value::Array(ns) => {
+                    let ns = ns.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+                    out.push_str(&format!(
+                        "        int32_t a{j}[] = {{{}}};\n",
+                        ns.join(", ")
+                    ));
+                    params.push(format!("a{j}, {}", ns.len()));
+                }
+                Value::Stack(ns) => {
+                    out.push_str(&format!("        janus_stack a{j} = {{0}};\n"));
+                    for n in ns {
+                        out.push_str(&format!(
+                            "        {{ int32_t n = {n}; janus_push(&n, &a{j}); }}\n"
+                        ));
+                    }
+                    params.push(format!("&a{j}"));
+                }
+            }
+        }
+        let function = match case.direction {
+            Direction::Forward => format!("janus_fwd_{}", case.q),
+            Direction::Backward => format!("janus_bwd_{}", case.q),
+        };
+        out.push_str(&format!("        {function}({});\n", params.join(", ")));
+        for (j, value) in case.input.iter().enumerate() {
+            let line = match value {
+                Value::Int(_) => format!("printf(\"a{j} = %d\\n\", a{j});"),
+                Value::Array(ns) => format!("janus_print_array(\"a{j}\", a{j}, {});", ns.len()),
+                Value::Stack(_) => format!("janus_print_stack(\"a{j}\", &a{j});"),
+            };
+            out.push_str(&format!("        {line}\n"));
+        }
+        out.push_str("        return 0;\n    }\n");
+    }
+    out.push_str("    }\n    return 2;\n}\n");
+    out
+}
+
+fn check_procedures(name: &str, prog: &Prog, checks: bool, facts: &Facts) -> usize {
+    let cases = cases(prog, 20)
+        .into_iter()
+        .filter_map(|case| interpret(prog, &case).map(|expected| (case, expected)))
+        .collect::<Vec<_>>();
+    let source = backend::c::emit(prog, checks, facts).unwrap();
+    let (cases, expected): (Vec<_>, Vec<_>) = cases.into_iter().unzip();
+    let binary = compile(name, &format!("{source}{}", driver(&cases)));
+    let mut failures = 0;
+    for (i, (case, expected)) in cases.iter().zip(expected).enumerate() {
+        if expected.is_err() && !checks {
+            continue;
+        }
+        let output = run(&binary, &[i.to_string()]);
+        match expected {
+            Ok(values) => {
+                assert!(
+                    output.status.success(),
+                    "{}: {}",
+                    describe(name, case),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                let expected = values
+                    .iter()
+                    .enumerate()
+                    .map(|(j, value)| format!("a{j} = {value}\n"))
+                    .collect::<String>();
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    expected,
+                    "{}",
+                    describe(name, case)
+                );
+            }
+            Err(e) if checks => {
+                failures += 1;
+                assert_eq!(
+                    output.status.code(),
+                    Some(1),
+                    "{}: expected {e:#}",
+                    describe(name, case)
+                );
+            }
+            Err(_) => {}
+        }
+    }
+    failures
+}
+
+#[test]
+fn compiled_main_agrees_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            check_main(&name, &prog, true, &Facts::default());
+        }
+    });
+}
+
+#[test]
+fn compiled_procedures_agree_with_interpreter_in_both_directions() {
+    deep(|| {
+        let mut failures = 0;
+        for (name, prog) in corpus() {
+            failures += check_procedures(&format!("{name}_procs"), &prog, true, &Facts::default());
+        }
+        assert!(failures > 0, "no input made the interpreter fail");
+    });
+}
+
+#[test]
+fn unchecked_code_agrees_on_successful_runs() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            let name = format!("{name}_unchecked");
+            check_main(&name, &prog, false, &Facts::default());
+            check_procedures(&format!("{name}_procs"), &prog, false, &Facts::default());
+        }
+    });
+}
+
+#[test]
+fn code_compiled_with_facts_agrees_and_still_traps() {
+    deep(|| {
+        let mut failures = 0;
+        for (name, prog) in corpus() {
+            let facts = absint::analyze(&prog);
+            let name = format!("{name}_facts");
+            check_main(&name, &prog, true, &facts);
+            failures += check_procedures(&format!("{name}_procs"), &prog, true, &facts);
+        }
+        assert!(failures > 0, "no input made the interpreter fail");
+    });
+}
+
+#[test]
+fn no_checks_skips_failing_assertions() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    if x = 0 then
+        x += 1
+    else
+        skip
+    fi x = 0
+",
+    ))
+    .unwrap();
+    assert!(interpret_main(&prog, Direction::Forward).is_err());
+    let facts = absint::analyze(&prog);
+    for (checks, name, facts) in [
+        (true, "assertion_checked", &Facts::default()),
+        (true, "assertion_checked_facts", &facts),
+        (false, "assertion_unchecked", &Facts::default()),
+    ] {
+        let source = backend::c::emit(&prog, checks, facts).unwrap();
+        let binary = compile(
+            name,
+            &format!("{source}\n#undef main\nint main(void) {{ return janus_main(); }}\n"),
+        );
+        let output = run(&binary, &[]);
+        assert_eq!(output.status.success(), !checks, "{name}");
+        if checks {
+            assert!(String::from_utf8_lossy(&output.stderr).contains("assertion fi failed"));
+        } else {
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "x = 1\n");
+        }
+    }
+}
+
+#[test]
+fn user_names_do_not_collide_with_generated_ones() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int janus_c
+    int janus_p
+    int janus_var_janus_c
+    int janus_len_a
+    int printf
+    int x
+    int f_fwd
+    int a[2]
+    x += 3
+    call f(x, janus_c)
+    janus_len_a += 2
+    call g(a, janus_len_a)
+    janus_var_janus_c += 1
+    printf += janus_c
+    f_fwd += 5
+
+procedure f(int x, int janus_c)
+    if x > 0 then
+        janus_c += x
+    else
+        skip
+    fi janus_c > 0
+    local int x = x + 1
+        janus_c += x
+    delocal int x = janus_c - 3
+
+procedure g(int a, int janus_len_a)
+    local int janus_p = 1
+        a[janus_p] += janus_len_a
+        a[0] += janus_p
+    delocal int janus_p = a[0]
+",
+    ))
+    .unwrap();
+    let store = interpret_main(&prog, Direction::Forward).unwrap();
+    assert!(store.contains(&("janus_c".to_string(), Value::Int(7))));
+    check_main("collisions", &prog, true, &Facts::default());
+}
+
+#[test]
+fn logical_operators_evaluate_both_operands() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    if (x = 1) && ((1 / x) = 1) then
+        y += 1
+    else
+        skip
+    fi y = 1
+",
+    ))
+    .unwrap();
+    assert!(interpret_main(&prog, Direction::Forward).is_err());
+    check_main("strict_and", &prog, true, &Facts::default());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::corpus;
+use janus::{ast, callgraph::CallGraph, interpreter::Direction, util::char_list};
+use std::collections::BTreeSet;
+
+const SOURCE: &str = "procedure main()
+    int x
+    int y
+    call f(x, y)
+    uncall g(x, y)
+
+procedure f(int x, int y)
+    if x > 0 then
+        x -= 1
+        call f(x, y)
+        x += 1
+    else
+        skip
+    fi x > 0
+
+procedure g(int x, int y)
+    call h(x, y)
+
+procedure h(int x, int y)
+    if x < 0 then
+        x += 1
+        uncall g(x, y)
+        x -= 1
+    else
+        y += x
+    fi x < 0
+
+procedure dead(int x)
+    call f(x, x)
+";
+
+fn graph() -> CallGraph {
+    CallGraph::new(&ast::parse(char_list(SOURCE)).unwrap())
+}
+
+fn set<'a>(qs: &[&'a str]) -> BTreeSet<&'a str> {
+    qs.iter().copied().collect()
+}
+
+#[test]
+fn edges_record_callers_callees_and_directions() {
+    let graph = graph();
+    let edges = graph
+        .edges
+        .iter()
+        .map(|edge| (edge.caller.as_str(), edge.callee.as_str(), edge.direction))
+        .collect::<Vec<_>>();
+    for edge in [
+        ("main", "f", Direction::Forward),
+        ("main", "g", Direction::Backward),
+        ("f", "f", Direction::Forward),
+        ("g", "h", Direction::Forward),
+        ("h", "g", Direction::Backward),
+        ("dead", "f", Direction::Forward),
+    ] {
+        assert!(edges.contains(&edge), "no {edge:?} in {edges:?}");
+    }
+    assert_eq!(edges.len(), 6);
+    assert_eq!(graph.callees("main"), set(&["f", "g"]));
+    assert_eq!(graph.callers("f"), set(&["dead", "f", "main"]));
+    assert_eq!(graph.callers("main"), set(&[]));
+}
+
+#[test]
+fn reachability_and_recursion() {
+    let graph = graph();
+    assert_eq!(graph.reachable("main"), set(&["f", "g", "h"]));
+    assert_eq!(graph.reachable("g"), set(&["g", "h"]));
+    assert!(!graph.reachable("main").contains("dead"));
+    for (q, recursive) in [
+        ("main", false),
+        ("f", true),
+        ("g", true),
+        ("h", true),
+        ("dead", false),
+    ] {
+        assert_eq!(graph.is_recursive(q), recursive, "{q}");
+    }
+    let mut cycles = graph
+        .cycles()
+        .into_iter()
+        .map(|mut cycle| {
+            cycle.sort();
+            cycle
+        })
+        .collect::<Vec<_>>();
+    cycles.sort();
+    assert_eq!(cycles, [vec!["f"], vec!["g", "h"]]);
+}
+
+#[test]
+fn exports_mark_inverse_edges_and_unreached_procedures() {
+    let graph = graph();
+    let dot = graph.dot();
+    assert!(dot.starts_with("digraph callgraph {\n"), "{dot}");
+    assert!(
+        dot.contains("\"main\" -> \"g\" [label=\"uncall\", style=dashed]"),
+        "{dot}"
+    );
+    assert!(dot.contains("\"main\" -> \"f\" [label=\"call\"]"), "{dot}");
+    assert!(
+        dot.contains("\"dead\" [color=gray, fontcolor=gray]"),
+        "{dot}"
+    );
+    assert!(dot.contains("\"f\" [peripheries=2]"), "{dot}");
+
+    let json = graph.to_json();
+    let procedures = json["procedures"].as_array().unwrap();
+    assert_eq!(procedures.len(), 5);
+    for procedure in procedures {
+        let q = procedure["name"].as_str().unwrap();
+        assert_eq!(procedure["reachable"], q != "dead", "{q}");
+        assert_eq!(procedure["recursive"], graph.is_recursive(q), "{q}");
+    }
+    let directions = json["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|edge| edge["from"] == "main")
+        .map(|edge| {
+            (
+                edge["to"].as_str().unwrap(),
+                edge["direction"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(directions, [("f", "forward"), ("g", "inverse")]);
+    assert!(json["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|edge| edge["defined"] == true));
+}
+
+#[test]
+fn corpus_recursion_matches_reachability() {
+    for (name, prog) in corpus() {
+        let graph = CallGraph::new(&prog);
+        let recursive = graph
+            .cycles()
+            .into_iter()
+            .flatten()
+            .collect::<BTreeSet<_>>();
+        for q in &graph.procs {
+            assert_eq!(
+                graph.is_recursive(q),
+                recursive.contains(q.as_str()),
+                "{name}: {q}"
+            );
+            for r in graph.reachable(q) {
+                assert!(graph.defines(r), "{name}: {q} reaches undefined {r}");
+            }
+        }
+        if name == "recursion" {
+            assert!(graph.is_recursive("fib"));
+            assert!(!graph.is_recursive("twice"));
+            assert_eq!(graph.reachable("main"), set(&["fib"]));
+            assert!(graph
+                .edges
+                .iter()
+                .any(|edge| edge.caller == "twice" && edge.direction == Direction::Backward));
+        }
+    }
+}
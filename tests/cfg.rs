@@ -0,0 +1,133 @@
+mod common;
+
+use common::corpus;
+use janus::{ast, cfg::dot, util::char_list};
+use std::collections::{BTreeMap, BTreeSet};
+
+struct Graph {
+    clusters: Vec<String>,
+    labels: BTreeMap<String, String>,
+    edges: Vec<(String, String)>,
+}
+
+fn graph(dot: &str) -> Graph {
+    let mut graph = Graph {
+        clusters: Vec::new(),
+        labels: BTreeMap::new(),
+        edges: Vec::new(),
+    };
+    for line in dot.lines().map(str::trim) {
+        if let Some(cluster) = line.strip_prefix("subgraph ") {
+            graph
+                .clusters
+                .push(cluster.trim_end_matches(" {").to_string());
+        } else if let Some((a, rest)) = line.split_once(" -> ") {
+            let b = rest.split_whitespace().next().unwrap();
+            graph.edges.push((a.to_string(), b.to_string()));
+        } else if let Some((node, rest)) = line.split_once(" [") {
+            if node[1..].parse::<usize>().is_ok() {
+                let label = rest.split("label=\"").nth(1).unwrap();
+                let label = label.rsplit_once("\"]").unwrap().0;
+                graph.labels.insert(node.to_string(), label.to_string());
+            }
+        }
+    }
+    graph
+}
+
+fn check(name: &str, q: &str, dot: &str, clusters: usize) -> Graph {
+    assert!(
+        dot.starts_with(&format!("digraph \"{q}\" {{\n")),
+        "{name}: {q}\n{dot}"
+    );
+    assert_eq!(
+        dot.matches('{').count(),
+        dot.matches('}').count(),
+        "{name}: {q}: unbalanced braces"
+    );
+    let graph = graph(dot);
+    assert_eq!(graph.clusters.len(), clusters, "{name}: {q}\n{dot}");
+    for (a, b) in &graph.edges {
+        assert!(
+            graph.labels.contains_key(a) && graph.labels.contains_key(b),
+            "{name}: {q}: edge {a} -> {b} has an undeclared end"
+        );
+    }
+    for label in ["begin", "end"] {
+        let count = graph.labels.values().filter(|x| *x == label).count();
+        assert_eq!(count, clusters, "{name}: {q}: {label} nodes");
+    }
+    let mut reached = graph
+        .labels
+        .iter()
+        .filter(|(_, label)| *label == "begin")
+        .map(|(node, _)| node.clone())
+        .collect::<BTreeSet<_>>();
+    let mut work = reached.iter().cloned().collect::<Vec<_>>();
+    while let Some(node) = work.pop() {
+        for (_, b) in graph.edges.iter().filter(|(a, _)| *a == node) {
+            if reached.insert(b.clone()) {
+                work.push(b.clone());
+            }
+        }
+    }
+    let unreached = graph
+        .labels
+        .keys()
+        .filter(|node| !reached.contains(*node))
+        .collect::<Vec<_>>();
+    assert!(
+        unreached.is_empty(),
+        "{name}: {q}: unreachable {unreached:?}"
+    );
+    graph
+}
+
+#[test]
+fn graphs_are_well_formed_for_corpus() {
+    for (name, prog) in corpus() {
+        for p in prog.procs() {
+            let q = p.name();
+            let forward = check(&name, q, &dot(&prog, q, false).unwrap(), 1);
+            let both = check(&name, q, &dot(&prog, q, true).unwrap(), 2);
+            assert_eq!(both.labels.len(), 2 * forward.labels.len(), "{name}: {q}");
+            assert_eq!(both.edges.len(), 2 * forward.edges.len(), "{name}: {q}");
+        }
+    }
+}
+
+#[test]
+fn inverse_graph_inverts_updates() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    x += 1
+    call f(x, y)
+
+procedure f(int x, int y)
+    if x < y then
+        y -= x
+    else
+        x += y
+    fi x < y
+",
+    ))
+    .unwrap();
+    let dot = dot(&prog, "f", true).unwrap();
+    let graph = check("inline", "f", &dot, 2);
+    assert_eq!(graph.clusters, ["cluster_forward", "cluster_backward"]);
+    let (forward, backward) = dot.split_once("subgraph cluster_backward").unwrap();
+    assert!(forward.contains("label=\"y -= x\\l\""), "{dot}");
+    assert!(forward.contains("label=\"x += y\\l\""), "{dot}");
+    assert!(backward.contains("label=\"y += x\\l\""), "{dot}");
+    assert!(backward.contains("label=\"x -= y\\l\""), "{dot}");
+    assert!(forward.contains("label=\"call f\""), "{dot}");
+    assert!(backward.contains("label=\"uncall f\""), "{dot}");
+}
+
+#[test]
+fn unknown_procedures_are_rejected() {
+    let (_, prog) = corpus().remove(0);
+    assert!(dot(&prog, "no_such_procedure", false).is_err());
+}
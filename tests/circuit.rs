@@ -0,0 +1,150 @@
+mod common;
+
+use common::{corpus, STEPS};
+use janus::{
+    ast::ast_node::{PId, Proc, Prog, Stm, Var},
+    callgraph::CallGraph,
+    circuit::{synth::synthesize, wrap, Overflow},
+    interpreter::{value::Value, Direction, Interpreter},
+    util::Rng,
+};
+use std::collections::LinkedList;
+
+const WIDTH: usize = 8;
+
+const SAMPLES: usize = 50;
+
+fn unsupported(message: &str) -> bool {
+    [
+        "not supported",
+        "only integer parameters",
+        "depends on circuit inputs",
+    ]
+    .iter()
+    .any(|reason| message.contains(reason))
+}
+
+fn flatten(values: Vec<Value>) -> Vec<i32> {
+    values
+        .into_iter()
+        .flat_map(|value| match value {
+            Value::Int(n) => vec![n],
+            Value::Array(ns) | Value::Stack(ns) => ns,
+        })
+        .collect()
+}
+
+fn interpret(prog: &Prog, q: &str, inputs: &[i32], direction: Direction) -> Option<Vec<i32>> {
+    let mut overflow = Overflow {
+        width: WIDTH,
+        overflowed: false,
+    };
+    let mut interpreter = Interpreter::new(prog).unwrap();
+    interpreter.limit_steps(STEPS);
+    interpreter.observe(&mut overflow);
+    let result = match q {
+        "main" => interpreter
+            .run(direction)
+            .map(|_| interpreter.store().into_iter().map(|(_, v)| v).collect()),
+        _ => interpreter.call(
+            q,
+            inputs.iter().map(|&n| Value::Int(n)).collect(),
+            direction,
+        ),
+    };
+    let ns = flatten(result.ok()?);
+    (!overflow.overflowed && ns.iter().all(|&n| n == wrap(n, WIDTH))).then_some(ns)
+}
+
+fn backward(prog: &Prog, q: &str) -> (Prog, String) {
+    let Some(Proc::Other { q: name, args, .. }) = prog.find(q) else {
+        unreachable!("{q} is a procedure");
+    };
+    let wrapper = format!("{q}_backward");
+    let mut prog = prog.clone();
+    prog.ps.push_back(Proc::Other {
+        q: PId(wrapper.clone(), name.1),
+        args: args.clone(),
+        s: Stm::Uncall {
+            q: name.clone(),
+            xs: args
+                .iter()
+                .map(|arg| Var(arg.x.0.clone(), arg.x.1))
+                .collect::<LinkedList<_>>(),
+            span: name.1,
+        },
+    });
+    (prog, wrapper)
+}
+
+fn check(
+    name: &str,
+    prog: &Prog,
+    q: &str,
+    synthesized: &Prog,
+    entry: &str,
+    direction: Direction,
+) -> usize {
+    let circuit = match synthesize(synthesized, entry, WIDTH) {
+        Ok(circuit) => circuit,
+        Err(e) if unsupported(&format!("{e:#}")) => return 0,
+        Err(e) => {
+            assert!(
+                q == "main" && interpret(prog, q, &[], direction).is_none(),
+                "{name}: {q}: {e:#}"
+            );
+            return 0;
+        }
+    };
+    let mut rng = Rng::new(1);
+    let low = wrap(1 << (WIDTH - 1), WIDTH);
+    let samples = match q {
+        "main" => 1,
+        _ => SAMPLES,
+    };
+    let mut agreed = 0;
+    for _ in 0..samples * 10 {
+        let inputs = (0..circuit.inputs.len())
+            .map(|_| rng.range(low, !low))
+            .collect::<Vec<_>>();
+        let Some(expected) = interpret(prog, q, &inputs, direction) else {
+            continue;
+        };
+        assert_eq!(
+            circuit
+                .simulate(&inputs)
+                .unwrap_or_else(|e| panic!("{name}: {q}: {e:#}")),
+            expected,
+            "{name}: {} {q} with input {inputs:?}",
+            direction.name()
+        );
+        agreed += 1;
+        if agreed == samples {
+            break;
+        }
+    }
+    agreed
+}
+
+#[test]
+fn circuits_agree_with_interpreter_in_both_directions() {
+    let mut agreed = [0, 0];
+    for (name, prog) in corpus() {
+        let graph = CallGraph::new(&prog);
+        for p in prog.ps.iter().chain([&prog.p_main]) {
+            let q = p.name();
+            if graph.reachable(q).iter().any(|r| graph.is_recursive(r)) {
+                continue;
+            }
+            agreed[0] += check(&name, &prog, q, &prog, q, Direction::Forward);
+            if q != "main" {
+                let (synthesized, wrapper) = backward(&prog, q);
+                agreed[1] += check(&name, &prog, q, &synthesized, &wrapper, Direction::Backward);
+            }
+        }
+    }
+    assert!(
+        agreed.iter().all(|&n| n > 0),
+        "too few circuits ran: {agreed:?}"
+    );
+}
@@ -0,0 +1,99 @@
+#![allow(dead_code)]
+
+use anyhow::Result;
+use janus::{
+    ast::{self, ast_node::*},
+    backend::array_params,
+    interpreter::{exhausted, value::Value, Direction, Interpreter},
+    util::Rng,
+    verify::random_input,
+};
+use std::{fs, path::Path};
+
+pub const STEPS: u64 = 100_000;
+
+pub const DEPTH: usize = 200;
+
+pub const DIRECTIONS: [Direction; 2] = [Direction::Forward, Direction::Backward];
+
+pub struct Case {
+    pub q: String,
+    pub direction: Direction,
+    pub input: Vec<Value>,
+}
+
+pub fn corpus() -> Vec<(String, Prog)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let prog = ast::load(&path).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+            (name, prog)
+        })
+        .collect()
+}
+
+pub fn cases(prog: &Prog, samples: usize) -> Vec<Case> {
+    let mut rng = Rng::new(1);
+    let arrays = array_params(prog);
+    let mut cases = Vec::new();
+    for p in &prog.ps {
+        let Proc::Other { q, args, .. } = p else {
+            continue;
+        };
+        for direction in DIRECTIONS {
+            for _ in 0..samples {
+                cases.push(Case {
+                    q: q.0.clone(),
+                    direction,
+                    input: random_input(args, &arrays[q.0.as_str()], &mut rng),
+                });
+            }
+        }
+    }
+    cases
+}
+
+pub fn interpret(prog: &Prog, case: &Case) -> Option<Result<Vec<Value>>> {
+    let mut interpreter = Interpreter::new(prog).unwrap();
+    interpreter.limit_steps(STEPS);
+    interpreter.limit_depth(DEPTH);
+    let result = interpreter.call(&case.q, case.input.clone(), case.direction);
+    match result {
+        Err(e) if exhausted(&e) => None,
+        result => Some(result),
+    }
+}
+
+pub fn interpret_main(prog: &Prog, direction: Direction) -> Result<Vec<(String, Value)>> {
+    let mut interpreter = Interpreter::new(prog)?;
+    interpreter.limit_steps(STEPS);
+    interpreter.limit_depth(DEPTH);
+    interpreter.run(direction)?;
+    Ok(interpreter.store())
+}
+
+pub fn describe(name: &str, case: &Case) -> String {
+    format!(
+        "{name}: {} {} with input {:?}",
+        case.direction.name(),
+        case.q,
+        case.input
+    )
+}
+
+pub fn deep(f: impl FnOnce() + Send + 'static) {
+    let child = std::thread::Builder::new()
+        .stack_size(512 * 1024 * 1024)
+        .spawn(f)
+        .unwrap();
+    if let Err(payload) = child.join() {
+        std::panic::resume_unwind(payload);
+    }
+}
@@ -0,0 +1,61 @@
+use janus::{
+    ast,
+    coverage::Coverage,
+    interpreter::{Direction, Interpreter},
+};
+use std::path::Path;
+
+#[test]
+fn lcov_records_cover_recursion_corpus() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs/recursion.txt");
+    let prog = ast::load(&path).unwrap();
+    let mut coverage = Coverage::default();
+    let mut interpreter = Interpreter::new(&prog).unwrap();
+    interpreter.observe(&mut coverage);
+    interpreter.run(Direction::Forward).unwrap();
+    interpreter.run(Direction::Backward).unwrap();
+    drop(interpreter);
+
+    let lcov = coverage.lcov(&prog, "recursion.txt");
+    let records = lcov.split("end_of_record\n").collect::<Vec<_>>();
+    assert_eq!(records.len(), 3, "{lcov}");
+    assert_eq!(records[2], "");
+    for (record, direction) in records.iter().zip(["forward", "backward"]) {
+        let lines = record.lines().collect::<Vec<_>>();
+        for expected in [
+            format!("TN:{direction}").as_str(),
+            "SF:recursion.txt",
+            "FN:1,main",
+            "FN:7,fib",
+            "FN:17,twice",
+            "FN:21,spin",
+            "FNDA:1,main",
+            "FNDA:6,fib",
+            "FNDA:0,twice",
+            "FNDA:0,spin",
+            "FNF:4",
+            "FNH:2",
+            "BRDA:8,0,0,5",
+            "BRDA:8,0,1,1",
+            "BRDA:22,1,0,-",
+            "BRDA:22,1,1,-",
+            "BRF:4",
+            "BRH:2",
+            "DA:4,1",
+            "DA:5,1",
+            "DA:8,6",
+            "DA:9,5",
+            "DA:14,1",
+            "DA:18,0",
+            "DA:25,0",
+            "LH:8",
+        ] {
+            assert!(
+                lines.contains(&expected),
+                "{direction}: no {expected}\n{record}"
+            );
+        }
+        let counts = lines.iter().filter(|line| line.starts_with("DA:")).count();
+        assert!(lines.contains(&format!("LF:{counts}").as_str()), "{record}");
+    }
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use common::{corpus, deep};
+use janus::{
+    ast::{self, ast_node::Prog},
+    circuit::wrap,
+    interpreter::{value::Value, Direction, Interpreter},
+    prove::{
+        equiv::{equiv, Options, Outcome, Verdict},
+        Method, UNROLL,
+    },
+    util::char_list,
+};
+
+const WIDTH: usize = 4;
+
+const METHODS: [Method; 3] = [Method::Random, Method::Exhaustive, Method::Symbolic];
+
+const SOURCE: &str = "procedure main()
+    int x
+    int y
+    call add(x, y)
+
+procedure add(int x, int y)
+    y += x
+    y += x
+
+procedure add_refactored(int x, int y)
+    local int t = x
+        t += x
+        y += t
+        t -= x
+    delocal int t = x
+
+procedure add_except_five(int x, int y)
+    if x = 5 then
+        y += 1
+    else
+        y += x
+        y += x
+    fi x = 5
+
+procedure add_positive(int x, int y)
+    from x >= 0 do
+        skip
+    loop
+        skip
+    until 1
+    y += x
+    y += x
+
+procedure add_three(int x, int y, int z)
+    y += x
+    y += x
+
+procedure add_array(int x, int a)
+    a[0] += x
+";
+
+fn options(method: Method) -> Options {
+    Options {
+        width: WIDTH,
+        method: Some(method),
+        unroll: UNROLL,
+        samples: 1000,
+        seed: 1,
+    }
+}
+
+fn prog() -> Prog {
+    ast::parse(char_list(SOURCE)).unwrap()
+}
+
+fn call(prog: &Prog, q: &str, input: &[(String, i32)]) -> Option<Vec<i32>> {
+    let mut interpreter = Interpreter::new(prog).unwrap();
+    let values = input.iter().map(|&(_, n)| Value::Int(n)).collect();
+    let output = interpreter.call(q, values, Direction::Forward).ok()?;
+    Some(
+        output
+            .into_iter()
+            .map(|value| match value {
+                Value::Int(n) => wrap(n, WIDTH),
+                _ => unreachable!("the parameters are integers"),
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn refactored_procedures_are_equivalent_by_every_method() {
+    let prog = prog();
+    for method in METHODS {
+        let report = equiv(&prog, "add", "add_refactored", &options(method)).unwrap();
+        assert!(
+            matches!(report.verdict, Verdict::Equivalent),
+            "{}: {:?}",
+            method.name(),
+            report.verdict
+        );
+        assert_eq!(report.method, method);
+        assert_eq!(report.bits, 2 * WIDTH);
+    }
+    let report = equiv(&prog, "add", "add", &options(Method::Exhaustive)).unwrap();
+    assert_eq!(report.inputs, 1 << (2 * WIDTH));
+}
+
+#[test]
+fn differences_come_with_a_counterexample() {
+    let prog = prog();
+    for method in METHODS {
+        let report = equiv(&prog, "add", "add_except_five", &options(method)).unwrap();
+        let Verdict::Differ { input, left, right } = report.verdict else {
+            panic!("{}: {:?}", method.name(), report.verdict);
+        };
+        assert_eq!(input[0], ("x".to_string(), 5), "{}", method.name());
+        let (Outcome::Returns { output: left }, Outcome::Returns { output: right }) = (left, right)
+        else {
+            panic!("{}: both sides return", method.name());
+        };
+        let values = |output: &[(String, i32)]| output.iter().map(|&(_, n)| n).collect::<Vec<_>>();
+        assert_ne!(values(&left), values(&right), "{}", method.name());
+        assert_eq!(
+            call(&prog, "add", &input),
+            Some(values(&left)),
+            "{}",
+            method.name()
+        );
+        assert_eq!(
+            call(&prog, "add_except_five", &input),
+            Some(values(&right)),
+            "{}",
+            method.name()
+        );
+    }
+}
+
+#[test]
+fn failing_runs_differ_from_returning_ones() {
+    let prog = prog();
+    for method in METHODS {
+        let report = equiv(&prog, "add", "add_positive", &options(method)).unwrap();
+        let Verdict::Differ { input, left, right } = report.verdict else {
+            panic!("{}: {:?}", method.name(), report.verdict);
+        };
+        assert!(input[0].1 < 0, "{}: {input:?}", method.name());
+        assert!(matches!(left, Outcome::Returns { .. }), "{}", method.name());
+        assert!(matches!(right, Outcome::Fails { .. }), "{}", method.name());
+        assert_eq!(call(&prog, "add_positive", &input), None);
+    }
+}
+
+#[test]
+fn argument_lists_must_match() {
+    let prog = prog();
+    for (left, right, message) in [
+        (
+            "add",
+            "add_three",
+            "add has 2 parameters but add_three has 3",
+        ),
+        (
+            "add",
+            "add_array",
+            "parameter 2 is int in add but array in add_array",
+        ),
+        ("main", "add", "main has no parameters to compare"),
+        ("add", "missing", "procedure missing is not defined"),
+    ] {
+        let e = equiv(&prog, left, right, &options(Method::Random)).unwrap_err();
+        assert!(format!("{e:#}").contains(message), "{left} {right}: {e:#}");
+    }
+    let mut wide = options(Method::Random);
+    wide.width = 33;
+    assert!(equiv(&prog, "add", "add", &wide).is_err());
+}
+
+#[test]
+fn corpus_procedures_are_equivalent_to_themselves() {
+    deep(|| {
+        let mut checked = 0;
+        for (name, prog) in corpus() {
+            for p in prog.ps.iter() {
+                let q = p.name();
+                let Ok(report) = equiv(&prog, q, q, &options(Method::Symbolic)) else {
+                    continue;
+                };
+                assert!(
+                    !matches!(report.verdict, Verdict::Differ { .. }),
+                    "{name}: {q}: {:?}",
+                    report.verdict
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    });
+}
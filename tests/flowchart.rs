@@ -0,0 +1,92 @@
+mod common;
+
+use anyhow::Result;
+use common::{corpus, deep, interpret_main, DIRECTIONS, STEPS};
+use janus::{
+    ast,
+    flowchart::{
+        convert::{janus_to_srl, rl_to_srl, srl_to_janus, srl_to_rl},
+        machine::Machine,
+        parser, Rl,
+    },
+    interpreter::{value::Value, Direction},
+    util::char_list,
+};
+
+type Store = Vec<(String, Value)>;
+
+fn run_rl(rl: &Rl, direction: Direction) -> Result<Store> {
+    let mut machine = Machine::new(rl)?;
+    machine.limit_steps(STEPS);
+    machine.run(direction)?;
+    Ok(machine.store())
+}
+
+fn assert_agrees(expected: &Result<Store>, actual: &Result<Store>, context: &str) {
+    match (expected, actual) {
+        (Ok(expected), Ok(actual)) => {
+            for (x, value) in expected {
+                let found = actual.iter().find(|(y, _)| y == x).map(|(_, v)| v);
+                assert_eq!(found, Some(value), "{context}: {x}");
+            }
+            for (x, value) in actual {
+                if !expected.iter().any(|(y, _)| y == x) {
+                    assert!(value.is_zero(), "{context}: {x} is left at {value}");
+                }
+            }
+        }
+        (Err(_), Err(_)) => {}
+        (Ok(_), Err(e)) => panic!("{context}: the translation fails: {e:#}"),
+        (Err(e), Ok(_)) => {
+            panic!("{context}: the translation succeeds but the source fails: {e:#}")
+        }
+    }
+}
+
+#[test]
+fn translations_agree_with_interpreter() {
+    deep(|| {
+        let mut translated = 0;
+        for (name, prog) in corpus() {
+            let srl = match janus_to_srl(&prog) {
+                Ok(srl) => srl,
+                Err(e) => {
+                    assert!(format!("{e:#}").contains("recursive"), "{name}: {e:#}");
+                    continue;
+                }
+            };
+            translated += 1;
+            let janus = srl_to_janus(&srl);
+            let reparsed = ast::parse(char_list(janus.to_string())).unwrap();
+            let rl = srl_to_rl(&srl);
+            let rl_text = parser::parse(char_list(rl.to_string())).unwrap();
+            let round_trip = srl_to_janus(&rl_to_srl(&rl).unwrap());
+            for direction in DIRECTIONS {
+                let expected = interpret_main(&prog, direction);
+                let context = |via: &str| format!("{name}: {} main via {via}", direction.name());
+                assert_agrees(
+                    &expected,
+                    &interpret_main(&janus, direction),
+                    &context("SRL"),
+                );
+                assert_agrees(
+                    &expected,
+                    &interpret_main(&reparsed, direction),
+                    &context("printed SRL"),
+                );
+                assert_agrees(&expected, &run_rl(&rl, direction), &context("RL"));
+                assert_agrees(
+                    &expected,
+                    &run_rl(&rl_text, direction),
+                    &context("printed RL"),
+                );
+                assert_agrees(
+                    &expected,
+                    &interpret_main(&round_trip, direction),
+                    &context("RL and back to Janus"),
+                );
+            }
+        }
+        assert!(translated > 0, "no program could be translated");
+    });
+}
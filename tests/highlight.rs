@@ -0,0 +1,52 @@
+use janus::{
+    highlight::{textmate, Class},
+    tokenizer::{Tokenizer, SYMBOLS_1, SYMBOLS_2},
+    util::char_list,
+};
+
+fn literals(pattern: &str) -> Option<Vec<String>> {
+    let mut alternatives = vec![String::new()];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => alternatives.last_mut()?.push(chars.next()?),
+            '|' => alternatives.push(String::new()),
+            c if c.is_ascii_alphanumeric() => alternatives.last_mut()?.push(c),
+            _ => return None,
+        }
+    }
+    Some(alternatives)
+}
+
+#[test]
+fn grammar_rules_agree_with_tokenizer() {
+    let grammar = textmate();
+    let rules = grammar["patterns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|rule| {
+            let alternatives = literals(rule["match"].as_str()?)?;
+            Some((alternatives, rule["name"].as_str()?.to_string()))
+        })
+        .collect::<Vec<_>>();
+    let symbols = SYMBOLS_2
+        .iter()
+        .map(|((a, b), _)| format!("{a}{b}"))
+        .chain(SYMBOLS_1.iter().map(|(a, _)| a.to_string()));
+    for symbol in symbols {
+        let mut tokenizer = Tokenizer::new(char_list(&symbol));
+        tokenizer.tokenize().unwrap();
+        let (token, _) = tokenizer.tokens().front().unwrap();
+        let matched = rules.iter().find_map(|(alternatives, scope)| {
+            alternatives
+                .iter()
+                .find(|x| symbol.starts_with(x.as_str()))
+                .map(|x| (x, scope))
+        });
+        let (alternative, scope) =
+            matched.unwrap_or_else(|| panic!("no grammar rule matches {symbol}"));
+        assert_eq!(alternative, &symbol, "{symbol} is split by the grammar");
+        assert_eq!(scope, Class::of(token).scope(), "{symbol}");
+    }
+}
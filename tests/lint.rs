@@ -0,0 +1,171 @@
+use janus::{
+    ast,
+    lint::{lint, Config, Finding, Rule, Severity, RULES},
+    util::char_list,
+};
+
+const CASES: &[(Rule, &str, &str)] = &[
+    (
+        Rule::UnusedVariable,
+        "procedure main()
+    int x
+    int y
+    x += 1
+",
+        "y is declared but never used",
+    ),
+    (
+        Rule::UnreachableProcedure,
+        "procedure main()
+    int x
+    x += 1
+
+procedure g(int x)
+    x += 1
+",
+        "g is never reached from main",
+    ),
+    (
+        Rule::UnusedParameter,
+        "procedure main()
+    int x
+    int y
+    call f(x, y)
+
+procedure f(int a, int b)
+    a += 1
+",
+        "f never reads or modifies its parameter b",
+    ),
+    (
+        Rule::UnusedLocal,
+        "procedure main()
+    int x
+    local int t = 0
+        x += 1
+    delocal int t = 0
+",
+        "the body of local t never touches it",
+    ),
+    (
+        Rule::NonTerminatingLoop,
+        "procedure main()
+    int x
+    from x = 0 do
+        x += 2
+    loop
+        skip
+    until x = 1
+",
+        "loop in main cannot terminate",
+    ),
+    (
+        Rule::LoopReentry,
+        "procedure main()
+    int x
+    from x = 0 do
+        x += 1
+    loop
+        x -= 1
+    until x = 2
+",
+        "loop in main cannot repeat",
+    ),
+    (
+        Rule::AssertionFails,
+        "procedure main()
+    int x
+    if x = 0 then
+        x += 1
+    else
+        skip
+    fi x = 0
+",
+        "assertion fi always fails",
+    ),
+    (
+        Rule::AssertionHolds,
+        "procedure main()
+    int x
+    if x = 0 then
+        x += 1
+    else
+        skip
+    fi x = 1
+",
+        "assertion fi always holds",
+    ),
+    (
+        Rule::IndexOutOfBounds,
+        "procedure main()
+    int a[3]
+    a[3] += 1
+",
+        "index 3 into a of length 3 is always out of bounds",
+    ),
+];
+
+fn findings(source: &str, config: &Config) -> Vec<Finding> {
+    lint(&ast::parse(char_list(source)).unwrap(), config)
+}
+
+#[test]
+fn every_rule_reports_its_program() {
+    for &rule in RULES {
+        let (_, source, message) = CASES
+            .iter()
+            .find(|(r, ..)| *r == rule)
+            .unwrap_or_else(|| panic!("no program for {}", rule.name()));
+        let findings = findings(source, &Config::default());
+        let finding = findings
+            .iter()
+            .find(|finding| finding.rule == rule)
+            .unwrap_or_else(|| panic!("{}: not reported in {findings:?}", rule.name()));
+        assert!(
+            finding.message.contains(message),
+            "{}: {}",
+            rule.name(),
+            finding.message
+        );
+        assert_eq!(finding.severity, rule.severity(), "{}", rule.name());
+    }
+}
+
+#[test]
+fn clean_program_has_no_warnings() {
+    let findings = findings(
+        "procedure main()
+    int x
+    int y
+    x += 2
+    call f(x, y)
+
+procedure f(int a, int b)
+    b += a
+",
+        &Config::default(),
+    );
+    assert!(
+        findings
+            .iter()
+            .all(|finding| finding.severity < Severity::Warning),
+        "{findings:?}"
+    );
+}
+
+#[test]
+fn configured_severities_apply() {
+    let (_, source, _) = CASES[0];
+    let config = Config::parse("unused-variable=error").unwrap();
+    assert!(
+        findings(source, &config)
+            .iter()
+            .any(|finding| finding.rule == Rule::UnusedVariable
+                && finding.severity == Severity::Error)
+    );
+    let config = Config::parse("unused-variable=off").unwrap();
+    assert!(findings(source, &config)
+        .iter()
+        .all(|finding| finding.rule != Rule::UnusedVariable));
+    assert!(Config::parse("no-such-rule=error").is_err());
+}
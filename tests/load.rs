@@ -0,0 +1,48 @@
+mod common;
+
+use common::{corpus, deep};
+use janus::ast::{self, sexpr};
+use serde_json::json;
+use std::{env, fs};
+
+#[test]
+fn json_and_sexpr_load_back_to_the_same_program() {
+    deep(|| {
+        let dir = env::temp_dir().join(format!("janus-load-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, prog) in corpus() {
+            let value = serde_json::to_value(&prog).unwrap();
+            for (extension, text) in [
+                ("json", serde_json::to_string_pretty(&value).unwrap()),
+                ("sexpr", sexpr::to_string(&value)),
+            ] {
+                let path = dir.join(format!("{name}.{extension}"));
+                fs::write(&path, text).unwrap();
+                let loaded =
+                    ast::load(&path).unwrap_or_else(|e| panic!("{name}.{extension}: {e:#}"));
+                assert_eq!(
+                    serde_json::to_value(&loaded).unwrap(),
+                    value,
+                    "{name}.{extension}"
+                );
+                assert_eq!(loaded.to_string(), prog.to_string(), "{name}.{extension}");
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    });
+}
+
+#[test]
+fn empty_lists_keep_their_kind() {
+    for value in [
+        json!({}),
+        json!([]),
+        json!({"a": {}, "b": [], "c": [{}, []]}),
+        json!({"": 1}),
+    ] {
+        let text = sexpr::to_string(&value);
+        assert_eq!(sexpr::from_str(&text).unwrap(), value, "{text}");
+    }
+    assert!(sexpr::from_str("(:a 1 2)").is_err());
+    assert!(sexpr::from_str("(1 :a 2)").is_err());
+}
@@ -0,0 +1,283 @@
+use janus::lsp::serve;
+use serde_json::{json, Value as Json};
+use std::io::{BufRead, Cursor, Read};
+
+const URI: &str = "file:///test.janus";
+
+const TEXT: &str = "procedure main()
+    int x
+    x +=  1
+    call inc(x)
+
+procedure inc(int n)
+    n += 1
+";
+
+fn frame(message: &Json) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+fn request(id: u64, method: &str, params: Json) -> String {
+    frame(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+}
+
+fn notification(method: &str, params: Json) -> String {
+    frame(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+fn at(line: u64, character: u64) -> Json {
+    json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": line, "character": character },
+    })
+}
+
+fn replies(output: Vec<u8>) -> Vec<Json> {
+    let mut output = Cursor::new(output);
+    let mut replies = Vec::new();
+    loop {
+        let mut header = String::new();
+        if output.read_line(&mut header).unwrap() == 0 {
+            return replies;
+        }
+        let len = header
+            .trim_end()
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let mut blank = String::new();
+        output.read_line(&mut blank).unwrap();
+        assert_eq!(blank, "\r\n");
+        let mut body = vec![0; len];
+        output.read_exact(&mut body).unwrap();
+        replies.push(serde_json::from_slice(&body).unwrap());
+    }
+}
+
+fn reply(replies: &[Json], id: u64) -> &Json {
+    replies
+        .iter()
+        .find(|reply| reply["id"] == id)
+        .unwrap_or_else(|| panic!("no reply to request {id}"))
+}
+
+#[test]
+fn scripted_session() {
+    let mut rename = at(5, 10);
+    rename["newName"] = json!("bump");
+    let mut references = at(2, 4);
+    references["context"] = json!({ "includeDeclaration": true });
+    let document = json!({ "textDocument": { "uri": URI } });
+    let input = [
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "janus", "version": 1, "text": TEXT },
+            }),
+        ),
+        request(2, "textDocument/definition", at(3, 9)),
+        request(3, "textDocument/hover", at(2, 4)),
+        request(4, "textDocument/references", references),
+        request(5, "textDocument/documentSymbol", document.clone()),
+        request(6, "textDocument/rename", rename),
+        request(7, "textDocument/formatting", document),
+        request(8, "shutdown", Json::Null),
+        request(9, "textDocument/hover", at(2, 4)),
+        notification("exit", Json::Null),
+        request(10, "shutdown", Json::Null),
+    ]
+    .concat();
+
+    let mut output = Vec::new();
+    serve(Cursor::new(input), &mut output).unwrap();
+    let replies = replies(output);
+    let range = |line: u64, start: u64, end: u64| {
+        json!({
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end },
+        })
+    };
+
+    let capabilities = &reply(&replies, 1)["result"]["capabilities"];
+    for provider in [
+        "definitionProvider",
+        "hoverProvider",
+        "referencesProvider",
+        "documentSymbolProvider",
+        "renameProvider",
+        "documentFormattingProvider",
+    ] {
+        assert_eq!(capabilities[provider], true, "{provider}");
+    }
+
+    let diagnostics = replies
+        .iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .unwrap();
+    assert_eq!(diagnostics["params"]["uri"], URI);
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    let definition = &reply(&replies, 2)["result"];
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(definition["range"], range(5, 10, 13));
+
+    let hover = &reply(&replies, 3)["result"]["contents"]["value"];
+    assert!(hover.as_str().unwrap().contains("int x"), "{hover}");
+
+    let references = reply(&replies, 4)["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| location["range"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        references,
+        [range(1, 8, 9), range(2, 4, 5), range(3, 13, 14)]
+    );
+
+    let symbols = reply(&replies, 5)["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| (symbol["name"].clone(), symbol["detail"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        symbols,
+        [
+            (json!("main"), json!("procedure main()")),
+            (json!("inc"), json!("procedure inc(int n)")),
+        ]
+    );
+
+    let mut edits = reply(&replies, 6)["result"]["changes"][URI]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edit| {
+            assert_eq!(edit["newText"], "bump");
+            edit["range"].clone()
+        })
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|range| range["start"]["line"].as_u64());
+    assert_eq!(edits, [range(3, 9, 12), range(5, 10, 13)]);
+
+    let formatting = &reply(&replies, 7)["result"][0];
+    assert_eq!(formatting["newText"], TEXT.replace("+=  1", "+= 1"));
+    assert_eq!(
+        formatting["range"],
+        json!({
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 7, "character": 0 },
+        })
+    );
+
+    assert_eq!(reply(&replies, 8)["result"], Json::Null);
+    assert_eq!(reply(&replies, 9)["error"]["code"], -32600);
+    assert!(
+        replies.iter().all(|reply| reply["id"] != 10),
+        "the server kept reading after exit"
+    );
+}
+
+fn session(capabilities: Json, text: &str, requests: &[String]) -> Vec<Json> {
+    let mut input = vec![
+        request(1, "initialize", json!({ "capabilities": capabilities })),
+        notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "janus", "version": 1, "text": text },
+            }),
+        ),
+    ];
+    input.extend(requests.iter().cloned());
+    input.push(notification("exit", Json::Null));
+    let mut output = Vec::new();
+    serve(Cursor::new(input.concat()), &mut output).unwrap();
+    replies(output)
+}
+
+fn diagnostics(replies: &[Json]) -> &Json {
+    &replies
+        .iter()
+        .find(|reply| reply["method"] == "textDocument/publishDiagnostics")
+        .unwrap()["params"]["diagnostics"]
+}
+
+#[test]
+fn parse_errors_point_at_the_failing_token() {
+    let replies = session(
+        json!({}),
+        "procedure main()\n    int a[3\n    a[0] += 1\n",
+        &[],
+    );
+    let diagnostics = diagnostics(&replies).as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({
+            "start": { "line": 2, "character": 4 },
+            "end": { "line": 2, "character": 5 },
+        })
+    );
+}
+
+#[test]
+fn positions_follow_the_negotiated_encoding() {
+    let text = "procedure main()\n    int \u{1f600}\n    int x\n    \u{1f600} += x\n";
+    let mut references = at(3, 0);
+    references["context"] = json!({ "includeDeclaration": false });
+    for (capabilities, encoding, character) in [
+        (json!({}), "utf-16", 10),
+        (
+            json!({ "general": { "positionEncodings": ["utf-16", "utf-32"] } }),
+            "utf-32",
+            9,
+        ),
+    ] {
+        references["position"]["character"] = json!(character);
+        let replies = session(
+            capabilities,
+            text,
+            &[
+                request(2, "textDocument/references", references.clone()),
+                request(3, "textDocument/semanticTokens/full", at(0, 0)),
+            ],
+        );
+        assert_eq!(
+            reply(&replies, 1)["result"]["capabilities"]["positionEncoding"],
+            encoding
+        );
+        assert_eq!(diagnostics(&replies), &json!([]), "{encoding}");
+        assert_eq!(
+            reply(&replies, 2)["result"],
+            json!([{
+                "uri": URI,
+                "range": {
+                    "start": { "line": 3, "character": character },
+                    "end": { "line": 3, "character": character + 1 },
+                },
+            }]),
+            "{encoding}"
+        );
+        let data = reply(&replies, 3)["result"]["data"]
+            .as_array()
+            .unwrap()
+            .chunks(5)
+            .map(|token| (token[0].clone(), token[1].clone(), token[2].clone()))
+            .collect::<Vec<_>>();
+        let emoji = character - 8;
+        assert_eq!(
+            data[data.len() - 3..],
+            [
+                (json!(1), json!(4), json!(emoji)),
+                (json!(0), json!(emoji + 1), json!(2)),
+                (json!(0), json!(3), json!(1)),
+            ],
+            "{encoding}"
+        );
+    }
+}
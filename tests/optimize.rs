@@ -0,0 +1,170 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, Case, DIRECTIONS};
+use janus::{
+    ast::{
+        self,
+        ast_node::{Proc, Stm, Type},
+    },
+    backend::array_params,
+    interpreter::{value::Value, Direction},
+    optimize::{inline::inline, optimize, partial::specialize},
+    util::char_list,
+};
+
+#[test]
+fn optimized_programs_agree_with_originals() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            let variants = [
+                ("optimized", optimize(prog.clone())),
+                ("inlined", optimize(inline(prog.clone(), 8))),
+            ];
+            for (variant, optimized) in &variants {
+                for direction in DIRECTIONS {
+                    let expected = interpret_main(&prog, direction);
+                    let actual = interpret_main(optimized, direction);
+                    assert_eq!(
+                        actual.ok(),
+                        expected.ok(),
+                        "{name}: {variant} {} main",
+                        direction.name()
+                    );
+                }
+                for case in cases(&prog, 50) {
+                    let (Some(expected), Some(actual)) =
+                        (interpret(&prog, &case), interpret(optimized, &case))
+                    else {
+                        continue;
+                    };
+                    assert_eq!(
+                        actual.ok(),
+                        expected.ok(),
+                        "{variant} {}",
+                        describe(&name, &case)
+                    );
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn negative_results_are_folded() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    x += 3 - 5
+    y += (3 - 5) * 4 + 1
+",
+    ))
+    .unwrap();
+    let optimized = optimize(prog.clone());
+    let mut updates = Vec::new();
+    optimized.p_main.body().walk(&mut |s| {
+        if let Stm::AssignScalar { e, .. } = s {
+            updates.push(e.to_string());
+        }
+    });
+    assert_eq!(updates, ["0 - 2", "0 - 10"]);
+    assert_eq!(
+        interpret_main(&optimized, Direction::Forward).ok(),
+        interpret_main(&prog, Direction::Forward).ok()
+    );
+}
+
+#[test]
+fn specialized_programs_agree_with_originals() {
+    deep(|| {
+        let mut agreed = 0;
+        for (name, prog) in corpus() {
+            let residual = optimize(specialize(&prog, "main", &[], 10_000).unwrap().prog);
+            assert_eq!(
+                interpret_main(&residual, Direction::Forward).ok(),
+                interpret_main(&prog, Direction::Forward).ok(),
+                "{name}: specialized main"
+            );
+
+            let arrays = array_params(&prog);
+            for p in &prog.ps {
+                let Proc::Other { q, args, .. } = p else {
+                    continue;
+                };
+                let Some(k) = args
+                    .iter()
+                    .zip(&arrays[q.0.as_str()])
+                    .position(|(arg, &array)| arg.t == Type::Int && !array)
+                else {
+                    continue;
+                };
+                let x = &args.iter().nth(k).unwrap().x.0;
+                let mut cases = cases(&prog, 20)
+                    .into_iter()
+                    .filter(|case| case.q == q.0 && case.direction == Direction::Forward);
+                let Some(first) = cases.next() else {
+                    continue;
+                };
+                let Value::Int(n) = first.input[k] else {
+                    unreachable!("{x} is an integer parameter");
+                };
+                let known = [(x.clone(), n)];
+                let residual = specialize(&prog, &q.0, &known, 10_000).unwrap();
+                let residual_prog = optimize(residual.prog.clone());
+                for mut case in std::iter::once(first).chain(cases) {
+                    case.input[k] = Value::Int(n);
+                    let Some(expected) = interpret(&prog, &case) else {
+                        continue;
+                    };
+                    let position = |y: &String| args.iter().position(|arg| arg.x.0 == *y).unwrap();
+                    let residual_case = Case {
+                        q: residual.q.clone(),
+                        direction: Direction::Forward,
+                        input: residual
+                            .params
+                            .iter()
+                            .map(|y| case.input[position(y)].clone())
+                            .collect(),
+                    };
+                    let actual = interpret(&residual_prog, &residual_case)
+                        .unwrap_or_else(|| panic!("{}: exhausted", describe(&name, &case)));
+                    let (expected, actual) = match (expected, actual) {
+                        (Ok(expected), Ok(actual)) => (expected, actual),
+                        (Err(_), Err(_)) => continue,
+                        (expected, actual) => panic!(
+                            "{}: original {:?}, specialized on {x} = {n} {:?}",
+                            describe(&name, &case),
+                            expected.ok(),
+                            actual.ok()
+                        ),
+                    };
+                    for (y, value) in residual.params.iter().zip(&actual) {
+                        assert_eq!(value, &expected[position(y)], "{}", describe(&name, &case));
+                    }
+                    for (y, value) in &residual.finals {
+                        assert_eq!(
+                            Value::Int(*value),
+                            expected[position(y)],
+                            "{}",
+                            describe(&name, &case)
+                        );
+                    }
+                    let input = residual_case.input.clone();
+                    let backward = Case {
+                        direction: Direction::Backward,
+                        input: actual,
+                        ..residual_case
+                    };
+                    assert_eq!(
+                        interpret(&residual_prog, &backward).unwrap().unwrap(),
+                        input,
+                        "{} backward",
+                        describe(&name, &case)
+                    );
+                    agreed += 1;
+                }
+            }
+        }
+        assert!(agreed > 0, "no run was accepted by the originals");
+    });
+}
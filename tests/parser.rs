@@ -0,0 +1,25 @@
+use janus::{
+    ast::{self, ast_node::Stm},
+    util::char_list,
+};
+
+#[test]
+fn pop_continues_a_sequence() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    stack s
+    x += 1
+    push(x, s)
+    pop(x, s)
+",
+    ))
+    .unwrap();
+    let mut pops = 0;
+    prog.p_main.body().walk(&mut |s| {
+        if let Stm::Pop(..) = s {
+            pops += 1;
+        }
+    });
+    assert_eq!(pops, 1);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, DIRECTIONS, STEPS};
+use janus::{
+    ast::{self, ast_node::Prog},
+    interpreter::{value::Value, Direction},
+    pisa::{self, codegen, emulator::Emulator},
+    util::char_list,
+};
+
+fn emulate_main(prog: &Prog, direction: Direction) -> anyhow::Result<Vec<(String, Value)>> {
+    let program = codegen::compile(prog)?;
+    let mut emulator = Emulator::new(&program);
+    emulator.limit_steps(100 * STEPS);
+    emulator.run(direction)?;
+    Ok(emulator.store())
+}
+
+#[test]
+fn emulated_main_agrees_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            for direction in DIRECTIONS {
+                let found = emulate_main(&prog, direction);
+                match interpret_main(&prog, direction) {
+                    Ok(store) => assert_eq!(
+                        found.unwrap_or_else(|e| panic!("{name}: {e:#}")),
+                        store,
+                        "{name}: {} main",
+                        direction.name()
+                    ),
+                    Err(e) => assert!(found.is_err(), "{name}: expected {e:#}"),
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn emulated_procedures_agree_with_interpreter_in_both_directions() {
+    deep(|| {
+        let mut failures = 0;
+        for (name, prog) in corpus() {
+            for case in cases(&prog, 20) {
+                let Some(expected) = interpret(&prog, &case) else {
+                    continue;
+                };
+                let found = pisa::call(&prog, &case.q, &case.input, case.direction, 100 * STEPS);
+                match expected {
+                    Ok(values) => assert_eq!(
+                        found.unwrap_or_else(|e| panic!("{}: {e:#}", describe(&name, &case))),
+                        values,
+                        "{}",
+                        describe(&name, &case)
+                    ),
+                    Err(e) => {
+                        failures += 1;
+                        assert!(found.is_err(), "{}: expected {e:#}", describe(&name, &case));
+                    }
+                }
+            }
+        }
+        assert!(failures > 0, "no input made the interpreter fail");
+    });
+}
+
+fn failure(source: &str, direction: Direction) -> String {
+    let prog = ast::parse(char_list(source)).unwrap();
+    assert!(interpret_main(&prog, direction).is_err());
+    format!("{:#}", emulate_main(&prog, direction).unwrap_err())
+}
+
+#[test]
+fn out_of_bounds_indices_are_reported() {
+    let source = "procedure main()
+    int a[3]
+    int i
+    i += 3
+    a[i] += 1
+";
+    let message = failure(source, Direction::Forward);
+    assert!(message.contains("index out of bounds at 5:5"), "{message}");
+    let source = "procedure main()
+    int a[3]
+    int i
+    int x
+    call bump(a, i, x)
+
+procedure bump(int a, int i, int x)
+    i -= 1
+    x += a[i]
+";
+    let message = failure(source, Direction::Forward);
+    assert!(message.contains("bump: index out of bounds"), "{message}");
+}
+
+#[test]
+fn empty_stacks_are_reported() {
+    let source = "procedure main()
+    int x
+    stack s
+    pop(x, s)
+";
+    let message = failure(source, Direction::Forward);
+    assert!(
+        message.contains("pop from an empty stack at 4:5"),
+        "{message}"
+    );
+    let source = "procedure main()
+    int x
+    stack s
+    x += top(s)
+";
+    let message = failure(source, Direction::Forward);
+    assert!(message.contains("top of an empty stack"), "{message}");
+    let source = "procedure main()
+    int x
+    stack s
+    x += 1
+    push(x, s)
+";
+    let message = failure(source, Direction::Backward);
+    assert!(message.contains("pop from an empty stack"), "{message}");
+}
+
+#[test]
+fn failed_assertions_are_reported() {
+    let source = "procedure main()
+    int x
+    int y
+    x += 13
+    y += 12
+    call choose(x, y)
+
+procedure choose(int x, int y)
+    if x < y then
+        y -= x
+    else
+        x -= y
+    fi x < y
+";
+    let message = failure(source, Direction::Forward);
+    assert!(message.contains("choose: assertion fi failed"), "{message}");
+    let source = "procedure main()
+    int x
+    from x = 0 do
+        x += 1
+    loop
+        x -= 1
+    until x = 2
+";
+    let message = failure(source, Direction::Forward);
+    assert!(
+        message.contains("assertion from failed on re-entry"),
+        "{message}"
+    );
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use common::{corpus, deep, interpret_main, DIRECTIONS};
+use janus::{ast, util::char_list};
+
+#[test]
+fn printed_programs_parse_back_to_the_same_program() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            let text = prog.to_string();
+            let reparsed = ast::parse(char_list(&text)).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+            assert_eq!(reparsed.to_string(), text, "{name}");
+            for direction in DIRECTIONS {
+                assert_eq!(
+                    interpret_main(&reparsed, direction).ok(),
+                    interpret_main(&prog, direction).ok(),
+                    "{name}: {} main",
+                    direction.name()
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{corpus, deep};
+use janus::{
+    ast::{
+        self,
+        ast_node::{Op, Proc, Prog},
+    },
+    circuit::wrap,
+    interpreter::eval_op,
+    prove::{
+        bits::Blaster,
+        prove,
+        sat::{Lit, Solver},
+        Method, Verdict, MAX_EXHAUSTIVE, UNROLL,
+    },
+    util::char_list,
+};
+
+const WIDTH: usize = 4;
+
+const CORPUS_WIDTH: usize = 3;
+
+const OPS: [Op; 16] = [
+    Op::Add,
+    Op::Sub,
+    Op::Xor,
+    Op::Mul,
+    Op::Div,
+    Op::Mod,
+    Op::And,
+    Op::Or,
+    Op::And2,
+    Op::Or2,
+    Op::Less,
+    Op::Greater,
+    Op::Equal,
+    Op::NotEqual,
+    Op::LessEqual,
+    Op::GreaterEqual,
+];
+
+#[test]
+fn satisfiable_formulas_have_models() {
+    let mut solver = Solver::new();
+    let (x, y, z) = (solver.var(), solver.var(), solver.var());
+    solver.clause(&[x, y]);
+    solver.clause(&[!x, z]);
+    solver.clause(&[!z]);
+    assert!(solver.solve(&[]));
+    assert!(!solver.model(x));
+    assert!(solver.model(y));
+    assert!(!solver.model(z));
+    assert!(!solver.solve(&[x]));
+    assert!(solver.solve(&[y]));
+}
+
+#[test]
+fn unsatisfiable_formulas_are_refuted() {
+    let mut solver = Solver::new();
+    let pigeons = (0..3)
+        .map(|_| [solver.var(), solver.var()])
+        .collect::<Vec<_>>();
+    for holes in &pigeons {
+        solver.clause(holes);
+    }
+    for hole in 0..2 {
+        for (i, a) in pigeons.iter().enumerate() {
+            for b in &pigeons[i + 1..] {
+                solver.clause(&[!a[hole], !b[hole]]);
+            }
+        }
+    }
+    assert!(!solver.solve(&[]));
+    assert_eq!(solver.solve_within(&[], 1_000), Some(false));
+
+    let mut solver = Solver::new();
+    let x = solver.var();
+    solver.clause(&[x]);
+    solver.clause(&[!x]);
+    assert!(!solver.solve(&[]));
+}
+
+fn fix(xs: &[Lit], n: i32) -> Vec<Lit> {
+    xs.iter()
+        .enumerate()
+        .map(|(i, &x)| match n >> i & 1 {
+            1 => x,
+            _ => !x,
+        })
+        .collect()
+}
+
+#[test]
+fn operators_agree_with_interpreter_at_width_4() {
+    let range = -(1 << (WIDTH - 1))..1 << (WIDTH - 1);
+    for op in OPS {
+        let mut blaster = Blaster::default();
+        let xs = (0..WIDTH).map(|_| blaster.input()).collect::<Vec<_>>();
+        let ys = (0..WIDTH).map(|_| blaster.input()).collect::<Vec<_>>();
+        let zs = blaster.binop(op, &xs, &ys);
+        for n in range.clone() {
+            for m in range.clone() {
+                let Ok(expected) = eval_op(&op, n, m) else {
+                    continue;
+                };
+                let mut assumptions = fix(&xs, n);
+                assumptions.extend(fix(&ys, m));
+                assert!(blaster.solver.solve(&assumptions), "{op:?} {n} {m}");
+                assert_eq!(blaster.model(&zs), wrap(expected, WIDTH), "{op:?} {n} {m}");
+            }
+        }
+    }
+}
+
+fn kind(verdict: &Verdict) -> &'static str {
+    match verdict {
+        Verdict::Proved { .. } => "proved",
+        Verdict::Fails { .. } => "fails",
+        Verdict::Collides { .. } => "collides",
+        Verdict::Unknown { .. } => "unknown",
+    }
+}
+
+fn same_verdicts(name: &str, prog: &Prog, q: &str, width: usize) -> Verdict {
+    let exhaustive = prove(prog, q, width, Some(Method::Exhaustive), UNROLL)
+        .unwrap_or_else(|e| panic!("{name}: {q}: {e:#}"));
+    let symbolic = prove(prog, q, width, Some(Method::Symbolic), UNROLL)
+        .unwrap_or_else(|e| panic!("{name}: {q}: {e:#}"));
+    assert_eq!(
+        kind(&exhaustive.verdict),
+        kind(&symbolic.verdict),
+        "{name}: {q}: {:?} exhaustively but {:?} symbolically",
+        exhaustive.verdict,
+        symbolic.verdict
+    );
+    symbolic.verdict
+}
+
+#[test]
+fn exhaustive_and_symbolic_verdicts_agree_on_corpus() {
+    deep(|| {
+        let mut proved = 0;
+        for (name, prog) in corpus() {
+            for p in &prog.ps {
+                let Proc::Other { q, args, .. } = p else {
+                    continue;
+                };
+                if args.len() * CORPUS_WIDTH > MAX_EXHAUSTIVE
+                    || prove(&prog, &q.0, CORPUS_WIDTH, Some(Method::Symbolic), UNROLL).is_err()
+                {
+                    continue;
+                }
+                if let Verdict::Proved { .. } = same_verdicts(&name, &prog, &q.0, CORPUS_WIDTH) {
+                    proved += 1;
+                }
+            }
+        }
+        assert!(proved > 0, "no corpus procedure was proved");
+    });
+}
+
+#[test]
+fn exhaustive_and_symbolic_verdicts_agree_on_failing_program() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    call f(x, y)
+
+procedure f(int x, int y)
+    if x = 0 then
+        y += 1
+    else
+        skip
+    fi y = 1
+",
+    ))
+    .unwrap();
+    let Verdict::Fails { error, .. } = same_verdicts("inline", &prog, "f", WIDTH) else {
+        panic!("f is not reversible");
+    };
+    assert!(error.contains("assertion fi failed"), "{error}");
+}
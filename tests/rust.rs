@@ -0,0 +1,270 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, Case, DIRECTIONS};
+use janus::{
+    ast::{
+        self,
+        ast_node::{Con, Exp, MainStuff, Proc, Prog, Stm, Vdec},
+    },
+    backend,
+    interpreter::{value::Value, Direction},
+    util::char_list,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const HELPERS: &str = r#"
+fn show_stack(ns: &[i32]) -> String {
+    match ns.is_empty() {
+        true => "nil".to_string(),
+        false => format!("<{}>", ns.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")),
+    }
+}
+"#;
+
+fn compile(name: &str, prog: &Prog, driver: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("rust");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{name}.rs"));
+    let binary = dir.join(name);
+    let source = backend::rust::emit(prog).unwrap();
+    fs::write(
+        &path,
+        format!("#![allow(warnings)]\n{source}{HELPERS}{driver}"),
+    )
+    .unwrap();
+    let output = Command::new("rustc")
+        .arg("--edition=2021")
+        .arg("-o")
+        .arg(&binary)
+        .arg(&path)
+        .output()
+        .expect("rustc is installed");
+    assert!(
+        output.status.success(),
+        "{name}: rustc failed\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    binary
+}
+
+fn run(binary: &Path, case: &str) -> Output {
+    Command::new(binary).arg(case).output().unwrap()
+}
+
+fn main_driver(prog: &Prog) -> String {
+    let Proc::Main { main_stuff, .. } = &prog.p_main else {
+        unreachable!("main is the first procedure");
+    };
+    let prints = main_stuff
+        .iter()
+        .map(|stuff| match stuff {
+            MainStuff::Int(Vdec::Scalar(x)) => format!("println!(\"{x} = {{}}\", m.{x});", x = x.0),
+            MainStuff::Int(Vdec::Array { x, .. }) => {
+                format!("println!(\"{x} = {{:?}}\", m.{x});", x = x.0)
+            }
+            MainStuff::Stack(x) => {
+                format!("println!(\"{x} = {{}}\", show_stack(&m.{x}));", x = x.0)
+            }
+        })
+        .collect::<String>();
+    format!(
+        "fn main() {{
+    let mut m = Main::default();
+    match std::env::args().nth(1).unwrap().as_str() {{
+        \"fwd\" => m.fwd(),
+        _ => m.bwd(),
+    }}
+    {prints}
+}}
+"
+    )
+}
+
+fn procedure_driver(cases: &[Case]) -> String {
+    let mut out = String::from(
+        "fn main() {\n    match std::env::args().nth(1).unwrap().parse::<usize>().unwrap() {\n",
+    );
+    for (i, case) in cases.iter().enumerate() {
+        let mut lets = String::new();
+        let mut params = Vec::new();
+        let mut prints = String::new();
+        for (j, value) in case.input.iter().enumerate() {
+            let (declaration, param, print) = match value {
+                Value::Int(n) => (
+                    format!("let mut a{j}: i32 = {n};"),
+                    format!("&mut a{j}"),
+                    format!("println!(\"a{j} = {{}}\", a{j});"),
+                ),
+                Value::Array(ns) => (
+                    format!("let mut a{j}: Vec<i32> = vec!{ns:?};"),
+                    format!("&mut a{j}[..]"),
+                    format!("println!(\"a{j} = {{:?}}\", a{j});"),
+                ),
+                Value::Stack(ns) => (
+                    format!("let mut a{j}: Vec<i32> = vec!{ns:?};"),
+                    format!("&mut a{j}"),
+                    format!("println!(\"a{j} = {{}}\", show_stack(&a{j}));"),
+                ),
+            };
+            lets.push_str(&declaration);
+            params.push(param);
+            prints.push_str(&print);
+        }
+        let suffix = match case.direction {
+            Direction::Forward => "fwd",
+            Direction::Backward => "bwd",
+        };
+        out.push_str(&format!(
+            "        {i} => {{ {lets} {}_{suffix}({}); {prints} }}\n",
+            case.q,
+            params.join(", ")
+        ));
+    }
+    out.push_str("        _ => std::process::exit(2),\n    }\n}\n");
+    out
+}
+
+fn check_main(name: &str, prog: &Prog) {
+    let binary = compile(name, prog, &main_driver(prog));
+    for direction in DIRECTIONS {
+        let suffix = match direction {
+            Direction::Forward => "fwd",
+            Direction::Backward => "bwd",
+        };
+        let output = run(&binary, suffix);
+        match interpret_main(prog, direction) {
+            Ok(store) => {
+                assert!(
+                    output.status.success(),
+                    "{name}: {} main: {}",
+                    direction.name(),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                let expected = store
+                    .iter()
+                    .map(|(x, value)| format!("{x} = {value}\n"))
+                    .collect::<String>();
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    expected,
+                    "{name}: {} main",
+                    direction.name()
+                );
+            }
+            Err(e) => assert_eq!(output.status.code(), Some(101), "{name}: expected {e:#}"),
+        }
+    }
+}
+
+fn check_procedures(name: &str, prog: &Prog, samples: usize) -> usize {
+    let (cases, expected): (Vec<_>, Vec<_>) = cases(prog, samples)
+        .into_iter()
+        .filter_map(|case| interpret(prog, &case).map(|expected| (case, expected)))
+        .unzip();
+    let binary = compile(name, prog, &procedure_driver(&cases));
+    let mut failures = 0;
+    for (i, (case, expected)) in cases.iter().zip(expected).enumerate() {
+        let output = run(&binary, &i.to_string());
+        match expected {
+            Ok(values) => {
+                assert!(
+                    output.status.success(),
+                    "{}: {}",
+                    describe(name, case),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                let expected = values
+                    .iter()
+                    .enumerate()
+                    .map(|(j, value)| format!("a{j} = {value}\n"))
+                    .collect::<String>();
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    expected,
+                    "{}",
+                    describe(name, case)
+                );
+            }
+            Err(e) => {
+                failures += 1;
+                assert_eq!(
+                    output.status.code(),
+                    Some(101),
+                    "{}: expected {e:#}",
+                    describe(name, case)
+                );
+            }
+        }
+    }
+    failures
+}
+
+#[test]
+fn compiled_main_agrees_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            check_main(&name, &prog);
+        }
+    });
+}
+
+#[test]
+fn compiled_procedures_agree_with_interpreter() {
+    deep(|| {
+        let mut failures = 0;
+        for (name, prog) in corpus() {
+            failures += check_procedures(&format!("{name}_procs"), &prog, 20);
+        }
+        assert!(failures > 0, "no input made the interpreter fail");
+    });
+}
+
+#[test]
+fn logical_operators_evaluate_both_operands() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    if (x = 1) && ((1 / x) = 1) then
+        y += 1
+    else
+        skip
+    fi y = 1
+",
+    ))
+    .unwrap();
+    check_main("strict_and", &prog);
+}
+
+#[test]
+fn negative_literals_are_parenthesized() {
+    let mut prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    int y
+    y += 3
+    x += 2 + y
+",
+    ))
+    .unwrap();
+    let Proc::Main { s, .. } = &mut prog.p_main else {
+        unreachable!("main is the first procedure");
+    };
+    let Stm::Sequence(_, s) = s else {
+        panic!("expected a sequence");
+    };
+    let Stm::AssignScalar { e, .. } = &mut **s else {
+        panic!("expected an update");
+    };
+    let Exp::BinOp(e_1, ..) = e else {
+        panic!("expected a binary operation");
+    };
+    **e_1 = Exp::Constant(Con(-2));
+    let store = interpret_main(&prog, Direction::Forward).unwrap();
+    assert!(store.contains(&("x".to_string(), Value::Int(1))));
+    check_main("negative_literal", &prog);
+}
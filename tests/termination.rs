@@ -0,0 +1,213 @@
+mod common;
+
+use common::{corpus, deep, STEPS};
+use janus::{
+    ast,
+    interpreter::{exhausted, Direction, Exhausted, Interpreter},
+    termination::{analyze, Verdict},
+    util::char_list,
+};
+
+fn program(init: &str, from: &str, s_do: &str, s_loop: &str, until: &str) -> String {
+    format!(
+        "procedure main()
+    int i
+    int n
+    int a[4]
+    n += 6
+    {init}
+    from {from} do
+        {s_do}
+    loop
+        {s_loop}
+    until {until}
+"
+    )
+}
+
+fn bounded(iterations: &str) -> Verdict {
+    Verdict::Bounded {
+        iterations: iterations.to_string(),
+        requires: None,
+    }
+}
+
+#[test]
+fn counter_loops_get_bounds_and_agree_with_the_interpreter() {
+    let cases = [
+        (
+            program("skip", "i = 0", "i += 1", "skip", "i = n"),
+            Verdict::Bounded {
+                iterations: "n - 1".to_string(),
+                requires: Some("n - 1 >= 0".to_string()),
+            },
+        ),
+        (
+            program("skip", "i = 0", "i += 2", "skip", "i = 6"),
+            bounded("2"),
+        ),
+        (
+            program("skip", "i = 0", "i += 1", "a[0] += i", "i >= 4"),
+            bounded("3"),
+        ),
+        (
+            program("i += 10", "i = 10", "i -= 1", "skip", "i < 3"),
+            bounded("7"),
+        ),
+        (
+            program("skip", "i = 0", "i += 1", "skip", "i = 1"),
+            bounded("0"),
+        ),
+        (
+            program("skip", "i = 0", "i += 2", "skip", "i = 5"),
+            Verdict::Diverges {
+                reason: String::new(),
+            },
+        ),
+        (
+            program("skip", "i = 0", "i += 1", "skip", "i < 0"),
+            Verdict::Diverges {
+                reason: String::new(),
+            },
+        ),
+        (
+            program("skip", "n = 6", "i += 1", "skip", "i = 3"),
+            Verdict::FailsOnReentry {
+                reason: String::new(),
+            },
+        ),
+        (
+            program("skip", "i = 0", "i += 1", "i -= 1", "i = 3"),
+            Verdict::FailsOnReentry {
+                reason: String::new(),
+            },
+        ),
+        (
+            program("skip", "i = 0", "i += 1", "skip", "n = 3"),
+            Verdict::Diverges {
+                reason: String::new(),
+            },
+        ),
+    ];
+    for (source, expected) in cases {
+        let prog = ast::parse(char_list(&source)).unwrap();
+        let reports = analyze(&prog);
+        assert_eq!(reports.len(), 1, "{source}");
+        let verdict = &reports[0].verdict;
+        match (verdict, &expected) {
+            (Verdict::Bounded { .. }, Verdict::Bounded { .. }) => {
+                assert_eq!(verdict, &expected, "{source}");
+                assert_eq!(reports[0].counter.as_deref(), Some("i"), "{source}");
+            }
+            (Verdict::Diverges { .. }, Verdict::Diverges { .. })
+            | (Verdict::FailsOnReentry { .. }, Verdict::FailsOnReentry { .. }) => {}
+            _ => panic!("{source}: expected {expected:?}, got {verdict:?}"),
+        }
+
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_steps(STEPS);
+        let result = interpreter.run(Direction::Forward);
+        match verdict {
+            Verdict::Bounded { .. } => {
+                result.unwrap_or_else(|e| panic!("{source}: {e:#}"));
+            }
+            Verdict::Diverges { .. } => assert!(
+                result.as_ref().is_err_and(exhausted),
+                "{source}: {result:?}"
+            ),
+            _ => assert!(
+                result
+                    .as_ref()
+                    .is_err_and(|e| format!("{e:#}").contains("assertion from failed")),
+                "{source}: {result:?}"
+            ),
+        }
+    }
+}
+
+#[test]
+fn symbolic_bounds_state_their_requirements() {
+    let source = "procedure main()
+    int n
+    int i
+    call count(i, n)
+
+procedure count(int i, int n)
+    from i = 0 do
+        i += 2
+    loop
+        skip
+    until i = n
+";
+    let reports = analyze(&ast::parse(char_list(source)).unwrap());
+    assert_eq!(
+        reports[0].verdict,
+        Verdict::Bounded {
+            iterations: "(n - 2) / 2".to_string(),
+            requires: Some("n - 2 >= 0 and a multiple of 2".to_string()),
+        }
+    );
+    assert_eq!(reports[0].proc, "count");
+}
+
+#[test]
+fn corpus_spin_loop_fails_on_reentry() {
+    let (_, prog) = corpus()
+        .into_iter()
+        .find(|(name, _)| name == "recursion")
+        .unwrap();
+    let reports = analyze(&prog);
+    let spin = reports.iter().find(|report| report.proc == "spin").unwrap();
+    assert!(
+        matches!(spin.verdict, Verdict::FailsOnReentry { .. }),
+        "{:?}",
+        spin.verdict
+    );
+}
+
+#[test]
+fn runtime_limits_stop_cleanly() {
+    let prog = ast::parse(char_list(program(
+        "skip", "i = 0", "i += 2", "skip", "i = 5",
+    )))
+    .unwrap();
+    let mut interpreter = Interpreter::new(&prog).unwrap();
+    interpreter.limit_steps(1000);
+    let e = interpreter.run(Direction::Forward).unwrap_err();
+    assert!(
+        matches!(e.downcast_ref(), Some(Exhausted::Steps(_))),
+        "{e:#}"
+    );
+    assert!(format!("{e:#}").contains("step limit exceeded"), "{e:#}");
+
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int n
+    n += 100
+    call down(n)
+
+procedure down(int n)
+    if n > 0 then
+        n -= 1
+        call down(n)
+        n += 1
+    else
+        skip
+    fi n > 0
+",
+    ))
+    .unwrap();
+    deep(move || {
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_depth(10);
+        let e = interpreter.run(Direction::Forward).unwrap_err();
+        assert!(exhausted(&e), "{e:#}");
+        assert!(
+            format!("{e:#}").contains("call depth exceeds 10 when calling down"),
+            "{e:#}"
+        );
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_depth(200);
+        interpreter.run(Direction::Forward).unwrap();
+    });
+}
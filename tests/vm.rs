@@ -0,0 +1,107 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, DIRECTIONS, STEPS};
+use janus::{
+    ast,
+    bytecode::{compiler::compile, vm::Vm},
+    interpreter::{value::Value, Direction, Exhausted, Interpreter},
+    util::char_list,
+};
+
+#[test]
+fn main_agrees_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            let program = compile(&prog).unwrap();
+            for direction in DIRECTIONS {
+                let expected = interpret_main(&prog, direction);
+                let mut vm = Vm::new(&program);
+                vm.limit_steps(100 * STEPS);
+                let found = vm.run(direction).map(|_| vm.store());
+                assert_eq!(
+                    found.ok(),
+                    expected.ok(),
+                    "{name}: {} main",
+                    direction.name()
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn procedures_agree_with_interpreter() {
+    deep(|| {
+        let mut failures = 0;
+        for (name, prog) in corpus() {
+            let program = compile(&prog).unwrap();
+            for case in cases(&prog, 50) {
+                let Some(expected) = interpret(&prog, &case) else {
+                    continue;
+                };
+                failures += expected.is_err() as usize;
+                let mut vm = Vm::new(&program);
+                vm.limit_steps(100 * STEPS);
+                let found = vm.call(&case.q, case.input.clone(), case.direction);
+                assert_eq!(found.ok(), expected.ok(), "{}", describe(&name, &case));
+            }
+        }
+        assert!(failures > 0, "no input made the interpreter fail");
+    });
+}
+
+#[test]
+fn exhaustion_is_typed() {
+    deep(|| {
+        let prog = ast::parse(char_list(
+            "procedure main()
+    int x
+    skip
+
+procedure spin(int x)
+    from x = 0 do
+        x += 2
+    loop
+        skip
+    until x = 1
+
+procedure down(int x)
+    x -= 1
+    call down(x)
+",
+        ))
+        .unwrap();
+        let program = compile(&prog).unwrap();
+        let input = vec![Value::Int(0)];
+
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_steps(STEPS);
+        let e = interpreter
+            .call("spin", input.clone(), Direction::Forward)
+            .unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(Exhausted::Steps(Some(_)))));
+        let mut interpreter = Interpreter::new(&prog).unwrap();
+        interpreter.limit_depth(10);
+        let e = interpreter
+            .call("down", input.clone(), Direction::Forward)
+            .unwrap_err();
+        assert!(matches!(
+            e.root_cause().downcast_ref(),
+            Some(Exhausted::Depth { limit: 10, q }) if q == "down"
+        ));
+
+        let mut vm = Vm::new(&program);
+        vm.limit_steps(STEPS);
+        let e = vm
+            .call("spin", input.clone(), Direction::Forward)
+            .unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(Exhausted::Steps(None))));
+        let mut vm = Vm::new(&program);
+        vm.limit_depth(10);
+        let e = vm.call("down", input, Direction::Forward).unwrap_err();
+        assert!(matches!(
+            e.root_cause().downcast_ref(),
+            Some(Exhausted::Depth { limit: 10, q }) if q == "down"
+        ));
+    });
+}
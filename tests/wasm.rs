@@ -0,0 +1,197 @@
+mod common;
+
+use common::{cases, corpus, deep, describe, interpret, interpret_main, DIRECTIONS};
+use janus::{
+    ast::{self, ast_node::Prog},
+    backend,
+    interpreter::{value::Value, Direction},
+    util::char_list,
+};
+use wasmi::{Engine, Instance, Linker, Memory, Module, Store, Val};
+
+const STACK_SIZE: usize = 8 + 1024 * 4;
+
+struct Machine {
+    store: Store<()>,
+    instance: Instance,
+    memory: Memory,
+}
+
+impl Machine {
+    fn new(module: &Module) -> Self {
+        let mut store = Store::new(module.engine(), ());
+        let instance = Linker::new(module.engine())
+            .instantiate(&mut store, module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        let memory = instance.get_memory(&store, "memory").unwrap();
+        Self {
+            store,
+            instance,
+            memory,
+        }
+    }
+
+    fn call(&mut self, name: &str, params: &[i32]) -> bool {
+        let f = self.instance.get_func(&self.store, name).unwrap();
+        let params = params.iter().map(|&n| Val::I32(n)).collect::<Vec<_>>();
+        f.call(&mut self.store, &params, &mut []).is_ok()
+    }
+
+    fn global(&self, x: &str) -> usize {
+        let global = self.instance.get_global(&self.store, x).unwrap();
+        global.get(&self.store).i32().unwrap() as usize
+    }
+
+    fn load(&self, address: usize) -> i32 {
+        let bytes = &self.memory.data(&self.store)[address..address + 4];
+        i32::from_le_bytes(bytes.try_into().unwrap())
+    }
+
+    fn store(&mut self, address: usize, n: i32) {
+        self.memory.data_mut(&mut self.store)[address..address + 4]
+            .copy_from_slice(&n.to_le_bytes());
+    }
+
+    fn read(&self, address: usize, shape: &Value) -> Value {
+        let cells = |start: usize, len: usize| {
+            (0..len)
+                .map(|i| self.load(start + 4 * i))
+                .collect::<Vec<_>>()
+        };
+        match shape {
+            Value::Int(_) => Value::Int(self.load(address)),
+            Value::Array(ns) => Value::Array(cells(address, ns.len())),
+            Value::Stack(_) => Value::Stack(cells(
+                self.load(address + 4) as usize,
+                self.load(address) as usize,
+            )),
+        }
+    }
+
+    fn write(&mut self, address: usize, value: &Value) {
+        let ns = match value {
+            Value::Int(n) => return self.store(address, *n),
+            Value::Array(ns) => return self.store_all(address, ns),
+            Value::Stack(ns) => ns,
+        };
+        self.store(address, ns.len() as i32);
+        self.store(address + 4, (address + 8) as i32);
+        self.store_all(address + 8, ns);
+    }
+
+    fn store_all(&mut self, address: usize, ns: &[i32]) {
+        for (i, &n) in ns.iter().enumerate() {
+            self.store(address + 4 * i, n);
+        }
+    }
+}
+
+fn size(value: &Value) -> usize {
+    match value {
+        Value::Int(_) => 4,
+        Value::Array(ns) => 4 * ns.len(),
+        Value::Stack(_) => STACK_SIZE,
+    }
+}
+
+fn module(prog: &Prog) -> Module {
+    let text = backend::wat::emit(prog).unwrap();
+    let wasm = wat::parse_str(&text).unwrap_or_else(|e| panic!("{e}\n{text}"));
+    Module::new(&Engine::default(), &wasm).unwrap()
+}
+
+#[test]
+fn main_agrees_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            let module = module(&prog);
+            for direction in DIRECTIONS {
+                let mut machine = Machine::new(&module);
+                let finished = machine.call(&format!("main_{}", suffix(direction)), &[]);
+                match interpret_main(&prog, direction) {
+                    Ok(store) => {
+                        assert!(finished, "{name}: {} main trapped", direction.name());
+                        for (x, value) in store {
+                            let found = machine.read(machine.global(&x), &value);
+                            assert_eq!(found, value, "{name}: {} main, {x}", direction.name());
+                        }
+                    }
+                    Err(e) => assert!(!finished, "{name}: expected {e:#}"),
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn procedures_agree_with_interpreter() {
+    deep(|| {
+        for (name, prog) in corpus() {
+            check_procedures(&name, &prog, 50);
+        }
+    });
+}
+
+#[test]
+fn parameters_do_not_collide_with_generated_locals() {
+    let prog = ast::parse(char_list(
+        "procedure main()
+    int x
+    skip
+
+procedure f(int janus_p, int janus_i, int a)
+    janus_p += janus_i
+    a[janus_i] += janus_p
+    local int janus_p = a[janus_i]
+        janus_i += janus_p
+    delocal int janus_p = a[janus_i]
+",
+    ))
+    .unwrap();
+    check_procedures("collisions", &prog, 50);
+}
+
+fn check_procedures(name: &str, prog: &Prog, samples: usize) {
+    let module = module(prog);
+    for case in cases(prog, samples) {
+        let Some(expected) = interpret(prog, &case) else {
+            continue;
+        };
+        let mut machine = Machine::new(&module);
+        let mut address = machine.memory.data(&machine.store).len();
+        let mut addresses = Vec::new();
+        let mut params = Vec::new();
+        for value in &case.input {
+            address -= size(value);
+            machine.write(address, value);
+            addresses.push(address);
+            params.push(address as i32);
+            if let Value::Array(ns) = value {
+                params.push(ns.len() as i32);
+            }
+        }
+        let function = format!("{}_{}", case.q, suffix(case.direction));
+        let finished = machine.call(&function, &params);
+        match expected {
+            Ok(values) => {
+                assert!(finished, "{} trapped", describe(name, &case));
+                let found = addresses
+                    .iter()
+                    .zip(&values)
+                    .map(|(&address, value)| machine.read(address, value))
+                    .collect::<Vec<_>>();
+                assert_eq!(found, values, "{}", describe(name, &case));
+            }
+            Err(e) => assert!(!finished, "{}: expected {e:#}", describe(name, &case)),
+        }
+    }
+}
+
+fn suffix(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Forward => "fwd",
+        Direction::Backward => "bwd",
+    }
+}